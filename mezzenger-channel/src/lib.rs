@@ -31,6 +31,9 @@ use futures::{
 };
 use pin_project::pin_project;
 
+mod mpsc;
+pub use mpsc::{mpsc_transport, Aggregated, Consumer, Producer, ProducerId};
+
 #[derive(Debug)]
 pub enum Error {
     ChannelIsFull,
@@ -207,6 +210,21 @@ mod tests {
         assert_eq!(left.receive().await.unwrap(), 1);
     }
 
+    async fn test_close_after_send_inner() {
+        let (mut left, mut right) = transports::<(), u32>();
+
+        left.send(1).await.unwrap();
+        left.send(2).await.unwrap();
+        left.close().await.unwrap();
+
+        assert_eq!(right.receive().await.unwrap(), 1);
+        assert_eq!(right.receive().await.unwrap(), 2);
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+    }
+
     async fn test_unit_message_inner() {
         let (mut left, mut right) = transports();
 
@@ -239,6 +257,12 @@ mod tests {
         test_stream_inner().await;
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_close_after_send() {
+        test_close_after_send_inner().await
+    }
+
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     async fn test_transport() {
@@ -256,4 +280,10 @@ mod tests {
     async fn test_stream() {
         test_stream_inner().await
     }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_close_after_send() {
+        test_close_after_send_inner().await
+    }
 }
@@ -0,0 +1,288 @@
+//! Transport aggregating messages of many producers into a single consumer.
+
+use std::{
+    fmt::Display,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    stream::{FusedStream, SelectAll},
+    Sink, Stream, StreamExt,
+};
+use pin_project::pin_project;
+
+use crate::{map_error, Error};
+
+/// Identifier of a producer created by [mpsc_transport].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProducerId(usize);
+
+impl ProducerId {
+    /// Index of the producer (in order they were returned by [mpsc_transport]).
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl Display for ProducerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "producer {}", self.0)
+    }
+}
+
+/// Item received by [Consumer].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Aggregated<Message> {
+    /// Message sent by a producer.
+    Message(ProducerId, Message),
+    /// Producer was closed or dropped - no more messages will be received from it.
+    ProducerClosed(ProducerId),
+}
+
+/// Messages of a single producer followed by its closing notification.
+struct ProducerStream<Message> {
+    id: ProducerId,
+    receiver: Receiver<Message>,
+    closed: bool,
+}
+
+impl<Message> Stream for ProducerStream<Message> {
+    type Item = Aggregated<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.closed {
+            return Poll::Ready(None);
+        }
+        let id = self.id;
+        match self.receiver.poll_next_unpin(cx) {
+            Poll::Ready(Some(message)) => Poll::Ready(Some(Aggregated::Message(id, message))),
+            Poll::Ready(None) => {
+                self.closed = true;
+                Poll::Ready(Some(Aggregated::ProducerClosed(id)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Receiving end of [mpsc_transport].
+///
+/// Stream terminates once all producers are closed (or dropped)
+/// and their remaining messages are received.
+pub struct Consumer<Message> {
+    producers: SelectAll<ProducerStream<Message>>,
+}
+
+impl<Message> Stream for Consumer<Message> {
+    type Item = Result<Aggregated<Message>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.producers
+            .poll_next_unpin(cx)
+            .map(|item_option| item_option.map(Ok))
+    }
+}
+
+impl<Message> FusedStream for Consumer<Message> {
+    fn is_terminated(&self) -> bool {
+        self.producers.is_terminated()
+    }
+}
+
+impl<Message> mezzenger::Reliable for Consumer<Message> {}
+
+impl<Message> mezzenger::Order for Consumer<Message> {}
+
+/// Sending end of [mpsc_transport].
+///
+/// Closing (or dropping) it is reported to [Consumer] with [Aggregated::ProducerClosed].
+#[pin_project]
+pub struct Producer<Message> {
+    id: ProducerId,
+    #[pin]
+    sender: Sender<Message>,
+}
+
+impl<Message> Producer<Message> {
+    /// Identifier tagging messages sent by this producer.
+    pub fn id(&self) -> ProducerId {
+        self.id
+    }
+}
+
+impl<Message> Sink<Message> for Producer<Message> {
+    type Error = mezzenger::Error<Error>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.sender.poll_ready(cx).map_err(map_error)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        let me = self.project();
+        me.sender.start_send(item).map_err(map_error)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        Sink::<Message>::poll_flush(me.sender, cx).map_err(map_error)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        Sink::<Message>::poll_close(me.sender, cx).map_err(map_error)
+    }
+}
+
+/// Create a consumer receiving messages of `producers` producers (tagged with their [ProducerId]).
+///
+/// Every producer has its own bounded queue - sending waits while `buffer` messages
+/// (plus one, see [channel]) sent by that producer wait for the consumer,
+/// other producers are not affected.<br>
+/// Flushing a producer waits until its queue has room for another message.
+pub fn mpsc_transport<Message>(
+    producers: usize,
+    buffer: usize,
+) -> (Consumer<Message>, Vec<Producer<Message>>) {
+    let mut streams = SelectAll::new();
+    let producers = (0..producers)
+        .map(|index| {
+            let id = ProducerId(index);
+            let (sender, receiver) = channel(buffer);
+            streams.push(ProducerStream {
+                id,
+                receiver,
+                closed: false,
+            });
+            Producer { id, sender }
+        })
+        .collect();
+
+    (Consumer { producers: streams }, producers)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, SinkExt, StreamExt};
+
+    use mezzenger::Receive;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{mpsc_transport, Aggregated};
+
+    async fn test_mpsc_transport_inner() {
+        let (mut consumer, producers) = mpsc_transport::<u32>(3, 4);
+        let ids: Vec<_> = producers.iter().map(|producer| producer.id()).collect();
+        assert_eq!(
+            ids.iter().map(|id| id.index()).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        let [mut first, mut second, mut third]: [_; 3] = producers.try_into().ok().unwrap();
+
+        first.send(1).await.unwrap();
+        second.send(2).await.unwrap();
+        third.send(3).await.unwrap();
+        first.send(11).await.unwrap();
+
+        let mut received = vec![];
+        for _ in 0..4 {
+            received.push(consumer.receive().await.unwrap());
+        }
+        for (id, message) in [(ids[0], 1), (ids[1], 2), (ids[2], 3), (ids[0], 11)] {
+            assert!(received.contains(&Aggregated::Message(id, message)));
+        }
+        let first_messages: Vec<_> = received
+            .iter()
+            .filter_map(|item| match item {
+                Aggregated::Message(id, message) if *id == ids[0] => Some(*message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(first_messages, vec![1, 11]);
+
+        // individual producers closing
+        second.send(22).await.unwrap();
+        second.close().await.unwrap();
+        assert_eq!(
+            consumer.receive().await.unwrap(),
+            Aggregated::Message(ids[1], 22)
+        );
+        assert_eq!(
+            consumer.receive().await.unwrap(),
+            Aggregated::ProducerClosed(ids[1])
+        );
+        drop(first);
+        assert_eq!(
+            consumer.receive().await.unwrap(),
+            Aggregated::ProducerClosed(ids[0])
+        );
+
+        // consumer keeps waiting while any producer is alive
+        assert!(consumer.next().now_or_never().is_none());
+        third.send(33).await.unwrap();
+        assert_eq!(
+            consumer.receive().await.unwrap(),
+            Aggregated::Message(ids[2], 33)
+        );
+
+        // terminated once all producers are gone
+        drop(third);
+        assert_eq!(
+            consumer.receive().await.unwrap(),
+            Aggregated::ProducerClosed(ids[2])
+        );
+        assert!(matches!(
+            consumer.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert!(futures::stream::FusedStream::is_terminated(&consumer));
+    }
+
+    async fn test_mpsc_backpressure_inner() {
+        let (mut consumer, mut producers) = mpsc_transport::<u32>(3, 0);
+
+        // with buffer of 0 every producer can queue a single message
+        producers[0].feed(1).await.unwrap();
+        assert!(producers[0].feed(2).now_or_never().is_none());
+        producers[1].feed(3).await.unwrap();
+        producers[2].feed(4).await.unwrap();
+
+        let mut received = vec![];
+        for _ in 0..3 {
+            received.push(consumer.receive().await.unwrap());
+        }
+        assert!(received.contains(&Aggregated::Message(producers[0].id(), 1)));
+        producers[0].feed(2).await.unwrap();
+        assert_eq!(
+            consumer.receive().await.unwrap(),
+            Aggregated::Message(producers[0].id(), 2)
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_mpsc_transport() {
+        test_mpsc_transport_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_mpsc_backpressure() {
+        test_mpsc_backpressure_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_mpsc_transport() {
+        test_mpsc_transport_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_mpsc_backpressure() {
+        test_mpsc_backpressure_inner().await
+    }
+}
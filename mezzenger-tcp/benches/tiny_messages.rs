@@ -0,0 +1,85 @@
+//! Measures receiving of many tiny messages already buffered by the transport.
+//!
+//! Plain frames are decoded on the fast path, frames with checksum
+//! go through the incremental path (and pay for computing checksums).
+//!
+//! Run with:
+//! ```text
+//! cargo bench -p mezzenger-tcp --bench tiny_messages
+//! ```
+
+use std::{
+    hint::black_box,
+    io::Cursor,
+    time::{Duration, Instant},
+};
+
+use futures::{SinkExt, StreamExt};
+use kodec::binary::Codec;
+use mezzenger_tcp::{Config, Transport};
+use tokio::{
+    io::{duplex, AsyncReadExt},
+    join,
+};
+
+const MESSAGES: u32 = 1_000_000;
+
+/// Frames of [MESSAGES] tiny messages.
+async fn frames(config: Config) -> Vec<u8> {
+    let (left, mut right) = duplex(1 << 16);
+    let mut frames = vec![];
+    let write = async move {
+        let mut transport: Transport<_, Codec, (), u32> =
+            Transport::new_with_config(left, Codec::default(), config);
+        for i in 0..MESSAGES {
+            transport.feed(i).await.unwrap();
+        }
+        transport.flush().await.unwrap();
+    };
+    let read = right.read_to_end(&mut frames);
+    let (_, result) = join!(write, read);
+    result.unwrap();
+    frames
+}
+
+async fn measure(name: &str, config: Config) -> Duration {
+    let frames = frames(config).await;
+    let config = Config {
+        read_chunk_size: 64 * 1024,
+        ..config
+    };
+    let mut transport: Transport<_, Codec, u32, ()> =
+        Transport::new_with_config(Cursor::new(frames), Codec::default(), config);
+
+    let start = Instant::now();
+    let mut sum = 0u64;
+    while let Some(message) = transport.next().await {
+        sum += black_box(message.unwrap()) as u64;
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(sum, (MESSAGES as u64 - 1) * MESSAGES as u64 / 2);
+    println!(
+        "{name:<24} {:>10.3} ms per {MESSAGES} messages ({:.1} ns per message)",
+        elapsed.as_secs_f64() * 1000.0,
+        elapsed.as_nanos() as f64 / MESSAGES as f64
+    );
+    elapsed
+}
+
+#[tokio::main]
+async fn main() {
+    let plain = measure("plain (fast path)", Config::default()).await;
+    let checksum = measure(
+        "checksum (incremental)",
+        Config {
+            checksum: true,
+            ..Default::default()
+        },
+    )
+    .await;
+
+    println!(
+        "\nplain frames: {:.1}x faster than frames with checksum",
+        checksum.as_secs_f64() / plain.as_secs_f64()
+    );
+}
@@ -3,13 +3,11 @@ use futures::{FutureExt, SinkExt, StreamExt};
 use kodec::binary::Codec;
 use mezzenger::{Messages, Receive};
 use mezzenger_tcp::Transport;
-use parity_tokio_ipc::Endpoint;
 use rustyline_async::{Readline, ReadlineEvent};
 use serde::{Deserialize, Serialize};
 use std::io::Write;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
-    net::TcpStream,
     select,
 };
 
@@ -24,19 +22,17 @@ pub enum Message {
 pub async fn run(ipc: bool, address: &str, path: &str) -> Result<()> {
     println!("Connecting to server...");
     if ipc {
-        run_inner(Endpoint::connect(&path).await?).await
+        run_inner(Transport::connect_ipc(path, Codec::default()).await?).await
     } else {
-        run_inner(TcpStream::connect(address).await?).await
+        run_inner(Transport::connect(address, Codec::default()).await?).await
     }
 }
 
-async fn run_inner<S>(stream: S) -> Result<()>
+async fn run_inner<S>(transport: Transport<S, Codec, server::Message, Message>) -> Result<()>
 where
     S: AsyncWrite + AsyncRead,
 {
-    let codec = Codec::default();
-    let (mut sender, mut receiver) =
-        Transport::<_, Codec, server::Message, Message>::new(stream, codec).split();
+    let (mut sender, mut receiver) = transport.split();
     println!("Connected.");
 
     let (mut readline, mut stdout) = Readline::new("> ".to_string())?;
@@ -18,7 +18,7 @@ struct Args {
     #[arg(short, long)]
     ipc: bool,
 
-    /// Use IPC instead of TCP
+    /// IPC path (socket file on Unix, pipe name on Windows).
     #[arg(short, long, default_value = "/tmp/chat.ipc")]
     path: String,
 }
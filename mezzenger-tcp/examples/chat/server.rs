@@ -4,12 +4,10 @@ use anyhow::{bail, Result};
 use futures::{pin_mut, FutureExt, SinkExt, StreamExt};
 use kodec::binary::Codec;
 use mezzenger::Receive;
-use mezzenger_tcp::Transport;
-use parity_tokio_ipc::{Endpoint, SecurityAttributes};
+use mezzenger_tcp::{IpcListener, Listener, Transport};
 use serde::{Deserialize, Serialize};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
-    net::TcpListener,
     select, spawn,
     sync::{
         mpsc::{unbounded_channel, UnboundedSender},
@@ -21,6 +19,7 @@ use tracing::{error, info, Level};
 
 use crate::client;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
     Init { name_already_taken: bool },
@@ -55,29 +54,22 @@ pub async fn run(ipc: bool, address: &str, path: &str) -> Result<()> {
     pin_mut!(break_signal);
 
     if ipc {
-        let mut endpoint = Endpoint::new(path.to_string());
-        endpoint.set_security_attributes(SecurityAttributes::allow_everyone_create()?);
-        let incoming = endpoint.incoming()?;
+        let mut listener = IpcListener::bind(path, Codec::default())?;
         info!("Listening at {}...", path);
 
-        futures::pin_mut!(incoming);
         loop {
             select! {
-                incoming_option = incoming.next() => {
-                    if let Some(incoming_result) = incoming_option {
-                        match incoming_result {
-                            Ok(stream) => {
-                                let state = state.clone();
-                                spawn(async move {
-                                    if let Err(error) = user_connected(stream, state).await {
-                                        error!("Error occurred: {error}");
-                                    }
-                                });
-                            }
-                            Err(error) => error!("Error occurred: {error}"),
+                listener_result = listener.accept() => {
+                    match listener_result {
+                        Ok(transport) => {
+                            let state = state.clone();
+                            spawn(async move {
+                                if let Err(error) = user_connected(transport, state).await {
+                                    error!("Error occurred: {error}");
+                                }
+                            });
                         }
-                    } else {
-                        break;
+                        Err(error) => error!("Error occurred: {error}"),
                     }
                 }
                 break_result = &mut break_signal => {
@@ -87,16 +79,16 @@ pub async fn run(ipc: bool, address: &str, path: &str) -> Result<()> {
             }
         }
     } else {
-        let listener = TcpListener::bind(&address).await?;
+        let listener = Listener::bind(&address, Codec::default()).await?;
         info!("Listening at {}...", address);
 
         loop {
             select! {
                 listener_result = listener.accept() => {
-                    let (stream, _address) = listener_result?;
+                    let (transport, _address) = listener_result?;
                     let state = state.clone();
                     spawn(async move {
-                        if let Err(error) = user_connected(stream, state).await {
+                        if let Err(error) = user_connected(transport, state).await {
                             error!("Error occurred: {error}");
                         }
                     });
@@ -113,13 +105,14 @@ pub async fn run(ipc: bool, address: &str, path: &str) -> Result<()> {
     Ok(())
 }
 
-async fn user_connected<S>(stream: S, state: Arc<RwLock<State>>) -> Result<()>
+async fn user_connected<S>(
+    transport: Transport<S, Codec, client::Message, Message>,
+    state: Arc<RwLock<State>>,
+) -> Result<()>
 where
     S: AsyncWrite + AsyncRead + Send + 'static,
 {
-    let codec = Codec::default();
-    let (mut sender, mut receiver) =
-        Transport::<_, Codec, client::Message, Message>::new(stream, codec).split();
+    let (mut sender, mut receiver) = transport.split();
 
     let init_message = receiver.receive().await?;
     match init_message {
@@ -0,0 +1,240 @@
+//! Building transports with socket options applied.
+
+use std::{io, time::Duration};
+
+use mezzenger::FlushOnDrop;
+use serde::Serialize;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::{
+    net::{TcpStream, ToSocketAddrs},
+    runtime::Handle,
+};
+
+use crate::{send::DropFlush, Config, Transport};
+
+/// Builder of [TcpStream] transports applying socket options before
+/// constructing the transport.
+///
+/// Single builder can be reused to wrap many streams (for example all accepted connections).
+///
+/// ## Example
+///
+/// ```ignore
+/// let builder = TransportBuilder::new()
+///     .recv_buffer_size(1 << 20)
+///     .keepalive(Some(Duration::from_secs(60)));
+/// let transport: Transport<_, Codec, i32, String> =
+///     builder.connect("127.0.0.1:8080", Codec::default()).await?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportBuilder {
+    nodelay: bool,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    keepalive: Option<Duration>,
+    flush_on_drop: FlushOnDrop,
+    config: Config,
+}
+
+impl TransportBuilder {
+    /// Create new builder with `TCP_NODELAY` enabled, other socket options
+    /// left at system defaults and default [Config].
+    pub fn new() -> Self {
+        TransportBuilder {
+            nodelay: true,
+            recv_buffer_size: None,
+            send_buffer_size: None,
+            keepalive: None,
+            flush_on_drop: FlushOnDrop::Disabled,
+            config: Config::default(),
+        }
+    }
+
+    /// Set `TCP_NODELAY` (enabled by default), so small messages are sent
+    /// immediately instead of being delayed by Nagle's algorithm.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Set size of socket's receive buffer (`SO_RCVBUF`).
+    ///
+    /// **NOTE**: Operating system may adjust the size (Linux doubles it).
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Set size of socket's send buffer (`SO_SNDBUF`).
+    ///
+    /// **NOTE**: Operating system may adjust the size (Linux doubles it).
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Enable TCP keepalive (`SO_KEEPALIVE`) probing connection after provided idle time
+    /// or disable it with `None` (default).
+    ///
+    /// Unlike application-level heartbeat ([Config::keepalive]) it doesn't require
+    /// the peer's cooperation, but detects dead connections much later.
+    pub fn keepalive(mut self, keepalive: Option<Duration>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// Set what transports do with messages sent but not flushed when they are dropped
+    /// ([FlushOnDrop::Disabled] by default).
+    ///
+    /// With [FlushOnDrop::Detached] transport keeps a duplicate of its socket, so
+    /// pending messages can be written (and connection shut down) by a task spawned
+    /// on the runtime the transport was created within - it's skipped if the transport
+    /// wasn't created within one.<br>
+    /// For [split](Transport::into_split) transports it applies to [Sender](crate::Sender).
+    pub fn flush_on_drop(mut self, flush_on_drop: FlushOnDrop) -> Self {
+        self.flush_on_drop = flush_on_drop;
+        self
+    }
+
+    /// Set transport [Config].
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Connect to provided address and wrap the connected stream.
+    pub async fn connect<A, Codec, Incoming, Outgoing>(
+        &self,
+        address: A,
+        codec: Codec,
+    ) -> io::Result<Transport<TcpStream, Codec, Incoming, Outgoing>>
+    where
+        A: ToSocketAddrs,
+        Codec: kodec::Codec,
+        for<'de> Incoming: serde::de::Deserialize<'de>,
+        Outgoing: Serialize,
+    {
+        let stream = TcpStream::connect(address).await?;
+        self.wrap(stream, codec)
+    }
+
+    /// Apply socket options to provided stream and wrap it in a transport.
+    pub fn wrap<Codec, Incoming, Outgoing>(
+        &self,
+        stream: TcpStream,
+        codec: Codec,
+    ) -> io::Result<Transport<TcpStream, Codec, Incoming, Outgoing>>
+    where
+        Codec: kodec::Codec,
+        for<'de> Incoming: serde::de::Deserialize<'de>,
+        Outgoing: Serialize,
+    {
+        self.apply(&stream)?;
+        let drop_flush = match (self.flush_on_drop, Handle::try_current()) {
+            (FlushOnDrop::Detached, Ok(handle)) => Some(DropFlush {
+                handle,
+                socket: SockRef::from(&stream).try_clone()?,
+            }),
+            _ => None,
+        };
+        let mut transport = Transport::new_with_config(stream, codec, self.config).with_peer();
+        transport.send_queue.flush_on_drop = drop_flush;
+        Ok(transport)
+    }
+
+    fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        let socket = SockRef::from(stream);
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(time) = self.keepalive {
+            socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(time))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for TransportBuilder {
+    fn default() -> Self {
+        TransportBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
+    use kodec::binary::Codec;
+    use mezzenger::{FlushOnDrop, Messages, Receive};
+    use socket2::SockRef;
+    use tokio::{join, net::TcpListener};
+
+    use crate::{Transport, TransportBuilder};
+
+    #[tokio::test]
+    async fn test_builder() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let builder = TransportBuilder::new()
+            .recv_buffer_size(1 << 16)
+            .send_buffer_size(1 << 16)
+            .keepalive(Some(Duration::from_secs(60)));
+
+        let client_builder = TransportBuilder::new().nodelay(false);
+        let (client, accepted) = join!(
+            client_builder.connect(address, Codec::default()),
+            listener.accept()
+        );
+        let mut client: Transport<_, Codec, String, String> = client.unwrap();
+        let mut server: Transport<_, Codec, String, String> =
+            builder.wrap(accepted.unwrap().0, Codec::default()).unwrap();
+
+        assert!(!client.nodelay().unwrap());
+        assert!(server.nodelay().unwrap());
+        let socket = SockRef::from(&server.inner);
+        assert!(socket.recv_buffer_size().unwrap() >= 1 << 16);
+        assert!(socket.send_buffer_size().unwrap() >= 1 << 16);
+        assert!(socket.keepalive().unwrap());
+        assert!(!SockRef::from(&client.inner).keepalive().unwrap());
+
+        client.send("Hello World!".to_string()).await.unwrap();
+        assert_eq!(server.receive().await.unwrap(), "Hello World!");
+        server.send("Hi".to_string()).await.unwrap();
+        assert_eq!(client.receive().await.unwrap(), "Hi");
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_drop() {
+        for flush_on_drop in [FlushOnDrop::Detached, FlushOnDrop::Disabled] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+
+            let builder = TransportBuilder::new().flush_on_drop(flush_on_drop);
+            let (client, accepted) = join!(
+                builder.connect(address, Codec::default()),
+                listener.accept()
+            );
+            let mut client: Transport<_, Codec, (), String> = client.unwrap();
+            let server: Transport<_, Codec, String, ()> =
+                Transport::new(accepted.unwrap().0, Codec::default());
+
+            client.feed("Hello".to_string()).await.unwrap();
+            client.feed("World!".to_string()).await.unwrap();
+            assert!(client.pending_send_bytes() > 0);
+            drop(client);
+
+            let received: Vec<String> = server.messages().collect().await;
+            if flush_on_drop == FlushOnDrop::Detached {
+                assert_eq!(received, vec!["Hello", "World!"]);
+            } else {
+                assert!(received.is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! [tokio_util] codec of the transport's wire format.
+
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut, BytesMut};
+use kodec::{Decode, Encode};
+use serde::Serialize;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[cfg(feature = "compression")]
+use crate::framing;
+use crate::{framing::FrameOptions, ByteOrder, Config, Error, Framing, OversizePolicy};
+
+/// [Encoder] and [Decoder] of messages framed exactly like messages of
+/// [Transport](crate::Transport) with the same [Config].
+///
+/// Lets [Framed](tokio_util::codec::Framed) based peers talk to peers
+/// using [Transport](crate::Transport).
+///
+/// Oversized incoming messages are skipped (or reported as [Error::MessageTooLarge]
+/// if [oversize_policy](Config::oversize_policy) is [OversizePolicy::Close] - errors
+/// end [Framed](tokio_util::codec::Framed) streams).
+pub struct MessageCodec<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    codec: Codec,
+    max_message_size: u64,
+    framing: Framing,
+    byte_order: ByteOrder,
+    frame_options: FrameOptions,
+    #[cfg(feature = "compression")]
+    compression_threshold: usize,
+    strict: bool,
+    oversize_policy: OversizePolicy,
+    frame_size: Option<usize>,
+    bytes_to_skip: u64,
+    frame_received: bool,
+    oversized_dropped: u64,
+    _incoming: PhantomData<Incoming>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<Codec, Incoming, Outgoing> MessageCodec<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Create new message codec with default [Config].
+    pub fn new(codec: Codec) -> Self {
+        MessageCodec::new_with_config(codec, Config::default())
+    }
+
+    /// Create new message codec using provided [Config].
+    ///
+    /// Buffer related options are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if heartbeat ([keepalive](Config::keepalive)) is enabled -
+    /// it requires answering peer's pings.
+    pub fn new_with_config(codec: Codec, config: Config) -> Self {
+        assert!(
+            config.keepalive.is_none(),
+            "message codec doesn't support heartbeat"
+        );
+        let frame_options = FrameOptions {
+            checksum: config.checksum,
+            keepalive: false,
+            #[cfg(feature = "compression")]
+            compression: config.compression,
+        };
+        let max_message_size = config
+            .max_message_size
+            .min(config.framing.max_message_size() - frame_options.overhead());
+        MessageCodec {
+            codec,
+            max_message_size,
+            framing: config.framing,
+            byte_order: config.byte_order,
+            frame_options,
+            #[cfg(feature = "compression")]
+            compression_threshold: config.compression_threshold,
+            strict: config.strict,
+            oversize_policy: config.oversize_policy,
+            frame_size: None,
+            bytes_to_skip: 0,
+            frame_received: false,
+            oversized_dropped: 0,
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// Get reference to inner codec.
+    pub fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    /// Maximum message size.
+    pub fn max_message_size(&self) -> u64 {
+        self.max_message_size
+    }
+
+    /// Number of incoming messages skipped for being too large.
+    pub fn oversized_dropped(&self) -> u64 {
+        self.oversized_dropped
+    }
+
+    /// Decode message from frame body.
+    ///
+    /// Returns `Ok(None)` if decompressed message was too large and got skipped.
+    #[allow(clippy::type_complexity)]
+    fn decode_body(
+        &mut self,
+        body: &[u8],
+    ) -> Result<Option<Incoming>, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>> {
+        let mut message_start = 0;
+        let mut compressed = false;
+        if self.frame_options.flags().is_some() {
+            compressed = self
+                .frame_options
+                .check_flags(body[0])
+                .map_err(|_| Error::ProtocolError)?;
+            message_start = 1;
+        }
+        let message_end = body.len() - self.frame_options.trailer_size();
+        let message = &body[message_start..message_end];
+        if self.frame_options.checksum {
+            let expected = u32::from_be_bytes(body[message_end..].try_into().unwrap());
+            let actual = crc32fast::hash(message);
+            if expected != actual {
+                return Err(Error::ChecksumMismatch { expected, actual });
+            }
+        }
+        #[cfg(not(feature = "compression"))]
+        debug_assert!(!compressed);
+        #[cfg(feature = "compression")]
+        if compressed {
+            let data = self
+                .frame_options
+                .compression
+                .decompress(message, self.max_message_size)
+                .map_err(Error::CompressionError)?;
+            if data.len() as u64 > self.max_message_size {
+                return self.oversized(data.len() as u64).map(|()| None);
+            }
+            return self.decode_message(&data).map(Some);
+        }
+        self.decode_message(message).map(Some)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn decode_message(
+        &self,
+        message: &[u8],
+    ) -> Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>> {
+        #[cfg(feature = "type-tag")]
+        let message = mezzenger::type_tag::check::<Incoming, Outgoing>(message)?;
+        self.codec
+            .decode(message)
+            .map_err(Error::DeserializationError)
+    }
+
+    /// Handle oversized incoming message according to oversize policy.
+    #[allow(clippy::type_complexity)]
+    fn oversized(
+        &mut self,
+        message_size: u64,
+    ) -> Result<(), Error<<Codec as Encode>::Error, <Codec as Decode>::Error>> {
+        self.oversized_dropped += 1;
+        match self.oversize_policy {
+            OversizePolicy::Skip => Ok(()),
+            OversizePolicy::Close => Err(Error::MessageTooLarge(message_size)),
+        }
+    }
+}
+
+impl<Codec, Incoming, Outgoing> Decoder for MessageCodec<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Item = Incoming;
+    type Error = Error<<Codec as Encode>::Error, <Codec as Decode>::Error>;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            if self.bytes_to_skip > 0 {
+                let skipped = (src.len() as u64).min(self.bytes_to_skip);
+                src.advance(skipped as usize);
+                self.bytes_to_skip -= skipped;
+                if self.bytes_to_skip > 0 {
+                    return Ok(None);
+                }
+            }
+
+            let frame_size = match self.frame_size {
+                Some(frame_size) => frame_size,
+                None => {
+                    let Some((header_size, body_size)) = self
+                        .framing
+                        .decode_header(src, self.byte_order)
+                        .map_err(|_| Error::ProtocolError)?
+                    else {
+                        return Ok(None);
+                    };
+                    src.advance(header_size);
+                    let overhead = self.frame_options.overhead();
+                    if body_size < overhead {
+                        return Err(Error::ProtocolError);
+                    }
+                    let message_size = body_size - overhead;
+                    let first_frame = !self.frame_received;
+                    self.frame_received = true;
+                    if message_size > self.max_message_size || usize::try_from(body_size).is_err() {
+                        if self.strict && first_frame {
+                            return Err(Error::ProtocolMismatch(message_size));
+                        }
+                        self.bytes_to_skip = body_size;
+                        self.oversized(message_size)?;
+                        continue;
+                    }
+                    body_size as usize
+                }
+            };
+
+            if src.len() < frame_size {
+                // Avoid repeated reallocations while large message arrives.
+                src.reserve(frame_size - src.len());
+                self.frame_size = Some(frame_size);
+                return Ok(None);
+            }
+            self.frame_size = None;
+            let body = src.split_to(frame_size);
+            if let Some(message) = self.decode_body(&body)? {
+                return Ok(Some(message));
+            }
+        }
+    }
+}
+
+impl<Codec, Incoming, Outgoing> Encoder<Outgoing> for MessageCodec<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Error = Error<<Codec as Encode>::Error, <Codec as Decode>::Error>;
+
+    fn encode(&mut self, item: Outgoing, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let frame_start = dst.len();
+        dst.put_bytes(0, self.framing.max_header_size());
+        let body_start = dst.len();
+        if let Some(flags) = self.frame_options.flags() {
+            dst.put_u8(flags);
+        }
+        let payload_start = dst.len();
+        #[cfg(feature = "type-tag")]
+        dst.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
+        if let Err(error) = self.codec.encode((&mut *dst).writer(), &item) {
+            dst.truncate(frame_start);
+            return Err(Error::SerializationError(error));
+        }
+        let message_size = (dst.len() - payload_start) as u64;
+        if message_size > self.max_message_size {
+            dst.truncate(frame_start);
+            return Err(Error::MessageTooLarge(message_size));
+        }
+        #[cfg(feature = "compression")]
+        if self.frame_options.compression.is_enabled()
+            && message_size > self.compression_threshold as u64
+        {
+            let payload = &dst[payload_start..];
+            match self.frame_options.compression.compress(payload) {
+                Ok(compressed) => {
+                    if compressed.len() < payload.len() {
+                        dst.truncate(payload_start);
+                        dst.extend_from_slice(&compressed);
+                        dst[body_start] |= framing::FLAG_COMPRESSED;
+                    }
+                }
+                Err(error) => {
+                    dst.truncate(frame_start);
+                    return Err(Error::CompressionError(error));
+                }
+            }
+        }
+        if self.frame_options.checksum {
+            let checksum = crc32fast::hash(&dst[payload_start..]);
+            dst.put_u32(checksum);
+        }
+        let body_size = (dst.len() - body_start) as u64;
+        self.framing
+            .finish_frame(dst, frame_start, body_size, self.byte_order);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::io::duplex;
+    use tokio_util::codec::Framed;
+
+    use crate::{ByteOrder, Config, Error, Framing, MessageCodec, OversizePolicy, Transport};
+
+    #[tokio::test]
+    async fn test_framed_peer() {
+        for config in [
+            Config::default(),
+            Config {
+                checksum: true,
+                ..Default::default()
+            },
+            Config {
+                framing: Framing::Varint,
+                ..Default::default()
+            },
+            Config {
+                framing: Framing::FixedU64,
+                byte_order: ByteOrder::Little,
+                checksum: true,
+                ..Default::default()
+            },
+        ] {
+            let (left, right) = duplex(1 << 20);
+
+            let mut framed = Framed::new(
+                left,
+                MessageCodec::<_, String, u32>::new_with_config(Codec::default(), config),
+            );
+            let mut transport: Transport<_, Codec, u32, String> =
+                Transport::new_with_config(right, Codec::default(), config);
+
+            for i in 0..100 {
+                framed.feed(i).await.unwrap();
+            }
+            framed.flush().await.unwrap();
+            for i in 0..100 {
+                assert_eq!(transport.receive().await.unwrap(), i);
+                transport.send(format!("Hello {i}")).await.unwrap();
+            }
+            for i in 0..100 {
+                assert_eq!(framed.next().await.unwrap().unwrap(), format!("Hello {i}"));
+            }
+
+            transport.close().await.unwrap();
+            assert!(framed.next().await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oversized_messages() {
+        let (left, right) = duplex(1 << 20);
+
+        let config = Config {
+            max_message_size: 16,
+            ..Default::default()
+        };
+        let mut framed = Framed::new(
+            left,
+            MessageCodec::<_, Vec<u8>, Vec<u8>>::new_with_config(Codec::default(), config),
+        );
+        let mut transport: Transport<_, Codec, Vec<u8>, Vec<u8>> =
+            Transport::new(right, Codec::default());
+
+        assert!(matches!(
+            framed.send(vec![0; 64]).await,
+            Err(Error::MessageTooLarge(_))
+        ));
+        transport.send(vec![1; 64]).await.unwrap();
+        transport.send(vec![2; 4]).await.unwrap();
+        assert_eq!(framed.next().await.unwrap().unwrap(), vec![2; 4]);
+        assert_eq!(framed.codec().oversized_dropped(), 1);
+
+        let (left, right) = duplex(1 << 20);
+        let config = Config {
+            max_message_size: 16,
+            oversize_policy: OversizePolicy::Close,
+            ..Default::default()
+        };
+        let mut framed = Framed::new(
+            left,
+            MessageCodec::<_, Vec<u8>, Vec<u8>>::new_with_config(Codec::default(), config),
+        );
+        let mut transport: Transport<_, Codec, Vec<u8>, Vec<u8>> =
+            Transport::new(right, Codec::default());
+        transport.send(vec![1; 64]).await.unwrap();
+        assert!(matches!(
+            framed.next().await,
+            Some(Err(Error::MessageTooLarge(_)))
+        ));
+    }
+}
@@ -0,0 +1,79 @@
+//! Transparent message compression.
+
+use std::io::{self, Read, Write};
+
+/// Default size above which messages are compressed.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Compression algorithm applied to serialized messages.
+///
+/// **NOTE**: Both ends of the connection have to use the same algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Messages are not compressed.
+    ///
+    /// This is the default.
+    #[default]
+    None,
+    /// [Deflate](https://en.wikipedia.org/wiki/Deflate) compression.
+    Deflate,
+    /// [Zstandard](https://en.wikipedia.org/wiki/Zstd) compression with provided level.
+    Zstd { level: i32 },
+}
+
+impl Compression {
+    pub(crate) fn is_enabled(&self) -> bool {
+        !matches!(self, Compression::None)
+    }
+
+    pub(crate) fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Deflate => {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zstd { level } => zstd::stream::encode_all(data, *level),
+        }
+    }
+
+    /// Decompress data, stopping after more than `limit` bytes were produced.
+    pub(crate) fn decompress(&self, data: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+        let mut output = vec![];
+        match self {
+            Compression::None => output.extend_from_slice(data),
+            Compression::Deflate => {
+                flate2::read::DeflateDecoder::new(data)
+                    .take(limit + 1)
+                    .read_to_end(&mut output)?;
+            }
+            Compression::Zstd { .. } => {
+                zstd::stream::read::Decoder::new(data)?
+                    .take(limit + 1)
+                    .read_to_end(&mut output)?;
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+
+    #[test]
+    fn test_decompression_limit() {
+        let data = vec![1u8; 100000];
+        for compression in [Compression::Deflate, Compression::Zstd { level: 3 }] {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(compressed.len() < 1000);
+            assert_eq!(compression.decompress(&compressed, 100000).unwrap(), data);
+            assert_eq!(
+                compression.decompress(&compressed, 1000).unwrap().len(),
+                1001
+            );
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! Connecting and accepting connections.
+
+use std::{future::Future, io, net::SocketAddr, pin::pin, time::Duration};
+
+use futures::future::{select, Either};
+use serde::Serialize;
+use tokio::{
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::sleep,
+};
+
+use crate::Transport;
+
+/// Policy of retrying failed connection attempts.
+///
+/// Delay between attempts starts at [initial_delay](RetryPolicy::initial_delay)
+/// and is multiplied by [multiplier](RetryPolicy::multiplier) after every failed attempt,
+/// up to [max_delay](RetryPolicy::max_delay).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of connection attempts (including the first one).
+    ///
+    /// At least one attempt is always made.
+    pub max_attempts: u32,
+    /// Delay after the first failed attempt.
+    pub initial_delay: Duration,
+    /// Factor delay is multiplied by after every failed attempt.
+    pub multiplier: f64,
+    /// Maximum delay between attempts.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    fn next_delay(&self, delay: Duration) -> Duration {
+        Duration::try_from_secs_f64(delay.as_secs_f64() * self.multiplier)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// 5 attempts, delay starting at 100 milliseconds, doubled after
+    /// every attempt, up to 5 seconds.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Is connection error worth retrying (peer might not be listening yet).
+fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable
+    )
+}
+
+impl<Codec, Incoming, Outgoing> Transport<TcpStream, Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Connect to provided address.
+    ///
+    /// `TCP_NODELAY` is enabled on the connected socket, so small messages
+    /// are sent immediately instead of being delayed by Nagle's algorithm.
+    pub async fn connect<A>(address: A, codec: Codec) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        Ok(Transport::new(stream, codec).with_peer())
+    }
+
+    /// Accept new connection from provided listener.
+    ///
+    /// Returns transport along with the peer address.<br>
+    /// `TCP_NODELAY` is enabled on the accepted socket (see [connect](Transport::connect)).
+    ///
+    /// See also [Listener](crate::Listener).
+    pub async fn accept(listener: &TcpListener, codec: Codec) -> io::Result<(Self, SocketAddr)> {
+        let (stream, address) = listener.accept().await?;
+        stream.set_nodelay(true)?;
+        Ok((Transport::new(stream, codec).with_peer(), address))
+    }
+
+    /// Connect to provided address, retrying according to provided [policy](RetryPolicy).
+    ///
+    /// Only transient errors (connection refused, reset or timed out,
+    /// unreachable host or network) are retried - other errors (like invalid address)
+    /// are returned immediately.<br>
+    /// Returns error of the last attempt if all attempts failed.
+    pub async fn connect_with_retry<A>(
+        address: A,
+        codec: Codec,
+        policy: RetryPolicy,
+    ) -> io::Result<Self>
+    where
+        A: ToSocketAddrs + Clone,
+    {
+        let mut delay = policy.initial_delay.min(policy.max_delay);
+        let mut attempt = 1;
+        loop {
+            match TcpStream::connect(address.clone()).await {
+                Ok(stream) => return Ok(Transport::new(stream, codec).with_peer()),
+                Err(error) if is_transient(&error) && attempt < policy.max_attempts => {
+                    sleep(delay).await;
+                    delay = policy.next_delay(delay);
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Connect to provided address, retrying according to provided [policy](RetryPolicy)
+    /// until `shutdown` completes.
+    ///
+    /// Returns `Ok(None)` if `shutdown` completed before connection was established.<br>
+    /// See [connect_with_retry](Transport::connect_with_retry).
+    pub async fn connect_with_retry_until<A>(
+        address: A,
+        codec: Codec,
+        policy: RetryPolicy,
+        shutdown: impl Future<Output = ()>,
+    ) -> io::Result<Option<Self>>
+    where
+        A: ToSocketAddrs + Clone,
+    {
+        let connect = pin!(Transport::connect_with_retry(address, codec, policy));
+        match select(connect, pin!(shutdown)).await {
+            Either::Left((result, _)) => result.map(Some),
+            Either::Right(_) => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::SinkExt;
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::{
+        join,
+        net::TcpListener,
+        time::{sleep, timeout, Instant},
+    };
+
+    use crate::{RetryPolicy, Transport};
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 20,
+        initial_delay: Duration::from_millis(10),
+        multiplier: 2.0,
+        max_delay: Duration::from_millis(100),
+    };
+
+    async fn unused_address() -> std::net::SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut delay = POLICY.initial_delay;
+        let delays: Vec<_> = (0..5)
+            .map(|_| {
+                let current = delay;
+                delay = POLICY.next_delay(delay);
+                current.as_millis()
+            })
+            .collect();
+        assert_eq!(delays, vec![10, 20, 40, 80, 100]);
+
+        let policy = RetryPolicy {
+            multiplier: f64::INFINITY,
+            ..POLICY
+        };
+        assert_eq!(policy.next_delay(POLICY.initial_delay), POLICY.max_delay);
+    }
+
+    #[tokio::test]
+    async fn test_connect_and_accept() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let (client, server) = join!(
+            Transport::connect(address, Codec::default()),
+            Transport::accept(&listener, Codec::default())
+        );
+        let mut client: Transport<_, Codec, String, String> = client.unwrap();
+        let (mut server, client_address): (Transport<_, Codec, String, String>, _) =
+            server.unwrap();
+
+        assert_eq!(client_address, client.inner.local_addr().unwrap());
+        assert!(client.inner.nodelay().unwrap());
+        assert!(server.inner.nodelay().unwrap());
+
+        client.send("Hello World!".to_string()).await.unwrap();
+        assert_eq!(server.receive().await.unwrap(), "Hello World!");
+        server.send("Hi".to_string()).await.unwrap();
+        assert_eq!(client.receive().await.unwrap(), "Hi");
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry() {
+        let address = unused_address().await;
+
+        let server = tokio::spawn(async move {
+            sleep(Duration::from_millis(200)).await;
+            let listener = TcpListener::bind(address).await.unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut transport: Transport<_, Codec, String, String> =
+                Transport::new(stream, Codec::default());
+            transport.send("Hello World!".to_string()).await.unwrap();
+        });
+
+        let start = Instant::now();
+        let mut client: Transport<_, Codec, String, String> =
+            Transport::connect_with_retry(address, Codec::default(), POLICY)
+                .await
+                .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(client.receive().await.unwrap(), "Hello World!");
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry_gives_up() {
+        let address = unused_address().await;
+
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            ..POLICY
+        };
+        let result: std::io::Result<Transport<_, Codec, String, String>> =
+            Transport::connect_with_retry(address, Codec::default(), policy).await;
+        assert_eq!(
+            result.err().unwrap().kind(),
+            std::io::ErrorKind::ConnectionRefused
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry_invalid_address() {
+        let policy = RetryPolicy {
+            initial_delay: Duration::from_secs(60),
+            ..POLICY
+        };
+        let result: std::io::Result<Transport<_, Codec, String, String>> = timeout(
+            Duration::from_secs(5),
+            Transport::connect_with_retry("127.0.0.1:invalid", Codec::default(), policy),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            result.err().unwrap().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry_until() {
+        let address = unused_address().await;
+
+        let result: std::io::Result<Option<Transport<_, Codec, String, String>>> =
+            Transport::connect_with_retry_until(
+                address,
+                Codec::default(),
+                POLICY,
+                sleep(Duration::from_millis(100)),
+            )
+            .await;
+        assert!(result.unwrap().is_none());
+    }
+}
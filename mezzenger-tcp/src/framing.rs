@@ -0,0 +1,449 @@
+//! Message framing (length prefix) strategies.
+
+use bytes::{BufMut, BytesMut};
+
+#[cfg(feature = "compression")]
+use crate::Compression;
+
+/// Strategy used to encode the length prefix preceding every message.
+///
+/// **NOTE**: Both ends of the connection have to use the same framing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// Fixed 4-byte big-endian length prefix.
+    ///
+    /// This is the default.
+    #[default]
+    FixedU32,
+    /// Variable-length [LEB128](https://en.wikipedia.org/wiki/LEB128) length prefix.
+    ///
+    /// Takes 1 byte for messages smaller than 128 bytes, 2 bytes for messages smaller
+    /// than 16384 bytes and so on (up to 5 bytes).<br>
+    /// Useful when sending a lot of tiny messages.
+    Varint,
+    /// Fixed 8-byte big-endian length prefix.
+    ///
+    /// Allows sending messages larger than 4 GiB.
+    FixedU64,
+}
+
+/// Byte order of fixed-size length prefixes.
+///
+/// Doesn't affect [Framing::Varint].
+///
+/// **NOTE**: Both ends of the connection have to use the same byte order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    /// Most significant byte first (network byte order).
+    ///
+    /// This is the default.
+    #[default]
+    Big,
+    /// Least significant byte first.
+    Little,
+}
+
+/// Marker stored in the upper bits of frame flags byte.
+const FLAGS_MARKER: u8 = 0xa0;
+const FLAGS_MARKER_MASK: u8 = 0xe0;
+/// Marker stored in the upper bits of control frame byte.
+const CONTROL_MARKER: u8 = 0xc0;
+/// Control frames are exchanged to detect dead connections.
+const FLAG_KEEPALIVE: u8 = 0x10;
+/// Frame payload is followed by CRC32 checksum.
+const FLAG_CHECKSUM: u8 = 0x01;
+/// Deflate compression is enabled.
+#[cfg(feature = "compression")]
+const FLAG_DEFLATE: u8 = 0x02;
+/// Zstd compression is enabled.
+#[cfg(feature = "compression")]
+const FLAG_ZSTD: u8 = 0x04;
+/// Frame payload is compressed.
+#[cfg(feature = "compression")]
+pub(crate) const FLAG_COMPRESSED: u8 = 0x08;
+
+/// Optional frame features.
+///
+/// If any feature is enabled each frame starts with a flags byte
+/// (following the length prefix), so the receiver can detect when ends of the
+/// connection disagree on used features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct FrameOptions {
+    pub checksum: bool,
+    pub keepalive: bool,
+    #[cfg(feature = "compression")]
+    pub compression: Compression,
+}
+
+impl FrameOptions {
+    /// Flags byte preceding the payload or `None` if no feature is enabled.
+    pub(crate) fn flags(&self) -> Option<u8> {
+        let mut flags = 0;
+        if self.checksum {
+            flags |= FLAG_CHECKSUM;
+        }
+        if self.keepalive {
+            flags |= FLAG_KEEPALIVE;
+        }
+        #[cfg(feature = "compression")]
+        match self.compression {
+            Compression::None => {}
+            Compression::Deflate => flags |= FLAG_DEFLATE,
+            Compression::Zstd { .. } => flags |= FLAG_ZSTD,
+        }
+        if flags == 0 {
+            None
+        } else {
+            Some(FLAGS_MARKER | flags)
+        }
+    }
+
+    /// Check if received flags byte matches enabled features.
+    ///
+    /// Returns whether frame payload is compressed.
+    pub(crate) fn check_flags(&self, flags: u8) -> Result<bool, InvalidHeader> {
+        #[cfg(feature = "compression")]
+        let (flags, compressed) = if self.compression.is_enabled() {
+            (flags & !FLAG_COMPRESSED, flags & FLAG_COMPRESSED != 0)
+        } else {
+            (flags, false)
+        };
+        #[cfg(not(feature = "compression"))]
+        let compressed = false;
+
+        if flags & FLAGS_MARKER_MASK == FLAGS_MARKER && Some(flags) == self.flags() {
+            Ok(compressed)
+        } else {
+            Err(InvalidHeader)
+        }
+    }
+
+    /// Decode control frame from the first byte of frame body.
+    ///
+    /// Returns `None` if it is a regular message frame.
+    pub(crate) fn control_frame(&self, byte: u8) -> Option<ControlFrame> {
+        if self.keepalive {
+            ControlFrame::from_byte(byte)
+        } else {
+            None
+        }
+    }
+
+    /// Can frame of provided body size be a control frame.
+    pub(crate) fn allows_control_frame(&self, body_size: u64) -> bool {
+        self.keepalive && body_size == 1
+    }
+
+    /// Number of bytes following the payload.
+    pub(crate) fn trailer_size(&self) -> usize {
+        if self.checksum {
+            4
+        } else {
+            0
+        }
+    }
+
+    /// Number of bytes added to every frame besides the length prefix.
+    pub(crate) fn overhead(&self) -> u64 {
+        let flags_size = if self.flags().is_some() { 1 } else { 0 };
+        flags_size + self.trailer_size() as u64
+    }
+}
+
+/// Frame used internally by the transport, never surfaced to the user.
+///
+/// Consists of a length prefix followed by a single byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ControlFrame {
+    Ping,
+    Pong,
+}
+
+impl ControlFrame {
+    fn to_byte(self) -> u8 {
+        match self {
+            ControlFrame::Ping => CONTROL_MARKER | 0x01,
+            ControlFrame::Pong => CONTROL_MARKER | 0x02,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        [ControlFrame::Ping, ControlFrame::Pong]
+            .into_iter()
+            .find(|frame| frame.to_byte() == byte)
+    }
+}
+
+/// Error returned when length prefix couldn't be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InvalidHeader;
+
+impl Framing {
+    /// Maximum number of bytes used by the length prefix.
+    pub(crate) fn max_header_size(&self) -> usize {
+        match self {
+            Framing::FixedU32 => 4,
+            Framing::Varint => 5,
+            Framing::FixedU64 => 8,
+        }
+    }
+
+    /// Maximum message size that can be encoded in the length prefix.
+    pub(crate) fn max_message_size(&self) -> u64 {
+        match self {
+            Framing::FixedU32 | Framing::Varint => u32::MAX as u64,
+            Framing::FixedU64 => u64::MAX,
+        }
+    }
+
+    /// Number of bytes used by the length prefix of a message of provided size.
+    pub(crate) fn header_size(&self, message_size: u64) -> usize {
+        match self {
+            Framing::FixedU32 => 4,
+            Framing::Varint => {
+                let bits = 64 - message_size.leading_zeros() as usize;
+                bits.max(1).div_ceil(7)
+            }
+            Framing::FixedU64 => 8,
+        }
+    }
+
+    /// Write length prefix into provided slice.
+    ///
+    /// Slice length has to be equal to [header_size](Framing::header_size)
+    /// and `message_size` can't exceed [max_message_size](Framing::max_message_size).
+    pub(crate) fn write_header(&self, header: &mut [u8], message_size: u64, byte_order: ByteOrder) {
+        match self {
+            Framing::FixedU32 => {
+                let message_size = message_size as u32;
+                header.copy_from_slice(&match byte_order {
+                    ByteOrder::Big => message_size.to_be_bytes(),
+                    ByteOrder::Little => message_size.to_le_bytes(),
+                })
+            }
+            Framing::FixedU64 => header.copy_from_slice(&match byte_order {
+                ByteOrder::Big => message_size.to_be_bytes(),
+                ByteOrder::Little => message_size.to_le_bytes(),
+            }),
+            Framing::Varint => {
+                let mut value = message_size;
+                let last = header.len() - 1;
+                for (index, byte) in header.iter_mut().enumerate() {
+                    *byte = (value & 0x7f) as u8;
+                    value >>= 7;
+                    if index != last {
+                        *byte |= 0x80;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Try to decode length prefix from the beginning of the buffer.
+    ///
+    /// Returns `Ok(None)` if more bytes are needed, otherwise returns
+    /// header size and decoded message size.
+    pub(crate) fn decode_header(
+        &self,
+        buffer: &[u8],
+        byte_order: ByteOrder,
+    ) -> Result<Option<(usize, u64)>, InvalidHeader> {
+        match self {
+            Framing::FixedU32 => {
+                if buffer.len() < 4 {
+                    Ok(None)
+                } else {
+                    let mut size = [0; 4];
+                    size.copy_from_slice(&buffer[..4]);
+                    let size = match byte_order {
+                        ByteOrder::Big => u32::from_be_bytes(size),
+                        ByteOrder::Little => u32::from_le_bytes(size),
+                    };
+                    Ok(Some((4, size as u64)))
+                }
+            }
+            Framing::FixedU64 => {
+                if buffer.len() < 8 {
+                    Ok(None)
+                } else {
+                    let mut size = [0; 8];
+                    size.copy_from_slice(&buffer[..8]);
+                    let size = match byte_order {
+                        ByteOrder::Big => u64::from_be_bytes(size),
+                        ByteOrder::Little => u64::from_le_bytes(size),
+                    };
+                    Ok(Some((8, size)))
+                }
+            }
+            Framing::Varint => {
+                let mut value: u64 = 0;
+                for (index, byte) in buffer.iter().take(5).enumerate() {
+                    value |= ((byte & 0x7f) as u64) << (7 * index);
+                    if byte & 0x80 == 0 {
+                        return if value > u32::MAX as u64 {
+                            Err(InvalidHeader)
+                        } else {
+                            Ok(Some((index + 1, value)))
+                        };
+                    }
+                }
+                if buffer.len() >= 5 {
+                    Err(InvalidHeader)
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    /// Finish frame started at `frame_start` position of the buffer.
+    ///
+    /// Buffer is expected to contain [max_header_size](Framing::max_header_size)
+    /// bytes reserved for the header followed by `message_size` bytes of payload.<br>
+    /// If actual header is shorter than reserved space the payload is moved to close the gap.
+    pub(crate) fn finish_frame(
+        &self,
+        buffer: &mut BytesMut,
+        frame_start: usize,
+        message_size: u64,
+        byte_order: ByteOrder,
+    ) {
+        let reserved = self.max_header_size();
+        let header_size = self.header_size(message_size);
+        let payload_start = frame_start + reserved;
+        if header_size != reserved {
+            buffer.copy_within(payload_start.., frame_start + header_size);
+            buffer.truncate(buffer.len() - (reserved - header_size));
+        }
+        self.write_header(
+            &mut buffer[frame_start..(frame_start + header_size)],
+            message_size,
+            byte_order,
+        );
+    }
+
+    /// Append control frame to the buffer.
+    pub(crate) fn put_control_frame(
+        &self,
+        buffer: &mut BytesMut,
+        frame: ControlFrame,
+        byte_order: ByteOrder,
+    ) {
+        let frame_start = buffer.len();
+        buffer.put_bytes(0, self.max_header_size());
+        buffer.put_u8(frame.to_byte());
+        self.finish_frame(buffer, frame_start, 1, byte_order);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::{ByteOrder, ControlFrame, FrameOptions, Framing};
+
+    #[test]
+    fn test_varint_header() {
+        let framing = Framing::Varint;
+        for (size, expected) in [
+            (0, vec![0x00]),
+            (1, vec![0x01]),
+            (127, vec![0x7f]),
+            (128, vec![0x80, 0x01]),
+            (300, vec![0xac, 0x02]),
+            (16383, vec![0xff, 0x7f]),
+            (16384, vec![0x80, 0x80, 0x01]),
+            (u32::MAX as u64, vec![0xff, 0xff, 0xff, 0xff, 0x0f]),
+        ] {
+            assert_eq!(framing.header_size(size), expected.len());
+            let mut header = vec![0; expected.len()];
+            framing.write_header(&mut header, size, ByteOrder::Big);
+            assert_eq!(header, expected);
+            assert_eq!(
+                framing.decode_header(&header, ByteOrder::Big),
+                Ok(Some((expected.len(), size)))
+            );
+            assert_eq!(
+                framing.decode_header(&header[..expected.len() - 1], ByteOrder::Big),
+                Ok(None)
+            );
+        }
+        assert!(framing
+            .decode_header(&[0xff, 0xff, 0xff, 0xff, 0xff], ByteOrder::Big)
+            .is_err());
+        assert!(framing
+            .decode_header(&[0xff, 0xff, 0xff, 0xff, 0x1f], ByteOrder::Big)
+            .is_err());
+    }
+
+    #[test]
+    fn test_fixed_u64_header() {
+        let framing = Framing::FixedU64;
+        let size = 5 * 1024 * 1024 * 1024;
+        let mut header = vec![0; framing.header_size(size)];
+        framing.write_header(&mut header, size, ByteOrder::Big);
+        assert_eq!(header, vec![0, 0, 0, 1, 64, 0, 0, 0]);
+        assert_eq!(
+            framing.decode_header(&header, ByteOrder::Big),
+            Ok(Some((8, size)))
+        );
+        assert_eq!(
+            framing.decode_header(&header[..7], ByteOrder::Big),
+            Ok(None)
+        );
+
+        framing.write_header(&mut header, size, ByteOrder::Little);
+        assert_eq!(header, vec![0, 0, 0, 64, 1, 0, 0, 0]);
+        assert_eq!(
+            framing.decode_header(&header, ByteOrder::Little),
+            Ok(Some((8, size)))
+        );
+    }
+
+    #[test]
+    fn test_frame_flags() {
+        let options = FrameOptions::default();
+        assert_eq!(options.flags(), None);
+        assert_eq!(options.overhead(), 0);
+
+        let options = FrameOptions {
+            checksum: true,
+            keepalive: false,
+            #[cfg(feature = "compression")]
+            compression: crate::Compression::None,
+        };
+        let flags = options.flags().unwrap();
+        assert_eq!(options.overhead(), 5);
+        assert!(options.check_flags(flags).is_ok());
+        assert!(options.check_flags(flags & 0x0f).is_err());
+        assert!(options.check_flags(0xa0).is_err());
+    }
+
+    #[test]
+    fn test_control_frames() {
+        let options = FrameOptions {
+            checksum: true,
+            keepalive: true,
+            #[cfg(feature = "compression")]
+            compression: crate::Compression::None,
+        };
+        let flags = options.flags().unwrap();
+        assert!(options.check_flags(flags).is_ok());
+        assert_eq!(options.control_frame(flags), None);
+        assert!(options.allows_control_frame(1));
+
+        let framing = Framing::Varint;
+        let mut buffer = BytesMut::new();
+        framing.put_control_frame(&mut buffer, ControlFrame::Ping, ByteOrder::Big);
+        framing.put_control_frame(&mut buffer, ControlFrame::Pong, ByteOrder::Big);
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer[0], 1);
+        assert_eq!(options.control_frame(buffer[1]), Some(ControlFrame::Ping));
+        assert_eq!(options.control_frame(buffer[3]), Some(ControlFrame::Pong));
+        assert!(options.check_flags(buffer[1]).is_err());
+
+        let options = FrameOptions::default();
+        assert_eq!(options.control_frame(buffer[1]), None);
+        assert!(!options.allows_control_frame(1));
+    }
+}
@@ -0,0 +1,208 @@
+//! Protocol identification before the normal framing starts.
+
+use kodec::{Decode, Encode};
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Config, Error, Transport};
+
+/// Size of the preamble exchanged during [Handshake].
+pub const HANDSHAKE_SIZE: usize = 6;
+
+/// Preamble exchanged by both ends of the connection before any message.
+///
+/// Every end sends its magic (4 bytes) followed by its version (2 bytes, big-endian)
+/// and checks the preamble received from the peer:
+/// - different magic means the peer doesn't speak the protocol at all (or doesn't perform
+///   the handshake) - [Error::ProtocolMismatch] is returned with received magic
+///   (read as big-endian number),
+/// - different version results in [Error::VersionMismatch].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handshake {
+    /// Application-specific protocol identifier.
+    pub magic: [u8; 4],
+    /// Protocol version - both ends have to use the same one.
+    pub version: u16,
+}
+
+impl Handshake {
+    /// Create new handshake.
+    pub fn new(magic: [u8; 4], version: u16) -> Self {
+        Handshake { magic, version }
+    }
+
+    fn preamble(&self) -> [u8; HANDSHAKE_SIZE] {
+        let mut preamble = [0; HANDSHAKE_SIZE];
+        preamble[..4].copy_from_slice(&self.magic);
+        preamble[4..].copy_from_slice(&self.version.to_be_bytes());
+        preamble
+    }
+
+    /// Exchange preambles over provided stream.
+    async fn perform<T, SerializationError, DeserializationError>(
+        &self,
+        stream: &mut T,
+    ) -> Result<(), Error<SerializationError, DeserializationError>>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        stream.write_all(&self.preamble()).await?;
+        stream.flush().await?;
+        let mut received = [0; HANDSHAKE_SIZE];
+        stream.read_exact(&mut received).await?;
+        let magic = [received[0], received[1], received[2], received[3]];
+        if magic != self.magic {
+            return Err(Error::ProtocolMismatch(u32::from_be_bytes(magic) as u64));
+        }
+        let theirs = u16::from_be_bytes([received[4], received[5]]);
+        if theirs != self.version {
+            return Err(Error::VersionMismatch {
+                ours: self.version,
+                theirs,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T, Codec, Incoming, Outgoing> Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead + Unpin,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Perform [handshake](Handshake) over provided stream and wrap it in a transport.
+    ///
+    /// Fails before any message is exchanged if the peer uses different magic
+    /// ([Error::ProtocolMismatch]) or version ([Error::VersionMismatch]).
+    ///
+    /// **NOTE**: Both ends of the connection have to perform the handshake.<br>
+    /// Handshake waits for the peer's preamble indefinitely - wrap it
+    /// in a timeout (for example [tokio::time::timeout]) when the peer may stay silent.
+    pub async fn new_with_handshake(
+        stream: T,
+        codec: Codec,
+        handshake: Handshake,
+    ) -> Result<Self, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>> {
+        Transport::new_with_handshake_and_config(stream, codec, handshake, Config::default()).await
+    }
+
+    /// Perform [handshake](Handshake) over provided stream and wrap it in a transport
+    /// using provided [config].
+    ///
+    /// See [new_with_handshake](Transport::new_with_handshake).
+    ///
+    /// [config]: crate::Config
+    pub async fn new_with_handshake_and_config(
+        mut stream: T,
+        codec: Codec,
+        handshake: Handshake,
+        config: Config,
+    ) -> Result<Self, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>> {
+        handshake.perform(&mut stream).await?;
+        Ok(Transport::new_with_config(stream, codec, config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::{io::duplex, join};
+
+    use crate::{Error, Handshake, Transport};
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let handshake = Handshake::new(*b"MZTS", 3);
+        let (left, right) = duplex(1024);
+        let (left, right) = join!(
+            Transport::<_, Codec, String, u32>::new_with_handshake(
+                left,
+                Codec::default(),
+                handshake
+            ),
+            Transport::<_, Codec, u32, String>::new_with_handshake(
+                right,
+                Codec::default(),
+                handshake
+            ),
+        );
+        let (mut left, mut right) = (left.unwrap(), right.unwrap());
+
+        left.send(7).await.unwrap();
+        right.send("Hello World!".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), 7);
+        assert_eq!(left.receive().await.unwrap(), "Hello World!");
+    }
+
+    #[tokio::test]
+    async fn test_mismatch() {
+        let (left, right) = duplex(1024);
+        let (left, right) = join!(
+            Transport::<_, Codec, u32, u32>::new_with_handshake(
+                left,
+                Codec::default(),
+                Handshake::new(*b"MZTS", 3)
+            ),
+            Transport::<_, Codec, u32, u32>::new_with_handshake(
+                right,
+                Codec::default(),
+                Handshake::new(*b"MZTS", 4)
+            ),
+        );
+        assert!(matches!(
+            left,
+            Err(Error::VersionMismatch { ours: 3, theirs: 4 })
+        ));
+        assert!(matches!(
+            right,
+            Err(Error::VersionMismatch { ours: 4, theirs: 3 })
+        ));
+
+        let (left, right) = duplex(1024);
+        let (left, right) = join!(
+            Transport::<_, Codec, u32, u32>::new_with_handshake(
+                left,
+                Codec::default(),
+                Handshake::new(*b"MZTS", 3)
+            ),
+            Transport::<_, Codec, u32, u32>::new_with_handshake(
+                right,
+                Codec::default(),
+                Handshake::new(*b"ABCD", 3)
+            ),
+        );
+        assert!(matches!(left, Err(Error::ProtocolMismatch(0x41424344))));
+        assert!(matches!(right, Err(Error::ProtocolMismatch(0x4d5a5453))));
+    }
+
+    #[tokio::test]
+    async fn test_peer_without_handshake() {
+        let (left, right) = duplex(1024);
+        let mut plain: Transport<_, Codec, u32, u32> = Transport::new(right, Codec::default());
+        let (left, sent) = join!(
+            Transport::<_, Codec, u32, u32>::new_with_handshake(
+                left,
+                Codec::default(),
+                Handshake::new(*b"MZTS", 3)
+            ),
+            plain.send(1),
+        );
+        sent.unwrap();
+        assert!(matches!(left, Err(Error::ProtocolMismatch(_))));
+
+        // peer hanging up before sending its preamble
+        let (left, right) = duplex(1024);
+        drop(right);
+        let left = Transport::<_, Codec, u32, u32>::new_with_handshake(
+            left,
+            Codec::default(),
+            Handshake::new(*b"MZTS", 3),
+        )
+        .await;
+        assert!(matches!(left, Err(Error::IoError(_))));
+    }
+}
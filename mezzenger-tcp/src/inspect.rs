@@ -0,0 +1,132 @@
+//! Hooks inspecting raw frames (for example to record traffic for replay or fuzzing).
+
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::Transport;
+
+/// Hook called with every encoded outgoing message.
+pub(crate) type OutgoingFrameHook = Box<dyn FnMut(&[u8]) + Send + Sync>;
+
+/// Hook called with every encoded incoming message and whether it's oversized.
+pub(crate) type IncomingFrameHook = Box<dyn FnMut(&[u8], bool) + Send + Sync>;
+
+impl<T, Codec, Incoming, Outgoing> Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Call `hook` with every outgoing message once it's encoded and queued for sending.
+    ///
+    /// Hook receives encoded message as it's passed to the peer's codec - without
+    /// the length prefix and other framing (flags, checksum), before compression.<br>
+    /// Messages sent with [send_encoded](Transport::send_encoded) are passed too.
+    pub fn on_outgoing_frame<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&[u8]) + Send + Sync + 'static,
+    {
+        self.outgoing_frame_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Call `hook` with every incoming message right before it's decoded.
+    ///
+    /// Hook receives encoded message as it's passed to the codec - without
+    /// the length prefix and other framing (flags, checksum), after decompression.<br>
+    /// Oversized frames are skipped without being buffered - hook is called for them
+    /// with empty message and the second argument set to `true`.
+    ///
+    /// **NOTE**: Messages decoded incrementally
+    /// ([enable_streaming_decode](Transport::enable_streaming_decode)) aren't passed to the hook.
+    pub fn on_incoming_frame<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&[u8], bool) + Send + Sync + 'static,
+    {
+        self.incoming_frame_hook = Some(Box::new(hook));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::SinkExt;
+    use kodec::{binary::Codec, Encode};
+    use mezzenger::Receive;
+    use tokio::io::duplex;
+
+    use crate::{Config, Error, Transport};
+
+    fn encoded(message: &str) -> Vec<u8> {
+        let mut buffer = vec![];
+        Codec::default()
+            .encode(&mut buffer, &message.to_string())
+            .unwrap();
+        buffer
+    }
+
+    fn framed(message: &str) -> Vec<u8> {
+        let mut frame = vec![];
+        #[cfg(feature = "type-tag")]
+        frame.extend_from_slice(&mezzenger::type_tag::tag::<String>());
+        frame.extend(encoded(message));
+        frame
+    }
+
+    #[tokio::test]
+    async fn test_frame_hooks() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let received = Arc::new(Mutex::new(vec![]));
+
+        let (left, right) = duplex(1 << 16);
+        let sent_clone = sent.clone();
+        let mut left: Transport<_, Codec, (), String> = Transport::new(left, Codec::default())
+            .on_outgoing_frame(move |frame| sent_clone.lock().unwrap().push(frame.to_vec()));
+        let received_clone = received.clone();
+        let config = Config {
+            max_message_size: 32,
+            ..Default::default()
+        };
+        let mut right: Transport<_, Codec, String, ()> =
+            Transport::new_with_config(right, Codec::default(), config).on_incoming_frame(
+                move |frame, oversized| {
+                    received_clone
+                        .lock()
+                        .unwrap()
+                        .push((frame.to_vec(), oversized))
+                },
+            );
+
+        let messages = ["Hello", "x", "Hello, world"];
+        for message in messages {
+            left.send(message.to_string()).await.unwrap();
+        }
+        left.send_encoded(&encoded("raw")).await.unwrap();
+        left.send("x".repeat(64)).await.unwrap();
+
+        for message in messages {
+            assert_eq!(right.receive().await.unwrap(), message);
+        }
+        assert_eq!(right.receive().await.unwrap(), "raw");
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(_)))
+        ));
+
+        let expected: Vec<Vec<u8>> = messages
+            .into_iter()
+            .chain(["raw", &"x".repeat(64)])
+            .map(framed)
+            .collect();
+        assert_eq!(*sent.lock().unwrap(), expected);
+        let mut expected: Vec<(Vec<u8>, bool)> = expected[..4]
+            .iter()
+            .map(|frame| (frame.clone(), false))
+            .collect();
+        expected.push((vec![], true));
+        assert_eq!(*received.lock().unwrap(), expected);
+    }
+}
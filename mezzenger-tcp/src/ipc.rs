@@ -0,0 +1,314 @@
+//! Transports over local inter-process communication channels -
+//! Unix domain sockets on Unix, named pipes on Windows.
+//!
+//! Messages are framed the same way as over TCP, all [Transport] features are available.
+//!
+//! Paths are platform-specific: socket file path on Unix (for example `/tmp/app.sock`),
+//! pipe name on Windows (for example `\\.\pipe\app`).
+
+use std::{io, marker::PhantomData, path::Path};
+
+use futures::Stream;
+use serde::Serialize;
+
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+#[cfg(windows)]
+use std::{ffi::OsString, time::Duration};
+#[cfg(windows)]
+use tokio::{
+    net::windows::named_pipe::{ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions},
+    time::sleep,
+};
+
+use crate::{Config, Transport};
+
+/// Stream of connection established with [connect_ipc](Transport::connect_ipc).
+#[cfg(unix)]
+pub type IpcClientStream = UnixStream;
+
+/// Stream of connection accepted by [IpcListener].
+#[cfg(unix)]
+pub type IpcServerStream = UnixStream;
+
+/// Stream of connection established with [connect_ipc](Transport::connect_ipc).
+#[cfg(windows)]
+pub type IpcClientStream = NamedPipeClient;
+
+/// Stream of connection accepted by [IpcListener].
+#[cfg(windows)]
+pub type IpcServerStream = NamedPipeServer;
+
+/// Windows error returned when all instances of the pipe are busy.
+#[cfg(windows)]
+const ERROR_PIPE_BUSY: i32 = 231;
+
+/// Delay before trying to open busy pipe again.
+#[cfg(windows)]
+const PIPE_BUSY_DELAY: Duration = Duration::from_millis(50);
+
+impl<Codec, Incoming, Outgoing> Transport<IpcClientStream, Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Connect to [IpcListener] listening at provided path.
+    pub async fn connect_ipc(path: impl AsRef<Path>, codec: Codec) -> io::Result<Self> {
+        Transport::connect_ipc_with_config(path, codec, Config::default()).await
+    }
+
+    /// Connect to [IpcListener] listening at provided path using provided [config](Config).
+    pub async fn connect_ipc_with_config(
+        path: impl AsRef<Path>,
+        codec: Codec,
+        config: Config,
+    ) -> io::Result<Self> {
+        let stream = connect(path.as_ref()).await?;
+        Ok(Transport::new_with_config(stream, codec, config))
+    }
+}
+
+#[cfg(unix)]
+async fn connect(path: &Path) -> io::Result<IpcClientStream> {
+    UnixStream::connect(path).await
+}
+
+#[cfg(windows)]
+async fn connect(path: &Path) -> io::Result<IpcClientStream> {
+    loop {
+        match ClientOptions::new().open(path) {
+            Err(error) if error.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                sleep(PIPE_BUSY_DELAY).await
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Listener accepting local IPC connections as [Transport]s.
+///
+/// All accepted transports use the same codec and [config](Config).
+///
+/// **NOTE**: On Unix the socket file is created when binding and removed when
+/// the listener is dropped - binding fails if the file already exists.
+pub struct IpcListener<Codec, Incoming, Outgoing> {
+    #[cfg(unix)]
+    listener: UnixListener,
+    #[cfg(unix)]
+    path: PathBuf,
+    #[cfg(windows)]
+    server: NamedPipeServer,
+    #[cfg(windows)]
+    path: OsString,
+    codec: Codec,
+    config: Config,
+    _incoming: PhantomData<Incoming>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<Codec, Incoming, Outgoing> IpcListener<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec + Clone,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Create new listener listening at provided path.
+    ///
+    /// Accepted transports use default [config](Config).
+    pub fn bind(path: impl AsRef<Path>, codec: Codec) -> io::Result<Self> {
+        IpcListener::bind_with_config(path, codec, Config::default())
+    }
+
+    /// Create new listener listening at provided path.
+    ///
+    /// Accepted transports use provided [config](Config).
+    pub fn bind_with_config(
+        path: impl AsRef<Path>,
+        codec: Codec,
+        config: Config,
+    ) -> io::Result<Self> {
+        let path = path.as_ref();
+        Ok(IpcListener {
+            #[cfg(unix)]
+            listener: UnixListener::bind(path)?,
+            #[cfg(unix)]
+            path: path.to_path_buf(),
+            #[cfg(windows)]
+            server: ServerOptions::new()
+                .first_pipe_instance(true)
+                .create(path)?,
+            #[cfg(windows)]
+            path: path.as_os_str().to_os_string(),
+            codec,
+            config,
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        })
+    }
+
+    /// Path the listener is listening at.
+    pub fn path(&self) -> &Path {
+        self.path.as_ref()
+    }
+
+    /// Config applied to accepted transports.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Change config applied to subsequently accepted transports.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Accept new connection.
+    pub async fn accept(
+        &mut self,
+    ) -> io::Result<Transport<IpcServerStream, Codec, Incoming, Outgoing>> {
+        let stream = self.accept_stream().await?;
+        Ok(Transport::new_with_config(
+            stream,
+            self.codec.clone(),
+            self.config,
+        ))
+    }
+
+    /// Stream of accepted connections.
+    ///
+    /// Never ends - accepting errors are returned as stream items.
+    pub fn incoming(
+        &mut self,
+    ) -> impl Stream<Item = io::Result<Transport<IpcServerStream, Codec, Incoming, Outgoing>>> + '_
+    {
+        futures::stream::unfold(self, |listener| async move {
+            let result = listener.accept().await;
+            Some((result, listener))
+        })
+    }
+
+    #[cfg(unix)]
+    async fn accept_stream(&mut self) -> io::Result<IpcServerStream> {
+        let (stream, _address) = self.listener.accept().await?;
+        Ok(stream)
+    }
+
+    /// Wait for a client to connect to the current pipe instance and create
+    /// next instance for subsequent clients.
+    #[cfg(windows)]
+    async fn accept_stream(&mut self) -> io::Result<IpcServerStream> {
+        self.server.connect().await?;
+        let next = ServerOptions::new().create(&self.path)?;
+        Ok(std::mem::replace(&mut self.server, next))
+    }
+}
+
+#[cfg(unix)]
+impl<Codec, Incoming, Outgoing> Drop for IpcListener<Codec, Incoming, Outgoing> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{path::PathBuf, pin::pin};
+
+    use futures::{SinkExt, StreamExt};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+
+    use crate::{Config, Error, IpcListener, Transport};
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mezzenger-{name}-{}.sock", std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn test_ipc() {
+        let path = socket_path("ipc");
+        let config = Config {
+            max_message_size: 64,
+            ..Default::default()
+        };
+        let mut listener: IpcListener<Codec, String, String> =
+            IpcListener::bind_with_config(&path, Codec::default(), config).unwrap();
+        assert_eq!(listener.path(), path);
+
+        let mut clients = vec![];
+        for _ in 0..2 {
+            let client: Transport<_, Codec, String, String> =
+                Transport::connect_ipc(&path, Codec::default())
+                    .await
+                    .unwrap();
+            clients.push(client);
+        }
+
+        {
+            let mut incoming = pin!(listener.incoming());
+            for (index, client) in clients.iter_mut().enumerate() {
+                let mut server = incoming.next().await.unwrap().unwrap();
+
+                client
+                    .send(format!("Hello from client {index}"))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    server.receive().await.unwrap(),
+                    format!("Hello from client {index}")
+                );
+                server.send(format!("Hello client {index}")).await.unwrap();
+                assert_eq!(
+                    client.receive().await.unwrap(),
+                    format!("Hello client {index}")
+                );
+
+                assert!(matches!(
+                    server.send("x".repeat(100)).await,
+                    Err(mezzenger::Error::Other(Error::MessageTooLarge(_)))
+                ));
+            }
+        }
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_ipc_peer_gone() {
+        let path = socket_path("peer-gone");
+        let mut listener: IpcListener<Codec, u32, u32> =
+            IpcListener::bind(&path, Codec::default()).unwrap();
+
+        let client: Transport<_, Codec, u32, u32> = Transport::connect_ipc(&path, Codec::default())
+            .await
+            .unwrap();
+        let mut server = listener.accept().await.unwrap();
+        drop(client);
+
+        assert!(matches!(
+            server.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        // writing into a socket without reader fails with broken pipe
+        let mut result = Ok(());
+        for i in 0..100 {
+            result = server.send(i).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(result, Err(mezzenger::Error::Closed)));
+    }
+
+    #[tokio::test]
+    async fn test_ipc_bind_existing() {
+        let path = socket_path("existing");
+        let _listener: IpcListener<Codec, u32, u32> =
+            IpcListener::bind(&path, Codec::default()).unwrap();
+        assert!(IpcListener::<Codec, u32, u32>::bind(&path, Codec::default()).is_err());
+    }
+}
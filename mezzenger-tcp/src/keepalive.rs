@@ -0,0 +1,105 @@
+//! Application-level heartbeat.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use tokio::time::{sleep_until, Instant, Sleep};
+
+/// Heartbeat configuration.
+///
+/// When enabled, transport sends ping frames if nothing was sent for
+/// [interval](Keepalive::interval) and answers received pings with pongs.<br>
+/// If nothing is received for [timeout](Keepalive::timeout) receiving stream
+/// returns [Error::TimedOut](crate::Error::TimedOut) and terminates.
+///
+/// Heartbeat frames are never returned from receiving stream.
+///
+/// **NOTE**: Both ends of the connection have to enable it -
+/// otherwise receiving end reports [Error::ProtocolError](crate::Error::ProtocolError).
+///
+/// **NOTE**: Heartbeat is driven by polling receiving stream - make sure it is polled
+/// (for example by awaiting incoming messages in a separate task).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keepalive {
+    /// Idle time after which ping is sent.
+    pub interval: Duration,
+    /// Time without receiving anything after which connection is considered dead.
+    ///
+    /// Should be noticeably longer than `interval` used by the peer.
+    pub timeout: Duration,
+}
+
+impl Keepalive {
+    /// Create new heartbeat configuration.
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Keepalive { interval, timeout }
+    }
+}
+
+/// Heartbeat event returned by [KeepaliveState::poll_event].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Event {
+    /// Ping should be sent.
+    Ping,
+    /// Nothing was received for too long.
+    TimedOut,
+}
+
+pub(crate) struct KeepaliveState {
+    keepalive: Keepalive,
+    next_ping: Instant,
+    receive_deadline: Instant,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl KeepaliveState {
+    pub(crate) fn new(keepalive: Keepalive) -> Self {
+        let now = Instant::now();
+        KeepaliveState {
+            keepalive,
+            next_ping: now + keepalive.interval,
+            receive_deadline: now + keepalive.timeout,
+            timer: None,
+        }
+    }
+
+    /// Postpone next ping (something was just sent).
+    pub(crate) fn sent(&mut self) {
+        self.next_ping = Instant::now() + self.keepalive.interval;
+    }
+
+    /// Postpone timeout (something was just received).
+    pub(crate) fn received(&mut self) {
+        self.receive_deadline = Instant::now() + self.keepalive.timeout;
+    }
+
+    /// Poll for next heartbeat event.
+    ///
+    /// Returns [Poll::Pending] and schedules wake up for the nearest deadline
+    /// if there is nothing to do yet.
+    pub(crate) fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Event> {
+        loop {
+            let now = Instant::now();
+            if now >= self.receive_deadline {
+                return Poll::Ready(Event::TimedOut);
+            }
+            if now >= self.next_ping {
+                self.sent();
+                return Poll::Ready(Event::Ping);
+            }
+
+            let deadline = self.next_ping.min(self.receive_deadline);
+            let timer = self
+                .timer
+                .get_or_insert_with(|| Box::pin(sleep_until(deadline)));
+            if timer.deadline() != deadline {
+                timer.as_mut().reset(deadline);
+            }
+            ready!(timer.as_mut().poll(cx));
+        }
+    }
+}
@@ -20,27 +20,232 @@
 
 use std::{
     fmt::{Debug, Display},
-    io::ErrorKind,
+    future::Future,
+    io::{self, ErrorKind},
     marker::PhantomData,
-    pin::Pin,
-    task::{Context, Poll},
+    ops::Range,
+    pin::{pin, Pin},
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::Duration,
 };
 
-use bytes::{Buf, BufMut, BytesMut};
-use futures::{ready, stream::FusedStream, Sink, Stream};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{future::poll_fn, ready, stream::FusedStream, task::noop_waker_ref, Sink, Stream};
 use kodec::{Decode, Encode};
 use pin_project::pin_project;
 use serde::Serialize;
-use tokio::io::{AsyncRead, AsyncWrite};
-use tokio_util::io::{poll_read_buf, poll_write_buf};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    time::{sleep_until, timeout_at, Instant, Sleep},
+};
+use tokio_util::io::poll_read_buf;
+
+mod builder;
+mod codec;
+mod connect;
+mod framing;
+mod handshake;
+mod inspect;
+#[cfg(feature = "ipc")]
+mod ipc;
+mod keepalive;
+mod listener;
+mod raw;
+mod send;
+mod split;
+mod stats;
+mod streaming;
+mod write_timeout;
+pub use builder::TransportBuilder;
+pub use codec::MessageCodec;
+pub use connect::RetryPolicy;
+pub use framing::{ByteOrder, Framing};
+use framing::{ControlFrame, FrameOptions};
+pub use handshake::{Handshake, HANDSHAKE_SIZE};
+use inspect::{IncomingFrameHook, OutgoingFrameHook};
+#[cfg(feature = "ipc")]
+pub use ipc::{IpcClientStream, IpcListener, IpcServerStream};
+pub use keepalive::Keepalive;
+use keepalive::KeepaliveState;
+pub use listener::Listener;
+pub use raw::{RawError, RawTransport};
+use send::SendQueue;
+pub use split::{Receiver, Sender};
+pub use stats::{Stats, TransportStats};
+use streaming::{BoxedDecoder, DecoderFactory};
+pub use streaming::{StreamingCodec, StreamingDecoder};
+use write_timeout::WriteTimeout;
+
+#[cfg(feature = "compression")]
+mod compression;
+#[cfg(feature = "offload")]
+mod offload;
+#[cfg(feature = "compression")]
+pub use compression::{Compression, DEFAULT_COMPRESSION_THRESHOLD};
+#[cfg(feature = "offload")]
+use offload::DecodeOffload;
 
 pub const DEFAULT_MAX_MESSAGE_SIZE: u32 = 65536;
 
+/// Handling of incoming messages larger than [max_message_size](Config::max_message_size).
+///
+/// Either way receiving stream returns [Error::MessageTooLarge] with size advertised by the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OversizePolicy {
+    /// Skip the message and keep receiving following ones.
+    ///
+    /// This is the default.
+    #[default]
+    Skip,
+    /// Terminate receiving stream and shut down the connection.
+    ///
+    /// Useful when oversized message means misbehaving or malicious peer.
+    Close,
+}
+
+/// Transport configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum serialized message size.
+    ///
+    /// Sending or receiving messages of larger size will result in [Error::MessageTooLarge].<br>
+    /// Also limited by maximum size supported by [framing](Config::framing).
+    pub max_message_size: u64,
+    /// Length prefix strategy.
+    ///
+    /// **NOTE**: Both ends of the connection have to use the same framing.
+    pub framing: Framing,
+    /// Byte order of fixed-size length prefix.
+    ///
+    /// **NOTE**: Both ends of the connection have to use the same byte order.
+    pub byte_order: ByteOrder,
+    /// Append CRC32 checksum to every message.
+    ///
+    /// Received messages with invalid checksum result in [Error::ChecksumMismatch].
+    ///
+    /// **NOTE**: Both ends of the connection have to enable it -
+    /// otherwise receiving end reports [Error::ProtocolError].
+    pub checksum: bool,
+    /// Compression applied to messages larger than
+    /// [compression_threshold](Config::compression_threshold).
+    ///
+    /// Maximum message size is enforced on decompressed messages.
+    ///
+    /// **NOTE**: Both ends of the connection have to use the same compression algorithm.
+    #[cfg(feature = "compression")]
+    pub compression: Compression,
+    /// Size of serialized message above which it gets compressed.
+    #[cfg(feature = "compression")]
+    pub compression_threshold: usize,
+    /// Application-level heartbeat detecting dead connections.
+    ///
+    /// Disabled by default.
+    ///
+    /// **NOTE**: Both ends of the connection have to enable it.
+    pub keepalive: Option<Keepalive>,
+    /// Maximum time a flush can go without writing anything.
+    ///
+    /// If the peer stops reading and nothing can be written for this long,
+    /// flush fails with [Error::WriteTimedOut] and the transport terminates.<br>
+    /// Every write (even partial) restarts the timer.
+    ///
+    /// Disabled by default.
+    pub write_timeout: Option<Duration>,
+    /// Maximum time receiving stream waits for the peer to send anything.
+    ///
+    /// If nothing (not even a part of a frame) is received for this long, receiving stream
+    /// returns [Error::ReadTimedOut] - see [read_timeout_fatal](Config::read_timeout_fatal).<br>
+    /// Every received byte restarts the timer, it doesn't run while reading is
+    /// [paused](Transport::pause_reading).
+    ///
+    /// Disabled by default.
+    pub read_timeout: Option<Duration>,
+    /// Terminate receiving stream after [read timeout](Config::read_timeout) elapses.
+    ///
+    /// Otherwise receiving stream keeps going and [Error::ReadTimedOut] is returned
+    /// again if the peer stays silent for another read timeout.
+    ///
+    /// Disabled by default.
+    pub read_timeout_fatal: bool,
+    /// Initial capacity of the buffer holding outgoing data.
+    pub send_buffer_capacity: usize,
+    /// Initial capacity of the buffer holding incoming data.
+    pub receive_buffer_capacity: usize,
+    /// Minimum free space in the buffer holding incoming data reserved before every read.
+    pub read_chunk_size: usize,
+    /// Maximum capacity of the buffer holding incoming data retained after
+    /// receiving larger messages.
+    ///
+    /// Buffer grown above this capacity (to hold a large message) is shrunk
+    /// back to [receive_buffer_capacity](Config::receive_buffer_capacity) once the message
+    /// is received.
+    pub receive_buffer_retained_capacity: usize,
+    /// Treat oversized first frame of the connection as protocol mismatch.
+    ///
+    /// When the length prefix of the very first frame exceeds
+    /// [max_message_size](Config::max_message_size), the peer most likely doesn't
+    /// speak this protocol at all - receiving stream returns [Error::ProtocolMismatch]
+    /// and terminates instead of skipping the frame.
+    ///
+    /// Disabled by default.
+    pub strict: bool,
+    /// Handling of incoming messages larger than [max_message_size](Config::max_message_size).
+    pub oversize_policy: OversizePolicy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE as u64,
+            framing: Framing::default(),
+            byte_order: ByteOrder::default(),
+            checksum: false,
+            #[cfg(feature = "compression")]
+            compression: Compression::default(),
+            #[cfg(feature = "compression")]
+            compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            keepalive: None,
+            write_timeout: None,
+            read_timeout: None,
+            read_timeout_fatal: false,
+            send_buffer_capacity: 0,
+            receive_buffer_capacity: 0,
+            read_chunk_size: 8192,
+            receive_buffer_retained_capacity: 2 * DEFAULT_MAX_MESSAGE_SIZE as usize,
+            strict: false,
+            oversize_policy: OversizePolicy::default(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error<SerializationError, DeserializationError> {
-    MessageTooLarge,
+    MessageTooLarge(u64),
     SerializationError(SerializationError),
     DeserializationError(DeserializationError),
+    ProtocolError,
+    ProtocolMismatch(u64),
+    VersionMismatch {
+        ours: u16,
+        theirs: u16,
+    },
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    #[cfg(feature = "compression")]
+    CompressionError(std::io::Error),
+    #[cfg(feature = "type-tag")]
+    TypeMismatch {
+        expected: &'static str,
+        got: String,
+    },
+    UnflushedData(usize),
+    TimedOut,
+    WriteTimedOut,
+    ReadTimedOut,
     IoError(std::io::Error),
 }
 
@@ -52,11 +257,40 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Error::MessageTooLarge => write!(f, "message was too large"),
+            Error::MessageTooLarge(size) => write!(f, "message was too large ({size} bytes)"),
             Error::SerializationError(error) => write!(f, "failed to serialize message: {error}"),
             Error::DeserializationError(error) => {
                 write!(f, "failed to deserialize message: {error}")
             }
+            Error::ProtocolError => write!(f, "received malformed message frame"),
+            Error::ProtocolMismatch(value) => write!(
+                f,
+                "peer doesn't seem to speak the protocol (connection started with {value:#x})"
+            ),
+            Error::VersionMismatch { ours, theirs } => write!(
+                f,
+                "peer uses different protocol version (ours: {ours}, theirs: {theirs})"
+            ),
+            Error::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "message checksum mismatch (expected {expected:#010x}, got {actual:#010x})"
+            ),
+            #[cfg(feature = "compression")]
+            Error::CompressionError(error) => {
+                write!(f, "failed to (de)compress message: {error}")
+            }
+            #[cfg(feature = "type-tag")]
+            Error::TypeMismatch { expected, got } => {
+                write!(f, "message type mismatch (expected {expected}, got {got})")
+            }
+            Error::UnflushedData(bytes) => {
+                write!(f, "timed out with {bytes} bytes not flushed")
+            }
+            Error::TimedOut => write!(f, "nothing was received from the peer for too long"),
+            Error::WriteTimedOut => write!(f, "nothing could be written to the peer for too long"),
+            Error::ReadTimedOut => {
+                write!(f, "nothing was received from the peer within read timeout")
+            }
             Error::IoError(error) => write!(f, "IO error occurred: {error}"),
         }
     }
@@ -70,26 +304,223 @@ where
 {
 }
 
+impl<SerializationError, DeserializationError> From<std::io::Error>
+    for Error<SerializationError, DeserializationError>
+{
+    fn from(error: std::io::Error) -> Self {
+        Error::IoError(error)
+    }
+}
+
+#[cfg(feature = "type-tag")]
+impl<SerializationError, DeserializationError> From<mezzenger::type_tag::TypeMismatch>
+    for Error<SerializationError, DeserializationError>
+{
+    fn from(mismatch: mezzenger::type_tag::TypeMismatch) -> Self {
+        Error::TypeMismatch {
+            expected: mismatch.expected,
+            got: mismatch.got,
+        }
+    }
+}
+
 struct ReceiveState {
     pub buffer: BytesMut,
-    pub message_size: u32,
-    pub receiving_size: bool,
-    pub bytes_to_receive: i64,
-    pub bytes_to_skip: u32,
+    pub initial_capacity: usize,
+    pub read_chunk_size: usize,
+    pub retained_capacity: usize,
+    pub oversized: bool,
+    pub frame_size: Option<u64>,
+    pub bytes_to_skip: u64,
+    pub frame_received: bool,
+    pub paused: bool,
+    pub deliver_buffered_while_paused: bool,
+    pub waker: Option<Waker>,
+    pub read_timeout: Option<Duration>,
+    pub read_timeout_fatal: bool,
+    pub read_timer: Option<Pin<Box<Sleep>>>,
 }
 
 impl ReceiveState {
-    fn new() -> Self {
+    fn new(buffer_capacity: usize, read_chunk_size: usize, retained_capacity: usize) -> Self {
         ReceiveState {
-            buffer: BytesMut::new(),
-            message_size: 0,
-            receiving_size: true,
-            bytes_to_receive: 4,
+            buffer: BytesMut::with_capacity(buffer_capacity),
+            initial_capacity: buffer_capacity,
+            read_chunk_size,
+            retained_capacity,
+            oversized: false,
+            frame_size: None,
             bytes_to_skip: 0,
+            frame_received: false,
+            paused: false,
+            deliver_buffered_while_paused: true,
+            waker: None,
+            read_timeout: None,
+            read_timeout_fatal: false,
+            read_timer: None,
+        }
+    }
+
+    fn with_read_timeout(mut self, read_timeout: Option<Duration>, fatal: bool) -> Self {
+        self.read_timeout = read_timeout;
+        self.read_timeout_fatal = fatal;
+        self
+    }
+
+    /// Something was received - restart read timeout.
+    fn restart_read_timer(&mut self) {
+        if let (Some(read_timeout), Some(timer)) = (self.read_timeout, &mut self.read_timer) {
+            timer.as_mut().reset(Instant::now() + read_timeout);
+        }
+    }
+
+    /// Poll whether nothing was received for longer than read timeout
+    /// (restarting the timer if so).
+    fn poll_read_timeout(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(read_timeout) = self.read_timeout else {
+            return Poll::Pending;
+        };
+        let timer = self
+            .read_timer
+            .get_or_insert_with(|| Box::pin(sleep_until(Instant::now() + read_timeout)));
+        ready!(timer.as_mut().poll(cx));
+        timer.as_mut().reset(Instant::now() + read_timeout);
+        Poll::Ready(())
+    }
+
+    /// Reserve space for `additional` bytes.
+    fn reserve(&mut self, additional: usize) {
+        self.buffer.reserve(additional);
+        if self.buffer.capacity() > self.retained_capacity {
+            self.oversized = true;
+        }
+    }
+
+    /// Shrink buffer grown above retained capacity, if its content fits in retained capacity.
+    fn release_excess(&mut self) {
+        if self.oversized && self.buffer.len() <= self.retained_capacity {
+            let mut buffer = BytesMut::with_capacity(self.initial_capacity.max(self.buffer.len()));
+            buffer.extend_from_slice(&self.buffer);
+            self.buffer = buffer;
+            self.oversized = false;
+        }
+    }
+
+    fn wait_for_resume(&mut self, waker: &Waker) {
+        match &self.waker {
+            Some(current) if current.will_wake(waker) => {}
+            _ => self.waker = Some(waker.clone()),
+        }
+    }
+}
+
+/// Message payload of a received frame.
+enum Payload<'a> {
+    /// Frame of `frame_size` bytes at the front of receive buffer,
+    /// message occupies `message` range of it.
+    Buffered {
+        buffer: &'a mut BytesMut,
+        frame_size: usize,
+        message: Range<usize>,
+    },
+    /// Decompressed message (frame was already removed from receive buffer).
+    #[cfg(feature = "compression")]
+    Decompressed(Vec<u8>),
+}
+
+impl Payload<'_> {
+    /// Encoded message.
+    fn message(&self) -> &[u8] {
+        match self {
+            Payload::Buffered {
+                buffer, message, ..
+            } => &buffer[message.clone()],
+            #[cfg(feature = "compression")]
+            Payload::Decompressed(data) => data,
+        }
+    }
+
+    /// Size of the message.
+    #[cfg(feature = "offload")]
+    fn len(&self) -> usize {
+        match self {
+            Payload::Buffered { message, .. } => message.len(),
+            #[cfg(feature = "compression")]
+            Payload::Decompressed(data) => data.len(),
+        }
+    }
+
+    /// Decode message and remove its frame from receive buffer.
+    fn decode<R>(self, decode: impl FnOnce(&[u8]) -> R) -> R {
+        match self {
+            Payload::Buffered {
+                buffer,
+                frame_size,
+                message,
+            } => {
+                let result = decode(&buffer[message]);
+                buffer.advance(frame_size);
+                result
+            }
+            #[cfg(feature = "compression")]
+            Payload::Decompressed(data) => decode(&data),
+        }
+    }
+
+    /// Remove frame from receive buffer returning the message without copying it.
+    fn freeze(self) -> Bytes {
+        match self {
+            Payload::Buffered {
+                buffer,
+                frame_size,
+                message,
+            } => buffer.split_to(frame_size).freeze().slice(message),
+            #[cfg(feature = "compression")]
+            Payload::Decompressed(data) => data.into(),
+        }
+    }
+
+    /// Remove frame from receive buffer discarding the message.
+    #[cfg(feature = "compression")]
+    fn skip(self) {
+        if let Payload::Buffered {
+            buffer, frame_size, ..
+        } = self
+        {
+            buffer.advance(frame_size);
         }
     }
 }
 
+/// Item produced from a received frame by [Transport::poll_next_with].
+trait Received<Incoming>: From<Incoming> {
+    /// Was the message already decoded (and should be counted as received).
+    fn decoded(&self) -> bool {
+        true
+    }
+}
+
+impl<Incoming> Received<Incoming> for Incoming {}
+
+/// Decode received message (checking its type tag first if `type-tag` feature is enabled).
+#[allow(clippy::type_complexity)]
+#[cfg_attr(not(feature = "type-tag"), allow(clippy::extra_unused_type_parameters))]
+fn decode_message<Codec, Incoming, Outgoing>(
+    codec: &Codec,
+    message: &[u8],
+) -> Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    #[cfg(feature = "type-tag")]
+    let message = match mezzenger::type_tag::check::<Incoming, Outgoing>(message) {
+        Ok(message) => message,
+        Err(mismatch) => return Err(Error::from(mismatch)),
+    };
+    codec.decode(message).map_err(Error::DeserializationError)
+}
+
 /// Transport for communication over [tokio](https://tokio.rs/)'s TCP implementation.
 ///
 /// Wraps over struct implementing [tokio::io::AsyncWrite] and [tokio::io::AsyncRead].
@@ -103,11 +534,32 @@ where
 {
     #[pin]
     inner: T,
-    send_buffer: BytesMut,
+    send_queue: SendQueue,
     receive_state: ReceiveState,
     codec: Codec,
+    receive_codec: Option<Codec>,
     terminated: bool,
-    max_message_size: u32,
+    read_closed: bool,
+    write_closed: bool,
+    max_message_size: u64,
+    strict: bool,
+    oversize_policy: OversizePolicy,
+    framing: Framing,
+    byte_order: ByteOrder,
+    frame_options: FrameOptions,
+    #[cfg(feature = "compression")]
+    compression_threshold: usize,
+    keepalive: Option<KeepaliveState>,
+    write_timeout: Option<WriteTimeout>,
+    streaming_decoder: Option<DecoderFactory<Codec, Incoming>>,
+    active_decoder: Option<(BoxedDecoder<Incoming, <Codec as Decode>::Error>, u64)>,
+    #[cfg(feature = "offload")]
+    decode_offload: Option<DecodeOffload<Codec, Incoming>>,
+    outgoing_frame_hook: Option<OutgoingFrameHook>,
+    incoming_frame_hook: Option<IncomingFrameHook>,
+    stats: Arc<TransportStats>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
     _incoming: PhantomData<Incoming>,
     _outgoing: PhantomData<Outgoing>,
 }
@@ -124,6 +576,9 @@ where
     ///
     /// **NOTE**: By default serialized message size is limited to [DEFAULT_MAX_MESSAGE_SIZE].<br>
     /// Sending or receiving messages of larger size will result in [Error::MessageTooLarge].
+    ///
+    /// **NOTE**: Socket options of streams other than [TcpStream] (like `TCP_NODELAY`)
+    /// have to be configured before constructing the transport.
     pub fn new(transport: T, codec: Codec) -> Self {
         Transport::new_with_max_message_size(transport, codec, DEFAULT_MAX_MESSAGE_SIZE)
     }
@@ -134,334 +589,3268 @@ where
     /// Serialized message size will be limited to `max_message_size`.<br>
     /// Sending or receiving messages of larger size will result in [Error::MessageTooLarge].
     pub fn new_with_max_message_size(transport: T, codec: Codec, max_message_size: u32) -> Self {
+        Transport::new_with_config(
+            transport,
+            codec,
+            Config {
+                max_message_size: max_message_size as u64,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create new transport wrapping a provided struct implementing
+    /// [tokio::io::AsyncWrite] and [tokio::io::AsyncRead], using provided [framing].
+    ///
+    /// **NOTE**: Both ends of the connection have to use the same [framing].
+    ///
+    /// [framing]: crate::Framing
+    pub fn new_with_framing(transport: T, codec: Codec, framing: Framing) -> Self {
+        Transport::new_with_config(
+            transport,
+            codec,
+            Config {
+                framing,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create new transport wrapping a provided struct implementing
+    /// [tokio::io::AsyncWrite] and [tokio::io::AsyncRead], using provided [byte order]
+    /// of the length prefix.
+    ///
+    /// **NOTE**: Both ends of the connection have to use the same [byte order].
+    ///
+    /// [byte order]: crate::ByteOrder
+    pub fn new_with_byte_order(transport: T, codec: Codec, byte_order: ByteOrder) -> Self {
+        Transport::new_with_config(
+            transport,
+            codec,
+            Config {
+                byte_order,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create new transport wrapping a provided struct implementing
+    /// [tokio::io::AsyncWrite] and [tokio::io::AsyncRead], with application-level
+    /// heartbeat enabled.
+    ///
+    /// Ping is sent when nothing was sent for `interval`, receiving stream returns
+    /// [Error::TimedOut] and terminates when nothing was received for `timeout`.<br>
+    /// See [Keepalive] for details.
+    ///
+    /// **NOTE**: Both ends of the connection have to enable heartbeat.
+    pub fn new_with_keepalive(
+        transport: T,
+        codec: Codec,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Self {
+        Transport::new_with_config(
+            transport,
+            codec,
+            Config {
+                keepalive: Some(Keepalive::new(interval, timeout)),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create new transport wrapping a provided struct implementing
+    /// [tokio::io::AsyncWrite] and [tokio::io::AsyncRead], using provided [config].
+    ///
+    /// [config]: crate::Config
+    pub fn new_with_config(transport: T, codec: Codec, config: Config) -> Self {
+        let Config {
+            max_message_size,
+            framing,
+            byte_order,
+            checksum,
+            #[cfg(feature = "compression")]
+            compression,
+            #[cfg(feature = "compression")]
+            compression_threshold,
+            keepalive,
+            write_timeout,
+            read_timeout,
+            read_timeout_fatal,
+            send_buffer_capacity,
+            receive_buffer_capacity,
+            read_chunk_size,
+            receive_buffer_retained_capacity,
+            strict,
+            oversize_policy,
+        } = config;
+        let frame_options = FrameOptions {
+            checksum,
+            keepalive: keepalive.is_some(),
+            #[cfg(feature = "compression")]
+            compression,
+        };
+        let max_message_size =
+            max_message_size.min(framing.max_message_size() - frame_options.overhead());
         Transport {
             inner: transport,
             codec,
-            send_buffer: BytesMut::new(),
-            receive_state: ReceiveState::new(),
+            receive_codec: None,
+            send_queue: SendQueue::new(send_buffer_capacity),
+            receive_state: ReceiveState::new(
+                receive_buffer_capacity,
+                read_chunk_size,
+                receive_buffer_retained_capacity,
+            )
+            .with_read_timeout(read_timeout, read_timeout_fatal),
             terminated: false,
+            read_closed: false,
+            write_closed: false,
             max_message_size,
+            strict,
+            oversize_policy,
+            framing,
+            byte_order,
+            frame_options,
+            #[cfg(feature = "compression")]
+            compression_threshold,
+            keepalive: keepalive.map(KeepaliveState::new),
+            write_timeout: write_timeout.map(WriteTimeout::new),
+            streaming_decoder: None,
+            active_decoder: None,
+            #[cfg(feature = "offload")]
+            decode_offload: None,
+            outgoing_frame_hook: None,
+            incoming_frame_hook: None,
+            stats: Arc::new(TransportStats::default()),
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("mezzenger_tcp::transport", peer = tracing::field::Empty),
             _incoming: PhantomData,
             _outgoing: PhantomData,
         }
     }
-}
-
-impl<T, Codec, Incoming, Outgoing> Sink<Outgoing> for Transport<T, Codec, Incoming, Outgoing>
-where
-    T: AsyncWrite + AsyncRead,
-    Codec: kodec::Codec,
-    for<'de> Incoming: serde::de::Deserialize<'de>,
-    Outgoing: Serialize,
-{
-    type Error = mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
 
-    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+    /// Stop reading from the underlying stream.
+    ///
+    /// While paused, receiving stream won't issue any reads, so unread data
+    /// accumulates in kernel buffers and backpressure propagates to the peer.<br>
+    /// The connection stays open.
+    ///
+    /// Complete messages already buffered by the transport are still returned
+    /// unless disabled with [set_deliver_buffered_while_paused].
+    ///
+    /// [set_deliver_buffered_while_paused]: Transport::set_deliver_buffered_while_paused
+    pub fn pause_reading(&mut self) {
+        self.receive_state.paused = true;
     }
 
-    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
-        if self.terminated {
-            Err(mezzenger::Error::Closed)
-        } else {
-            let me = self.project();
-            let size_position = me.send_buffer.len();
-            me.send_buffer.put_u32(0);
-            let current_length = me.send_buffer.len();
-            me.codec
-                .encode(me.send_buffer.writer(), &item)
-                .map_err(Error::SerializationError)
-                .map_err(mezzenger::Error::Other)?;
-            let message_size = me.send_buffer.len() - current_length;
-            if message_size > *me.max_message_size as usize {
-                me.send_buffer.truncate(size_position);
-                Err(mezzenger::Error::Other(Error::MessageTooLarge))
-            } else {
-                let size_slice = &mut me.send_buffer[size_position..(size_position + 4)];
-                let message_size = message_size as u32;
-                size_slice.swap_with_slice(&mut message_size.to_be_bytes());
-                Ok(())
-            }
+    /// Resume reading paused with [pause_reading](Transport::pause_reading).
+    ///
+    /// Heartbeat timeout (if [enabled](Config::keepalive)) is not enforced while
+    /// reading is paused and restarts when reading is resumed.
+    pub fn resume_reading(&mut self) {
+        self.receive_state.paused = false;
+        self.receive_state.restart_read_timer();
+        if let Some(keepalive) = &mut self.keepalive {
+            keepalive.received();
+        }
+        if let Some(waker) = self.receive_state.waker.take() {
+            waker.wake();
         }
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        let mut me = self.project();
+    /// Is reading currently paused.
+    pub fn is_reading_paused(&self) -> bool {
+        self.receive_state.paused
+    }
 
-        let result = if me.send_buffer.is_empty() {
-            ready!(me.inner.poll_flush(cx))
-        } else {
-            ready!(poll_write_buf(me.inner.as_mut(), cx, me.send_buffer)).map(|_| ())
-        }
-        .map_err(|error| match error.kind() {
-            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => mezzenger::Error::Closed,
-            _ => mezzenger::Error::Other(Error::IoError(error)),
-        });
+    /// Pre-size buffer messages are serialized into to `capacity` bytes.
+    ///
+    /// Every message is serialized into the same buffer (retaining its capacity) before
+    /// being framed and queued - pre-sizing it avoids reallocations while the first
+    /// (large) messages are sent.
+    pub fn with_send_capacity(mut self, capacity: usize) -> Self {
+        self.send_queue.encode_buffer.reserve(capacity);
+        self
+    }
 
-        Poll::Ready(result)
+    /// Maximum serialized message size.
+    ///
+    /// See [Config::max_message_size].
+    pub fn max_message_size(&self) -> u64 {
+        self.max_message_size
     }
 
-    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        let me = self.project();
-        let result = ready!(me.inner.poll_shutdown(cx)).map_err(|error| match error.kind() {
-            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => mezzenger::Error::Closed,
-            _ => mezzenger::Error::Other(Error::IoError(error)),
-        });
-        Poll::Ready(result)
+    /// Maximum time receiving stream waits for the peer to send anything.
+    ///
+    /// See [Config::read_timeout].
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.receive_state.read_timeout
     }
-}
 
-impl<T, Codec, Incoming, Outgoing> Stream for Transport<T, Codec, Incoming, Outgoing>
-where
-    T: AsyncWrite + AsyncRead,
-    Codec: kodec::Codec,
-    for<'de> Incoming: serde::de::Deserialize<'de>,
-    Outgoing: Serialize,
-{
-    type Item = Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+    /// Change (or disable) read timeout.
+    ///
+    /// Timer restarts from the moment of this call.<br>
+    /// See [Config::read_timeout].
+    pub fn set_read_timeout(&mut self, read_timeout: Option<Duration>) {
+        self.receive_state.read_timeout = read_timeout;
+        self.receive_state.read_timer = None;
+    }
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if self.terminated {
-            return Poll::Ready(None);
-        }
+    /// Change whether receiving stream terminates after read timeout elapses.
+    ///
+    /// See [Config::read_timeout_fatal].
+    pub fn set_read_timeout_fatal(&mut self, fatal: bool) {
+        self.receive_state.read_timeout_fatal = fatal;
+    }
 
-        let mut me = self.project();
-        loop {
-            if me.receive_state.bytes_to_receive <= 0 {
-                if me.receive_state.receiving_size {
-                    let message_size = me.receive_state.buffer.get_u32();
-                    me.receive_state.message_size = message_size;
-                    me.receive_state.bytes_to_receive += message_size as i64;
-                    if message_size > *me.max_message_size {
-                        me.receive_state.bytes_to_receive += 4;
-                        if me.receive_state.bytes_to_receive > 0 {
-                            me.receive_state.bytes_to_skip = message_size;
-                        } else {
-                            me.receive_state.buffer.advance(message_size as usize);
-                        }
-                        return Poll::Ready(Some(Err(Error::MessageTooLarge)));
-                    } else {
-                        me.receive_state.receiving_size = false;
-                    }
-                } else {
-                    let message_size = me.receive_state.message_size as usize;
-                    let message = &me.receive_state.buffer[0..message_size];
-                    let result: Result<Incoming, _> = me.codec.decode(message);
-                    me.receive_state.buffer.advance(message_size);
-                    me.receive_state.receiving_size = true;
-                    me.receive_state.bytes_to_receive += 4;
-                    return {
-                        match result {
-                            Ok(message) => Poll::Ready(Some(Ok(message))),
-                            Err(error) => {
-                                Poll::Ready(Some(Err(Error::DeserializationError(error))))
-                            }
-                        }
-                    };
-                }
-            } else {
-                let result = ready!(poll_read_buf(
-                    me.inner.as_mut(),
-                    cx,
-                    &mut me.receive_state.buffer
-                ));
-                match result {
-                    Ok(bytes_read) => {
-                        if bytes_read == 0 {
-                            *me.terminated = true;
-                            return Poll::Ready(None);
-                        }
-                        me.receive_state.bytes_to_receive = me
-                            .receive_state
-                            .bytes_to_receive
-                            .saturating_sub_unsigned(bytes_read as u64);
-                        if me.receive_state.bytes_to_skip > 0 {
-                            let buffer_len = me.receive_state.buffer.len();
-                            let skipped = buffer_len.min(me.receive_state.bytes_to_skip as usize);
-                            if skipped == buffer_len {
-                                me.receive_state.buffer.clear();
-                            } else {
-                                me.receive_state.buffer.advance(skipped);
-                            }
-                            me.receive_state.bytes_to_skip = me
-                                .receive_state
-                                .bytes_to_skip
-                                .saturating_sub(skipped as u32);
-                        }
-                    }
-                    Err(error) => match error.kind() {
-                        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => {
-                            *me.terminated = true;
-                            return Poll::Ready(None);
-                        }
-                        _ => return Poll::Ready(Some(Err(Error::IoError(error)))),
-                    },
-                }
-            }
-        }
+    /// Change maximum serialized message size.
+    ///
+    /// Applies to messages sent after this call and to incoming messages whose
+    /// length prefix wasn't received yet - message currently being received is
+    /// finished (or skipped) according to the limit in effect when its length
+    /// prefix arrived.<br>
+    /// Limited by maximum size supported by [framing](Config::framing).
+    pub fn set_max_message_size(&mut self, max_message_size: u64) {
+        self.max_message_size =
+            max_message_size.min(self.framing.max_message_size() - self.frame_options.overhead());
     }
-}
 
-impl<T, Codec, Incoming, Outgoing> FusedStream for Transport<T, Codec, Incoming, Outgoing>
-where
-    T: AsyncWrite + AsyncRead,
-    Codec: kodec::Codec,
-    for<'de> Incoming: serde::de::Deserialize<'de>,
-    Outgoing: Serialize,
-{
-    fn is_terminated(&self) -> bool {
-        self.terminated
+    /// Codec used to encode outgoing messages.
+    ///
+    /// It's also used to decode incoming messages unless a separate receiving
+    /// codec was set with [swap_receive_codec](Transport::swap_receive_codec)
+    /// or [swap_send_codec](Transport::swap_send_codec).
+    pub fn codec(&self) -> &Codec {
+        &self.codec
     }
-}
 
-impl<T, Codec, Incoming, Outgoing> mezzenger::Reliable for Transport<T, Codec, Incoming, Outgoing>
-where
-    T: AsyncWrite + AsyncRead,
-    Codec: kodec::Codec,
-    for<'de> Incoming: serde::de::Deserialize<'de>,
-    Outgoing: Serialize,
-{
-}
+    /// Mutable reference to codec used to encode outgoing messages.
+    ///
+    /// See [codec](Transport::codec).
+    pub fn codec_mut(&mut self) -> &mut Codec {
+        &mut self.codec
+    }
 
-impl<T, Codec, Incoming, Outgoing> mezzenger::Order for Transport<T, Codec, Incoming, Outgoing>
-where
-    T: AsyncWrite + AsyncRead,
-    Codec: kodec::Codec,
-    for<'de> Incoming: serde::de::Deserialize<'de>,
-    Outgoing: Serialize,
-{
-}
+    /// Replace codec used in both directions.
+    ///
+    /// Messages sent after this call are encoded with the new codec,
+    /// received messages are decoded with the new codec starting with the next one -
+    /// even if it is already (partially) buffered, framing doesn't depend on codec.<br>
+    /// Message currently being fed to a [streaming decoder](Transport::enable_streaming_decode)
+    /// is finished with the old codec.
+    ///
+    /// Useful for protocol version negotiation: exchange first messages using bootstrap codec,
+    /// then switch both ends to codec of agreed version.<br>
+    /// See [swap_send_codec](Transport::swap_send_codec) if peer might send messages
+    /// using the new codec before receiving the cut-over message.
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.swap_codec(codec);
+    }
 
-#[cfg(test)]
-mod tests {
-    use futures::{stream, SinkExt, StreamExt};
-    use kodec::binary::Codec;
-    use mezzenger::{Messages, Receive};
-    use tokio::net::{TcpListener, TcpStream};
+    /// Replace codec used in both directions, returning the current (sending) one.
+    ///
+    /// Messages sent after this call are encoded with the new codec,
+    /// next received message is decoded with the new codec.
+    pub fn swap_codec(&mut self, codec: Codec) -> Codec {
+        self.receive_codec = None;
+        std::mem::replace(&mut self.codec, codec)
+    }
 
-    use crate::{Error, Transport};
+    /// Replace codec used to encode outgoing messages.
+    ///
+    /// Messages sent before this call (even if not flushed yet) keep
+    /// using the old codec. Received messages are still decoded with
+    /// the old codec until [swap_receive_codec](Transport::swap_receive_codec) is called.
+    ///
+    /// Useful for switching codecs on a live connection: send a cut-over message,
+    /// then swap sending codec - the peer swaps its receiving codec after
+    /// receiving the cut-over message.
+    pub fn swap_send_codec(&mut self, codec: Codec) {
+        let old = std::mem::replace(&mut self.codec, codec);
+        self.receive_codec.get_or_insert(old);
+    }
 
-    #[tokio::test]
-    async fn test_transport() {
-        let left = TcpListener::bind("127.0.0.1:8080").await.unwrap();
-        let right = TcpStream::connect("127.0.0.1:8080").await.unwrap();
+    /// Replace codec used to decode incoming messages.
+    ///
+    /// Takes effect starting with the next received message, even if it is
+    /// already buffered.
+    pub fn swap_receive_codec(&mut self, codec: Codec) {
+        self.receive_codec = Some(codec);
+    }
 
-        let (left, _) = left.accept().await.unwrap();
+    /// Decode incoming messages incrementally, feeding their bytes to codec's
+    /// [StreamingDecoder] as they arrive instead of buffering whole messages first.
+    ///
+    /// Takes effect starting with the next incoming message.
+    ///
+    /// **NOTE**: Messages are still buffered as a whole if any per-frame option
+    /// ([checksum](Config::checksum), compression or [keepalive](Config::keepalive))
+    /// is enabled or when `type-tag` feature is enabled.
+    pub fn enable_streaming_decode(&mut self)
+    where
+        Codec: StreamingCodec,
+        <Codec as StreamingCodec>::Decoder<Incoming>: Send + Sync + 'static,
+    {
+        self.streaming_decoder = Some(streaming::new_decoder::<Codec, Incoming>);
+    }
 
-        let mut left: Transport<TcpStream, Codec, u32, String> =
-            Transport::new(left, Codec::default());
-        let mut right: Transport<TcpStream, Codec, String, u32> =
-            Transport::new(right, Codec::default());
+    /// Number of bytes written to the underlying stream (including framing overhead).
+    pub fn bytes_sent(&self) -> u64 {
+        self.stats.bytes_sent()
+    }
 
-        left.send("Hello World!".to_string()).await.unwrap();
-        left.send("Hello World again!".to_string()).await.unwrap();
+    /// Number of bytes read from the underlying stream (including framing overhead).
+    pub fn bytes_received(&self) -> u64 {
+        self.stats.bytes_received()
+    }
+
+    /// Number of messages accepted for sending.
+    pub fn messages_sent(&self) -> u64 {
+        self.stats.messages_sent()
+    }
+
+    /// Number of successfully received messages.
+    pub fn messages_received(&self) -> u64 {
+        self.stats.messages_received()
+    }
+
+    /// Reset all counters (and other [statistics](Transport::stats)) to zero.
+    pub fn reset_counters(&mut self) {
+        self.stats.reset();
+    }
+
+    /// Snapshot of transport statistics.
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// Handle to live transport statistics.
+    ///
+    /// Can be sent to other tasks (for example metrics collector)
+    /// while the transport is owned elsewhere.
+    pub fn stats_handle(&self) -> Arc<TransportStats> {
+        self.stats.clone()
+    }
+
+    /// Number of bytes of queued messages not yet written to the underlying stream.
+    ///
+    /// Messages queued (for example with [feed](futures::SinkExt::feed)) but not flushed
+    /// are lost when the transport is dropped - use [into_flushed_close] (or
+    /// [close_gracefully]) to deliver them.
+    ///
+    /// [into_flushed_close]: Transport::into_flushed_close
+    /// [close_gracefully]: Transport::close_gracefully
+    pub fn pending_send_bytes(&self) -> usize {
+        self.send_queue.len()
+    }
+
+    /// Number of queued messages not yet (fully) written to the underlying stream.
+    ///
+    /// Heartbeat frames aren't counted.
+    pub fn pending_send_messages(&self) -> usize {
+        self.send_queue.messages()
+    }
+
+    /// Write queued messages until fewer than `threshold` bytes are
+    /// [pending](Transport::pending_send_bytes).
+    ///
+    /// Useful for flow control - producer can stop producing messages while the peer
+    /// (or the network) can't keep up, without waiting for the whole queue to be flushed.<br>
+    /// With `threshold` of `0` it's the same as [poll_flush](Sink::poll_flush).
+    #[allow(clippy::type_complexity)]
+    pub fn poll_send_capacity(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        threshold: usize,
+    ) -> Poll<Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>
+    {
+        if self.send_queue.len() < threshold {
+            return Poll::Ready(Ok(()));
+        }
+        match self.as_mut().poll_flush(cx) {
+            Poll::Pending if self.send_queue.len() >= threshold => Poll::Pending,
+            Poll::Pending => Poll::Ready(Ok(())),
+            result => result,
+        }
+    }
+
+    /// Wait until fewer than `threshold` bytes are [pending](Transport::pending_send_bytes).
+    ///
+    /// See [poll_send_capacity](Transport::poll_send_capacity).
+    pub async fn send_capacity(
+        &mut self,
+        threshold: usize,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    where
+        T: Unpin,
+    {
+        poll_fn(|cx| Pin::new(&mut *self).poll_send_capacity(cx, threshold)).await
+    }
+
+    /// Flush all pending messages and shut down the write direction of the connection
+    /// within `linger` time, consuming the transport.
+    ///
+    /// Unlike [close_gracefully](Transport::close_gracefully) it doesn't wait for the peer
+    /// to close its side.<br>
+    /// If pending messages couldn't be flushed before `linger` elapsed
+    /// [Error::UnflushedData] with a number of unsent bytes is returned.
+    pub async fn into_flushed_close(
+        self,
+        linger: Duration,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        let mut transport = pin!(self);
+        let deadline = Instant::now() + linger;
+        let shutdown = timeout_at(
+            deadline,
+            poll_fn(|cx| transport.as_mut().poll_shutdown_write(cx)),
+        )
+        .await;
+        match shutdown {
+            Ok(result) => result,
+            Err(_) => {
+                let unflushed = transport.send_queue.len();
+                Err(mezzenger::Error::Other(Error::UnflushedData(unflushed)))
+            }
+        }
+    }
+
+    /// Try to flush all pending messages and shut down the write direction of the connection
+    /// without waiting, consuming the transport.
+    ///
+    /// Useful when the underlying stream accepts data immediately (for example in-memory
+    /// [duplex](tokio::io::duplex) stream with enough capacity) - otherwise prefer
+    /// [into_flushed_close](Transport::into_flushed_close) or
+    /// [flush on drop](crate::TransportBuilder::flush_on_drop).<br>
+    /// If pending messages couldn't be written right away [Error::UnflushedData]
+    /// with a number of unsent bytes is returned (they are lost).
+    #[allow(clippy::type_complexity)]
+    pub fn try_flush_sync_on_drop(
+        self,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        let mut transport = pin!(self);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        match transport.as_mut().poll_shutdown_write(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => {
+                let unflushed = transport.send_queue.len();
+                Err(mezzenger::Error::Other(Error::UnflushedData(unflushed)))
+            }
+        }
+    }
+
+    /// Flush all pending messages, shut down the connection and wait for the peer
+    /// to close its side.
+    ///
+    /// Returns once the peer closed the connection or `timeout` elapsed.<br>
+    /// If pending messages couldn't be flushed before `timeout` elapsed
+    /// [Error::UnflushedData] with a number of unsent bytes is returned.
+    ///
+    /// **NOTE**: Messages received while waiting for the peer are discarded.
+    pub async fn close_gracefully(
+        self,
+        timeout: Duration,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        let deadline = Instant::now() + timeout;
+        let mut transport = pin!(self);
+
+        let flushed = timeout_at(deadline, poll_fn(|cx| transport.as_mut().poll_flush(cx))).await;
+        match flushed {
+            Ok(result) => result?,
+            Err(_) => {
+                let unflushed = transport.send_queue.len();
+                return Err(mezzenger::Error::Other(Error::UnflushedData(unflushed)));
+            }
+        }
+
+        let mut me = transport.project();
+        let shutdown =
+            timeout_at(deadline, poll_fn(|cx| me.inner.as_mut().poll_shutdown(cx))).await;
+        match shutdown {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => {
+                return if is_closed(&error) {
+                    Ok(())
+                } else {
+                    Err(mezzenger::Error::Other(Error::IoError(error)))
+                }
+            }
+            Err(_) => return Ok(()),
+        }
+
+        let buffer = &mut me.receive_state.buffer;
+        let _ = timeout_at(
+            deadline,
+            poll_fn(|cx| loop {
+                buffer.clear();
+                match ready!(poll_read_buf(me.inner.as_mut(), cx, buffer)) {
+                    Ok(0) | Err(_) => return Poll::Ready(()),
+                    Ok(_) => {}
+                }
+            }),
+        )
+        .await;
+        *me.terminated = true;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &*me.span, cause = "closed gracefully", "transport closed");
+
+        Ok(())
+    }
+
+    /// Flush pending messages and shut down the write direction of the connection,
+    /// signaling end of stream to the peer.
+    ///
+    /// Receiving keeps working until the peer closes its side of the connection,
+    /// so a response to the already sent messages can still be received.
+    ///
+    /// After the write direction is shut down, sending (including
+    /// [poll_ready](Sink::poll_ready)) fails with [mezzenger::Error::Closed] and
+    /// [poll_close](Sink::poll_close) completes immediately.
+    #[allow(clippy::type_complexity)]
+    pub fn poll_shutdown_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>
+    {
+        if self.write_closed {
+            return Poll::Ready(Ok(()));
+        }
+        ready!(self.as_mut().poll_flush(cx))?;
+        let me = self.project();
+        match ready!(me.inner.poll_shutdown(cx)) {
+            // Peer closing the connection at the same time may make shutdown fail
+            // although the connection ends up closed either way.
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    ErrorKind::NotConnected | ErrorKind::BrokenPipe
+                ) => {}
+            result => result.map_err(map_write_error)?,
+        }
+        *me.write_closed = true;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Flush pending messages and shut down the write direction of the connection,
+    /// leaving receiving functional until the peer closes its side.
+    ///
+    /// See [poll_shutdown_write](Transport::poll_shutdown_write).
+    pub async fn shutdown_write(
+        &mut self,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    where
+        T: Unpin,
+    {
+        poll_fn(|cx| Pin::new(&mut *self).poll_shutdown_write(cx)).await
+    }
+
+    /// Set whether complete messages already buffered by the transport should be
+    /// returned while reading is paused (`true` by default).
+    pub fn set_deliver_buffered_while_paused(&mut self, deliver: bool) {
+        self.receive_state.deliver_buffered_while_paused = deliver;
+    }
+
+    /// Send message already encoded with transport's codec.
+    ///
+    /// Useful for sending the same message to many clients without encoding it
+    /// for each of them separately (see `SharedEncoded` of
+    /// [mezzenger-utils](https://crates.io/crates/mezzenger-utils)).<br>
+    /// Encoded message is framed like any other message - [max message size](Config::max_message_size),
+    /// compression and checksum apply.
+    ///
+    /// **NOTE**: Transport can't verify message was encoded as `Outgoing` with its codec -
+    /// otherwise the peer fails to decode it.<br>
+    /// With `type-tag` feature enabled message is tagged as `Outgoing`.
+    #[allow(clippy::type_complexity)]
+    pub fn start_send_encoded(
+        self: Pin<&mut Self>,
+        message: &[u8],
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        self.start_send_with(|_, buffer| {
+            #[cfg(feature = "type-tag")]
+            buffer.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
+            buffer.extend_from_slice(message);
+            Ok(())
+        })
+    }
+
+    /// Send message already encoded with transport's codec and flush it.
+    ///
+    /// See [start_send_encoded](Transport::start_send_encoded).
+    pub async fn send_encoded(
+        &mut self,
+        message: &[u8],
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    where
+        T: Unpin,
+    {
+        poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+        Pin::new(&mut *self).start_send_encoded(message)?;
+        poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)).await
+    }
+
+    /// Encode message on a thread dedicated to blocking operations
+    /// (see [spawn_blocking](tokio::task::spawn_blocking)), then send and flush it.
+    ///
+    /// Useful for messages taking long to serialize, which would otherwise stall
+    /// the async runtime when encoded inline in [start_send](Sink::start_send).
+    ///
+    /// **NOTE**: Message is queued for sending only once it is encoded - ordering of messages
+    /// sent concurrently through other handles (for example other transports writing
+    /// to the same peer) is caller's responsibility.
+    #[cfg(feature = "offload")]
+    pub async fn send_offloaded(
+        &mut self,
+        message: Outgoing,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    where
+        T: Unpin,
+        Codec: Clone + Send + 'static,
+        Outgoing: Send + 'static,
+        <Codec as Encode>::Error: Send + 'static,
+    {
+        poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+        let codec = self.codec.clone();
+        let encoded = tokio::task::spawn_blocking(move || {
+            #[allow(unused_mut)]
+            let mut buffer = BytesMut::new();
+            #[cfg(feature = "type-tag")]
+            buffer.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
+            let mut writer = buffer.writer();
+            codec
+                .encode(&mut writer, &message)
+                .map(|()| writer.into_inner().freeze())
+        })
+        .await
+        .unwrap_or_else(|error| std::panic::resume_unwind(error.into_panic()))
+        .map_err(|error| mezzenger::Error::Other(Error::SerializationError(error)))?;
+        Pin::new(&mut *self).queue_raw(encoded)?;
+        poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)).await
+    }
+
+    /// Queue already encoded message for sending.
+    ///
+    /// If no per-frame option is enabled the message is queued as is (without copying),
+    /// otherwise it is framed like any other message.
+    #[allow(clippy::type_complexity)]
+    fn queue_raw(
+        self: Pin<&mut Self>,
+        message: Bytes,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        if self.frame_options.flags().is_some() {
+            return self.start_send_with(|_, buffer| {
+                buffer.extend_from_slice(&message);
+                Ok(())
+            });
+        }
+        if self.terminated || self.write_closed {
+            return Err(mezzenger::Error::Closed);
+        }
+        let me = self.project();
+        let message_size = message.len() as u64;
+        if message_size > *me.max_message_size {
+            return Err(mezzenger::Error::Other(Error::MessageTooLarge(
+                message_size,
+            )));
+        }
+        if let Some(hook) = me.outgoing_frame_hook {
+            hook(&message);
+        }
+        let scratch = &mut me.send_queue.scratch;
+        let header_start = scratch.len();
+        let header_size = me.framing.header_size(message_size);
+        scratch.put_bytes(0, header_size);
+        me.framing
+            .write_header(&mut scratch[header_start..], message_size, *me.byte_order);
+        me.send_queue.push(message);
+        me.send_queue.message_queued();
+        me.stats.message_sent();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(parent: &*me.span, message_size, "frame sent");
+        Ok(())
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn start_send_with<F>(
+        self: Pin<&mut Self>,
+        encode: F,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    where
+        F: FnOnce(
+            &Codec,
+            &mut BytesMut,
+        ) -> Result<(), Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>,
+    {
+        if self.terminated || self.write_closed {
+            return Err(mezzenger::Error::Closed);
+        }
+        let me = self.project();
+        let message = &mut me.send_queue.encode_buffer;
+        message.clear();
+        encode(me.codec, message).map_err(mezzenger::Error::Other)?;
+        let message_size = message.len() as u64;
+        if message_size > *me.max_message_size {
+            return Err(mezzenger::Error::Other(Error::MessageTooLarge(
+                message_size,
+            )));
+        }
+        if let Some(hook) = me.outgoing_frame_hook {
+            hook(message);
+        }
+        let flags = me.frame_options.flags();
+        #[cfg(feature = "compression")]
+        let compressed = if me.frame_options.compression.is_enabled()
+            && message_size > *me.compression_threshold as u64
+        {
+            match me.frame_options.compression.compress(message) {
+                Ok(compressed) if compressed.len() < message.len() => Some(compressed),
+                Ok(_) => None,
+                Err(error) => return Err(mezzenger::Error::Other(Error::CompressionError(error))),
+            }
+        } else {
+            None
+        };
+        #[cfg(feature = "compression")]
+        let flags = match compressed {
+            Some(_) => flags.map(|flags| flags | framing::FLAG_COMPRESSED),
+            None => flags,
+        };
+        #[cfg(feature = "compression")]
+        let payload = compressed.as_deref().unwrap_or(&message[..]);
+        #[cfg(not(feature = "compression"))]
+        let payload = &message[..];
+
+        let body_size = payload.len() as u64 + me.frame_options.overhead();
+        let header_size = me.framing.header_size(body_size);
+        let send_buffer = &mut me.send_queue.scratch;
+        let frame_start = send_buffer.len();
+        send_buffer.reserve(header_size + body_size as usize);
+        send_buffer.put_bytes(0, header_size);
+        me.framing
+            .write_header(&mut send_buffer[frame_start..], body_size, *me.byte_order);
+        if let Some(flags) = flags {
+            send_buffer.put_u8(flags);
+        }
+        send_buffer.extend_from_slice(payload);
+        if me.frame_options.checksum {
+            send_buffer.put_u32(crc32fast::hash(payload));
+        }
+        me.send_queue.commit();
+        me.send_queue.message_queued();
+        me.stats.message_sent();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(parent: &*me.span, message_size, body_size, "frame sent");
+        if let Some(keepalive) = me.keepalive {
+            keepalive.sent();
+        }
+        Ok(())
+    }
+}
+
+/// Socket options of the wrapped [TcpStream].
+///
+/// Transports wrapping other streams (like TLS streams) don't expose socket options -
+/// configure them on the socket before constructing the transport.
+impl<Codec, Incoming, Outgoing> Transport<TcpStream, Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Record peer address of the socket in the transport's span.
+    pub(crate) fn with_peer(self) -> Self {
+        #[cfg(feature = "tracing")]
+        if let Ok(address) = self.inner.peer_addr() {
+            self.span.record("peer", tracing::field::display(address));
+        }
+        self
+    }
+
+    /// Set `TCP_NODELAY` option of the socket.
+    ///
+    /// When enabled, small messages are sent immediately instead of being
+    /// delayed by Nagle's algorithm.<br>
+    /// See [TcpStream::set_nodelay].
+    pub fn set_nodelay(&self, nodelay: bool) -> std::io::Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    /// Get `TCP_NODELAY` option of the socket.
+    ///
+    /// See [TcpStream::nodelay].
+    pub fn nodelay(&self) -> std::io::Result<bool> {
+        self.inner.nodelay()
+    }
+
+    /// Set `IP_TTL` option of the socket.
+    ///
+    /// See [TcpStream::set_ttl].
+    pub fn set_ttl(&self, ttl: u32) -> std::io::Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    /// Get `IP_TTL` option of the socket.
+    ///
+    /// See [TcpStream::ttl].
+    pub fn ttl(&self) -> std::io::Result<u32> {
+        self.inner.ttl()
+    }
+}
+
+impl<T, Codec, Incoming, Outgoing> Sink<Outgoing> for Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Error = mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.write_closed {
+            Poll::Ready(Err(mezzenger::Error::Closed))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        self.start_send_with(|codec, buffer| {
+            #[cfg(feature = "type-tag")]
+            buffer.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
+            codec
+                .encode(buffer.writer(), &item)
+                .map_err(Error::SerializationError)
+        })
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        #[cfg(feature = "tracing")]
+        let span = &*me.span;
+
+        let unwritten = me.send_queue.len();
+        let result = me.send_queue.poll_write_all(me.inner, cx, me.stats);
+        if let Some(write_timeout) = me.write_timeout {
+            if result.is_ready() {
+                write_timeout.stop();
+            } else {
+                let progress = me.send_queue.len() != unwritten;
+                if write_timeout.poll_stalled(progress, cx).is_ready() {
+                    write_timeout.stop();
+                    *me.terminated = true;
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        parent: span,
+                        cause = "write timed out",
+                        "transport closed"
+                    );
+                    return Poll::Ready(Err(mezzenger::Error::Other(Error::WriteTimedOut)));
+                }
+            }
+        }
+
+        let result = ready!(result).map_err(map_write_error);
+
+        Poll::Ready(result)
+    }
+
+    /// Flush pending messages and shut down the write direction of the connection
+    /// (see [poll_shutdown_write](Transport::poll_shutdown_write)).
+    ///
+    /// Closing already closed transport completes immediately.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_shutdown_write(cx)
+    }
+}
+
+impl<T, Codec, Incoming, Outgoing> Stream for Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Item = Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        #[cfg(feature = "offload")]
+        if self.decode_offload.is_some() {
+            return self.poll_next_offloaded(cx);
+        }
+        self.poll_next_with(cx, |codec, payload| {
+            payload.decode(|message| decode_message::<Codec, Incoming, Outgoing>(codec, message))
+        })
+    }
+}
+
+impl<T, Codec, Incoming, Outgoing> Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Poll for the next message, turning payloads of received frames into messages with `decode`.
+    #[allow(clippy::type_complexity)]
+    fn poll_next_with<F, M>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut decode: F,
+    ) -> Poll<Option<Result<M, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>
+    where
+        F: FnMut(
+            &Codec,
+            Payload<'_>,
+        ) -> Result<M, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>,
+        M: Received<Incoming>,
+    {
+        if self.terminated || self.read_closed {
+            return Poll::Ready(None);
+        }
+
+        let mut me = self.project();
+        #[cfg(feature = "tracing")]
+        let span = &*me.span;
+        loop {
+            let state = &mut *me.receive_state;
+            if state.paused && !state.deliver_buffered_while_paused {
+                state.wait_for_resume(cx.waker());
+                return Poll::Pending;
+            }
+
+            if state.frame_size.is_none() {
+                state.release_excess();
+            }
+
+            if state.bytes_to_skip > 0 {
+                let skipped = (state.buffer.len() as u64).min(state.bytes_to_skip);
+                state.buffer.advance(skipped as usize);
+                state.bytes_to_skip -= skipped;
+            }
+
+            if state.bytes_to_skip == 0 {
+                if let Some((decoder, remaining)) = me.active_decoder {
+                    let chunk_size = (state.buffer.len() as u64).min(*remaining) as usize;
+                    if chunk_size > 0 {
+                        let result = decoder.feed(&state.buffer[..chunk_size]);
+                        state.buffer.advance(chunk_size);
+                        *remaining -= chunk_size as u64;
+                        if let Err(error) = result {
+                            state.bytes_to_skip = *remaining;
+                            *me.active_decoder = None;
+                            me.stats.decode_error();
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(parent: span, "failed to decode message");
+                            return Poll::Ready(Some(Err(Error::DeserializationError(error))));
+                        }
+                    }
+                    if *remaining == 0 {
+                        let result = decoder.finish();
+                        *me.active_decoder = None;
+                        if result.is_ok() {
+                            me.stats.message_received();
+                        } else {
+                            me.stats.decode_error();
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(parent: span, "failed to decode message");
+                        }
+                        return Poll::Ready(Some(
+                            result.map(M::from).map_err(Error::DeserializationError),
+                        ));
+                    }
+                } else if let Some(body_size) = state.frame_size {
+                    let body_size = body_size as usize;
+                    if state.buffer.len() >= body_size {
+                        let mut message_start = 0;
+                        let mut compressed = false;
+                        if me.frame_options.flags().is_some() {
+                            if let Some(frame) = me.frame_options.control_frame(state.buffer[0]) {
+                                state.buffer.advance(body_size);
+                                state.frame_size = None;
+                                if frame == ControlFrame::Ping && !*me.write_closed {
+                                    me.framing.put_control_frame(
+                                        &mut me.send_queue.scratch,
+                                        ControlFrame::Pong,
+                                        *me.byte_order,
+                                    );
+                                    me.send_queue.commit();
+                                    if let Some(keepalive) = me.keepalive {
+                                        keepalive.sent();
+                                    }
+                                }
+                                continue;
+                            }
+                            if (body_size as u64) < me.frame_options.overhead() {
+                                *me.terminated = true;
+                                #[cfg(feature = "tracing")]
+                                tracing::debug!(
+                                    parent: span,
+                                    cause = "malformed frame",
+                                    "transport closed"
+                                );
+                                return Poll::Ready(Some(Err(Error::ProtocolError)));
+                            }
+                            match me.frame_options.check_flags(state.buffer[0]) {
+                                Ok(is_compressed) => compressed = is_compressed,
+                                Err(_) => {
+                                    *me.terminated = true;
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!(
+                                        parent: span,
+                                        cause = "malformed frame",
+                                        "transport closed"
+                                    );
+                                    return Poll::Ready(Some(Err(Error::ProtocolError)));
+                                }
+                            }
+                            message_start = 1;
+                        }
+                        let message_end = body_size - me.frame_options.trailer_size();
+                        if me.frame_options.checksum {
+                            let trailer = &state.buffer[message_end..body_size];
+                            let expected = u32::from_be_bytes(trailer.try_into().unwrap());
+                            let actual = crc32fast::hash(&state.buffer[message_start..message_end]);
+                            if expected != actual {
+                                state.buffer.advance(body_size);
+                                state.frame_size = None;
+                                return Poll::Ready(Some(Err(Error::ChecksumMismatch {
+                                    expected,
+                                    actual,
+                                })));
+                            }
+                        }
+                        #[cfg(not(feature = "compression"))]
+                        debug_assert!(!compressed);
+                        #[cfg(feature = "compression")]
+                        let decompressed = if compressed {
+                            let max_message_size = *me.max_message_size;
+                            match me.frame_options.compression.decompress(
+                                &state.buffer[message_start..message_end],
+                                max_message_size,
+                            ) {
+                                Ok(data) if data.len() as u64 > max_message_size => {
+                                    state.buffer.advance(body_size);
+                                    state.frame_size = None;
+                                    if let Some(hook) = me.incoming_frame_hook {
+                                        hook(&[], true);
+                                    }
+                                    me.stats.oversized_dropped();
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!(
+                                        parent: span,
+                                        message_size = data.len(),
+                                        max_message_size,
+                                        "oversized message skipped"
+                                    );
+                                    if *me.oversize_policy == OversizePolicy::Close {
+                                        *me.terminated = true;
+                                        #[cfg(feature = "tracing")]
+                                        tracing::debug!(
+                                            parent: span,
+                                            cause = "oversized message",
+                                            "transport closed"
+                                        );
+                                        poll_shutdown_on_close(
+                                            me.inner.as_mut(),
+                                            cx,
+                                            me.write_closed,
+                                        );
+                                    }
+                                    return Poll::Ready(Some(Err(Error::MessageTooLarge(
+                                        data.len() as u64,
+                                    ))));
+                                }
+                                Ok(data) => Some(data),
+                                Err(error) => {
+                                    state.buffer.advance(body_size);
+                                    state.frame_size = None;
+                                    return Poll::Ready(Some(Err(Error::CompressionError(error))));
+                                }
+                            }
+                        } else {
+                            None
+                        };
+                        state.frame_size = None;
+                        let buffered = Payload::Buffered {
+                            buffer: &mut state.buffer,
+                            frame_size: body_size,
+                            message: message_start..message_end,
+                        };
+                        #[cfg(feature = "compression")]
+                        let payload = match decompressed {
+                            Some(data) => {
+                                buffered.skip();
+                                Payload::Decompressed(data)
+                            }
+                            None => buffered,
+                        };
+                        #[cfg(not(feature = "compression"))]
+                        let payload = buffered;
+                        if let Some(hook) = me.incoming_frame_hook {
+                            hook(payload.message(), false);
+                        }
+                        let codec = me.receive_codec.as_ref().unwrap_or(me.codec);
+                        let result = decode(codec, payload);
+                        if let Ok(message) = &result {
+                            if message.decoded() {
+                                me.stats.message_received();
+                            }
+                        } else {
+                            me.stats.decode_error();
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(parent: span, "failed to decode message");
+                        }
+                        return Poll::Ready(Some(result));
+                    }
+                } else {
+                    match me.framing.decode_header(&state.buffer, *me.byte_order) {
+                        Ok(Some((header_size, body_size))) => {
+                            state.buffer.advance(header_size);
+                            let overhead = me.frame_options.overhead();
+                            if body_size < overhead
+                                && !me.frame_options.allows_control_frame(body_size)
+                            {
+                                *me.terminated = true;
+                                #[cfg(feature = "tracing")]
+                                tracing::debug!(
+                                    parent: span,
+                                    cause = "malformed frame",
+                                    "transport closed"
+                                );
+                                return Poll::Ready(Some(Err(Error::ProtocolError)));
+                            }
+                            let message_size = body_size.saturating_sub(overhead);
+                            let first_frame = !state.frame_received;
+                            state.frame_received = true;
+                            if message_size > *me.max_message_size
+                                || usize::try_from(body_size).is_err()
+                            {
+                                if *me.strict && first_frame {
+                                    *me.terminated = true;
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!(
+                                        parent: span,
+                                        cause = "protocol mismatch",
+                                        "transport closed"
+                                    );
+                                    return Poll::Ready(Some(Err(Error::ProtocolMismatch(
+                                        message_size,
+                                    ))));
+                                }
+                                state.bytes_to_skip = body_size;
+                                if let Some(hook) = me.incoming_frame_hook {
+                                    hook(&[], true);
+                                }
+                                me.stats.oversized_dropped();
+                                #[cfg(feature = "tracing")]
+                                tracing::debug!(
+                                    parent: span,
+                                    message_size,
+                                    max_message_size = *me.max_message_size,
+                                    "oversized message skipped"
+                                );
+                                if *me.oversize_policy == OversizePolicy::Close {
+                                    *me.terminated = true;
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!(
+                                        parent: span,
+                                        cause = "oversized message",
+                                        "transport closed"
+                                    );
+                                    poll_shutdown_on_close(me.inner.as_mut(), cx, me.write_closed);
+                                }
+                                return Poll::Ready(Some(Err(Error::MessageTooLarge(
+                                    message_size,
+                                ))));
+                            }
+                            #[cfg(feature = "tracing")]
+                            tracing::trace!(parent: span, message_size, "frame received");
+                            if let (Some(new_decoder), None, false) = (
+                                *me.streaming_decoder,
+                                me.frame_options.flags(),
+                                cfg!(feature = "type-tag"),
+                            ) {
+                                let codec = me.receive_codec.as_ref().unwrap_or(me.codec);
+                                *me.active_decoder =
+                                    Some((new_decoder(codec, body_size), body_size));
+                                continue;
+                            }
+                            let frame_size = body_size as usize;
+                            if me.frame_options.flags().is_none()
+                                && state.buffer.len() >= frame_size
+                            {
+                                // Fast path - whole frame is already buffered (typical for small
+                                // messages), decode it without going through frame state.
+                                let payload = Payload::Buffered {
+                                    buffer: &mut state.buffer,
+                                    frame_size,
+                                    message: 0..frame_size,
+                                };
+                                if let Some(hook) = me.incoming_frame_hook {
+                                    hook(payload.message(), false);
+                                }
+                                let codec = me.receive_codec.as_ref().unwrap_or(me.codec);
+                                let result = decode(codec, payload);
+                                if let Ok(message) = &result {
+                                    if message.decoded() {
+                                        me.stats.message_received();
+                                    }
+                                } else {
+                                    me.stats.decode_error();
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!(parent: span, "failed to decode message");
+                                }
+                                return Poll::Ready(Some(result));
+                            }
+                            // Avoid repeated reallocations while large message arrives.
+                            state.reserve(frame_size.saturating_sub(state.buffer.len()));
+                            state.frame_size = Some(body_size);
+                            continue;
+                        }
+                        Ok(None) => {}
+                        Err(_) => {
+                            *me.terminated = true;
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(
+                                parent: span,
+                                cause = "malformed frame",
+                                "transport closed"
+                            );
+                            return Poll::Ready(Some(Err(Error::ProtocolError)));
+                        }
+                    }
+                }
+            }
+
+            if state.paused {
+                state.wait_for_resume(cx.waker());
+                return Poll::Pending;
+            }
+
+            let read_chunk_size = state.read_chunk_size;
+            state.reserve(read_chunk_size);
+            let result = match poll_read_buf(me.inner.as_mut(), cx, &mut state.buffer) {
+                Poll::Ready(result) => result,
+                Poll::Pending => {
+                    if let Some(keepalive) = me.keepalive {
+                        while let Poll::Ready(event) = keepalive.poll_event(cx) {
+                            match event {
+                                keepalive::Event::Ping if *me.write_closed => {}
+                                keepalive::Event::Ping => {
+                                    me.framing.put_control_frame(
+                                        &mut me.send_queue.scratch,
+                                        ControlFrame::Ping,
+                                        *me.byte_order,
+                                    );
+                                    me.send_queue.commit();
+                                }
+                                keepalive::Event::TimedOut => {
+                                    *me.terminated = true;
+                                    #[cfg(feature = "tracing")]
+                                    tracing::debug!(
+                                        parent: span,
+                                        cause = "keepalive timed out",
+                                        "transport closed"
+                                    );
+                                    return Poll::Ready(Some(Err(Error::TimedOut)));
+                                }
+                            }
+                        }
+                    }
+                    if state.poll_read_timeout(cx).is_ready() {
+                        if state.read_timeout_fatal {
+                            *me.terminated = true;
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(
+                                parent: span,
+                                cause = "read timed out",
+                                "transport closed"
+                            );
+                        }
+                        return Poll::Ready(Some(Err(Error::ReadTimedOut)));
+                    }
+                    if !me.send_queue.is_empty() {
+                        // Write errors will be reported by subsequent reads or flushes.
+                        let _ = me.send_queue.poll_write_all(me.inner, cx, me.stats);
+                    }
+                    return Poll::Pending;
+                }
+            };
+            match result {
+                Ok(bytes_read) => {
+                    if bytes_read == 0 {
+                        *me.read_closed = true;
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(
+                            parent: span,
+                            cause = "closed by peer",
+                            "transport closed"
+                        );
+                        return Poll::Ready(None);
+                    }
+                    me.stats.add_bytes_received(bytes_read);
+                    state.restart_read_timer();
+                    if let Some(keepalive) = me.keepalive {
+                        keepalive.received();
+                    }
+                }
+                Err(error) if is_closed(&error) => {
+                    *me.terminated = true;
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        parent: span,
+                        cause = "connection reset",
+                        "transport closed"
+                    );
+                    return Poll::Ready(None);
+                }
+                Err(error) => return Poll::Ready(Some(Err(Error::IoError(error)))),
+            }
+        }
+    }
+}
+
+/// Start shutting down the underlying stream after receiving stream was terminated.
+///
+/// If shutdown doesn't complete immediately it is finished by [poll_close](Sink::poll_close).
+fn poll_shutdown_on_close<T: AsyncWrite>(
+    inner: Pin<&mut T>,
+    cx: &mut Context<'_>,
+    write_closed: &mut bool,
+) {
+    // Errors are irrelevant - connection is being dropped anyway.
+    if inner.poll_shutdown(cx).is_ready() {
+        *write_closed = true;
+    }
+}
+
+/// Does I/O error mean the connection was closed by the peer.
+///
+/// Besides resets of TCP connections covers errors reported by Unix sockets
+/// and pipes (used for IPC) once the peer is gone.
+fn is_closed(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::NotConnected
+    )
+}
+
+fn map_write_error<SerializationError, DeserializationError>(
+    error: io::Error,
+) -> mezzenger::Error<Error<SerializationError, DeserializationError>> {
+    if is_closed(&error) {
+        mezzenger::Error::Closed
+    } else {
+        mezzenger::Error::Other(Error::IoError(error))
+    }
+}
+
+impl<T, Codec, Incoming, Outgoing> FusedStream for Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    fn is_terminated(&self) -> bool {
+        #[cfg(feature = "offload")]
+        if let Some(decode_offload) = &self.decode_offload {
+            if !decode_offload.is_empty() {
+                return false;
+            }
+        }
+        self.terminated || self.read_closed
+    }
+}
+
+impl<T, Codec, Incoming, Outgoing> mezzenger::Reliable for Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+}
+
+impl<T, Codec, Incoming, Outgoing> mezzenger::Order for Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read, Write},
+        marker::PhantomData,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    };
+
+    use futures::{stream, stream::FusedStream, FutureExt, SinkExt, StreamExt};
+    use kodec::{binary::Codec, Decode, Encode};
+    use mezzenger::{Messages, Receive};
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use tokio::{
+        io::{duplex, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf},
+        join,
+        net::{TcpListener, TcpStream},
+        time::{timeout, Duration, Instant},
+    };
+
+    use crate::{
+        ByteOrder, Config, Error, Framing, Keepalive, OversizePolicy, StreamingCodec,
+        StreamingDecoder, Transport,
+    };
+
+    /// Size of type tag prepended to every message payload.
+    #[cfg(feature = "type-tag")]
+    const TAG_SIZE: usize = mezzenger::type_tag::TAG_SIZE;
+    #[cfg(not(feature = "type-tag"))]
+    const TAG_SIZE: usize = 0;
+
+    /// Payload of message of type `T` already encoded with transport's codec.
+    #[cfg_attr(not(feature = "type-tag"), allow(clippy::extra_unused_type_parameters))]
+    fn payload<T: ?Sized>(encoded: &[u8]) -> Vec<u8> {
+        let mut payload = vec![];
+        #[cfg(feature = "type-tag")]
+        payload.extend_from_slice(&mezzenger::type_tag::tag::<T>());
+        payload.extend_from_slice(encoded);
+        payload
+    }
+
+    /// Binary codec obfuscating messages with XOR.
+    struct XorCodec {
+        key: u8,
+    }
+
+    impl Encode for XorCodec {
+        type Error = <Codec as Encode>::Error;
+
+        fn encode<W, T>(&self, mut writer: W, message: &T) -> Result<(), Self::Error>
+        where
+            W: Write,
+            T: Serialize,
+        {
+            let mut buffer = vec![];
+            Codec::default().encode(&mut buffer, message)?;
+            buffer.iter_mut().for_each(|byte| *byte ^= self.key);
+            Ok(writer.write_all(&buffer)?)
+        }
+    }
+
+    impl Decode for XorCodec {
+        type Error = <Codec as Decode>::Error;
+
+        fn decode<R, T>(&self, mut reader: R) -> Result<T, Self::Error>
+        where
+            R: Read,
+            for<'de> T: Deserialize<'de>,
+        {
+            let mut buffer = vec![];
+            reader.read_to_end(&mut buffer)?;
+            buffer.iter_mut().for_each(|byte| *byte ^= self.key);
+            Codec::default().decode(&buffer[..])
+        }
+    }
+
+    /// Binary codec counting chunks fed to its streaming decoder.
+    struct ChunkedCodec {
+        chunks: Arc<AtomicUsize>,
+    }
+
+    impl Encode for ChunkedCodec {
+        type Error = <Codec as Encode>::Error;
+
+        fn encode<W, T>(&self, writer: W, message: &T) -> Result<(), Self::Error>
+        where
+            W: Write,
+            T: Serialize,
+        {
+            Codec::default().encode(writer, message)
+        }
+    }
+
+    impl Decode for ChunkedCodec {
+        type Error = <Codec as Decode>::Error;
+
+        fn decode<R, T>(&self, reader: R) -> Result<T, Self::Error>
+        where
+            R: Read,
+            for<'de> T: Deserialize<'de>,
+        {
+            Codec::default().decode(reader)
+        }
+    }
+
+    struct ChunkedDecoder<T> {
+        data: Vec<u8>,
+        chunks: Arc<AtomicUsize>,
+        _message: PhantomData<fn() -> T>,
+    }
+
+    impl<T> StreamingDecoder<T> for ChunkedDecoder<T>
+    where
+        T: DeserializeOwned,
+    {
+        type Error = <Codec as Decode>::Error;
+
+        fn feed(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+            self.chunks.fetch_add(1, Ordering::Relaxed);
+            self.data.extend_from_slice(chunk);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<T, Self::Error> {
+            Codec::default().decode(&self.data[..])
+        }
+    }
+
+    impl StreamingCodec for ChunkedCodec {
+        type Decoder<T>
+            = ChunkedDecoder<T>
+        where
+            T: DeserializeOwned;
+
+        fn decoder<T>(&self, _message_size: u64) -> Self::Decoder<T>
+        where
+            T: DeserializeOwned,
+        {
+            ChunkedDecoder {
+                data: vec![],
+                chunks: self.chunks.clone(),
+                _message: PhantomData,
+            }
+        }
+    }
+
+    /// Stream flipping bits of a single written byte.
+    struct Corrupting {
+        inner: DuplexStream,
+        position: usize,
+        written: usize,
+    }
+
+    impl AsyncRead for Corrupting {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for Corrupting {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let mut buf = buf.to_vec();
+            if let Some(byte) = self
+                .position
+                .checked_sub(self.written)
+                .and_then(|index| buf.get_mut(index))
+            {
+                *byte = !*byte;
+            }
+            let result = Pin::new(&mut self.inner).poll_write(cx, &buf);
+            if let Poll::Ready(Ok(written)) = result {
+                self.written += written;
+            }
+            result
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    /// Stream failing to shut down once the peer shut down its side first
+    /// (like TCP socket when both sides close the connection at the same time).
+    struct RacingShutdown {
+        inner: DuplexStream,
+        shutdowns: Arc<AtomicUsize>,
+        error: io::ErrorKind,
+    }
+
+    impl AsyncRead for RacingShutdown {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for RacingShutdown {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            if self.shutdowns.fetch_add(1, Ordering::SeqCst) > 0 {
+                return Poll::Ready(Err(self.error.into()));
+            }
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transport() {
+        let left = TcpListener::bind("127.0.0.1:8080").await.unwrap();
+        let right = TcpStream::connect("127.0.0.1:8080").await.unwrap();
+
+        let (left, _) = left.accept().await.unwrap();
+
+        let mut left: Transport<TcpStream, Codec, u32, String> =
+            Transport::new(left, Codec::default());
+        let mut right: Transport<TcpStream, Codec, String, u32> =
+            Transport::new(right, Codec::default());
+
+        left.send("Hello World!".to_string()).await.unwrap();
+        left.send("Hello World again!".to_string()).await.unwrap();
         right.send(128).await.unwrap();
         right.send(1).await.unwrap();
 
         assert_eq!(right.receive().await.unwrap(), "Hello World!");
-        assert_eq!(right.receive().await.unwrap(), "Hello World again!");
-        assert_eq!(left.receive().await.unwrap(), 128);
-        assert_eq!(left.receive().await.unwrap(), 1);
+        assert_eq!(right.receive().await.unwrap(), "Hello World again!");
+        assert_eq!(left.receive().await.unwrap(), 128);
+        assert_eq!(left.receive().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unit_message() {
+        let left = TcpListener::bind("127.0.0.1:8081").await.unwrap();
+        let right = TcpStream::connect("127.0.0.1:8081").await.unwrap();
+
+        let (left, _) = left.accept().await.unwrap();
+
+        let mut left: Transport<TcpStream, Codec, (), ()> = Transport::new(left, Codec::default());
+        let mut right: Transport<TcpStream, Codec, (), ()> =
+            Transport::new(right, Codec::default());
+
+        left.send(()).await.unwrap();
+        left.send(()).await.unwrap();
+        right.send(()).await.unwrap();
+        right.send(()).await.unwrap();
+
+        assert_eq!(right.receive().await.unwrap(), ());
+        assert_eq!(right.receive().await.unwrap(), ());
+        assert_eq!(left.receive().await.unwrap(), ());
+        assert_eq!(left.receive().await.unwrap(), ());
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let left = TcpListener::bind("127.0.0.1:8082").await.unwrap();
+        let right = TcpStream::connect("127.0.0.1:8082").await.unwrap();
+
+        let (left, _) = left.accept().await.unwrap();
+
+        let mut left: Transport<TcpStream, Codec, (), u32> = Transport::new(left, Codec::default());
+        let right: Transport<TcpStream, Codec, u32, ()> = Transport::new(right, Codec::default());
+
+        left.send_all(&mut stream::iter(vec![1, 2, 3].into_iter().map(Ok)))
+            .await
+            .unwrap();
+        drop(left);
+
+        assert_eq!(right.messages().collect::<Vec<u32>>().await, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_socket_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let transport: Transport<TcpStream, Codec, (), ()> =
+            Transport::new(stream, Codec::default());
+        transport.set_nodelay(true).unwrap();
+        assert!(transport.nodelay().unwrap());
+        transport.set_nodelay(false).unwrap();
+        assert!(!transport.nodelay().unwrap());
+        transport.set_ttl(42).unwrap();
+        assert_eq!(transport.ttl().unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_size_limit() {
+        let left = TcpListener::bind("127.0.0.1:1234").await.unwrap();
+        let right = TcpStream::connect("127.0.0.1:1234").await.unwrap();
+
+        let (left, _) = left.accept().await.unwrap();
+
+        let mut left: Transport<TcpStream, Codec, String, String> =
+            Transport::new_with_max_message_size(left, Codec::default(), 15);
+        let mut right: Transport<TcpStream, Codec, String, String> =
+            Transport::new(right, Codec::default());
+
+        left.send("Hey".to_string()).await.unwrap();
+        assert!(matches!(
+            left.send("Hello, hello, hello".to_string()).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+        ));
+        left.send("Hi".to_string()).await.unwrap();
+
+        assert_eq!(right.receive().await.unwrap(), "Hey");
+        assert_eq!(right.receive().await.unwrap(), "Hi");
+
+        right.send("Hey".to_string()).await.unwrap();
+        for _i in 0..139 {
+            right.send("Hello, hello, hello".to_string()).await.unwrap();
+        }
+        right.send("Hi".to_string()).await.unwrap();
+
+        assert_eq!(left.receive().await.unwrap(), "Hey");
+        for _i in 0..139 {
+            assert!(matches!(
+                left.receive().await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+            ));
+        }
+        assert_eq!(left.receive().await.unwrap(), "Hi");
+
+        right.send("Hey".to_string()).await.unwrap();
+        for _i in 0..17 {
+            right.send("Hello, hello, hello".to_string()).await.unwrap();
+            right
+                .send("Hello, hello, hello, hi".to_string())
+                .await
+                .unwrap();
+        }
+        right.send("Hi".to_string()).await.unwrap();
+
+        assert_eq!(left.receive().await.unwrap(), "Hey");
+        for _i in 0..17 {
+            assert!(matches!(
+                left.receive().await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+            ));
+            assert!(matches!(
+                left.receive().await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 31 + TAG_SIZE as u64
+            ));
+        }
+        assert_eq!(left.receive().await.unwrap(), "Hi");
+    }
+
+    #[tokio::test]
+    async fn test_change_max_message_size() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, String, String> =
+            Transport::new_with_max_message_size(left, Codec::default(), 15);
+        let mut right: Transport<_, Codec, String, String> =
+            Transport::new(right, Codec::default());
+        assert_eq!(left.max_message_size(), 15);
+
+        right.send("Hello, hello, hello".to_string()).await.unwrap();
+        assert!(matches!(
+            left.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+        ));
+
+        left.set_max_message_size(64);
+        assert_eq!(left.max_message_size(), 64);
+        right.send("Hello, hello, hello".to_string()).await.unwrap();
+        assert_eq!(left.receive().await.unwrap(), "Hello, hello, hello");
+        left.send("Hello, hello, hello".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "Hello, hello, hello");
+
+        left.set_max_message_size(u64::MAX);
+        assert_eq!(left.max_message_size(), u32::MAX as u64);
+    }
+
+    #[tokio::test]
+    async fn test_lower_max_message_size_mid_frame() {
+        let (mut left, right) = duplex(1 << 20);
+
+        let mut right: Transport<_, Codec, String, ()> = Transport::new(right, Codec::default());
+
+        let mut frame = vec![];
+        for message in ["Hello, hello, hello", "Hello, hello, hello", "Hi"] {
+            let mut encoded = vec![];
+            Codec::default()
+                .encode(&mut encoded, &message.to_string())
+                .unwrap();
+            let payload = payload::<String>(&encoded);
+            frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            frame.extend_from_slice(&payload);
+        }
+
+        left.write_all(&frame[..10]).await.unwrap();
+        assert!(right.receive().now_or_never().is_none());
+        right.set_max_message_size(15);
+        left.write_all(&frame[10..]).await.unwrap();
+
+        assert_eq!(right.receive().await.unwrap(), "Hello, hello, hello");
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+        ));
+        assert_eq!(right.receive().await.unwrap(), "Hi");
+    }
+
+    #[tokio::test]
+    async fn test_oversize_policy() {
+        for oversize_policy in [OversizePolicy::Skip, OversizePolicy::Close] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let right = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (left, _) = listener.accept().await.unwrap();
+
+            let mut left: Transport<TcpStream, Codec, String, String> = Transport::new_with_config(
+                left,
+                Codec::default(),
+                Config {
+                    max_message_size: 15,
+                    oversize_policy,
+                    ..Default::default()
+                },
+            );
+            let mut right: Transport<TcpStream, Codec, String, String> =
+                Transport::new(right, Codec::default());
+
+            right.send("Hey".to_string()).await.unwrap();
+            for _i in 0..3 {
+                right.send("Hello, hello, hello".to_string()).await.unwrap();
+            }
+            right.send("Hi".to_string()).await.unwrap();
+
+            assert_eq!(left.receive().await.unwrap(), "Hey");
+            assert!(matches!(
+                left.receive().await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+            ));
+            match oversize_policy {
+                OversizePolicy::Skip => {
+                    for _i in 0..2 {
+                        assert!(matches!(
+                            left.receive().await,
+                            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+                        ));
+                    }
+                    assert_eq!(left.receive().await.unwrap(), "Hi");
+                    assert!(!left.is_terminated());
+                }
+                OversizePolicy::Close => {
+                    assert!(left.is_terminated());
+                    assert!(matches!(
+                        left.receive().await,
+                        Err(mezzenger::Error::Closed)
+                    ));
+                    assert!(matches!(
+                        left.send("Hi".to_string()).await,
+                        Err(mezzenger::Error::Closed)
+                    ));
+                    assert!(matches!(
+                        right.receive().await,
+                        Err(mezzenger::Error::Closed)
+                    ));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_varint_framing() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, Vec<u8>, Vec<u8>> =
+            Transport::new_with_framing(left, Codec::default(), Framing::Varint);
+        let mut right: Transport<_, Codec, Vec<u8>, Vec<u8>> =
+            Transport::new_with_framing(right, Codec::default(), Framing::Varint);
+
+        // binary codec prefixes vectors with 8-byte length
+        let sizes = [0, 1, 118, 119, 120, 16374, 16375, 16376, 60000];
+        for size in sizes {
+            left.send(vec![7u8; size]).await.unwrap();
+        }
+        for size in sizes {
+            assert_eq!(right.receive().await.unwrap(), vec![7u8; size]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_varint_overhead() {
+        let (left, mut right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, (), u8> =
+            Transport::new_with_framing(left, Codec::default(), Framing::Varint);
+
+        left.send(5).await.unwrap();
+        let mut frame = vec![0; 2 + TAG_SIZE];
+        right.read_exact(&mut frame).await.unwrap();
+        assert_eq!(frame[0] as usize, 1 + TAG_SIZE);
+        assert_eq!(frame[1..], payload::<u8>(&[5]));
+
+        let (left, mut right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, (), Vec<u8>> =
+            Transport::new_with_framing(left, Codec::default(), Framing::Varint);
+
+        left.send(vec![0; 192]).await.unwrap();
+        let mut header = [0; 2];
+        right.read_exact(&mut header).await.unwrap();
+        let size = 200 + TAG_SIZE;
+        assert_eq!(header, [(size & 0x7f) as u8 | 0x80, (size >> 7) as u8]);
+    }
+
+    #[tokio::test]
+    async fn test_varint_size_limit() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, String, String> =
+            Transport::new_with_framing(left, Codec::default(), Framing::Varint);
+        let mut right: Transport<_, Codec, String, String> = Transport::new_with_config(
+            right,
+            Codec::default(),
+            Config {
+                max_message_size: 15,
+                framing: Framing::Varint,
+                ..Default::default()
+            },
+        );
+
+        left.send("Hey".to_string()).await.unwrap();
+        left.send("Hello, hello, hello".to_string()).await.unwrap();
+        left.send("Hi".to_string()).await.unwrap();
+
+        assert_eq!(right.receive().await.unwrap(), "Hey");
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+        ));
+        assert_eq!(right.receive().await.unwrap(), "Hi");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_varint() {
+        let (mut left, right) = duplex(1 << 20);
+
+        let mut right: Transport<_, Codec, String, String> =
+            Transport::new_with_framing(right, Codec::default(), Framing::Varint);
+
+        left.write_all(&[0xff; 8]).await.unwrap();
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::ProtocolError))
+        ));
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pause_reading() {
+        let (left, right) = duplex(64);
+
+        let mut left: Transport<_, Codec, (), u32> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, ()> = Transport::new(right, Codec::default());
+
+        right.pause_reading();
+        assert!(right.is_reading_paused());
+
+        for i in 0..100 {
+            left.feed(i).await.unwrap();
+        }
+        // nobody reads - peer's buffer fills up and flush can't complete
+        assert!(timeout(Duration::from_millis(50), left.flush())
+            .await
+            .is_err());
+        assert!(timeout(Duration::from_millis(50), right.receive())
+            .await
+            .is_err());
+
+        right.resume_reading();
+        let receive = async {
+            for i in 0..100 {
+                assert_eq!(right.receive().await.unwrap(), i);
+            }
+        };
+        let (flush, _) = join!(left.flush(), receive);
+        flush.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pause_reading_buffered() {
+        let (left, right) = duplex(1024);
+
+        let mut left: Transport<_, Codec, (), u32> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, ()> = Transport::new(right, Codec::default());
+
+        for i in 0..3 {
+            left.feed(i).await.unwrap();
+        }
+        left.flush().await.unwrap();
+
+        // first read pulls all three frames into transport's buffer
+        assert_eq!(right.receive().await.unwrap(), 0);
+        right.pause_reading();
+        assert_eq!(right.receive().await.unwrap(), 1);
+
+        right.set_deliver_buffered_while_paused(false);
+        assert!(timeout(Duration::from_millis(50), right.receive())
+            .await
+            .is_err());
+
+        right.resume_reading();
+        assert_eq!(right.receive().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_close_gracefully() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let right = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (left, _) = listener.accept().await.unwrap();
+
+        let mut left: Transport<TcpStream, Codec, (), String> =
+            Transport::new(left, Codec::default());
+        let right: Transport<TcpStream, Codec, String, ()> =
+            Transport::new(right, Codec::default());
+
+        for i in 0..1000 {
+            left.feed(format!("Message {i}")).await.unwrap();
+        }
+        let receive = async move {
+            let messages = right.messages().collect::<Vec<String>>().await;
+            assert_eq!(messages.len(), 1000);
+            assert_eq!(messages.last().unwrap(), "Message 999");
+        };
+        let (result, _) = join!(left.close_gracefully(Duration::from_secs(5)), receive);
+        result.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_simultaneous_close() {
+        for i in 0..100 {
+            let (left, right) = duplex(64);
+            let shutdowns = Arc::new(AtomicUsize::new(0));
+            let error = if i % 2 == 0 {
+                io::ErrorKind::NotConnected
+            } else {
+                io::ErrorKind::BrokenPipe
+            };
+            let racing = |inner| RacingShutdown {
+                inner,
+                shutdowns: shutdowns.clone(),
+                error,
+            };
+            let mut left: Transport<_, Codec, u32, u32> =
+                Transport::new(racing(left), Codec::default());
+            let mut right: Transport<_, Codec, u32, u32> =
+                Transport::new(racing(right), Codec::default());
+
+            left.feed(i).await.unwrap();
+            right.feed(i + 1).await.unwrap();
+            let (left_result, right_result) = join!(left.close(), right.close());
+            left_result.unwrap();
+            right_result.unwrap();
+
+            // closing again doesn't touch the stream
+            left.close().await.unwrap();
+            right.close().await.unwrap();
+            assert_eq!(shutdowns.load(Ordering::SeqCst), 2);
+
+            // messages fed before closing were flushed
+            assert_eq!(right.receive().await.unwrap(), i);
+            assert_eq!(left.receive().await.unwrap(), i + 1);
+            assert!(matches!(
+                right.receive().await,
+                Err(mezzenger::Error::Closed)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close_gracefully_timeout() {
+        let (left, _right) = duplex(16);
+
+        let mut left: Transport<_, Codec, (), u64> = Transport::new(left, Codec::default());
+
+        for i in 0..10 {
+            left.feed(i).await.unwrap();
+        }
+        let result = left.close_gracefully(Duration::from_millis(50)).await;
+        assert!(matches!(
+            result,
+            Err(mezzenger::Error::Other(Error::UnflushedData(bytes))) if bytes == 10 * (12 + TAG_SIZE) - 16
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_capacity() {
+        let (left, mut right) = duplex(16);
+        let mut left: Transport<_, Codec, (), u64> = Transport::new(left, Codec::default());
+        for i in 0..10 {
+            left.feed(i).await.unwrap();
+        }
+        assert_eq!(left.pending_send_messages(), 10);
+        let frame_size = left.pending_send_bytes() / 10;
+        assert_eq!(left.pending_send_bytes(), 10 * frame_size);
+
+        // Peer doesn't read - only 16 bytes fit into the pipe.
+        let threshold = 5 * frame_size;
+        assert!(left.send_capacity(threshold).now_or_never().is_none());
+        assert_eq!(left.pending_send_bytes(), 10 * frame_size - 16);
+        assert_eq!(left.pending_send_messages(), 10 - 16 / frame_size);
+
+        let draining = tokio::spawn(async move {
+            let mut buffer = vec![0; 10 * frame_size];
+            right.read_exact(&mut buffer).await.unwrap();
+            right
+        });
+        timeout(Duration::from_secs(2), left.send_capacity(threshold))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(left.pending_send_bytes() < threshold);
+        assert!(left.pending_send_messages() <= 5);
+
+        left.flush().await.unwrap();
+        assert_eq!(left.pending_send_bytes(), 0);
+        assert_eq!(left.pending_send_messages(), 0);
+        let _right = draining.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_into_flushed_close() {
+        let (left, right) = duplex(64);
+
+        let mut left: Transport<_, Codec, (), u64> = Transport::new(left, Codec::default());
+        let right: Transport<_, Codec, u64, ()> = Transport::new(right, Codec::default());
+
+        assert_eq!(left.pending_send_bytes(), 0);
+        for i in 0..100 {
+            left.feed(i).await.unwrap();
+        }
+        assert!(left.pending_send_bytes() > 0);
+        let receive = async move { right.messages().collect::<Vec<u64>>().await };
+        let (result, messages) = join!(left.into_flushed_close(Duration::from_secs(5)), receive);
+        result.unwrap();
+        assert_eq!(messages, (0..100).collect::<Vec<u64>>());
+
+        let (left, _right) = duplex(16);
+        let mut left: Transport<_, Codec, (), u64> = Transport::new(left, Codec::default());
+        for i in 0..10 {
+            left.feed(i).await.unwrap();
+        }
+        let result = left.into_flushed_close(Duration::from_millis(50)).await;
+        assert!(matches!(
+            result,
+            Err(mezzenger::Error::Other(Error::UnflushedData(bytes))) if bytes == 10 * (12 + TAG_SIZE) - 16
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_try_flush_sync_on_drop() {
+        let (left, right) = duplex(1024);
+        let mut left: Transport<_, Codec, (), u64> = Transport::new(left, Codec::default());
+        let right: Transport<_, Codec, u64, ()> = Transport::new(right, Codec::default());
+        for i in 0..10 {
+            left.feed(i).await.unwrap();
+        }
+        left.try_flush_sync_on_drop().unwrap();
+        assert_eq!(
+            right.messages().collect::<Vec<u64>>().await,
+            (0..10).collect::<Vec<u64>>()
+        );
+
+        let (left, _right) = duplex(16);
+        let mut left: Transport<_, Codec, (), u64> = Transport::new(left, Codec::default());
+        for i in 0..10 {
+            left.feed(i).await.unwrap();
+        }
+        assert!(matches!(
+            left.try_flush_sync_on_drop(),
+            Err(mezzenger::Error::Other(Error::UnflushedData(bytes))) if bytes == 10 * (12 + TAG_SIZE) - 16
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fixed_u64_framing() {
+        let (left, right) = duplex(1 << 20);
+
+        let config = Config {
+            max_message_size: 1 << 20,
+            framing: Framing::FixedU64,
+            ..Default::default()
+        };
+        let mut left: Transport<_, Codec, Vec<u8>, Vec<u8>> =
+            Transport::new_with_config(left, Codec::default(), config);
+        let mut right: Transport<_, Codec, Vec<u8>, Vec<u8>> =
+            Transport::new_with_config(right, Codec::default(), config);
+
+        for size in [0, 1, 70000] {
+            left.send(vec![3u8; size]).await.unwrap();
+        }
+        for size in [0, 1, 70000] {
+            assert_eq!(right.receive().await.unwrap(), vec![3u8; size]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fixed_u64_size_limit() {
+        let (mut left, right) = duplex(4096);
+
+        let mut right: Transport<_, Codec, String, String> = Transport::new_with_config(
+            right,
+            Codec::default(),
+            Config {
+                max_message_size: 1000,
+                framing: Framing::FixedU64,
+                ..Default::default()
+            },
+        );
+
+        let oversized: u64 = 100_000;
+        let write = async move {
+            left.write_all(&oversized.to_be_bytes()).await.unwrap();
+            for _ in 0..(oversized / 1000) {
+                left.write_all(&[0; 1000]).await.unwrap();
+            }
+            let message = payload::<String>(b"\x02\0\0\0\0\0\0\0Hi");
+            left.write_all(&(message.len() as u64).to_be_bytes())
+                .await
+                .unwrap();
+            left.write_all(&message).await.unwrap();
+            left
+        };
+        let read = async {
+            assert!(matches!(
+                right.receive().await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == oversized
+            ));
+            assert_eq!(right.receive().await.unwrap(), "Hi");
+        };
+        let (_left, _) = join!(write, read);
+        assert!(right.receive_state.buffer.capacity() < oversized as usize);
+
+        let (left, _right) = duplex(4096);
+        let mut left: Transport<_, Codec, (), Vec<u8>> = Transport::new_with_config(
+            left,
+            Codec::default(),
+            Config {
+                max_message_size: 1000,
+                framing: Framing::FixedU64,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            left.send(vec![0; 1000]).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 1008 + TAG_SIZE as u64
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_counters() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, (), u32> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, ()> = Transport::new(right, Codec::default());
+
+        for i in 0..3 {
+            left.send(i).await.unwrap();
+        }
+        for i in 0..3 {
+            assert_eq!(right.receive().await.unwrap(), i);
+        }
+
+        assert_eq!(left.messages_sent(), 3);
+        assert_eq!(left.bytes_sent(), 3 * (8 + TAG_SIZE as u64));
+        assert_eq!(right.messages_received(), 3);
+        assert_eq!(right.bytes_received(), 3 * (8 + TAG_SIZE as u64));
+        assert_eq!(left.bytes_received(), 0);
+        assert_eq!(right.messages_sent(), 0);
+
+        left.reset_counters();
+        assert_eq!(left.messages_sent(), 0);
+        assert_eq!(left.bytes_sent(), 0);
+
+        left.send(7).await.unwrap();
+        assert_eq!(left.messages_sent(), 1);
+        assert_eq!(left.bytes_sent(), 8 + TAG_SIZE as u64);
+    }
+
+    #[tokio::test]
+    async fn test_checksum() {
+        let (left, right) = duplex(1 << 20);
+
+        let config = Config {
+            checksum: true,
+            ..Default::default()
+        };
+        let mut left: Transport<_, Codec, String, String> =
+            Transport::new_with_config(left, Codec::default(), config);
+        let mut right: Transport<_, Codec, String, String> =
+            Transport::new_with_config(right, Codec::default(), config);
+
+        left.send("Hello World!".to_string()).await.unwrap();
+        right.send("Hi".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "Hello World!");
+        assert_eq!(left.receive().await.unwrap(), "Hi");
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch() {
+        let (left, right) = duplex(1 << 20);
+
+        // 4-byte length + 1-byte flags + (tag +) 4-byte payload + 4-byte checksum
+        let frame_size = 13 + TAG_SIZE;
+        let left = Corrupting {
+            inner: left,
+            position: frame_size + 6 + TAG_SIZE,
+            written: 0,
+        };
+
+        let config = Config {
+            checksum: true,
+            ..Default::default()
+        };
+        let mut left: Transport<_, Codec, (), u32> =
+            Transport::new_with_config(left, Codec::default(), config);
+        let mut right: Transport<_, Codec, u32, ()> =
+            Transport::new_with_config(right, Codec::default(), config);
+
+        left.send(1).await.unwrap();
+        left.send(2).await.unwrap();
+        left.send(3).await.unwrap();
+
+        assert_eq!(right.receive().await.unwrap(), 1);
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::ChecksumMismatch { expected, actual }))
+                if expected == crc32fast::hash(&payload::<u32>(&2u32.to_le_bytes()))
+                    && actual != expected
+        ));
+        assert_eq!(right.receive().await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_one_sided() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, (), u32> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, ()> = Transport::new_with_config(
+            right,
+            Codec::default(),
+            Config {
+                checksum: true,
+                ..Default::default()
+            },
+        );
+
+        left.send(1234567).await.unwrap();
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::ProtocolError))
+        ));
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_swap_codec() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, XorCodec, String, String> =
+            Transport::new(left, XorCodec { key: 0 });
+        let mut right: Transport<_, XorCodec, String, String> =
+            Transport::new(right, XorCodec { key: 0 });
+
+        left.send("before".to_string()).await.unwrap();
+        left.send("cut-over".to_string()).await.unwrap();
+        left.swap_send_codec(XorCodec { key: 0x5a });
+        left.send("after".to_string()).await.unwrap();
+
+        right.send("before".to_string()).await.unwrap();
+
+        assert_eq!(right.receive().await.unwrap(), "before");
+        assert_eq!(right.receive().await.unwrap(), "cut-over");
+        right.swap_receive_codec(XorCodec { key: 0x5a });
+        assert_eq!(right.receive().await.unwrap(), "after");
+
+        // left still decodes with the old codec
+        assert_eq!(left.receive().await.unwrap(), "before");
+
+        right.send("cut-over".to_string()).await.unwrap();
+        right.swap_send_codec(XorCodec { key: 0x5a });
+        right.send("after".to_string()).await.unwrap();
+
+        assert_eq!(left.receive().await.unwrap(), "cut-over");
+        left.swap_receive_codec(XorCodec { key: 0x5a });
+        assert_eq!(left.receive().await.unwrap(), "after");
+
+        left.swap_codec(XorCodec { key: 0 });
+        right.swap_codec(XorCodec { key: 0 });
+        left.send("again".to_string()).await.unwrap();
+        right.send("again".to_string()).await.unwrap();
+        assert_eq!(left.receive().await.unwrap(), "again");
+        assert_eq!(right.receive().await.unwrap(), "again");
+    }
+
+    #[tokio::test]
+    async fn test_set_codec() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, XorCodec, String, String> =
+            Transport::new(left, XorCodec { key: 0 });
+        let mut right: Transport<_, XorCodec, String, String> =
+            Transport::new(right, XorCodec { key: 0 });
+
+        left.send("version 2".to_string()).await.unwrap();
+        left.set_codec(XorCodec { key: 2 });
+        left.send("Hello World!".to_string()).await.unwrap();
+
+        // second message is already buffered when codec is replaced
+        assert_eq!(right.receive().await.unwrap(), "version 2");
+        right.set_codec(XorCodec { key: 2 });
+        assert_eq!(right.receive().await.unwrap(), "Hello World!");
+
+        right.send("Hello!".to_string()).await.unwrap();
+        assert_eq!(left.receive().await.unwrap(), "Hello!");
+    }
+
+    #[tokio::test]
+    async fn test_send_encoded() {
+        let codec = Codec::default();
+        let mut encoded = vec![];
+        codec
+            .encode(&mut encoded, &"Hello World!".to_string())
+            .unwrap();
+
+        let config = Config {
+            checksum: true,
+            ..Default::default()
+        };
+        let mut clients = vec![];
+        for _ in 0..3 {
+            let (server, client) = duplex(1 << 20);
+            let server: Transport<_, Codec, String, String> =
+                Transport::new_with_config(server, Codec::default(), config);
+            let client: Transport<_, Codec, String, String> =
+                Transport::new_with_config(client, Codec::default(), config);
+            clients.push((server, client));
+        }
+
+        for (server, client) in clients.iter_mut() {
+            server.send("before".to_string()).await.unwrap();
+            server.send_encoded(&encoded).await.unwrap();
+            server.send("after".to_string()).await.unwrap();
+
+            assert_eq!(client.receive().await.unwrap(), "before");
+            assert_eq!(client.receive().await.unwrap(), "Hello World!");
+            assert_eq!(client.receive().await.unwrap(), "after");
+        }
+
+        let (server, _client) = &mut clients[0];
+        let large = vec![0; crate::DEFAULT_MAX_MESSAGE_SIZE as usize + 1];
+        assert!(matches!(
+            server.send_encoded(&large).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_little_endian() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, String, String> =
+            Transport::new_with_byte_order(left, Codec::default(), ByteOrder::Little);
+        let mut right: Transport<_, Codec, String, String> =
+            Transport::new_with_byte_order(right, Codec::default(), ByteOrder::Little);
+
+        left.send("Hello World!".to_string()).await.unwrap();
+        right.send("Hi".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "Hello World!");
+        assert_eq!(left.receive().await.unwrap(), "Hi");
+
+        let (left, mut right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, (), u32> =
+            Transport::new_with_byte_order(left, Codec::default(), ByteOrder::Little);
+
+        left.send(5).await.unwrap();
+        let mut header = [0; 4];
+        right.read_exact(&mut header).await.unwrap();
+        assert_eq!(header, (4 + TAG_SIZE as u32).to_le_bytes());
     }
 
     #[tokio::test]
-    async fn test_unit_message() {
-        let left = TcpListener::bind("127.0.0.1:8081").await.unwrap();
-        let right = TcpStream::connect("127.0.0.1:8081").await.unwrap();
+    async fn test_byte_order_mismatch() {
+        let (left, right) = duplex(1 << 20);
 
-        let (left, _) = left.accept().await.unwrap();
+        let mut left: Transport<_, Codec, (), u32> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, ()> =
+            Transport::new_with_byte_order(right, Codec::default(), ByteOrder::Little);
 
-        let mut left: Transport<TcpStream, Codec, (), ()> = Transport::new(left, Codec::default());
-        let mut right: Transport<TcpStream, Codec, (), ()> =
-            Transport::new(right, Codec::default());
+        left.send(5).await.unwrap();
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == (4 + TAG_SIZE as u64) << 24
+        ));
+    }
 
-        left.send(()).await.unwrap();
-        left.send(()).await.unwrap();
-        right.send(()).await.unwrap();
-        right.send(()).await.unwrap();
+    #[tokio::test]
+    async fn test_strict_protocol_mismatch() {
+        let (mut left, right) = duplex(1 << 20);
 
-        assert_eq!(right.receive().await.unwrap(), ());
-        assert_eq!(right.receive().await.unwrap(), ());
-        assert_eq!(left.receive().await.unwrap(), ());
-        assert_eq!(left.receive().await.unwrap(), ());
+        let mut right: Transport<_, Codec, String, ()> = Transport::new_with_config(
+            right,
+            Codec::default(),
+            Config {
+                strict: true,
+                ..Default::default()
+            },
+        );
+
+        left.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::ProtocolMismatch(size))) if size == 0x47455420
+        ));
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, (), String> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, String, ()> = Transport::new_with_config(
+            right,
+            Codec::default(),
+            Config {
+                max_message_size: 15,
+                strict: true,
+                ..Default::default()
+            },
+        );
+
+        left.send("Hey".to_string()).await.unwrap();
+        left.send("Hello, hello, hello".to_string()).await.unwrap();
+        left.send("Hi".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "Hey");
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+        ));
+        assert_eq!(right.receive().await.unwrap(), "Hi");
     }
 
+    #[cfg(feature = "compression")]
     #[tokio::test]
-    async fn test_stream() {
-        let left = TcpListener::bind("127.0.0.1:8082").await.unwrap();
-        let right = TcpStream::connect("127.0.0.1:8082").await.unwrap();
+    async fn test_compression() {
+        use crate::Compression;
 
-        let (left, _) = left.accept().await.unwrap();
+        for compression in [Compression::Deflate, Compression::Zstd { level: 3 }] {
+            let (left, right) = duplex(1 << 20);
 
-        let mut left: Transport<TcpStream, Codec, (), u32> = Transport::new(left, Codec::default());
-        let right: Transport<TcpStream, Codec, u32, ()> = Transport::new(right, Codec::default());
+            let config = Config {
+                max_message_size: 2 << 20,
+                compression,
+                ..Default::default()
+            };
+            let mut left: Transport<_, Codec, (), String> =
+                Transport::new_with_config(left, Codec::default(), config);
+            let mut right: Transport<_, Codec, String, ()> =
+                Transport::new_with_config(right, Codec::default(), config);
 
-        left.send_all(&mut stream::iter(vec![1, 2, 3].into_iter().map(Ok)))
-            .await
-            .unwrap();
-        drop(left);
+            let message = "Hello World! ".repeat(80000);
+            left.send("Hi".to_string()).await.unwrap();
+            left.send(message.clone()).await.unwrap();
 
-        assert_eq!(right.messages().collect::<Vec<u32>>().await, vec![1, 2, 3]);
+            assert_eq!(right.receive().await.unwrap(), "Hi");
+            assert_eq!(right.receive().await.unwrap(), message);
+            assert!(left.bytes_sent() < message.len() as u64 / 100);
+            assert_eq!(right.bytes_received(), left.bytes_sent());
+        }
     }
 
+    #[cfg(feature = "compression")]
     #[tokio::test]
-    async fn test_size_limit() {
-        let left = TcpListener::bind("127.0.0.1:1234").await.unwrap();
-        let right = TcpStream::connect("127.0.0.1:1234").await.unwrap();
+    async fn test_decompressed_size_limit() {
+        use crate::{Compression, DEFAULT_MAX_MESSAGE_SIZE};
 
-        let (left, _) = left.accept().await.unwrap();
+        let (left, right) = duplex(1 << 20);
 
-        let mut left: Transport<TcpStream, Codec, String, String> =
-            Transport::new_with_max_message_size(left, Codec::default(), 15);
-        let mut right: Transport<TcpStream, Codec, String, String> =
-            Transport::new(right, Codec::default());
+        let mut left: Transport<_, Codec, (), String> = Transport::new_with_config(
+            left,
+            Codec::default(),
+            Config {
+                max_message_size: 2 << 20,
+                compression: Compression::Deflate,
+                ..Default::default()
+            },
+        );
+        let mut right: Transport<_, Codec, String, ()> = Transport::new_with_config(
+            right,
+            Codec::default(),
+            Config {
+                compression: Compression::Deflate,
+                ..Default::default()
+            },
+        );
+
+        left.send("a".repeat(1 << 20)).await.unwrap();
+        left.send("Hi".to_string()).await.unwrap();
 
-        left.send("Hey".to_string()).await.unwrap();
         assert!(matches!(
-            left.send("Hello, hello, hello".to_string()).await,
-            Err(mezzenger::Error::Other(Error::MessageTooLarge))
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size)))
+                if size == DEFAULT_MAX_MESSAGE_SIZE as u64 + 1
         ));
+        assert_eq!(right.receive().await.unwrap(), "Hi");
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let (left, right) = duplex(1 << 20);
+
+        let mut left: Transport<_, Codec, (), String> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, String, ()> =
+            Transport::new_with_max_message_size(right, Codec::default(), 20);
+        let handle = right.stats_handle();
+
+        assert_eq!(left.stats().last_sent, None);
+
+        // each message: 4-byte length (+ type tag) + 8-byte string length + string bytes
+        left.send("Hello".to_string()).await.unwrap();
+        left.send("Hello World! Hello World!".to_string())
+            .await
+            .unwrap();
         left.send("Hi".to_string()).await.unwrap();
 
-        assert_eq!(right.receive().await.unwrap(), "Hey");
+        assert_eq!(right.receive().await.unwrap(), "Hello");
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 33 + TAG_SIZE as u64
+        ));
         assert_eq!(right.receive().await.unwrap(), "Hi");
 
-        right.send("Hey".to_string()).await.unwrap();
-        for _i in 0..139 {
-            right.send("Hello, hello, hello".to_string()).await.unwrap();
+        let stats = left.stats();
+        assert_eq!(stats.messages_sent, 3);
+        assert_eq!(stats.bytes_sent, 17 + 37 + 14 + 3 * TAG_SIZE as u64);
+        assert!(stats.last_sent.is_some());
+        assert_eq!(stats.last_received, None);
+
+        let stats = tokio::spawn(async move { handle.snapshot() })
+            .await
+            .unwrap();
+        assert_eq!(stats.messages_received, 2);
+        assert_eq!(stats.bytes_received, 17 + 37 + 14 + 3 * TAG_SIZE as u64);
+        assert_eq!(stats.oversized_dropped, 1);
+        assert_eq!(stats.decode_errors, 0);
+        assert!(stats.last_received.is_some());
+        assert_eq!(stats, right.stats());
+    }
+
+    #[tokio::test]
+    async fn test_checksum_instead_of_decode_error() {
+        // corrupt the first character making the string invalid UTF-8
+        // (4-byte length prefix (+ flags) + type tag + 8-byte string length)
+        for (checksum, position) in [(false, 4 + TAG_SIZE + 8), (true, 4 + 1 + TAG_SIZE + 8)] {
+            let (left, right) = duplex(1 << 20);
+            let left = Corrupting {
+                inner: left,
+                position,
+                written: 0,
+            };
+
+            let config = Config {
+                checksum,
+                ..Default::default()
+            };
+            let mut left: Transport<_, Codec, (), String> =
+                Transport::new_with_config(left, Codec::default(), config);
+            let mut right: Transport<_, Codec, String, ()> =
+                Transport::new_with_config(right, Codec::default(), config);
+
+            left.send("Hello World!".to_string()).await.unwrap();
+            left.send("Hi".to_string()).await.unwrap();
+
+            let result = right.receive().await;
+            if checksum {
+                assert!(matches!(
+                    result,
+                    Err(mezzenger::Error::Other(Error::ChecksumMismatch { .. }))
+                ));
+            } else {
+                assert!(matches!(
+                    result,
+                    Err(mezzenger::Error::Other(Error::DeserializationError(_)))
+                ));
+            }
+            assert_eq!(right.receive().await.unwrap(), "Hi");
         }
-        right.send("Hi".to_string()).await.unwrap();
+    }
 
-        assert_eq!(left.receive().await.unwrap(), "Hey");
-        for _i in 0..139 {
-            assert!(matches!(
-                left.receive().await,
-                Err(mezzenger::Error::Other(Error::MessageTooLarge))
-            ));
+    #[tokio::test]
+    async fn test_keepalive_timeout() {
+        let (left, mut right) = duplex(1024);
+
+        let mut left: Transport<_, Codec, String, String> = Transport::new_with_keepalive(
+            left,
+            Codec::default(),
+            Duration::from_millis(50),
+            Duration::from_millis(200),
+        );
+
+        let start = Instant::now();
+        let result = timeout(Duration::from_secs(2), left.next()).await.unwrap();
+        assert!(matches!(result, Some(Err(Error::TimedOut))));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_secs(1));
+        assert!(left.next().await.is_none());
+        assert!(matches!(
+            left.send("Hello".to_string()).await,
+            Err(mezzenger::Error::Closed)
+        ));
+
+        let mut ping = [0; 5];
+        right.read_exact(&mut ping).await.unwrap();
+        assert_eq!(ping, [0, 0, 0, 1, 0xc1]);
+    }
+
+    #[tokio::test]
+    async fn test_write_timeout() {
+        let config = Config {
+            write_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        // Peer stops reading.
+        let (left, _right) = duplex(64);
+        let mut left: Transport<_, Codec, (), Vec<u8>> =
+            Transport::new_with_config(left, Codec::default(), config);
+
+        let start = Instant::now();
+        let result = timeout(Duration::from_secs(2), left.send(vec![0; 1024]))
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            Err(mezzenger::Error::Other(Error::WriteTimedOut))
+        ));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_secs(1));
+        assert!(matches!(
+            left.send(vec![]).await,
+            Err(mezzenger::Error::Closed)
+        ));
+
+        // Peer reads slowly - every write restarts the timer.
+        let (left, mut right) = duplex(64);
+        let mut left: Transport<_, Codec, (), Vec<u8>> =
+            Transport::new_with_config(left, Codec::default(), config);
+
+        let reading = tokio::spawn(async move {
+            let mut received = 0;
+            let mut buffer = [0; 64];
+            while received < 1024 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                received += right.read(&mut buffer).await.unwrap();
+            }
+            // keep the pipe open - rest of the frame still has to fit in
+            right
+        });
+        let start = Instant::now();
+        left.send(vec![0; 1024]).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+        let _right = reading.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        // Silent peer - timeout isn't fatal, the timer restarts.
+        let (left, right) = duplex(64);
+        let mut left: Transport<_, Codec, (), u32> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, ()> = Transport::new(right, Codec::default());
+        right.set_read_timeout(Some(Duration::from_millis(100)));
+        assert_eq!(right.read_timeout(), Some(Duration::from_millis(100)));
+
+        for _ in 0..2 {
+            let start = Instant::now();
+            let result = timeout(Duration::from_secs(2), right.next()).await.unwrap();
+            assert!(matches!(result, Some(Err(Error::ReadTimedOut))));
+            let elapsed = start.elapsed();
+            assert!(elapsed >= Duration::from_millis(100));
+            assert!(elapsed < Duration::from_secs(1));
         }
-        assert_eq!(left.receive().await.unwrap(), "Hi");
+        left.send(7).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), 7);
 
-        right.send("Hey".to_string()).await.unwrap();
-        for _i in 0..17 {
-            right.send("Hello, hello, hello".to_string()).await.unwrap();
+        // Fatal timeout terminates the stream.
+        right.set_read_timeout_fatal(true);
+        assert!(matches!(right.next().await, Some(Err(Error::ReadTimedOut))));
+        assert!(right.next().await.is_none());
+        assert!(right.is_terminated());
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout_slow_drip() {
+        let (left, mut right) = duplex(64);
+        let config = Config {
+            read_timeout: Some(Duration::from_millis(200)),
+            read_timeout_fatal: true,
+            ..Default::default()
+        };
+        let mut left: Transport<_, Codec, Vec<u8>, ()> =
+            Transport::new_with_config(left, Codec::default(), config);
+
+        // Single frame arriving byte by byte, slower than the whole timeout
+        // but with every byte arriving before it elapses.
+        let mut encoded = vec![];
+        Codec::default()
+            .encode(&mut encoded, &vec![1u8; 8])
+            .unwrap();
+        let encoded = payload::<Vec<u8>>(&encoded);
+        let mut frame = (encoded.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&encoded);
+        let dripping = tokio::spawn(async move {
+            for byte in frame {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                right.write_all(&[byte]).await.unwrap();
+            }
             right
-                .send("Hello, hello, hello, hi".to_string())
-                .await
-                .unwrap();
+        });
+
+        let start = Instant::now();
+        assert_eq!(left.receive().await.unwrap(), vec![1; 8]);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        let _right = dripping.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keepalive() {
+        let (left, right) = duplex(1024);
+
+        let mut left: Transport<_, Codec, String, String> = Transport::new_with_keepalive(
+            left,
+            Codec::default(),
+            Duration::from_millis(50),
+            Duration::from_millis(200),
+        );
+        // Never pings on its own - left end relies on its pongs.
+        let mut right: Transport<_, Codec, String, String> = Transport::new_with_keepalive(
+            right,
+            Codec::default(),
+            Duration::from_secs(10),
+            Duration::from_secs(10),
+        );
+
+        let idle = Duration::from_millis(500);
+        let (left_result, right_result) =
+            join!(timeout(idle, left.next()), timeout(idle, right.next()));
+        assert!(left_result.is_err());
+        assert!(right_result.is_err());
+
+        left.send("Hello".to_string()).await.unwrap();
+        right.send("World".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "Hello");
+        assert_eq!(left.receive().await.unwrap(), "World");
+        assert_eq!(left.messages_received(), 1);
+        assert_eq!(right.messages_received(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_with_checksum() {
+        let (left, right) = duplex(1024);
+
+        let config = Config {
+            checksum: true,
+            keepalive: Some(Keepalive::new(
+                Duration::from_millis(20),
+                Duration::from_secs(10),
+            )),
+            ..Default::default()
+        };
+        let mut left: Transport<_, Codec, String, String> =
+            Transport::new_with_config(left, Codec::default(), config);
+        let mut right: Transport<_, Codec, String, String> =
+            Transport::new_with_config(right, Codec::default(), config);
+
+        let idle = Duration::from_millis(100);
+        let (left_result, right_result) =
+            join!(timeout(idle, left.next()), timeout(idle, right.next()));
+        assert!(left_result.is_err());
+        assert!(right_result.is_err());
+
+        left.send("Hello".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_one_sided() {
+        let (left, right) = duplex(1024);
+
+        let mut left: Transport<_, Codec, (), ()> = Transport::new_with_keepalive(
+            left,
+            Codec::default(),
+            Duration::from_secs(10),
+            Duration::from_secs(10),
+        );
+        let mut right: Transport<_, Codec, (), ()> = Transport::new(right, Codec::default());
+
+        right.send(()).await.unwrap();
+        assert!(matches!(left.next().await, Some(Err(Error::ProtocolError))));
+        assert!(left.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+
+        let mut client: Transport<TcpStream, Codec, usize, String> =
+            Transport::new(client, Codec::default());
+        let mut server: Transport<TcpStream, Codec, String, usize> =
+            Transport::new(server, Codec::default());
+
+        for i in 0..100 {
+            client.feed(format!("Chunk {i}")).await.unwrap();
         }
-        right.send("Hi".to_string()).await.unwrap();
+        client.shutdown_write().await.unwrap();
+        assert!(matches!(
+            client.send("Chunk".to_string()).await,
+            Err(mezzenger::Error::Closed)
+        ));
+        client.shutdown_write().await.unwrap();
 
-        assert_eq!(left.receive().await.unwrap(), "Hey");
-        for _i in 0..17 {
-            assert!(matches!(
-                left.receive().await,
-                Err(mezzenger::Error::Other(Error::MessageTooLarge))
-            ));
-            assert!(matches!(
-                left.receive().await,
-                Err(mezzenger::Error::Other(Error::MessageTooLarge))
-            ));
+        let chunks: Vec<String> = (&mut server).messages().collect().await;
+        assert_eq!(chunks.len(), 100);
+        assert!(server.is_terminated());
+        server.send(chunks.len()).await.unwrap();
+        server.close().await.unwrap();
+
+        assert_eq!(client.receive().await.unwrap(), 100);
+        assert!(client.next().await.is_none());
+        client.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_streaming_decode() {
+        let (left, right) = duplex(64);
+
+        let mut left: Transport<_, Codec, (), Vec<u8>> = Transport::new(left, Codec::default());
+        let chunks = Arc::new(AtomicUsize::new(0));
+        let mut right: Transport<_, ChunkedCodec, Vec<u8>, ()> = Transport::new(
+            right,
+            ChunkedCodec {
+                chunks: chunks.clone(),
+            },
+        );
+        right.enable_streaming_decode();
+
+        let message: Vec<u8> = (0..10000).map(|i| i as u8).collect();
+        let (sent, received) = join!(left.send(message.clone()), right.receive());
+        sent.unwrap();
+        assert_eq!(received.unwrap(), message);
+        // tagged messages are always buffered as a whole
+        if !cfg!(feature = "type-tag") {
+            assert!(chunks.load(Ordering::Relaxed) > 1);
+            assert!(right.receive_state.buffer.capacity() < message.len());
         }
-        assert_eq!(left.receive().await.unwrap(), "Hi");
+
+        left.send(vec![]).await.unwrap();
+        left.send(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), Vec::<u8>::new());
+        assert_eq!(right.receive().await.unwrap(), vec![1, 2, 3]);
+        assert_eq!(right.messages_received(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_receive_buffer_shrinks() {
+        let config = Config {
+            max_message_size: 2 << 20,
+            receive_buffer_capacity: 1024,
+            read_chunk_size: 1024,
+            receive_buffer_retained_capacity: 64 * 1024,
+            ..Default::default()
+        };
+        let (left, right) = duplex(1 << 16);
+        let mut left: Transport<_, Codec, (), Vec<u8>> =
+            Transport::new_with_config(left, Codec::default(), config);
+        let mut right: Transport<_, Codec, Vec<u8>, ()> =
+            Transport::new_with_config(right, Codec::default(), config);
+
+        let large = vec![7; 1 << 20];
+        let write = async {
+            left.feed(large.clone()).await.unwrap();
+            for i in 0..10 {
+                left.feed(vec![i; 100]).await.unwrap();
+            }
+            left.flush().await.unwrap();
+        };
+        let read = async {
+            assert_eq!(right.receive().await.unwrap(), large);
+            assert!(right.receive_state.oversized);
+            for i in 0..10 {
+                assert_eq!(right.receive().await.unwrap(), vec![i; 100]);
+            }
+        };
+        join!(write, read);
+
+        assert!(!right.receive_state.oversized);
+        assert!(right.receive_state.buffer.capacity() <= config.receive_buffer_retained_capacity);
+
+        // buffer is reused for small messages
+        left.send(vec![1; 100]).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), vec![1; 100]);
+        assert!(right.receive_state.buffer.capacity() <= config.receive_buffer_retained_capacity);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_and_split_frames() {
+        let mut frames = vec![];
+        for i in 0..100u32 {
+            let mut encoded = vec![];
+            Codec::default().encode(&mut encoded, &i).unwrap();
+            if i == 50 {
+                // undecodable frame
+                encoded.pop();
+            }
+            let encoded = payload::<u32>(&encoded);
+            frames.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+            frames.extend_from_slice(&encoded);
+        }
+
+        for chunk_size in [frames.len(), 7, 1] {
+            let (mut left, right) = duplex(1 << 16);
+            let mut right: Transport<_, Codec, u32, ()> = Transport::new(right, Codec::default());
+            let write = async {
+                for chunk in frames.chunks(chunk_size) {
+                    left.write_all(chunk).await.unwrap();
+                }
+            };
+            let read = async {
+                for i in 0..100 {
+                    let result = right.receive().await;
+                    if i == 50 {
+                        assert!(matches!(
+                            result,
+                            Err(mezzenger::Error::Other(Error::DeserializationError(_)))
+                        ));
+                    } else {
+                        assert_eq!(result.unwrap(), i);
+                    }
+                }
+            };
+            join!(write, read);
+            assert_eq!(right.messages_received(), 99);
+            assert!(right.receive().now_or_never().is_none());
+        }
+    }
+
+    #[cfg(feature = "offload")]
+    #[tokio::test]
+    async fn test_send_offloaded() {
+        for config in [
+            Config::default(),
+            Config {
+                checksum: true,
+                ..Default::default()
+            },
+        ] {
+            let (left, right) = duplex(1 << 20);
+            let mut left: Transport<_, Codec, (), Vec<String>> =
+                Transport::new_with_config(left, Codec::default(), config);
+            let mut right: Transport<_, Codec, Vec<String>, ()> =
+                Transport::new_with_config(right, Codec::default(), config);
+
+            let message: Vec<String> = (0..1000).map(|i| format!("Hello {i}")).collect();
+            left.feed(vec!["first".to_string()]).await.unwrap();
+            left.send_offloaded(message.clone()).await.unwrap();
+            left.send(vec!["last".to_string()]).await.unwrap();
+            assert_eq!(right.receive().await.unwrap(), vec!["first".to_string()]);
+            assert_eq!(right.receive().await.unwrap(), message);
+            assert_eq!(right.receive().await.unwrap(), vec!["last".to_string()]);
+            assert_eq!(left.messages_sent(), 3);
+        }
+
+        let (left, _right) = duplex(1 << 20);
+        let mut left: Transport<_, Codec, (), Vec<u8>> =
+            Transport::new_with_max_message_size(left, Codec::default(), 16);
+        assert!(matches!(
+            left.send_offloaded(vec![0; 32]).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(_)))
+        ));
+    }
+
+    #[cfg(feature = "type-tag")]
+    #[tokio::test]
+    async fn test_type_mismatch() {
+        // both ends (mistakenly) using the same message type order
+        let (left, right) = duplex(1024);
+        let mut left: Transport<_, Codec, u32, String> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, String> = Transport::new(right, Codec::default());
+        left.send("Hello World!".to_string()).await.unwrap();
+        let error = right.receive().await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "message type mismatch (expected u32, got alloc::string::String)"
+        );
+
+        // messages of a type unknown to the receiving end
+        let (left, right) = duplex(1024);
+        let mut left: Transport<_, Codec, (), i64> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u64, ()> = Transport::new(right, Codec::default());
+        left.send(-1).await.unwrap();
+        left.send(2).await.unwrap();
+        for _ in 0..2 {
+            match right.receive().await {
+                Err(mezzenger::Error::Other(Error::TypeMismatch { expected, got })) => {
+                    assert_eq!(expected, "u64");
+                    assert!(got.starts_with("unknown type (tag 0x"));
+                }
+                result => panic!("unexpected result: {result:?}"),
+            }
+        }
+
+        // correctly matched ends
+        let (left, right) = duplex(1024);
+        let mut left: Transport<_, Codec, (), i64> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, i64, ()> = Transport::new(right, Codec::default());
+        left.send(3).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), 3);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_tracing() {
+        use std::sync::Mutex;
+
+        #[derive(Clone, Default)]
+        struct Output(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Output {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                Write::write(&mut *self.0.lock().unwrap(), buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (left, right) = join!(
+            Transport::<TcpStream, Codec, String, String>::connect(address, Codec::default()),
+            Transport::<TcpStream, Codec, String, String>::accept(&listener, Codec::default()),
+        );
+        let mut left = left.unwrap();
+        let (mut right, _) = right.unwrap();
+        right.set_max_message_size(15);
+
+        left.send("Hey".to_string()).await.unwrap();
+        left.send("Hello, hello, hello".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "Hey");
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 27 + TAG_SIZE as u64
+        ));
+        drop(left);
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains(&format!("peer={address}")));
+        assert!(output.contains(&format!("frame sent message_size={}", 11 + TAG_SIZE)));
+        assert!(output.contains(&format!("frame received message_size={}", 11 + TAG_SIZE)));
+        assert!(output.contains(&format!(
+            "oversized message skipped message_size={} max_message_size=15",
+            27 + TAG_SIZE
+        )));
+        assert!(output.contains("transport closed cause=\"closed by peer\""));
+    }
+
+    #[tokio::test]
+    async fn test_encode_buffer_capacity() {
+        let (left, _right) = duplex(1 << 20);
+        let mut transport: Transport<_, Codec, (), Vec<u8>> =
+            Transport::new(left, Codec::default()).with_send_capacity(1024);
+        let capacity = transport.send_queue.encode_buffer.capacity();
+        assert!(capacity >= 1024);
+
+        for _ in 0..100 {
+            transport.feed(vec![7; 1000]).await.unwrap();
+            assert_eq!(transport.send_queue.encode_buffer.capacity(), capacity);
+        }
+        transport.flush().await.unwrap();
+
+        // buffer grows once for larger messages and keeps its capacity afterwards
+        let (left, _right) = duplex(1 << 20);
+        let mut transport: Transport<_, Codec, (), Vec<u8>> =
+            Transport::new(left, Codec::default());
+        transport.feed(vec![7; 4000]).await.unwrap();
+        let capacity = transport.send_queue.encode_buffer.capacity();
+        for size in (0..4000).step_by(40) {
+            transport.feed(vec![7; size]).await.unwrap();
+            assert_eq!(transport.send_queue.encode_buffer.capacity(), capacity);
+        }
+        assert!(matches!(
+            transport
+                .send(vec![7; crate::DEFAULT_MAX_MESSAGE_SIZE as usize])
+                .await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(_)))
+        ));
+        assert!(transport.send_queue.encode_buffer.capacity() > capacity);
+        transport.flush().await.unwrap();
     }
 }
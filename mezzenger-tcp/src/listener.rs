@@ -0,0 +1,180 @@
+//! TCP listener producing ready-made transports.
+
+use std::{
+    io,
+    marker::PhantomData,
+    net::SocketAddr,
+    task::{Context, Poll},
+};
+
+use futures::{ready, Stream};
+use serde::Serialize;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::{Config, Transport};
+
+/// TCP listener accepting connections as [Transport]s.
+///
+/// All accepted transports use the same codec and [config](Config).
+pub struct Listener<Codec, Incoming, Outgoing> {
+    listener: TcpListener,
+    codec: Codec,
+    config: Config,
+    _incoming: PhantomData<Incoming>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<Codec, Incoming, Outgoing> Listener<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec + Clone,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Create new listener bound to provided address.
+    ///
+    /// Accepted transports use default [config](Config).
+    pub async fn bind(address: impl ToSocketAddrs, codec: Codec) -> io::Result<Self> {
+        Listener::bind_with_config(address, codec, Config::default()).await
+    }
+
+    /// Create new listener bound to provided address.
+    ///
+    /// Accepted transports use provided [config](Config).
+    pub async fn bind_with_config(
+        address: impl ToSocketAddrs,
+        codec: Codec,
+        config: Config,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+        Ok(Listener::new_with_config(listener, codec, config))
+    }
+
+    /// Create new listener wrapping provided [TcpListener].
+    pub fn new(listener: TcpListener, codec: Codec) -> Self {
+        Listener::new_with_config(listener, codec, Config::default())
+    }
+
+    /// Create new listener wrapping provided [TcpListener],
+    /// accepted transports use provided [config](Config).
+    pub fn new_with_config(listener: TcpListener, codec: Codec, config: Config) -> Self {
+        Listener {
+            listener,
+            codec,
+            config,
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// Local address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Config applied to accepted transports.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Change config applied to subsequently accepted transports.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Accept new connection.
+    #[allow(clippy::type_complexity)]
+    pub async fn accept(
+        &self,
+    ) -> io::Result<(Transport<TcpStream, Codec, Incoming, Outgoing>, SocketAddr)> {
+        let (stream, address) = self.listener.accept().await?;
+        Ok((self.transport(stream), address))
+    }
+
+    /// Poll to accept new connection.
+    #[allow(clippy::type_complexity)]
+    pub fn poll_accept(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(Transport<TcpStream, Codec, Incoming, Outgoing>, SocketAddr)>> {
+        let (stream, address) = ready!(self.listener.poll_accept(cx))?;
+        Poll::Ready(Ok((self.transport(stream), address)))
+    }
+
+    /// Stream of accepted connections.
+    ///
+    /// Never ends - accepting errors are returned as stream items.
+    pub fn incoming(
+        &self,
+    ) -> impl Stream<Item = io::Result<(Transport<TcpStream, Codec, Incoming, Outgoing>, SocketAddr)>> + '_
+    {
+        futures::stream::poll_fn(|cx| self.poll_accept(cx).map(Some))
+    }
+
+    /// Unwrap underlying [TcpListener].
+    pub fn into_inner(self) -> TcpListener {
+        self.listener
+    }
+
+    fn transport(&self, stream: TcpStream) -> Transport<TcpStream, Codec, Incoming, Outgoing> {
+        Transport::new_with_config(stream, self.codec.clone(), self.config).with_peer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::net::TcpStream;
+
+    use crate::{Config, Error, Listener, Transport};
+
+    #[tokio::test]
+    async fn test_listener() {
+        let config = Config {
+            max_message_size: 64,
+            ..Default::default()
+        };
+        let listener: Listener<Codec, String, String> =
+            Listener::bind_with_config("127.0.0.1:0", Codec::default(), config)
+                .await
+                .unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let mut clients = vec![];
+        for _ in 0..2 {
+            let stream = TcpStream::connect(address).await.unwrap();
+            let client: Transport<_, Codec, String, String> =
+                Transport::new(stream, Codec::default());
+            clients.push(client);
+        }
+
+        let mut incoming = listener.incoming();
+        for (index, client) in clients.iter_mut().enumerate() {
+            let (mut server, client_address) = incoming.next().await.unwrap().unwrap();
+            assert_eq!(
+                client_address.port(),
+                client.inner.local_addr().unwrap().port()
+            );
+
+            client
+                .send(format!("Hello from client {index}"))
+                .await
+                .unwrap();
+            assert_eq!(
+                server.receive().await.unwrap(),
+                format!("Hello from client {index}")
+            );
+            server.send(format!("Hello client {index}")).await.unwrap();
+            assert_eq!(
+                client.receive().await.unwrap(),
+                format!("Hello client {index}")
+            );
+
+            assert!(matches!(
+                server.send("x".repeat(100)).await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(_)))
+            ));
+        }
+    }
+}
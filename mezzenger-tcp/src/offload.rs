@@ -0,0 +1,311 @@
+//! Decoding large incoming messages on a thread dedicated to blocking operations.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use kodec::{Decode, Encode};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    task::JoinHandle,
+};
+
+use crate::{decode_message, Error, Received, Transport};
+
+type TransportError<Codec> = Error<<Codec as Encode>::Error, <Codec as Decode>::Error>;
+
+type Decoding<Codec, Incoming> = JoinHandle<Result<Incoming, TransportError<Codec>>>;
+
+type Spawner<Codec, Incoming> = fn(&Codec, Bytes) -> Decoding<Codec, Incoming>;
+
+/// Message received by a transport with decode offloading enabled.
+enum Delivery<Codec, Incoming>
+where
+    Codec: kodec::Codec,
+{
+    /// Message decoded inline (or error).
+    Ready(Result<Incoming, TransportError<Codec>>),
+    /// Message being decoded on a blocking thread.
+    Decoding(Decoding<Codec, Incoming>),
+}
+
+impl<Codec, Incoming> From<Incoming> for Delivery<Codec, Incoming>
+where
+    Codec: kodec::Codec,
+{
+    fn from(message: Incoming) -> Self {
+        Delivery::Ready(Ok(message))
+    }
+}
+
+impl<Codec, Incoming> Received<Incoming> for Delivery<Codec, Incoming>
+where
+    Codec: kodec::Codec,
+{
+    fn decoded(&self) -> bool {
+        matches!(self, Delivery::Ready(_))
+    }
+}
+
+/// Messages received but not yet delivered, in order they were received.
+pub(crate) struct DecodeOffload<Codec, Incoming>
+where
+    Codec: kodec::Codec,
+{
+    threshold: usize,
+    max_in_flight: usize,
+    spawn: Spawner<Codec, Incoming>,
+    queue: VecDeque<Delivery<Codec, Incoming>>,
+}
+
+impl<Codec, Incoming> DecodeOffload<Codec, Incoming>
+where
+    Codec: kodec::Codec,
+{
+    /// Are there no messages waiting for delivery.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+fn spawn_decode<Codec, Incoming, Outgoing>(
+    codec: &Codec,
+    message: Bytes,
+) -> Decoding<Codec, Incoming>
+where
+    Codec: kodec::Codec + Clone + Send + 'static,
+    Incoming: DeserializeOwned + Send + 'static,
+    Outgoing: 'static,
+    <Codec as Encode>::Error: Send + 'static,
+    <Codec as Decode>::Error: Send + 'static,
+{
+    let codec = codec.clone();
+    tokio::task::spawn_blocking(move || {
+        decode_message::<Codec, Incoming, Outgoing>(&codec, &message)
+    })
+}
+
+impl<T, Codec, Incoming, Outgoing> Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Decode incoming messages of `threshold` or more bytes on a thread dedicated
+    /// to blocking operations (see [spawn_blocking](tokio::task::spawn_blocking)),
+    /// so decoding them doesn't stall receiving task (and other tasks of the runtime).
+    ///
+    /// Messages are still delivered in order they were received - smaller messages
+    /// decoded inline wait for the preceding offloaded ones.<br>
+    /// Reading stops while `max_in_flight` messages are waiting for delivery
+    /// (bounding number of concurrently offloaded decodes).
+    ///
+    /// Takes effect starting with the next incoming message.
+    ///
+    /// **NOTE**: Messages decoded incrementally
+    /// ([enable_streaming_decode](Transport::enable_streaming_decode)) aren't offloaded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_in_flight` is `0`.
+    pub fn enable_decode_offload(&mut self, threshold: usize, max_in_flight: usize)
+    where
+        Codec: Clone + Send + 'static,
+        Incoming: Send + 'static,
+        Outgoing: 'static,
+        <Codec as Encode>::Error: Send + 'static,
+        <Codec as Decode>::Error: Send + 'static,
+    {
+        assert!(max_in_flight > 0, "max_in_flight must be greater than 0");
+        match &mut self.decode_offload {
+            Some(decode_offload) => {
+                decode_offload.threshold = threshold;
+                decode_offload.max_in_flight = max_in_flight;
+            }
+            None => {
+                self.decode_offload = Some(DecodeOffload {
+                    threshold,
+                    max_in_flight,
+                    spawn: spawn_decode::<Codec, Incoming, Outgoing>,
+                    queue: VecDeque::new(),
+                })
+            }
+        }
+    }
+
+    /// Poll for the next message with decode offloading enabled.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn poll_next_offloaded(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Incoming, TransportError<Codec>>>> {
+        loop {
+            let me = self.as_mut().project();
+            let decode_offload = me.decode_offload.as_mut().unwrap();
+            match decode_offload.queue.front_mut() {
+                Some(Delivery::Ready(_)) => {
+                    if let Some(Delivery::Ready(result)) = decode_offload.queue.pop_front() {
+                        return Poll::Ready(Some(result));
+                    }
+                }
+                Some(Delivery::Decoding(decoding)) => {
+                    if let Poll::Ready(joined) = Pin::new(decoding).poll(cx) {
+                        decode_offload.queue.pop_front();
+                        let result = joined
+                            .unwrap_or_else(|error| std::panic::resume_unwind(error.into_panic()));
+                        if result.is_ok() {
+                            me.stats.message_received();
+                        } else {
+                            me.stats.decode_error();
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(parent: &*me.span, "failed to decode message");
+                        }
+                        return Poll::Ready(Some(result));
+                    }
+                }
+                None => {}
+            }
+            if decode_offload.queue.len() >= decode_offload.max_in_flight {
+                return Poll::Pending;
+            }
+
+            let threshold = decode_offload.threshold;
+            let spawn = decode_offload.spawn;
+            let polled = self.as_mut().poll_next_with(cx, |codec, payload| {
+                if payload.len() >= threshold {
+                    Ok(Delivery::Decoding(spawn(codec, payload.freeze())))
+                } else {
+                    payload
+                        .decode(|message| {
+                            decode_message::<Codec, Incoming, Outgoing>(codec, message)
+                        })
+                        .map(Delivery::from)
+                }
+            });
+            let queue = &mut self
+                .as_mut()
+                .project()
+                .decode_offload
+                .as_mut()
+                .unwrap()
+                .queue;
+            match polled {
+                Poll::Ready(Some(Ok(delivery))) => queue.push_back(delivery),
+                Poll::Ready(Some(Err(error))) => queue.push_back(Delivery::Ready(Err(error))),
+                // Remaining messages are still being decoded - polling them registered wake-up.
+                Poll::Ready(None) if !queue.is_empty() => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use futures::{SinkExt, StreamExt};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use serde::{Deserialize, Deserializer, Serialize};
+    use tokio::{io::duplex, join, time::sleep};
+
+    use crate::Transport;
+
+    /// Message taking long to deserialize when its payload is large.
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    struct Expensive {
+        index: u32,
+        payload: Vec<u8>,
+    }
+
+    impl<'de> Deserialize<'de> for Expensive {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            #[derive(Deserialize)]
+            struct Raw {
+                index: u32,
+                payload: Vec<u8>,
+            }
+
+            let Raw { index, payload } = Raw::deserialize(deserializer)?;
+            if payload.len() >= 1024 {
+                thread::sleep(Duration::from_millis(100));
+            }
+            Ok(Expensive { index, payload })
+        }
+    }
+
+    fn message(index: u32) -> Expensive {
+        let size = if index % 3 == 1 { 4096 } else { 16 };
+        Expensive {
+            index,
+            payload: vec![index as u8; size],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_offload_order() {
+        let (left, right) = duplex(1 << 20);
+        let mut left: Transport<_, Codec, (), Expensive> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, Expensive, ()> = Transport::new(right, Codec::default());
+        right.enable_decode_offload(1024, 2);
+
+        for index in 0..12 {
+            left.feed(message(index)).await.unwrap();
+        }
+        left.flush().await.unwrap();
+        left.close().await.unwrap();
+
+        let received: Vec<Expensive> = right.by_ref().map(Result::unwrap).collect().await;
+        assert_eq!(received, (0..12).map(message).collect::<Vec<_>>());
+        assert_eq!(right.stats().messages_received, 12);
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_decode_offload_doesnt_block() {
+        let (left, right) = duplex(1 << 20);
+        let mut left: Transport<_, Codec, (), Expensive> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, Expensive, ()> = Transport::new(right, Codec::default());
+        right.enable_decode_offload(1024, 4);
+
+        for index in 0..6 {
+            left.feed(message(index)).await.unwrap();
+        }
+        left.flush().await.unwrap();
+
+        // heartbeat running on the same (single-threaded) runtime as receiving
+        let heartbeat = async {
+            let mut max_latency = Duration::ZERO;
+            for _ in 0..20 {
+                let start = Instant::now();
+                sleep(Duration::from_millis(5)).await;
+                max_latency = max_latency.max(start.elapsed());
+            }
+            max_latency
+        };
+        let receive = async {
+            for index in 0..6 {
+                assert_eq!(right.receive().await.unwrap().index, index);
+            }
+        };
+        let (max_latency, ()) = join!(heartbeat, receive);
+        assert!(max_latency < Duration::from_millis(50), "{max_latency:?}");
+    }
+}
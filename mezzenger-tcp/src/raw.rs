@@ -0,0 +1,251 @@
+//! Transport of raw byte messages.
+
+use std::{
+    convert::Infallible,
+    io::{Read, Write},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::{stream::FusedStream, Sink, Stream};
+use kodec::{Decode, Encode};
+use pin_project::pin_project;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{Config, Error, Stats, Transport, TransportStats};
+
+/// Error of [RawTransport].
+pub type RawError = Error<Infallible, Infallible>;
+
+/// Placeholder codec of [RawTransport] - messages are never encoded nor decoded.
+struct Raw;
+
+impl Encode for Raw {
+    type Error = Infallible;
+
+    fn encode<W, T>(&self, _writer: W, _message: &T) -> Result<(), Self::Error>
+    where
+        W: Write,
+        T: Serialize,
+    {
+        unreachable!("raw transport doesn't encode messages")
+    }
+}
+
+impl Decode for Raw {
+    type Error = Infallible;
+
+    fn decode<R, T>(&self, _reader: R) -> Result<T, Self::Error>
+    where
+        R: Read,
+        for<'de> T: Deserialize<'de>,
+    {
+        unreachable!("raw transport doesn't decode messages")
+    }
+}
+
+/// Transport sending and receiving messages as raw bytes, without any codec.
+///
+/// Messages are framed exactly like messages of [Transport] with the same [Config],
+/// so raw transport can talk to codec-based transport on the other end
+/// (provided it sends and expects messages encoded with that transport's codec).
+///
+/// Received messages are handed out without copying them out of receive buffer
+/// (unless they were compressed) and sent messages aren't copied into send buffer
+/// (unless any per-frame option, like [checksum](Config::checksum), is enabled).
+///
+/// Useful for proxies forwarding messages without decoding them or as a base
+/// for wrappers transforming messages (for example compressing or encrypting them).
+#[pin_project]
+pub struct RawTransport<T>
+where
+    T: AsyncWrite + AsyncRead,
+{
+    #[pin]
+    inner: Transport<T, Raw, Bytes, ()>,
+}
+
+impl<T> RawTransport<T>
+where
+    T: AsyncWrite + AsyncRead,
+{
+    /// Create new raw transport wrapping a provided struct implementing
+    /// [tokio::io::AsyncWrite] and [tokio::io::AsyncRead].
+    pub fn new(transport: T) -> Self {
+        RawTransport::new_with_config(transport, Config::default())
+    }
+
+    /// Create new raw transport with provided [Config].
+    ///
+    /// Config has to match config of the transport on the other end.
+    pub fn new_with_config(transport: T, config: Config) -> Self {
+        RawTransport {
+            inner: Transport::new_with_config(transport, Raw, config),
+        }
+    }
+
+    /// Maximum message size.
+    pub fn max_message_size(&self) -> u64 {
+        self.inner.max_message_size()
+    }
+
+    /// Snapshot of transport statistics.
+    pub fn stats(&self) -> Stats {
+        self.inner.stats()
+    }
+
+    /// Handle to live transport statistics.
+    pub fn stats_handle(&self) -> Arc<TransportStats> {
+        self.inner.stats_handle()
+    }
+}
+
+impl<T> Sink<Bytes> for RawTransport<T>
+where
+    T: AsyncWrite + AsyncRead,
+{
+    type Error = mezzenger::Error<RawError>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<()>::poll_ready(self.project().inner, cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        self.project().inner.queue_raw(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<()>::poll_flush(self.project().inner, cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<()>::poll_close(self.project().inner, cx)
+    }
+}
+
+impl<T> Sink<Vec<u8>> for RawTransport<T>
+where
+    T: AsyncWrite + AsyncRead,
+{
+    type Error = mezzenger::Error<RawError>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<()>::poll_ready(self.project().inner, cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), Self::Error> {
+        self.project().inner.queue_raw(item.into())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<()>::poll_flush(self.project().inner, cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<()>::poll_close(self.project().inner, cx)
+    }
+}
+
+impl<T> Stream for RawTransport<T>
+where
+    T: AsyncWrite + AsyncRead,
+{
+    type Item = Result<Bytes, RawError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project()
+            .inner
+            .poll_next_with(cx, |_, payload| Ok(payload.freeze()))
+    }
+}
+
+impl<T> FusedStream for RawTransport<T>
+where
+    T: AsyncWrite + AsyncRead,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+impl<T> mezzenger::Reliable for RawTransport<T> where T: AsyncWrite + AsyncRead {}
+
+impl<T> mezzenger::Order for RawTransport<T> where T: AsyncWrite + AsyncRead {}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::SinkExt;
+    use kodec::{binary::Codec, Encode};
+    use mezzenger::Receive;
+    use tokio::io::duplex;
+
+    use crate::{Config, Error, RawTransport, Transport};
+
+    fn encode(message: &str) -> Vec<u8> {
+        let mut encoded = vec![];
+        #[cfg(feature = "type-tag")]
+        encoded.extend_from_slice(&mezzenger::type_tag::tag::<String>());
+        Codec::default()
+            .encode(&mut encoded, &message.to_string())
+            .unwrap();
+        encoded
+    }
+
+    #[tokio::test]
+    async fn test_raw_transport() {
+        for config in [
+            Config::default(),
+            Config {
+                checksum: true,
+                ..Default::default()
+            },
+        ] {
+            let (left, right) = duplex(1 << 20);
+
+            let mut raw = RawTransport::new_with_config(left, config);
+            let mut transport: Transport<_, Codec, String, String> =
+                Transport::new_with_config(right, Codec::default(), config);
+
+            transport.send("Hello World!".to_string()).await.unwrap();
+            transport.send(String::new()).await.unwrap();
+            assert_eq!(raw.receive().await.unwrap(), encode("Hello World!"));
+            assert_eq!(raw.receive().await.unwrap(), encode(""));
+
+            raw.send(Bytes::from(encode("Hi"))).await.unwrap();
+            raw.send(encode("Hello")).await.unwrap();
+            assert_eq!(transport.receive().await.unwrap(), "Hi");
+            assert_eq!(transport.receive().await.unwrap(), "Hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raw_transport_max_message_size() {
+        let (left, right) = duplex(1 << 20);
+
+        let config = Config {
+            max_message_size: 16,
+            ..Default::default()
+        };
+        let mut left = RawTransport::new_with_config(left, config);
+        let mut right = RawTransport::new(right);
+
+        assert!(matches!(
+            left.send(vec![0; 17]).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(17)))
+        ));
+        right.send(vec![1; 17]).await.unwrap();
+        right.send(vec![2; 16]).await.unwrap();
+        assert!(matches!(
+            left.receive().await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(17)))
+        ));
+        assert_eq!(left.receive().await.unwrap(), vec![2; 16]);
+
+        left.send(vec![3; 16]).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), vec![3; 16]);
+    }
+}
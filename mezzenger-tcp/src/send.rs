@@ -0,0 +1,290 @@
+//! Queue of outgoing frames.
+
+use std::{
+    collections::VecDeque,
+    io::{self, IoSlice},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::ready;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    runtime::Handle,
+};
+
+use crate::TransportStats;
+
+/// Maximum number of frames passed to a single vectored write.
+const MAX_WRITE_SLICES: usize = 64;
+
+/// Outgoing frames waiting to be written to the underlying stream.
+///
+/// Messages are encoded into [encode buffer](SendQueue::encode_buffer), frames are built
+/// in [scratch](SendQueue::scratch) buffer and moved to the queue
+/// with [commit](SendQueue::commit) - they are written with vectored writes
+/// (if supported by the stream) without copying them into one contiguous buffer.
+pub(crate) struct SendQueue {
+    /// Buffer messages are encoded into before being framed.
+    ///
+    /// Cleared before every message - retains its capacity, so it is only reallocated
+    /// when a message larger than all previous ones is encoded.
+    pub encode_buffer: BytesMut,
+    /// Buffer frames are built in before being committed.
+    pub scratch: BytesMut,
+    frames: VecDeque<Bytes>,
+    queued_bytes: usize,
+    /// Number of bytes written since the queue was created.
+    written: u64,
+    /// Positions (in bytes queued since the queue was created) where queued messages end.
+    message_ends: VecDeque<u64>,
+    /// Set when frames left in the queue should be written once it's dropped.
+    pub flush_on_drop: Option<DropFlush>,
+}
+
+/// Duplicate of the transport's socket and runtime used to write frames left
+/// in a dropped queue (see [FlushOnDrop](mezzenger::FlushOnDrop)).
+pub(crate) struct DropFlush {
+    pub handle: Handle,
+    pub socket: socket2::Socket,
+}
+
+impl SendQueue {
+    pub(crate) fn new(capacity: usize) -> Self {
+        SendQueue {
+            encode_buffer: BytesMut::new(),
+            scratch: BytesMut::with_capacity(capacity),
+            frames: VecDeque::new(),
+            queued_bytes: 0,
+            written: 0,
+            message_ends: VecDeque::new(),
+            flush_on_drop: None,
+        }
+    }
+
+    /// Move frames built in scratch buffer to the queue.
+    pub(crate) fn commit(&mut self) {
+        if !self.scratch.is_empty() {
+            let frame = self.scratch.split().freeze();
+            self.queued_bytes += frame.len();
+            self.frames.push_back(frame);
+        }
+    }
+
+    /// Queue frame (or its part) built outside of scratch buffer.
+    ///
+    /// Frames already built in scratch buffer are committed first to preserve order.
+    pub(crate) fn push(&mut self, frame: Bytes) {
+        self.commit();
+        if !frame.is_empty() {
+            self.queued_bytes += frame.len();
+            self.frames.push_back(frame);
+        }
+    }
+
+    /// Mark everything queued so far as the end of a message
+    /// (control frames aren't counted as messages).
+    pub(crate) fn message_queued(&mut self) {
+        self.message_ends
+            .push_back(self.written + self.len() as u64);
+    }
+
+    /// Number of messages not (fully) written yet.
+    pub(crate) fn messages(&self) -> usize {
+        self.message_ends.len()
+    }
+
+    /// Number of bytes not written yet.
+    pub(crate) fn len(&self) -> usize {
+        self.queued_bytes + self.scratch.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write all committed frames and flush the underlying stream.
+    pub(crate) fn poll_write_all<T: AsyncWrite>(
+        &mut self,
+        mut inner: Pin<&mut T>,
+        cx: &mut Context<'_>,
+        stats: &TransportStats,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            if self.frames.is_empty() {
+                return inner.as_mut().poll_flush(cx);
+            }
+            let bytes_written = if inner.is_write_vectored() {
+                let mut slices = [IoSlice::new(&[]); MAX_WRITE_SLICES];
+                let count = self
+                    .frames
+                    .iter()
+                    .zip(slices.iter_mut())
+                    .map(|(frame, slice)| *slice = IoSlice::new(frame))
+                    .count();
+                ready!(inner.as_mut().poll_write_vectored(cx, &slices[..count]))?
+            } else {
+                ready!(inner.as_mut().poll_write(cx, &self.frames[0]))?
+            };
+            if bytes_written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            stats.add_bytes_sent(bytes_written);
+            self.advance(bytes_written);
+        }
+    }
+
+    fn advance(&mut self, mut bytes: usize) {
+        self.queued_bytes -= bytes;
+        self.written += bytes as u64;
+        while self
+            .message_ends
+            .front()
+            .is_some_and(|&end| end <= self.written)
+        {
+            self.message_ends.pop_front();
+        }
+        while bytes > 0 {
+            let frame = self.frames.front_mut().unwrap();
+            if frame.len() <= bytes {
+                bytes -= frame.len();
+                self.frames.pop_front();
+            } else {
+                frame.advance(bytes);
+                bytes = 0;
+            }
+        }
+    }
+}
+
+impl Drop for SendQueue {
+    fn drop(&mut self) {
+        if let Some(DropFlush { handle, socket }) = self.flush_on_drop.take() {
+            self.commit();
+            let frames = std::mem::take(&mut self.frames);
+            handle.spawn(async move {
+                let stream = socket
+                    .set_nonblocking(true)
+                    .and_then(|()| TcpStream::from_std(socket.into()));
+                if let Ok(mut stream) = stream {
+                    for frame in frames {
+                        if stream.write_all(&frame).await.is_err() {
+                            return;
+                        }
+                    }
+                    let _ = stream.shutdown().await;
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures::SinkExt;
+    use kodec::binary::Codec;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    use crate::Transport;
+
+    /// Writer accepting at most `max_write` bytes per call and counting calls.
+    struct CountingWriter {
+        vectored: bool,
+        max_write: usize,
+        written: Vec<u8>,
+        writes: usize,
+    }
+
+    impl CountingWriter {
+        fn new(vectored: bool, max_write: usize) -> Self {
+            CountingWriter {
+                vectored,
+                max_write,
+                written: vec![],
+                writes: 0,
+            }
+        }
+    }
+
+    impl AsyncRead for CountingWriter {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Pending
+        }
+    }
+
+    impl AsyncWrite for CountingWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.writes += 1;
+            let length = buf.len().min(self.max_write);
+            self.written.extend_from_slice(&buf[..length]);
+            Poll::Ready(Ok(length))
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            self.writes += 1;
+            let mut length = 0;
+            for buf in bufs {
+                let remaining = self.max_write - length;
+                let chunk = &buf[..buf.len().min(remaining)];
+                self.written.extend_from_slice(chunk);
+                length += chunk.len();
+            }
+            Poll::Ready(Ok(length))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.vectored
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    async fn send_all(writer: CountingWriter) -> CountingWriter {
+        let mut transport: Transport<_, Codec, (), u32> = Transport::new(writer, Codec::default());
+        for i in 0..100 {
+            transport.feed(i).await.unwrap();
+        }
+        transport.flush().await.unwrap();
+        transport.inner
+    }
+
+    #[tokio::test]
+    async fn test_vectored_writes() {
+        let sequential = send_all(CountingWriter::new(false, usize::MAX)).await;
+        let vectored = send_all(CountingWriter::new(true, usize::MAX)).await;
+        assert_eq!(vectored.written, sequential.written);
+        assert_eq!(sequential.writes, 100);
+        assert_eq!(vectored.writes, 2);
+
+        // Partial writes resume in the middle of a frame.
+        let partial = send_all(CountingWriter::new(true, 13)).await;
+        assert_eq!(partial.written, sequential.written);
+        assert_eq!(partial.writes, sequential.written.len().div_ceil(13));
+    }
+}
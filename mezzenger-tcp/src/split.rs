@@ -0,0 +1,659 @@
+//! Transport split into independently owned sending and receiving halves.
+
+use std::{
+    io::{self, IoSlice},
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::{ready, stream::FusedStream, task::noop_waker_ref, Sink, Stream};
+use kodec::{Decode, Encode};
+use mezzenger::SplitState;
+use pin_project::{pin_project, pinned_drop};
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf, ReadHalf, WriteHalf};
+
+use crate::{
+    send::SendQueue, Error, OversizePolicy, ReceiveState, Stats, StreamingCodec, Transport,
+    TransportStats,
+};
+
+/// Write half of the underlying stream, reading from it always reports end of stream.
+struct WriteOnly<T>(WriteHalf<T>);
+
+impl<T: AsyncWrite> AsyncWrite for WriteOnly<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.0.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl<T> AsyncRead for WriteOnly<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Read half of the underlying stream parked by dropped [Receiver],
+/// so [Sender] can discard incoming data.
+type Parked<T> = Arc<Mutex<Option<ReadHalf<T>>>>;
+
+/// Read half of the underlying stream, writing to it always fails.
+///
+/// Read half is parked when dropped.
+struct ReadOnly<T> {
+    read_half: Option<ReadHalf<T>>,
+    parked: Parked<T>,
+    state: Arc<SplitState>,
+}
+
+impl<T: AsyncRead> AsyncRead for ReadOnly<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(self.read_half.as_mut().unwrap()).poll_read(cx, buf)
+    }
+}
+
+impl<T> Drop for ReadOnly<T> {
+    fn drop(&mut self) {
+        if let Ok(mut parked) = self.parked.lock() {
+            *parked = self.read_half.take();
+        }
+        self.state.close_receive();
+    }
+}
+
+impl<T> AsyncWrite for ReadOnly<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Err(io::ErrorKind::Unsupported.into()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Discard data arriving after [Receiver] was dropped.
+fn discard_incoming<T: AsyncRead>(parked: &Parked<T>, state: &SplitState, cx: &mut Context<'_>) {
+    if !state.is_receive_closed() {
+        return;
+    }
+    let Ok(mut parked) = parked.lock() else {
+        return;
+    };
+    let Some(read_half) = parked.as_mut() else {
+        return;
+    };
+    let mut buffer = [0; 4096];
+    loop {
+        let mut read_buffer = ReadBuf::new(&mut buffer);
+        match Pin::new(&mut *read_half).poll_read(cx, &mut read_buffer) {
+            Poll::Ready(Ok(())) if !read_buffer.filled().is_empty() => {
+                state.add_discarded_bytes(read_buffer.filled().len() as u64);
+            }
+            Poll::Ready(_) => {
+                // End of stream or error - nothing more to discard.
+                *parked = None;
+                return;
+            }
+            Poll::Pending => return,
+        }
+    }
+}
+
+/// Sending half of a [Transport] created with [Transport::into_split].
+///
+/// Dropping sender flushes pending messages on a best-effort basis (without waiting),
+/// connection stays open for [Receiver] - see [mezzenger::split] for details.
+#[pin_project(PinnedDrop)]
+pub struct Sender<T, Codec, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    Outgoing: Serialize,
+{
+    #[pin]
+    inner: Transport<WriteOnly<T>, Codec, (), Outgoing>,
+    state: Arc<SplitState>,
+    parked: Parked<T>,
+}
+
+#[pinned_drop]
+impl<T, Codec, Outgoing> PinnedDrop for Sender<T, Codec, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    Outgoing: Serialize,
+{
+    fn drop(self: Pin<&mut Self>) {
+        let me = self.project();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let _ = me.inner.poll_flush(&mut cx);
+        me.state.close_send();
+    }
+}
+
+/// Receiving half of a [Transport] created with [Transport::into_split].
+///
+/// Dropping receiver doesn't close the connection - data arriving afterwards
+/// is discarded by [Sender] (see [Sender::discarded_bytes]).
+#[pin_project]
+pub struct Receiver<T, Codec, Incoming>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    #[pin]
+    inner: Transport<ReadOnly<T>, Codec, Incoming, ()>,
+    state: Arc<SplitState>,
+}
+
+impl<T, Codec, Incoming, Outgoing> Transport<T, Codec, Incoming, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec + Clone,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Split transport into sending and receiving halves that can be owned
+    /// (and used) by different tasks.
+    ///
+    /// Underlying stream is split with [tokio::io::split].<br>
+    /// Buffered data, codecs and [statistics](Transport::stats) are carried over
+    /// (statistics are shared by both halves).
+    ///
+    /// Halves can be dropped in any order - connection is closed once both
+    /// of them are dropped (see [mezzenger::split] for details).
+    ///
+    /// # Panics
+    ///
+    /// Panics if heartbeat ([keepalive](crate::Config::keepalive)) is enabled -
+    /// it requires receiving side to send frames.
+    pub fn into_split(self) -> (Sender<T, Codec, Outgoing>, Receiver<T, Codec, Incoming>) {
+        let Transport {
+            inner,
+            send_queue,
+            receive_state,
+            codec,
+            receive_codec,
+            terminated,
+            read_closed,
+            write_closed,
+            max_message_size,
+            strict,
+            oversize_policy,
+            framing,
+            byte_order,
+            frame_options,
+            #[cfg(feature = "compression")]
+            compression_threshold,
+            keepalive,
+            write_timeout,
+            streaming_decoder,
+            active_decoder,
+            #[cfg(feature = "offload")]
+            decode_offload,
+            outgoing_frame_hook,
+            incoming_frame_hook,
+            stats,
+            #[cfg(feature = "tracing")]
+            span,
+            ..
+        } = self;
+        assert!(
+            keepalive.is_none(),
+            "transport with heartbeat enabled can't be split"
+        );
+
+        let (read_half, write_half) = tokio::io::split(inner);
+        let receive_codec = receive_codec.unwrap_or_else(|| codec.clone());
+        let state = Arc::new(SplitState::new());
+        let parked = Arc::new(Mutex::new(None));
+
+        let sender = Transport {
+            inner: WriteOnly(write_half),
+            send_queue,
+            receive_state: ReceiveState::new(0, 0, 0),
+            codec,
+            receive_codec: None,
+            terminated,
+            read_closed: true,
+            write_closed,
+            max_message_size,
+            strict: false,
+            oversize_policy: OversizePolicy::Skip,
+            framing,
+            byte_order,
+            frame_options,
+            #[cfg(feature = "compression")]
+            compression_threshold,
+            keepalive: None,
+            write_timeout,
+            streaming_decoder: None,
+            active_decoder: None,
+            #[cfg(feature = "offload")]
+            decode_offload: None,
+            outgoing_frame_hook,
+            incoming_frame_hook: None,
+            stats: stats.clone(),
+            #[cfg(feature = "tracing")]
+            span: span.clone(),
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        };
+        let receiver = Transport {
+            inner: ReadOnly {
+                read_half: Some(read_half),
+                parked: parked.clone(),
+                state: state.clone(),
+            },
+            send_queue: SendQueue::new(0),
+            receive_state,
+            codec: receive_codec,
+            receive_codec: None,
+            terminated,
+            read_closed,
+            write_closed: true,
+            max_message_size,
+            strict,
+            oversize_policy,
+            framing,
+            byte_order,
+            frame_options,
+            #[cfg(feature = "compression")]
+            compression_threshold,
+            keepalive: None,
+            write_timeout: None,
+            streaming_decoder,
+            active_decoder,
+            #[cfg(feature = "offload")]
+            decode_offload,
+            outgoing_frame_hook: None,
+            incoming_frame_hook,
+            stats,
+            #[cfg(feature = "tracing")]
+            span,
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        };
+
+        (
+            Sender {
+                inner: sender,
+                state: state.clone(),
+                parked,
+            },
+            Receiver {
+                inner: receiver,
+                state,
+            },
+        )
+    }
+}
+
+impl<T, Codec, Outgoing> Sender<T, Codec, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    Outgoing: Serialize,
+{
+    /// Replace codec used to encode outgoing messages.
+    ///
+    /// Messages sent before this call (even if not flushed yet) keep using the old codec.
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.inner.set_codec(codec);
+    }
+
+    /// Send message already encoded with transport's codec.
+    ///
+    /// See [Transport::start_send_encoded].
+    #[allow(clippy::type_complexity)]
+    pub fn start_send_encoded(
+        self: Pin<&mut Self>,
+        message: &[u8],
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        self.project().inner.start_send_encoded(message)
+    }
+
+    /// Send message already encoded with transport's codec and flush it.
+    ///
+    /// See [Transport::start_send_encoded].
+    pub async fn send_encoded(
+        &mut self,
+        message: &[u8],
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    where
+        T: Unpin,
+    {
+        self.inner.send_encoded(message).await
+    }
+
+    /// Snapshot of transport statistics (shared with [Receiver]).
+    pub fn stats(&self) -> Stats {
+        self.inner.stats()
+    }
+
+    /// Handle to live transport statistics (shared with [Receiver]).
+    pub fn stats_handle(&self) -> Arc<TransportStats> {
+        self.inner.stats_handle()
+    }
+
+    /// Was [Receiver] dropped.
+    pub fn is_receiver_dropped(&self) -> bool {
+        self.state.is_receive_closed()
+    }
+
+    /// Number of incoming bytes discarded after [Receiver] was dropped.
+    ///
+    /// Incoming data is discarded while sender is being polled (when sending or flushing).
+    pub fn discarded_bytes(&self) -> u64 {
+        self.state.discarded_bytes()
+    }
+}
+
+impl<T, Codec, Outgoing> Sink<Outgoing> for Sender<T, Codec, Outgoing>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    Outgoing: Serialize,
+{
+    type Error = mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        discard_incoming(me.parked, me.state, cx);
+        me.inner.poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        self.project().inner.start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        discard_incoming(me.parked, me.state, cx);
+        me.inner.poll_flush(cx)
+    }
+
+    /// Flush pending messages and shut down the write direction of the connection.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        let result = ready!(me.inner.poll_shutdown_write(cx));
+        me.state.close_send();
+        Poll::Ready(result)
+    }
+}
+
+impl<T, Codec, Incoming> Receiver<T, Codec, Incoming>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    /// Stop reading from the underlying stream.
+    ///
+    /// See [Transport::pause_reading].
+    pub fn pause_reading(&mut self) {
+        self.inner.pause_reading();
+    }
+
+    /// Resume reading from the underlying stream.
+    pub fn resume_reading(&mut self) {
+        self.inner.resume_reading();
+    }
+
+    /// Is reading currently paused.
+    pub fn is_reading_paused(&self) -> bool {
+        self.inner.is_reading_paused()
+    }
+
+    /// Set whether complete messages already buffered should be
+    /// returned while reading is paused (`true` by default).
+    pub fn set_deliver_buffered_while_paused(&mut self, deliver: bool) {
+        self.inner.set_deliver_buffered_while_paused(deliver);
+    }
+
+    /// Replace codec used to decode incoming messages.
+    ///
+    /// Takes effect starting with the next received message, even if it is
+    /// already buffered.
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.inner.set_codec(codec);
+    }
+
+    /// Decode incoming messages incrementally.
+    ///
+    /// See [Transport::enable_streaming_decode].
+    pub fn enable_streaming_decode(&mut self)
+    where
+        Codec: StreamingCodec,
+        <Codec as StreamingCodec>::Decoder<Incoming>: Send + Sync + 'static,
+    {
+        self.inner.enable_streaming_decode();
+    }
+
+    /// Snapshot of transport statistics (shared with [Sender]).
+    pub fn stats(&self) -> Stats {
+        self.inner.stats()
+    }
+
+    /// Handle to live transport statistics (shared with [Sender]).
+    pub fn stats_handle(&self) -> Arc<TransportStats> {
+        self.inner.stats_handle()
+    }
+
+    /// Was [Sender] closed or dropped.
+    pub fn is_sender_closed(&self) -> bool {
+        self.state.is_send_closed()
+    }
+}
+
+impl<T, Codec, Incoming> Stream for Receiver<T, Codec, Incoming>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    type Item = Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().inner.poll_next(cx)
+    }
+}
+
+impl<T, Codec, Incoming> FusedStream for Receiver<T, Codec, Incoming>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+impl<T, Codec, Incoming> mezzenger::Reliable for Receiver<T, Codec, Incoming>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+}
+
+impl<T, Codec, Incoming> mezzenger::Order for Receiver<T, Codec, Incoming>
+where
+    T: AsyncWrite + AsyncRead,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::io::duplex;
+
+    use crate::{Config, Transport};
+
+    #[tokio::test]
+    async fn test_split() {
+        let (left, right) = duplex(1 << 20);
+
+        let config = Config {
+            checksum: true,
+            ..Default::default()
+        };
+        let left: Transport<_, Codec, String, u32> =
+            Transport::new_with_config(left, Codec::default(), config);
+        let mut right: Transport<_, Codec, u32, String> =
+            Transport::new_with_config(right, Codec::default(), config);
+
+        let (mut sender, mut receiver) = left.into_split();
+
+        let receiving = tokio::spawn(async move {
+            let mut received = vec![];
+            while let Some(message) = receiver.next().await {
+                received.push(message.unwrap());
+            }
+            (received, receiver.stats().messages_received)
+        });
+        let sending = tokio::spawn(async move {
+            for i in 0..100 {
+                sender.send(i).await.unwrap();
+            }
+            sender.close().await.unwrap();
+            assert!(sender.send(100).await.is_err());
+            sender.stats().messages_sent
+        });
+
+        for i in 0..100 {
+            assert_eq!(right.receive().await.unwrap(), i);
+            right.send(format!("Hello {i}")).await.unwrap();
+        }
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        right.close().await.unwrap();
+
+        assert_eq!(sending.await.unwrap(), 100);
+        let (received, messages_received) = receiving.await.unwrap();
+        assert_eq!(
+            received,
+            (0..100).map(|i| format!("Hello {i}")).collect::<Vec<_>>()
+        );
+        assert_eq!(messages_received, 100);
+    }
+
+    #[tokio::test]
+    async fn test_drop_receiver() {
+        let (left, right) = duplex(1 << 20);
+
+        let left: Transport<_, Codec, String, u32> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, String> = Transport::new(right, Codec::default());
+
+        let (mut sender, receiver) = left.into_split();
+        assert!(!sender.is_receiver_dropped());
+        drop(receiver);
+        assert!(sender.is_receiver_dropped());
+
+        right.send("Hello".to_string()).await.unwrap();
+        right.send("World".to_string()).await.unwrap();
+
+        for i in 0..10 {
+            sender.send(i).await.unwrap();
+            assert_eq!(right.receive().await.unwrap(), i);
+        }
+        assert_eq!(sender.discarded_bytes(), right.stats().bytes_sent);
+        assert_eq!(sender.stats().messages_received, 0);
+    }
+
+    #[tokio::test]
+    async fn test_drop_sender() {
+        let (left, right) = duplex(1 << 20);
+
+        let left: Transport<_, Codec, String, u32> = Transport::new(left, Codec::default());
+        let mut right: Transport<_, Codec, u32, String> = Transport::new(right, Codec::default());
+
+        let (mut sender, mut receiver) = left.into_split();
+        for i in 0..10 {
+            sender.feed(i).await.unwrap();
+        }
+        assert!(!receiver.is_sender_closed());
+        drop(sender);
+        assert!(receiver.is_sender_closed());
+
+        // Pending messages were flushed on drop.
+        for i in 0..10 {
+            assert_eq!(right.receive().await.unwrap(), i);
+        }
+
+        // Connection stays open for receiver.
+        right.send("Hello".to_string()).await.unwrap();
+        assert_eq!(receiver.receive().await.unwrap(), "Hello");
+
+        // Connection is closed once both halves are dropped.
+        drop(receiver);
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_split_with_keepalive() {
+        let (left, _right) = duplex(1 << 20);
+        let left: Transport<_, Codec, String, u32> = Transport::new_with_keepalive(
+            left,
+            Codec::default(),
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(3),
+        );
+        let _ = left.into_split();
+    }
+}
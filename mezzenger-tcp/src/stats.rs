@@ -0,0 +1,133 @@
+//! Transport statistics.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Snapshot of transport statistics returned by [Transport::stats](crate::Transport::stats).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Number of messages accepted for sending.
+    pub messages_sent: u64,
+    /// Number of successfully received messages.
+    pub messages_received: u64,
+    /// Number of bytes written to the underlying stream (including framing overhead).
+    pub bytes_sent: u64,
+    /// Number of bytes read from the underlying stream (including framing overhead).
+    pub bytes_received: u64,
+    /// Number of received messages skipped because they exceeded maximum message size.
+    pub oversized_dropped: u64,
+    /// Number of received messages that failed to deserialize.
+    pub decode_errors: u64,
+    /// Time of the last sent message.
+    pub last_sent: Option<SystemTime>,
+    /// Time of the last received message.
+    pub last_received: Option<SystemTime>,
+}
+
+/// Live transport statistics that can be shared with other tasks.
+///
+/// Obtained with [Transport::stats_handle](crate::Transport::stats_handle).
+#[derive(Debug, Default)]
+pub struct TransportStats {
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    oversized_dropped: AtomicU64,
+    decode_errors: AtomicU64,
+    last_sent: AtomicU64,
+    last_received: AtomicU64,
+}
+
+impl TransportStats {
+    /// Take a snapshot of current statistics.
+    pub fn snapshot(&self) -> Stats {
+        Stats {
+            messages_sent: self.messages_sent.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            oversized_dropped: self.oversized_dropped.load(Ordering::Relaxed),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+            last_sent: from_micros(self.last_sent.load(Ordering::Relaxed)),
+            last_received: from_micros(self.last_received.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Reset all statistics.
+    pub fn reset(&self) {
+        for counter in [
+            &self.messages_sent,
+            &self.messages_received,
+            &self.bytes_sent,
+            &self.bytes_received,
+            &self.oversized_dropped,
+            &self.decode_errors,
+            &self.last_sent,
+            &self.last_received,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn messages_sent(&self) -> u64 {
+        self.messages_sent.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn messages_received(&self) -> u64 {
+        self.messages_received.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn message_sent(&self) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.last_sent.store(now_micros(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn message_received(&self) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.last_received.store(now_micros(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_bytes_received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn oversized_dropped(&self) {
+        self.oversized_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_micros() as u64)
+        .unwrap_or(0)
+        .max(1)
+}
+
+fn from_micros(micros: u64) -> Option<SystemTime> {
+    if micros == 0 {
+        None
+    } else {
+        Some(UNIX_EPOCH + Duration::from_micros(micros))
+    }
+}
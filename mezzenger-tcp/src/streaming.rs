@@ -0,0 +1,53 @@
+//! Incremental message decoding.
+
+use kodec::Decode;
+use serde::de::DeserializeOwned;
+
+/// Decoder consuming serialized message in chunks, as they arrive.
+pub trait StreamingDecoder<T> {
+    type Error;
+
+    /// Feed next chunk of serialized message.
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), Self::Error>;
+
+    /// Finish decoding after whole message was fed.
+    fn finish(&mut self) -> Result<T, Self::Error>;
+}
+
+/// Codec able to decode messages incrementally.
+///
+/// By default transport waits until whole serialized message is buffered before
+/// decoding it. Codecs implementing this trait can be fed with incoming bytes
+/// as they arrive instead (see [Transport::enable_streaming_decode]), so the transport
+/// doesn't have to hold whole serialized message in memory.
+///
+/// Decoders have to be `Send + Sync + 'static`.
+///
+/// [Transport::enable_streaming_decode]: crate::Transport::enable_streaming_decode
+pub trait StreamingCodec: Decode {
+    type Decoder<T>: StreamingDecoder<T, Error = Self::Error>
+    where
+        T: DeserializeOwned;
+
+    /// Create decoder for a message of provided serialized size.
+    fn decoder<T>(&self, message_size: u64) -> Self::Decoder<T>
+    where
+        T: DeserializeOwned;
+}
+
+pub(crate) type BoxedDecoder<T, Error> = Box<dyn StreamingDecoder<T, Error = Error> + Send + Sync>;
+
+pub(crate) type DecoderFactory<Codec, T> =
+    fn(&Codec, u64) -> BoxedDecoder<T, <Codec as Decode>::Error>;
+
+pub(crate) fn new_decoder<Codec, T>(
+    codec: &Codec,
+    message_size: u64,
+) -> BoxedDecoder<T, <Codec as Decode>::Error>
+where
+    Codec: StreamingCodec,
+    T: DeserializeOwned,
+    Codec::Decoder<T>: Send + Sync + 'static,
+{
+    Box::new(codec.decoder::<T>(message_size))
+}
@@ -0,0 +1,47 @@
+//! Detection of stalled writes.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use tokio::time::{sleep_until, Instant, Sleep};
+
+/// Timer measuring how long a flush hasn't made any progress.
+pub(crate) struct WriteTimeout {
+    timeout: Duration,
+    timer: Option<Pin<Box<Sleep>>>,
+    armed: bool,
+}
+
+impl WriteTimeout {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        WriteTimeout {
+            timeout,
+            timer: None,
+            armed: false,
+        }
+    }
+
+    /// Flush completed - stop measuring.
+    pub(crate) fn stop(&mut self) {
+        self.armed = false;
+    }
+
+    /// Poll whether pending flush stalled for longer than the timeout.
+    ///
+    /// Timer is (re)started when flush starts waiting or `progress` was made since last poll.
+    pub(crate) fn poll_stalled(&mut self, progress: bool, cx: &mut Context<'_>) -> Poll<()> {
+        let deadline = Instant::now() + self.timeout;
+        let timer = self
+            .timer
+            .get_or_insert_with(|| Box::pin(sleep_until(deadline)));
+        if progress || !self.armed {
+            timer.as_mut().reset(deadline);
+            self.armed = true;
+        }
+        timer.as_mut().poll(cx)
+    }
+}
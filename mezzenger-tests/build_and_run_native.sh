@@ -0,0 +1,18 @@
+#!/bin/sh
+set -e
+
+echo "Building server..."
+cd server
+cargo build
+cd ..
+
+echo "\nBuilding native client..."
+cd client-native
+cargo build
+cd ..
+
+echo "\nRunning native client..."
+./server/target/debug/server &
+SERVER=$!
+trap "kill $SERVER 2>/dev/null || true" EXIT
+sleep 1 && ./client-native/target/debug/client-native
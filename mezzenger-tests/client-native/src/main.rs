@@ -5,7 +5,9 @@ use clap::Parser;
 use futures::{stream, SinkExt, StreamExt};
 use kodec::binary::Codec;
 use mezzenger::{Messages, Receive};
-use tokio::time::sleep;
+use mezzenger_udp::broadcast;
+use mezzenger_utils::{numbered::Wrapper, Numbered};
+use tokio::time::{sleep, timeout};
 use tokio_tungstenite::connect_async;
 use url::Url;
 
@@ -114,7 +116,87 @@ async fn main() -> Result<()> {
     drop(receiver);
     println!("Transport dropped.");
 
+    println!("Testing numbered transport...");
+    test_numbered(&args.url).await?;
+
+    println!("Testing latest-only transport over lossy UDP...");
+    test_latest(&args.url).await?;
+
     println!("Tests passed.");
 
     Ok(())
 }
+
+/// Exchange numbered messages with the server, checking their numbers.
+async fn test_numbered(url: &str) -> Result<()> {
+    let url = Url::parse(&format!("{url}/numbered"))?;
+    let (web_socket, _) = connect_async(&url).await?;
+    let transport = mezzenger_websocket::Transport::<
+        _,
+        Codec,
+        Wrapper<u32, common::Message1>,
+        Wrapper<u32, common::Message2>,
+    >::new(web_socket, Codec::default());
+    let mut transport = Numbered::new(transport);
+
+    for (number, message) in common::numbered_sequence() {
+        let received = transport.receive().await.unwrap();
+        assert_eq!((received.number, received.wrapped), (number, message));
+    }
+
+    transport
+        .send_all(&mut stream::iter(
+            common::messages2_all().into_iter().map(Ok),
+        ))
+        .await
+        .unwrap();
+    transport.close().await.unwrap();
+    println!("Numbered messages exchanged.");
+
+    Ok(())
+}
+
+/// Receive states broadcast by the server through its lossy relay, checking that
+/// only increasingly fresh states are delivered.
+async fn test_latest(url: &str) -> Result<()> {
+    let url = Url::parse(&format!("{url}/latest"))?;
+    let (web_socket, _) = connect_async(&url).await?;
+    let mut control =
+        mezzenger_websocket::Transport::<_, Codec, common::UdpControl, common::UdpControl>::new(
+            web_socket,
+            Codec::default(),
+        );
+
+    let mut receiver: broadcast::Receiver<Codec, common::State> =
+        broadcast::Receiver::bind(0, Codec::default()).await?;
+    control
+        .send(common::UdpControl::Listening {
+            port: receiver.local_addr().port(),
+        })
+        .await
+        .unwrap();
+
+    let mut last_tick = None;
+    let mut received = 0;
+    loop {
+        let state = timeout(Duration::from_secs(5), receiver.receive())
+            .await?
+            .unwrap();
+        assert!(last_tick < Some(state.tick));
+        last_tick = Some(state.tick);
+        received += 1;
+        if state == common::final_state() {
+            break;
+        }
+    }
+    println!(
+        "Received {received}/{} states (stale, lost and duplicated ones skipped).",
+        common::STATE_COUNT
+    );
+    assert!(received < common::STATE_COUNT);
+
+    control.send(common::UdpControl::Received).await.unwrap();
+    control.close().await.unwrap();
+
+    Ok(())
+}
@@ -22,7 +22,7 @@ pub async fn test_webworker() {
     }
 
     for message in common::messages2_part2().into_iter() {
-        transport.send(message).await.unwrap();
+        transport.send_transfer(message).unwrap();
     }
     console_log!("Messages sent.");
 
@@ -91,6 +91,113 @@ pub async fn test_websocket() {
     console_log!("Tests passed.");
 }
 
+fn url(path: &str) -> String {
+    let host = window()
+        .location()
+        .host()
+        .expect("couldn't extract host from location");
+    format!("ws://{host}/{path}")
+}
+
+async fn connect(
+    path: &str,
+) -> mezzenger_websocket::Transport<Codec, common::Message1, common::Message2> {
+    let web_socket = Rc::new(WebSocket::new(&url(path)).unwrap());
+    mezzenger_websocket::Transport::new(&web_socket, Codec::default())
+        .await
+        .unwrap()
+}
+
+pub async fn test_websocket_close_code() {
+    console_log!("Connecting...");
+    let mut transport = connect("ws/close").await;
+
+    assert!(transport.next().await.is_none());
+    assert_eq!(transport.close_code(), Some(common::CUSTOM_CLOSE_CODE));
+    assert_eq!(
+        transport.close_reason().as_deref(),
+        Some(common::CUSTOM_CLOSE_REASON)
+    );
+    assert!(transport.send(common::Message2::Float(1.0)).await.is_err());
+    console_log!("Tests passed.");
+}
+
+pub async fn test_websocket_reconnect() {
+    for attempt in 1..=common::RECONNECT_ATTEMPTS {
+        console_log!("Connecting (attempt {attempt})...");
+        let mut transport = connect("ws/drop").await;
+
+        assert_eq!(
+            transport.receive().await.unwrap(),
+            common::reconnect_message()
+        );
+        assert!(transport.next().await.is_none());
+        assert_ne!(transport.close_code(), Some(1000));
+        console_log!("Connection dropped by the server.");
+    }
+    console_log!("Tests passed.");
+}
+
+pub async fn test_websocket_oversized_message() {
+    console_log!("Connecting...");
+    let mut transport = connect("ws/limited").await;
+
+    transport.send(common::Message2::Float(1.0)).await.unwrap();
+    transport.send(common::oversized_message()).await.unwrap();
+    assert!(transport.next().await.is_none());
+    assert!(transport.close_code().is_some());
+    assert!(transport.send(common::Message2::Float(1.0)).await.is_err());
+    console_log!("Tests passed.");
+}
+
+pub async fn test_websocket_queue_while_connecting() {
+    use mezzenger_websocket::Transport;
+
+    console_log!("Connecting...");
+    let web_socket = Rc::new(WebSocket::new(&url("ws/queued")).unwrap());
+    let mut transport: Transport<Codec, common::Message1, common::Message2> =
+        Transport::new_assuming_open(&web_socket, Codec::default()).unwrap();
+
+    for message in common::messages2_all() {
+        transport.feed(message).await.unwrap();
+    }
+    assert_eq!(web_socket.ready_state(), WebSocket::CONNECTING);
+    transport.flush().await.unwrap();
+    assert_eq!(web_socket.ready_state(), WebSocket::OPEN);
+    assert_eq!(transport.receive().await.unwrap(), common::queued_ack());
+    transport.close().await.unwrap();
+
+    console_log!("Connecting to missing route...");
+    let web_socket = Rc::new(WebSocket::new(&url("ws/missing")).unwrap());
+    let mut transport: Transport<Codec, common::Message1, common::Message2> =
+        Transport::new_assuming_open(&web_socket, Codec::default()).unwrap();
+
+    transport.feed(common::Message2::Float(1.0)).await.unwrap();
+    assert!(matches!(
+        transport.flush().await,
+        Err(mezzenger::Error::Closed)
+    ));
+    console_log!("Tests passed.");
+}
+
+pub async fn test_websocket_deserialization_error() {
+    use mezzenger_websocket::Error;
+
+    console_log!("Connecting...");
+    let mut transport = connect("ws/malformed").await;
+
+    assert!(matches!(
+        transport.next().await,
+        Some(Err(Error::DeserializationError(_)))
+    ));
+    assert_eq!(
+        transport.receive().await.unwrap(),
+        common::recovery_message()
+    );
+    assert!(transport.next().await.is_none());
+    console_log!("Tests passed.");
+}
+
 #[wasm_bindgen(start)]
 pub async fn main() -> Result<(), JsValue> {
     set_panic_hook();
@@ -104,6 +211,32 @@ pub async fn main() -> Result<(), JsValue> {
     console_log!("Web Worker transport test passed!");
     console_log!("\n");
 
+    console_log!("Testing Web Socket close code...");
+    test_websocket_close_code().await;
+    console_log!("Web Socket close code test passed!");
+    console_log!("\n");
+
+    console_log!("Testing Web Socket reconnection...");
+    test_websocket_reconnect().await;
+    console_log!("Web Socket reconnection test passed!");
+    console_log!("\n");
+
+    console_log!("Testing Web Socket oversized message rejection...");
+    test_websocket_oversized_message().await;
+    console_log!("Web Socket oversized message rejection test passed!");
+    console_log!("\n");
+
+    console_log!("Testing Web Socket deserialization error recovery...");
+    test_websocket_deserialization_error().await;
+    console_log!("Web Socket deserialization error recovery test passed!");
+    console_log!("\n");
+
+    console_log!("Testing Web Socket queueing while connecting...");
+    test_websocket_queue_while_connecting().await;
+    console_log!("Web Socket queueing while connecting test passed!");
+    console_log!("\n");
+
+    // Runs last - server shuts down once it passes.
     console_log!("Testing Web Socket transport...");
     test_websocket().await;
     console_log!("Web Socket transport test passed!");
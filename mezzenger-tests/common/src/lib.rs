@@ -64,3 +64,109 @@ pub fn messages2_all() -> Vec<Message2> {
         Message2::String("Hello again".to_string()),
     ]
 }
+
+/// Close code used by the server when closing the connection on purpose.
+pub const CUSTOM_CLOSE_CODE: u16 = 4001;
+
+/// Close reason used by the server when closing the connection on purpose.
+pub const CUSTOM_CLOSE_REASON: &str = "scripted close";
+
+/// Maximum message size accepted by the size-limited server route.
+pub const MAX_MESSAGE_SIZE: usize = 1024;
+
+/// Message exceeding [MAX_MESSAGE_SIZE] when serialized.
+pub fn oversized_message() -> Message2 {
+    Message2::String("x".repeat(4 * MAX_MESSAGE_SIZE))
+}
+
+/// Bytes that can't be deserialized as [Message1].
+pub fn malformed_message() -> Vec<u8> {
+    vec![0xff, 0xff, 0xff, 0xff]
+}
+
+/// Message sent by the server after [malformed_message].
+pub fn recovery_message() -> Message1 {
+    Message1::String("Still alive".to_string())
+}
+
+/// Message sent by the server on every connection before dropping it.
+pub fn reconnect_message() -> Message1 {
+    Message1::Int(42)
+}
+
+/// Message sent by the server after receiving all messages queued by the client
+/// while connecting.
+pub fn queued_ack() -> Message1 {
+    Message1::String("All queued messages received".to_string())
+}
+
+/// Number of times the client connects to the dropping server route.
+pub const RECONNECT_ATTEMPTS: usize = 3;
+
+/// Number of the first message sent by the server over the numbered route.
+pub const NUMBERED_START: u32 = 100;
+
+/// Difference between numbers of consecutive messages sent by the server over the numbered route.
+pub const NUMBERED_STEP: u32 = 3;
+
+/// Messages sent by the server over the numbered route along with numbers
+/// they are expected to arrive with (in that order).
+pub fn numbered_sequence() -> Vec<(u32, Message1)> {
+    messages1_all()
+        .into_iter()
+        .enumerate()
+        .map(|(index, message)| (NUMBERED_START + NUMBERED_STEP * index as u32, message))
+        .collect()
+}
+
+/// Messages sent by the client over the numbered route along with numbers
+/// they are expected to arrive with (in that order).
+pub fn numbered_replies() -> Vec<(u32, Message2)> {
+    messages2_all()
+        .into_iter()
+        .enumerate()
+        .map(|(index, message)| (index as u32, message))
+        .collect()
+}
+
+/// Control messages of the latest-only route, exchanged over WebSocket
+/// while states are broadcast over UDP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum UdpControl {
+    /// Client listens for states on provided port.
+    Listening { port: u16 },
+    /// Client received the final state.
+    Received,
+}
+
+/// State broadcast by the server over the latest-only route.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub tick: u32,
+    pub position: (f32, f32),
+}
+
+/// State of provided tick.
+pub fn state(tick: u32) -> State {
+    State {
+        tick,
+        position: (tick as f32, -(tick as f32) / 2.0),
+    }
+}
+
+/// Number of states broadcast by the server over the latest-only route.
+pub const STATE_COUNT: u32 = 200;
+
+/// State the server keeps broadcasting until the client confirms receiving it.
+pub fn final_state() -> State {
+    state(STATE_COUNT - 1)
+}
+
+/// Every n-th datagram is dropped by the server's lossy relay.
+pub const RELAY_DROP_EVERY: usize = 3;
+
+/// Every n-th datagram is sent twice by the server's lossy relay.
+pub const RELAY_DUPLICATE_EVERY: usize = 5;
+
+/// Every n-th datagram is held back (and sent after the next one) by the server's lossy relay.
+pub const RELAY_REORDER_EVERY: usize = 7;
@@ -1,19 +1,40 @@
 use std::cell::RefCell;
 use std::env::current_dir;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::Result;
 use futures::{future::FutureExt, pin_mut, select, SinkExt, StreamExt};
-use kodec::binary::Codec;
+use kodec::{binary::Codec, Encode};
 use mezzenger::{Messages, Receive};
+use mezzenger_udp::broadcast;
+use mezzenger_utils::{numbered::Wrapper, Numbered};
 use tokio::{
+    net::UdpSocket,
     signal::ctrl_c,
     spawn,
     sync::oneshot::{self, Sender},
+    task::JoinHandle,
+    time::interval,
 };
 use tracing::{error, info, Level};
-use warp::{hyper::StatusCode, ws::WebSocket, Filter};
+use warp::{
+    hyper::StatusCode,
+    ws::{Message, WebSocket, Ws},
+    Filter,
+};
+
+/// Number of native client tests (connections to the `ws` route
+/// and to wrapper routes) that have to pass.
+const NATIVE_TESTS: usize = 4;
+
+/// Interval of broadcasting the final state over the latest-only route.
+const STATE_INTERVAL: Duration = Duration::from_millis(20);
+
+type Notifier = Arc<Mutex<Option<Sender<()>>>>;
+type Counter = Arc<Mutex<RefCell<usize>>>;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -39,8 +60,8 @@ async fn main() -> Result<()> {
     let websocket = warp::path("ws")
         .and(warp::ws())
         .and(browser_tests_notifier)
-        .and(native_tests_notifier)
-        .and(native_tests_counter)
+        .and(native_tests_notifier.clone())
+        .and(native_tests_counter.clone())
         .map(
             |ws: warp::ws::Ws,
              browser_tests_notifier,
@@ -56,7 +77,49 @@ async fn main() -> Result<()> {
                 })
             },
         );
-    let routes = websocket.or(static_files).recover(handle_rejection);
+    let scripted = warp::path("ws").and(
+        warp::path!("close")
+            .and(warp::ws())
+            .map(|ws: Ws| ws.on_upgrade(handle_close))
+            .or(warp::path!("drop")
+                .and(warp::ws())
+                .map(|ws: Ws| ws.on_upgrade(handle_drop)))
+            .or(warp::path!("limited").and(warp::ws()).map(|ws: Ws| {
+                ws.max_message_size(common::MAX_MESSAGE_SIZE)
+                    .on_upgrade(handle_limited)
+            }))
+            .or(warp::path!("malformed")
+                .and(warp::ws())
+                .map(|ws: Ws| ws.on_upgrade(handle_malformed)))
+            .or(warp::path!("queued")
+                .and(warp::ws())
+                .map(|ws: Ws| ws.on_upgrade(handle_queued))),
+    );
+    let wrappers = warp::path("ws").and(
+        warp::path!("numbered")
+            .and(warp::ws())
+            .and(native_tests_notifier.clone())
+            .and(native_tests_counter.clone())
+            .map(|ws: Ws, native_tests_notifier, native_tests_counter| {
+                ws.on_upgrade(move |socket| {
+                    handle_numbered(socket, native_tests_notifier, native_tests_counter)
+                })
+            })
+            .or(warp::path!("latest")
+                .and(warp::ws())
+                .and(native_tests_notifier.clone())
+                .and(native_tests_counter.clone())
+                .map(|ws: Ws, native_tests_notifier, native_tests_counter| {
+                    ws.on_upgrade(move |socket| {
+                        handle_latest(socket, native_tests_notifier, native_tests_counter)
+                    })
+                })),
+    );
+    let routes = scripted
+        .or(wrappers)
+        .or(websocket)
+        .or(static_files)
+        .recover(handle_rejection);
 
     let (address, server_future) =
         warp::serve(routes).bind_with_graceful_shutdown(([127, 0, 0, 1], 3030), async move {
@@ -85,9 +148,9 @@ async fn main() -> Result<()> {
 
 async fn handle_websocket(
     web_socket: WebSocket,
-    browser_tests_notifier: Arc<Mutex<Option<Sender<()>>>>,
-    native_tests_notifier: Arc<Mutex<Option<Sender<()>>>>,
-    native_tests_counter: Arc<Mutex<RefCell<usize>>>,
+    browser_tests_notifier: Notifier,
+    native_tests_notifier: Notifier,
+    native_tests_counter: Counter,
 ) {
     info!("Opening transport...");
     let codec = Codec::default();
@@ -128,17 +191,7 @@ async fn handle_websocket(
     );
 
     if native_client {
-        let mut native_tests_counter = native_tests_counter.lock().unwrap();
-        *native_tests_counter.get_mut() += 1;
-        info!(
-            "Native client test {}/2 passed.",
-            *native_tests_counter.get_mut()
-        );
-        if *native_tests_counter.get_mut() >= 2 {
-            if let Some(notifier) = native_tests_notifier.lock().unwrap().take() {
-                notifier.send(()).unwrap();
-            }
-        }
+        native_test_passed(&native_tests_notifier, &native_tests_counter);
     } else {
         info!("Browser client tests passed.");
         if let Some(notifier) = browser_tests_notifier.lock().unwrap().take() {
@@ -147,6 +200,223 @@ async fn handle_websocket(
     }
 }
 
+fn native_test_passed(native_tests_notifier: &Notifier, native_tests_counter: &Counter) {
+    let mut native_tests_counter = native_tests_counter.lock().unwrap();
+    *native_tests_counter.get_mut() += 1;
+    info!(
+        "Native client test {}/{NATIVE_TESTS} passed.",
+        *native_tests_counter.get_mut()
+    );
+    if *native_tests_counter.get_mut() >= NATIVE_TESTS {
+        if let Some(notifier) = native_tests_notifier.lock().unwrap().take() {
+            notifier.send(()).unwrap();
+        }
+    }
+}
+
+/// Exchange numbered messages, checking their numbers.
+async fn handle_numbered(
+    web_socket: WebSocket,
+    native_tests_notifier: Notifier,
+    native_tests_counter: Counter,
+) {
+    let transport = mezzenger_websocket::warp::Transport::<
+        _,
+        Codec,
+        Wrapper<u32, common::Message2>,
+        Wrapper<u32, common::Message1>,
+    >::new(web_socket, Codec::default());
+    let mut transport = Numbered::new_starting_at(transport, common::NUMBERED_START);
+    transport.set_step(common::NUMBERED_STEP);
+
+    info!("Sending numbered messages...");
+    for (_, message) in common::numbered_sequence() {
+        transport.send(message).await.unwrap();
+    }
+
+    let received: Vec<_> = transport
+        .messages()
+        .map(|message| (message.number, message.wrapped))
+        .collect()
+        .await;
+    assert_eq!(received, common::numbered_replies());
+    info!("Numbered messages received.");
+
+    native_test_passed(&native_tests_notifier, &native_tests_counter);
+}
+
+/// Broadcast states to the client through [LossyRelay] until it receives the final one.
+async fn handle_latest(
+    web_socket: WebSocket,
+    native_tests_notifier: Notifier,
+    native_tests_counter: Counter,
+) {
+    let mut control = mezzenger_websocket::warp::Transport::<
+        _,
+        Codec,
+        common::UdpControl,
+        common::UdpControl,
+    >::new(web_socket, Codec::default());
+    let port = match control.receive().await.unwrap() {
+        common::UdpControl::Listening { port } => port,
+        message => panic!("received unexpected message: {message:?}"),
+    };
+
+    let relay = LossyRelay::spawn(([127, 0, 0, 1], port).into())
+        .await
+        .unwrap();
+    let mut sender: broadcast::Sender<Codec, common::State> = broadcast::Sender::bind(
+        "127.0.0.1:0",
+        relay.address(),
+        STATE_INTERVAL,
+        Codec::default(),
+    )
+    .await
+    .unwrap();
+
+    info!("Broadcasting states...");
+    for tick in 0..common::STATE_COUNT {
+        sender.send(common::state(tick)).await.unwrap();
+    }
+    let mut ticker = interval(sender.interval_hint());
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => sender.send(common::final_state()).await.unwrap(),
+            message = control.receive() => {
+                assert_eq!(message.unwrap(), common::UdpControl::Received);
+                break;
+            }
+        }
+    }
+    info!("Final state received by the client.");
+
+    native_test_passed(&native_tests_notifier, &native_tests_counter);
+}
+
+/// Relay forwarding datagrams to target address with induced loss, duplication
+/// and reordering (see `common::RELAY_*` constants).
+struct LossyRelay {
+    address: SocketAddr,
+    handle: JoinHandle<()>,
+}
+
+impl LossyRelay {
+    async fn spawn(target: SocketAddr) -> Result<Self> {
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let address = socket.local_addr()?;
+        let handle = spawn(async move {
+            let mut buffer = vec![0; 65536];
+            let mut held = None;
+            for index in 1.. {
+                let Ok(size) = socket.recv(&mut buffer).await else {
+                    break;
+                };
+                let datagram = buffer[..size].to_vec();
+                if index % common::RELAY_DROP_EVERY == 0 {
+                    continue;
+                }
+                if index % common::RELAY_REORDER_EVERY == 0 {
+                    held = Some(datagram);
+                    continue;
+                }
+                let copies = if index % common::RELAY_DUPLICATE_EVERY == 0 {
+                    2
+                } else {
+                    1
+                };
+                for _ in 0..copies {
+                    let _ = socket.send_to(&datagram, target).await;
+                }
+                if let Some(held) = held.take() {
+                    let _ = socket.send_to(&held, target).await;
+                }
+            }
+        });
+        Ok(LossyRelay { address, handle })
+    }
+
+    fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for LossyRelay {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Close connection with custom close code.
+async fn handle_close(mut web_socket: WebSocket) {
+    info!("Closing connection with custom code...");
+    web_socket
+        .send(Message::close_with(
+            common::CUSTOM_CLOSE_CODE,
+            common::CUSTOM_CLOSE_REASON,
+        ))
+        .await
+        .unwrap();
+    while let Some(Ok(_)) = web_socket.next().await {}
+}
+
+/// Send single message, then drop connection without closing handshake.
+async fn handle_drop(web_socket: WebSocket) {
+    let mut transport =
+        mezzenger_websocket::warp::Transport::<_, Codec, common::Message2, common::Message1>::new(
+            web_socket,
+            Codec::default(),
+        );
+    transport.send(common::reconnect_message()).await.unwrap();
+    info!("Dropping connection...");
+}
+
+/// Receive messages until connection is terminated by too large message.
+async fn handle_limited(web_socket: WebSocket) {
+    let mut transport =
+        mezzenger_websocket::warp::Transport::<_, Codec, common::Message2, common::Message1>::new(
+            web_socket,
+            Codec::default(),
+        );
+    while let Some(result) = transport.next().await {
+        if let Err(error) = result {
+            info!("Oversized message rejected: {error}");
+            break;
+        }
+    }
+}
+
+/// Send message that can't be deserialized followed by a valid one.
+async fn handle_malformed(mut web_socket: WebSocket) {
+    info!("Sending malformed message...");
+    web_socket
+        .send(Message::binary(common::malformed_message()))
+        .await
+        .unwrap();
+    let mut buffer = vec![];
+    Codec::default()
+        .encode(&mut buffer, &common::recovery_message())
+        .unwrap();
+    web_socket.send(Message::binary(buffer)).await.unwrap();
+    web_socket.close().await.unwrap();
+}
+
+/// Receive messages the client queued while connecting, then acknowledge them.
+async fn handle_queued(web_socket: WebSocket) {
+    let mut transport =
+        mezzenger_websocket::warp::Transport::<_, Codec, common::Message2, common::Message1>::new(
+            web_socket,
+            Codec::default(),
+        );
+    let mut messages = vec![];
+    for _ in 0..common::messages2_all().len() {
+        messages.push(transport.receive().await.unwrap());
+    }
+    assert_eq!(messages, common::messages2_all());
+    info!("Queued messages received.");
+    transport.send(common::queued_ack()).await.unwrap();
+    while let Some(Ok(_)) = transport.next().await {}
+}
+
 async fn handle_rejection(
     err: warp::Rejection,
 ) -> Result<impl warp::Reply, std::convert::Infallible> {
@@ -1,5 +1,6 @@
 use std::{
     io::{stdout, Write},
+    net::SocketAddr,
     time::{Duration, Instant},
 };
 
@@ -7,25 +8,28 @@ use anyhow::Result;
 use futures::{pin_mut, FutureExt, StreamExt};
 use kodec::binary::Codec;
 use mezzenger::Messages;
-use mezzenger_udp::Transport;
-use mezzenger_utils::{numbered, LatestOnly};
-use tokio::{net::UdpSocket, select, signal::ctrl_c, time::interval};
+use mezzenger_udp::{broadcast::Receiver, multicast};
+use tokio::{select, signal::ctrl_c, time::interval};
 
 use crate::server;
 
 const SERVER_DOWN_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub async fn run() -> Result<()> {
-    let udp_socket = UdpSocket::bind("0.0.0.0:1234").await?;
-    let codec = Codec::default();
-    let transport =
-        Transport::<_, Codec, numbered::Wrapper<u64, server::Message>, ()>::new(udp_socket, codec);
-    let transport = LatestOnly::new(transport).into_unwrapping();
+    let receiver = Receiver::bind(server::PORT, Codec::default()).await?;
+    receive_positions(receiver).await
+}
+
+pub async fn run_multicast(group: SocketAddr) -> Result<()> {
+    let receiver = Receiver::new(multicast::bind(group)?, Codec::default())?;
+    receive_positions(receiver).await
+}
 
+async fn receive_positions(receiver: Receiver<Codec, server::Message>) -> Result<()> {
     print!("Connecting...");
     stdout().flush()?;
 
-    let mut messages = transport.messages_with_error_callback(|_| println!("Error!!!"));
+    let mut messages = receiver.messages_with_error_callback(|_| println!("Error!!!"));
 
     let mut server_down = false;
     let mut last_time_received = Instant::now();
@@ -1,4 +1,4 @@
-//! Broadcast (and receive) server's mouse position.
+//! Broadcast (or multicast) and receive server's mouse position.
 
 mod client;
 mod server;
@@ -12,19 +12,33 @@ struct Args {
     #[arg(short, long)]
     server: bool,
 
-    /// Broadcast address.
-    #[arg(short, long, default_value = "192.168.0.255:1234")]
-    url: String,
+    /// Use multicast (as opposed to broadcast).
+    #[arg(short, long)]
+    multicast: bool,
+
+    /// Broadcast address (multicast group address if `--multicast` is set).
+    #[arg(short, long)]
+    url: Option<String>,
 }
 
+const BROADCAST_ADDRESS: &str = "192.168.0.255:1234";
+const MULTICAST_GROUP: &str = "239.255.12.34:1234";
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    if args.server {
-        server::run(&args.url).await?;
+    let url = args.url.as_deref().unwrap_or(if args.multicast {
+        MULTICAST_GROUP
     } else {
-        client::run().await?;
+        BROADCAST_ADDRESS
+    });
+
+    match (args.server, args.multicast) {
+        (true, false) => server::run(url).await?,
+        (true, true) => server::run_multicast(url.parse()?).await?,
+        (false, false) => client::run().await?,
+        (false, true) => client::run_multicast(url.parse()?).await?,
     }
 
     Ok(())
@@ -1,15 +1,19 @@
-use std::time::Duration;
+use std::{net::SocketAddr, time::Duration};
 
 use anyhow::Result;
 use device_query::{DeviceQuery, DeviceState};
-use futures::{pin_mut, FutureExt};
+use futures::{pin_mut, sink, FutureExt, Sink, SinkExt};
 use kodec::binary::Codec;
-use mezzenger_udp::Transport;
-use mezzenger_utils::numbered;
+use mezzenger_udp::{broadcast::Sender, Transport};
+use mezzenger_utils::numbered::Wrapper;
 use serde::{Deserialize, Serialize};
 use tokio::{net::UdpSocket, select, signal::ctrl_c, time::interval};
 use tracing::{info, Level};
 
+pub const PORT: u16 = 1234;
+
+const INTERVAL: Duration = Duration::from_millis(50);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub mouse_x: i32,
@@ -21,27 +25,59 @@ pub async fn run(address: &str) -> Result<()> {
 
     info!("Server running!");
 
+    let sender: Sender<Codec, Message> =
+        Sender::bind("0.0.0.0:4321", address, INTERVAL, Codec::default()).await?;
+
+    send_positions(sender).await
+}
+
+pub async fn run_multicast(group: SocketAddr) -> Result<()> {
+    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+
+    info!("Server running (multicast)!");
+
     let udp_socket = UdpSocket::bind("0.0.0.0:4321").await?;
-    udp_socket.set_broadcast(true)?;
-    let codec = Codec::default();
-    let mut transport =
-        Transport::<_, Codec, (), numbered::Wrapper<u64, Message>>::new(udp_socket, codec);
+    let mut transport: Transport<_, Codec, Wrapper<u64, ()>, Wrapper<u64, Message>> =
+        Transport::new(udp_socket, Codec::default());
+    transport.set_multicast_ttl(1)?;
+    transport.set_multicast_group(Some(group));
+
+    // number states the same way broadcast sender does, so clients can discard stale ones
+    let sender = sink::unfold(
+        (transport, 0),
+        |(mut transport, number), message| async move {
+            transport
+                .send_to_group(Wrapper {
+                    number,
+                    wrapped: message,
+                })
+                .await?;
+            Ok::<_, anyhow::Error>((transport, number + 1))
+        },
+    );
+
+    send_positions(sender).await
+}
+
+async fn send_positions<S>(sender: S) -> Result<()>
+where
+    S: Sink<Message>,
+    S::Error: Into<anyhow::Error>,
+{
+    pin_mut!(sender);
 
     let device_state = DeviceState::new();
 
-    let mut interval = interval(Duration::from_millis(50));
+    let mut interval = interval(INTERVAL);
     let break_signal = ctrl_c().fuse();
     pin_mut!(break_signal);
-    let mut message_number = 0;
     loop {
         select! {
             _tick = interval.tick() => {
                 let mouse_state = device_state.get_mouse();
                 let (x, y) = mouse_state.coords;
                 let message = Message { mouse_x: x, mouse_y: y };
-                let message = numbered::Wrapper { number: message_number, wrapped: message };
-                transport.send_to(message, address).await?;
-                message_number = message_number.wrapping_add(1);
+                sender.send(message).await.map_err(Into::into)?;
             },
             break_result = &mut break_signal => {
                 break_result.expect("failed to listen for event");
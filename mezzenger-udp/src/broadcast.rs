@@ -0,0 +1,280 @@
+//! Broadcasting the latest state over UDP.
+//!
+//! [Sender] periodically broadcasts a state (numbered, so receivers can discard stale
+//! and duplicated datagrams), [Receiver] yields only states newer than the previously
+//! yielded one.
+//!
+//! **NOTE**: Receivers ignore states numbered lower than the last received one - if the
+//! sender restarts (and starts numbering from zero again), receivers have to be recreated.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! let mut sender: Sender<Codec, Position> = Sender::bind(
+//!     "0.0.0.0:0",
+//!     "192.168.0.255:1234",
+//!     Duration::from_millis(50),
+//!     Codec::default(),
+//! )
+//! .await?;
+//! sender.send(position).await?;
+//!
+//! // on other host
+//! let mut receiver: Receiver<Codec, Position> = Receiver::bind(1234, Codec::default()).await?;
+//! let position = receiver.receive().await?;
+//! ```
+
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{stream::FusedStream, Sink, Stream};
+use kodec::{Decode, Encode};
+use mezzenger_utils::{
+    latest_only::LatestOnlyUnwrapping,
+    numbered::{Numbered, Wrapper},
+    LatestOnly,
+};
+use pin_project::pin_project;
+use serde::Serialize;
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+use crate::{Error, Transport};
+
+type TransportError<Codec> = Error<<Codec as Encode>::Error, <Codec as Decode>::Error>;
+
+type ReceiverTransport<Codec, Message> =
+    Transport<UdpSocket, Codec, Wrapper<u64, Message>, Wrapper<u64, ()>>;
+
+type SenderTransport<Codec, Message> =
+    Transport<UdpSocket, Codec, Wrapper<u64, ()>, Wrapper<u64, Message>>;
+
+/// Receiver of states broadcast by [Sender].
+///
+/// Stream yields only states newer than the previously yielded one.
+#[pin_project]
+pub struct Receiver<Codec, Message>
+where
+    Codec: kodec::Codec,
+    for<'de> Message: serde::de::Deserialize<'de>,
+{
+    #[pin]
+    #[allow(clippy::type_complexity)]
+    inner: LatestOnlyUnwrapping<
+        ReceiverTransport<Codec, Message>,
+        TransportError<Codec>,
+        u64,
+        Wrapper<u64, Message>,
+        Message,
+        Wrapper<u64, ()>,
+    >,
+    local_address: SocketAddr,
+}
+
+impl<Codec, Message> Receiver<Codec, Message>
+where
+    Codec: kodec::Codec,
+    for<'de> Message: serde::de::Deserialize<'de>,
+{
+    /// Create new receiver listening on provided port (on all interfaces).
+    pub async fn bind(port: u16, codec: Codec) -> io::Result<Self> {
+        let udp_socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).await?;
+        Receiver::new(udp_socket, codec)
+    }
+
+    /// Create new receiver listening on provided socket.
+    pub fn new(udp_socket: UdpSocket, codec: Codec) -> io::Result<Self> {
+        let local_address = udp_socket.local_addr()?;
+        let transport = Transport::new(udp_socket, codec);
+        Ok(Receiver {
+            inner: LatestOnly::new(transport).into_unwrapping(),
+            local_address,
+        })
+    }
+
+    /// Address the receiver is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_address
+    }
+}
+
+impl<Codec, Message> Stream for Receiver<Codec, Message>
+where
+    Codec: kodec::Codec,
+    for<'de> Message: serde::de::Deserialize<'de>,
+{
+    type Item = Result<Message, TransportError<Codec>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().inner.poll_next(cx)
+    }
+}
+
+impl<Codec, Message> FusedStream for Receiver<Codec, Message>
+where
+    Codec: kodec::Codec,
+    for<'de> Message: serde::de::Deserialize<'de>,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+impl<Codec, Message> mezzenger::Order for Receiver<Codec, Message>
+where
+    Codec: kodec::Codec,
+    for<'de> Message: serde::de::Deserialize<'de>,
+{
+}
+
+/// Sender broadcasting states to [Receiver]s.
+///
+/// Sink numbers every sent state and sends it to the target (broadcast) address.
+#[pin_project]
+pub struct Sender<Codec, Message>
+where
+    Codec: kodec::Codec,
+    Message: Serialize,
+{
+    #[pin]
+    inner: Numbered<u64, SenderTransport<Codec, Message>, TransportError<Codec>, (), Message>,
+    interval_hint: Duration,
+}
+
+impl<Codec, Message> Sender<Codec, Message>
+where
+    Codec: kodec::Codec,
+    Message: Serialize,
+{
+    /// Create new sender bound to provided address, sending to `target` address.
+    ///
+    /// `interval_hint` is the interval states are going to be sent with
+    /// (see [Sender::interval_hint]).
+    pub async fn bind<A, T>(
+        address: A,
+        target: T,
+        interval_hint: Duration,
+        codec: Codec,
+    ) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+        T: ToSocketAddrs,
+    {
+        let udp_socket = UdpSocket::bind(address).await?;
+        Sender::new(udp_socket, target, interval_hint, codec).await
+    }
+
+    /// Create new sender using provided socket, sending to `target` address.
+    ///
+    /// Enables broadcasting on the socket (`SO_BROADCAST`) and connects it
+    /// to the target address.
+    pub async fn new<T>(
+        udp_socket: UdpSocket,
+        target: T,
+        interval_hint: Duration,
+        codec: Codec,
+    ) -> io::Result<Self>
+    where
+        T: ToSocketAddrs,
+    {
+        udp_socket.set_broadcast(true)?;
+        udp_socket.connect(target).await?;
+        let transport = Transport::new(udp_socket, codec);
+        Ok(Sender {
+            inner: Numbered::new(transport),
+            interval_hint,
+        })
+    }
+
+    /// Interval states are going to be sent with.
+    ///
+    /// Senders should send state at least that often (even if it doesn't change),
+    /// receivers may consider the sender down after a few intervals without a state.
+    pub fn interval_hint(&self) -> Duration {
+        self.interval_hint
+    }
+}
+
+impl<Codec, Message> Sink<Message> for Sender<Codec, Message>
+where
+    Codec: kodec::Codec,
+    Message: Serialize,
+{
+    type Error = mezzenger::Error<TransportError<Codec>>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        self.project().inner.start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::SinkExt;
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::time::timeout;
+
+    use super::{Receiver, Sender};
+
+    async fn bind_sender(target: (&str, u16)) -> Sender<Codec, u32> {
+        Sender::bind(
+            "127.0.0.1:0",
+            target,
+            Duration::from_millis(10),
+            Codec::default(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_broadcast() {
+        let mut receiver: Receiver<Codec, u32> = Receiver::bind(0, Codec::default()).await.unwrap();
+        let port = receiver.local_addr().port();
+
+        // broadcast on loopback isn't available everywhere (for example in CI containers),
+        // fall back to unicast
+        let mut sender = bind_sender(("127.255.255.255", port)).await;
+        let delivered = sender.send(0).await.is_ok()
+            && timeout(Duration::from_secs(1), receiver.receive())
+                .await
+                .is_ok();
+        let start = if delivered {
+            1
+        } else {
+            sender = bind_sender(("127.0.0.1", port)).await;
+            0
+        };
+        assert_eq!(sender.interval_hint(), Duration::from_millis(10));
+
+        let mut last = None;
+        for state in start..100 {
+            sender.send(state).await.unwrap();
+            let received = timeout(Duration::from_secs(1), receiver.receive())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(last < Some(received));
+            last = Some(received);
+        }
+        assert_eq!(last, Some(99));
+    }
+}
@@ -0,0 +1,97 @@
+//! Buffers datagrams are received into.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Default size of receive buffer - the largest possible datagram fits in it.
+pub const DEFAULT_RECEIVE_BUFFER_SIZE: usize = 65536;
+
+/// Receive buffer shared by many transports (for example thousands of lightweight
+/// per-peer transports) instead of every transport allocating its own.
+///
+/// Transports use the buffer only while decoding a received datagram - sharing it
+/// costs a short lock on every receipt.
+#[derive(Debug, Clone)]
+pub struct SharedReceiveBuffer {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SharedReceiveBuffer {
+    /// Allocate new buffer fitting datagrams up to `size` bytes.
+    pub fn new(size: usize) -> Self {
+        SharedReceiveBuffer {
+            buffer: Arc::new(Mutex::new(allocate(size))),
+        }
+    }
+
+    /// Size of the largest datagram fitting in the buffer.
+    pub fn size(&self) -> usize {
+        self.buffer.lock().unwrap().len() - 1
+    }
+}
+
+impl Default for SharedReceiveBuffer {
+    fn default() -> Self {
+        SharedReceiveBuffer::new(DEFAULT_RECEIVE_BUFFER_SIZE)
+    }
+}
+
+/// Receive buffer of a transport.
+///
+/// Allocated one byte larger than requested so truncated datagrams can be detected.
+pub(crate) enum ReceiveBuffer {
+    Owned(Vec<u8>),
+    Shared(SharedReceiveBuffer),
+}
+
+impl ReceiveBuffer {
+    pub(crate) fn new(size: usize) -> Self {
+        ReceiveBuffer::Owned(allocate(size))
+    }
+
+    /// Size of the largest datagram fitting in the buffer.
+    pub(crate) fn size(&self) -> usize {
+        match self {
+            ReceiveBuffer::Owned(buffer) => buffer.len() - 1,
+            ReceiveBuffer::Shared(buffer) => buffer.size(),
+        }
+    }
+
+    pub(crate) fn lock(&mut self) -> Guard<'_> {
+        match self {
+            ReceiveBuffer::Owned(buffer) => Guard::Owned(buffer),
+            ReceiveBuffer::Shared(buffer) => Guard::Shared(buffer.buffer.lock().unwrap()),
+        }
+    }
+}
+
+pub(crate) enum Guard<'a> {
+    Owned(&'a mut Vec<u8>),
+    Shared(MutexGuard<'a, Vec<u8>>),
+}
+
+impl Deref for Guard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Guard::Owned(buffer) => buffer,
+            Guard::Shared(buffer) => buffer,
+        }
+    }
+}
+
+impl DerefMut for Guard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Guard::Owned(buffer) => buffer,
+            Guard::Shared(buffer) => buffer,
+        }
+    }
+}
+
+fn allocate(size: usize) -> Vec<u8> {
+    vec![0; size + 1]
+}
@@ -0,0 +1,376 @@
+//! Packing many small messages into a single datagram.
+//!
+//! Enabled with [Builder::coalescing](crate::Builder::coalescing) - both peers
+//! have to enable it, as every message within a datagram is prefixed with its length:
+//!
+//! | bytes          | content                                  |
+//! |----------------|------------------------------------------|
+//! | 0..2           | length of first message `n` (big endian `u16`) |
+//! | 2..2+n         | first message                            |
+//! | 2+n..4+n       | length of second message (big endian `u16`) |
+//! | ...            | ...                                      |
+//!
+//! Messages queued between flushes are packed into datagrams up to
+//! [max datagram size](Coalescing::max_datagram_size) (for example messages sent with
+//! [feed](futures::SinkExt::feed) and flushed once).<br>
+//! With non-zero [max delay](Coalescing::max_delay) the last, partially filled datagram
+//! is held back on flush until the delay passes since its first message was sent - so bursts
+//! of messages sent one by one share datagrams too. It trades a bit of latency
+//! (waiting to fill a datagram) for fewer packets.
+//!
+//! **NOTE**: Transport has no background task - held datagram is sent by the first
+//! flush after the delay passed, by [force_flush](crate::Transport::force_flush)
+//! or when transport is closed. Force flush at the end of a burst.
+
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use crate::MAX_DATAGRAM_SIZE;
+
+/// Size of length prefix of every message within a datagram.
+pub const LENGTH_PREFIX_SIZE: usize = 2;
+
+/// Default maximum size of datagrams - fits in a datagram on most paths.
+pub const DEFAULT_MAX_DATAGRAM_SIZE: usize = 1200;
+
+/// Coalescing options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coalescing {
+    /// Maximum size of sent datagrams (including [length prefixes](LENGTH_PREFIX_SIZE)).
+    ///
+    /// Messages that don't fit in a datagram alone can't be sent.
+    pub max_datagram_size: usize,
+    /// Time partially filled datagram is held back waiting for more messages.
+    ///
+    /// With zero delay (the default) only messages queued between flushes share datagrams.
+    pub max_delay: Duration,
+}
+
+impl Default for Coalescing {
+    fn default() -> Self {
+        Coalescing {
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+            max_delay: Duration::ZERO,
+        }
+    }
+}
+
+/// Datagram ready to be sent along with its target.
+pub(crate) type Packed = (Vec<u8>, Option<SocketAddr>);
+
+/// Coalescing state of a transport.
+pub(crate) struct Coalescer {
+    options: Coalescing,
+    /// Datagram being filled.
+    datagram: Vec<u8>,
+    target: Option<SocketAddr>,
+    started: Instant,
+}
+
+impl Coalescer {
+    pub(crate) fn new(options: Coalescing) -> Self {
+        assert!(
+            options.max_datagram_size > LENGTH_PREFIX_SIZE
+                && options.max_datagram_size <= MAX_DATAGRAM_SIZE,
+            "max datagram size has to be larger than length prefix and fit in UDP datagram"
+        );
+        Coalescer {
+            options,
+            datagram: vec![],
+            target: None,
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) fn options(&self) -> Coalescing {
+        self.options
+    }
+
+    /// Size of the largest message fitting in a datagram.
+    pub(crate) fn max_message_size(&self) -> usize {
+        self.options.max_datagram_size - LENGTH_PREFIX_SIZE
+    }
+
+    /// Whether partially filled datagram is held back.
+    pub(crate) fn is_holding(&self) -> bool {
+        !self.datagram.is_empty()
+    }
+
+    /// Pack single encoded message into a datagram.
+    ///
+    /// Returns `None` if message doesn't fit in a datagram.
+    pub(crate) fn single(&self, message: &[u8]) -> Option<Vec<u8>> {
+        self.fits(message).then(|| {
+            let mut datagram = Vec::with_capacity(LENGTH_PREFIX_SIZE + message.len());
+            append(&mut datagram, message);
+            datagram
+        })
+    }
+
+    /// Add encoded message to datagram being filled.
+    ///
+    /// Returns previous datagram if message didn't fit in it (or has different target)
+    /// or `Err(())` if message doesn't fit in a datagram at all.
+    pub(crate) fn push(
+        &mut self,
+        message: &[u8],
+        target: Option<SocketAddr>,
+    ) -> Result<Option<Packed>, ()> {
+        self.push_at(message, target, Instant::now())
+    }
+
+    fn push_at(
+        &mut self,
+        message: &[u8],
+        target: Option<SocketAddr>,
+        now: Instant,
+    ) -> Result<Option<Packed>, ()> {
+        if !self.fits(message) {
+            return Err(());
+        }
+        let full = self.datagram.len() + LENGTH_PREFIX_SIZE + message.len()
+            > self.options.max_datagram_size;
+        let previous = if self.is_holding() && (full || target != self.target) {
+            self.take_at(true, now)
+        } else {
+            None
+        };
+        if !self.is_holding() {
+            self.target = target;
+            self.started = now;
+        }
+        append(&mut self.datagram, message);
+        Ok(previous)
+    }
+
+    /// Take partially filled datagram if [max delay](Coalescing::max_delay)
+    /// passed (or `force` is set).
+    pub(crate) fn take(&mut self, force: bool) -> Option<Packed> {
+        self.take_at(force, Instant::now())
+    }
+
+    fn take_at(&mut self, force: bool, now: Instant) -> Option<Packed> {
+        let due = force || now.saturating_duration_since(self.started) >= self.options.max_delay;
+        (self.is_holding() && due).then(|| (std::mem::take(&mut self.datagram), self.target))
+    }
+
+    fn fits(&self, message: &[u8]) -> bool {
+        message.len() <= self.max_message_size()
+    }
+}
+
+fn append(datagram: &mut Vec<u8>, message: &[u8]) {
+    datagram.extend_from_slice(&(message.len() as u16).to_be_bytes());
+    datagram.extend_from_slice(message);
+}
+
+/// Split received datagram into messages.
+///
+/// Returns `Err(())` if datagram isn't validly framed.
+pub(crate) fn unpack(mut datagram: &[u8]) -> Result<Vec<&[u8]>, ()> {
+    if datagram.is_empty() {
+        return Err(());
+    }
+    let mut messages = vec![];
+    while let [high, low, rest @ ..] = datagram {
+        let length = u16::from_be_bytes([*high, *low]) as usize;
+        if rest.len() < length {
+            return Err(());
+        }
+        let (message, rest) = rest.split_at(length);
+        messages.push(message);
+        datagram = rest;
+    }
+    if !datagram.is_empty() {
+        return Err(());
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        time::{Duration, Instant},
+    };
+
+    use futures::SinkExt;
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::{net::UdpSocket, time::timeout};
+
+    use super::{unpack, Coalescer, Coalescing};
+    use crate::{Builder, Error, Transport};
+
+    /// Size of type tag prepended to every message.
+    #[cfg(feature = "type-tag")]
+    const TAG_SIZE: usize = mezzenger::type_tag::TAG_SIZE;
+    #[cfg(not(feature = "type-tag"))]
+    const TAG_SIZE: usize = 0;
+
+    #[test]
+    fn test_pack() {
+        let other: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        let mut coalescer = Coalescer::new(Coalescing {
+            max_datagram_size: 10,
+            max_delay: Duration::from_secs(1),
+        });
+        let now = Instant::now();
+
+        assert_eq!(coalescer.push_at(&[1, 2], None, now), Ok(None));
+        assert_eq!(coalescer.push_at(&[], None, now), Ok(None));
+        assert_eq!(coalescer.push_at(&[3; 9], None, now), Err(()));
+        assert!(coalescer.take_at(false, now).is_none());
+
+        // message not fitting in the datagram starts new one
+        assert_eq!(
+            coalescer.push_at(&[3, 4, 5], None, now),
+            Ok(Some((vec![0, 2, 1, 2, 0, 0], None)))
+        );
+        // as does message with different target
+        assert_eq!(
+            coalescer.push_at(&[6], Some(other), now),
+            Ok(Some((vec![0, 3, 3, 4, 5], None)))
+        );
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            coalescer.take_at(false, later),
+            Some((vec![0, 1, 6], Some(other)))
+        );
+        assert!(!coalescer.is_holding());
+
+        assert_eq!(
+            coalescer.single(&[7; 8]),
+            Some(vec![0, 8, 7, 7, 7, 7, 7, 7, 7, 7])
+        );
+        assert_eq!(coalescer.single(&[7; 9]), None);
+    }
+
+    #[test]
+    fn test_unpack() {
+        assert_eq!(
+            unpack(&[0, 2, 1, 2, 0, 0, 0, 1, 3]),
+            Ok(vec![&[1, 2][..], &[], &[3]])
+        );
+        assert_eq!(unpack(&[]), Err(()));
+        assert_eq!(unpack(&[0]), Err(()));
+        assert_eq!(unpack(&[0, 3, 1, 2]), Err(()));
+    }
+
+    #[tokio::test]
+    async fn test_coalescing() {
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        left.connect(right.local_addr().unwrap()).await.unwrap();
+        right.connect(left.local_addr().unwrap()).await.unwrap();
+
+        let coalescing = Coalescing {
+            max_datagram_size: 64,
+            max_delay: Duration::ZERO,
+        };
+        let mut left: Transport<UdpSocket, Codec, (), u32> = Builder::new(left, Codec::default())
+            .coalescing(coalescing)
+            .build();
+        let mut right: Transport<UdpSocket, Codec, u32, ()> = Builder::new(right, Codec::default())
+            .coalescing(coalescing)
+            .build();
+        assert_eq!(left.coalescing(), Some(coalescing));
+
+        // 6 bytes per message (+ type tag) - 10 messages per datagram (6 with type tag)
+        let message_size = 6 + TAG_SIZE;
+        for i in 0..25 {
+            left.feed(i).await.unwrap();
+        }
+        left.flush().await.unwrap();
+        let per_datagram = 64 / message_size;
+        assert_eq!(
+            left.stats().datagrams_sent,
+            25_usize.div_ceil(per_datagram) as u64
+        );
+        for i in 0..25 {
+            assert_eq!(right.receive().await.unwrap(), i);
+        }
+        let stats = right.stats();
+        assert_eq!(stats.messages_received, 25);
+        assert_eq!(stats.bytes_received, 25 * message_size as u64);
+
+        // message too large to fit in a datagram
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut large: Transport<UdpSocket, Codec, (), String> =
+            Builder::new(socket, Codec::default())
+                .coalescing(coalescing)
+                .build();
+        assert!(matches!(
+            large.send("x".repeat(64)).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge {
+                size: _,
+                max: 62
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_max_delay() {
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        left.connect(right.local_addr().unwrap()).await.unwrap();
+        right.connect(left.local_addr().unwrap()).await.unwrap();
+
+        let coalescing = Coalescing {
+            max_datagram_size: 64,
+            max_delay: Duration::from_millis(100),
+        };
+        let mut left: Transport<UdpSocket, Codec, (), u32> = Builder::new(left, Codec::default())
+            .coalescing(coalescing)
+            .build();
+        let mut right: Transport<UdpSocket, Codec, u32, ()> = Builder::new(right, Codec::default())
+            .coalescing(coalescing)
+            .build();
+
+        // held back until the delay passes
+        left.send(1).await.unwrap();
+        left.send(2).await.unwrap();
+        assert_eq!(left.stats().datagrams_sent, 0);
+        assert!(timeout(Duration::from_millis(50), right.receive())
+            .await
+            .is_err());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        left.send(3).await.unwrap();
+        assert_eq!(left.stats().datagrams_sent, 1);
+        for i in 1..=3 {
+            assert_eq!(right.receive().await.unwrap(), i);
+        }
+
+        // sent right away when forced
+        left.send(4).await.unwrap();
+        assert_eq!(left.stats().datagrams_sent, 1);
+        left.force_flush().await.unwrap();
+        assert_eq!(left.stats().datagrams_sent, 2);
+        assert_eq!(right.receive().await.unwrap(), 4);
+
+        // and on close
+        left.send(5).await.unwrap();
+        left.close().await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_datagram() {
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        left.connect(right.local_addr().unwrap()).await.unwrap();
+
+        let mut right: Transport<UdpSocket, Codec, u32, ()> = Builder::new(right, Codec::default())
+            .coalescing(Coalescing::default())
+            .build();
+
+        left.send(&[0, 4, 1]).await.unwrap();
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::InvalidCoalescedDatagram))
+        ));
+        assert_eq!(right.stats().decode_errors, 1);
+    }
+}
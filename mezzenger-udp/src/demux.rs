@@ -0,0 +1,495 @@
+//! Per-peer transports demultiplexed from a single unconnected socket.
+//!
+//! [`PeerDemux`] wraps [`Unconnected`] transport and announces a [`PeerTransport`]
+//! the first time a datagram arrives from a new address. Peer transport receives
+//! only messages of its peer and sends messages only to it (through the shared socket).
+//!
+//! Peer is evicted once no datagram arrived from it for the idle timeout - its stream
+//! terminates and sending fails with [mezzenger::Error::Closed].<br>
+//! Next datagram from its address announces it again (as a new peer transport).
+//!
+//! **NOTE**: Socket is read by whichever transport (demultiplexer or peer) is polled -
+//! incoming datagrams are dispatched only while at least one of them is polled.
+
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, VecDeque},
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures::{stream::FusedStream, task::waker, Sink, SinkExt, Stream, StreamExt};
+use kodec::{Decode, Encode};
+use mezzenger::WakerSet;
+use serde::Serialize;
+use tokio::{
+    net::UdpSocket,
+    time::{sleep_until, Instant, Sleep},
+};
+
+use crate::{Error, Unconnected};
+
+/// Maximum number of received messages queued for a single peer -
+/// further messages from it are discarded until its transport receives queued ones.
+pub const PEER_BUFFER: usize = 64;
+
+/// Waker set key of the demultiplexer itself (peers are numbered starting with 1).
+const DEMUX: u64 = 0;
+
+type ReceiveError<Codec> = Error<<Codec as Encode>::Error, <Codec as Decode>::Error>;
+
+struct Peer<Incoming> {
+    id: u64,
+    incoming: VecDeque<Incoming>,
+    last_seen: Instant,
+}
+
+struct Shared<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    transport: Unconnected<U, Codec, Incoming, Outgoing>,
+    idle_timeout: Duration,
+    peers: HashMap<SocketAddr, Peer<Incoming>>,
+    /// Peers not announced by the demultiplexer yet.
+    new_peers: VecDeque<(SocketAddr, u64)>,
+    errors: VecDeque<ReceiveError<Codec>>,
+    next_id: u64,
+    receivers: Arc<WakerSet<u64>>,
+    receive_waker: Waker,
+    senders: Arc<WakerSet<u64>>,
+    send_waker: Waker,
+    terminated: bool,
+}
+
+impl<U, Codec, Incoming, Outgoing> Shared<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    fn peer(&self, address: &SocketAddr, id: u64) -> Option<&Peer<Incoming>> {
+        self.peers.get(address).filter(|peer| peer.id == id)
+    }
+
+    fn is_live(&self, address: &SocketAddr, id: u64) -> bool {
+        !self.terminated && self.peer(address, id).is_some()
+    }
+
+    fn forget(&mut self, address: &SocketAddr, id: u64) {
+        if self.peer(address, id).is_some() {
+            self.peers.remove(address);
+        }
+        self.receivers.remove(&id);
+        self.senders.remove(&id);
+    }
+
+    /// Read datagrams until socket has no more, dispatching them to peers.
+    fn poll_receive(&mut self) {
+        let receive_waker = self.receive_waker.clone();
+        let mut cx = Context::from_waker(&receive_waker);
+        while !self.terminated {
+            match self.transport.poll_next_unpin(&mut cx) {
+                Poll::Ready(Some(Ok((message, address)))) => self.dispatch(message, address),
+                Poll::Ready(Some(Err(error))) => {
+                    self.errors.push_back(error);
+                    self.receivers.wake_one(&DEMUX);
+                }
+                Poll::Ready(None) => {
+                    self.terminated = true;
+                    self.receivers.wake_all();
+                    self.senders.wake_all();
+                }
+                Poll::Pending => break,
+            }
+        }
+    }
+
+    fn dispatch(&mut self, message: Incoming, address: SocketAddr) {
+        let now = Instant::now();
+        match self.peers.get_mut(&address) {
+            Some(peer) if now < peer.last_seen + self.idle_timeout => {
+                peer.last_seen = now;
+                if peer.incoming.len() < PEER_BUFFER {
+                    peer.incoming.push_back(message);
+                }
+                let id = peer.id;
+                self.receivers.wake_one(&id);
+            }
+            _ => {
+                if let Some(evicted) = self.peers.remove(&address) {
+                    self.receivers.wake_one(&evicted.id);
+                    self.senders.wake_one(&evicted.id);
+                }
+                self.next_id += 1;
+                let id = self.next_id;
+                self.peers.insert(
+                    address,
+                    Peer {
+                        id,
+                        incoming: VecDeque::from([message]),
+                        last_seen: now,
+                    },
+                );
+                self.new_peers.push_back((address, id));
+                self.receivers.wake_one(&DEMUX);
+            }
+        }
+    }
+}
+
+type SharedRef<U, Codec, Incoming, Outgoing> = Arc<Mutex<Shared<U, Codec, Incoming, Outgoing>>>;
+
+/// Demultiplexer of peers sending datagrams to a single unconnected socket.
+///
+/// Stream of [`PeerTransport`]s of newly seen peers (and errors of received datagrams
+/// that couldn't be attributed to any peer, for example undecodable ones).
+///
+/// See [module documentation](self) for details.
+pub struct PeerDemux<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    shared: SharedRef<U, Codec, Incoming, Outgoing>,
+}
+
+impl<U, Codec, Incoming, Outgoing> PeerDemux<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Create new demultiplexer over provided transport.
+    ///
+    /// Peers are evicted once no datagram arrived from them for `idle_timeout`.
+    pub fn new(
+        transport: Unconnected<U, Codec, Incoming, Outgoing>,
+        idle_timeout: Duration,
+    ) -> Self {
+        let receivers = Arc::new(WakerSet::default());
+        let senders = Arc::new(WakerSet::default());
+        let shared = Shared {
+            transport,
+            idle_timeout,
+            peers: HashMap::new(),
+            new_peers: VecDeque::new(),
+            errors: VecDeque::new(),
+            next_id: DEMUX,
+            receive_waker: waker(receivers.clone()),
+            receivers,
+            send_waker: waker(senders.clone()),
+            senders,
+            terminated: false,
+        };
+        PeerDemux {
+            shared: Arc::new(Mutex::new(shared)),
+        }
+    }
+
+    /// Idle timeout after which peers are evicted.
+    pub fn idle_timeout(&self) -> Duration {
+        self.shared.lock().unwrap().idle_timeout
+    }
+
+    /// Number of peers that weren't evicted (nor closed) yet.
+    pub fn peer_count(&self) -> usize {
+        self.shared.lock().unwrap().peers.len()
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Stream for PeerDemux<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Item = Result<PeerTransport<U, Codec, Incoming, Outgoing>, ReceiveError<Codec>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap();
+        shared.poll_receive();
+        if let Some(error) = shared.errors.pop_front() {
+            return Poll::Ready(Some(Err(error)));
+        }
+        while let Some((address, id)) = shared.new_peers.pop_front() {
+            if shared.peer(&address, id).is_some() {
+                let deadline = Instant::now() + shared.idle_timeout;
+                return Poll::Ready(Some(Ok(PeerTransport {
+                    shared: self.shared.clone(),
+                    address,
+                    id,
+                    sleep: Box::pin(sleep_until(deadline)),
+                })));
+            }
+        }
+        if shared.terminated {
+            Poll::Ready(None)
+        } else {
+            shared.receivers.register(DEMUX, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> FusedStream for PeerDemux<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    fn is_terminated(&self) -> bool {
+        let shared = self.shared.lock().unwrap();
+        shared.terminated && shared.new_peers.is_empty() && shared.errors.is_empty()
+    }
+}
+
+/// Transport exchanging messages with a single peer of a [`PeerDemux`].
+///
+/// Closing (or dropping) it forgets the peer - next datagram from its address
+/// announces it again.<br>
+/// The shared socket stays open.
+///
+/// **NOTE**: Like [Transport](crate::Transport) it is **unreliable** and **unordered**.
+pub struct PeerTransport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    shared: SharedRef<U, Codec, Incoming, Outgoing>,
+    address: SocketAddr,
+    id: u64,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl<U, Codec, Incoming, Outgoing> PeerTransport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Address of the peer.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Was peer evicted (or closed).
+    pub fn is_evicted(&self) -> bool {
+        !self.shared.lock().unwrap().is_live(&self.address, self.id)
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Sink<Outgoing> for PeerTransport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Error = mezzenger::Error<ReceiveError<Codec>>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.is_live(&self.address, self.id) {
+            return Poll::Ready(Err(mezzenger::Error::Closed));
+        }
+        shared.senders.register(self.id, cx.waker());
+        let send_waker = shared.send_waker.clone();
+        let result = futures::ready!(Sink::<(Outgoing, SocketAddr)>::poll_ready(
+            Pin::new(&mut shared.transport),
+            &mut Context::from_waker(&send_waker)
+        ));
+        shared.senders.remove(&self.id);
+        Poll::Ready(result)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.is_live(&self.address, self.id) {
+            return Err(mezzenger::Error::Closed);
+        }
+        shared.transport.start_send_unpin((item, self.address))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.is_live(&self.address, self.id) {
+            return Poll::Ready(Err(mezzenger::Error::Closed));
+        }
+        shared.senders.register(self.id, cx.waker());
+        let send_waker = shared.send_waker.clone();
+        let result = futures::ready!(Sink::<(Outgoing, SocketAddr)>::poll_flush(
+            Pin::new(&mut shared.transport),
+            &mut Context::from_waker(&send_waker)
+        ));
+        shared.senders.remove(&self.id);
+        Poll::Ready(result)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.is_evicted() {
+            return Poll::Ready(Ok(()));
+        }
+        futures::ready!(self.as_mut().poll_flush(cx))?;
+        self.shared.lock().unwrap().forget(&self.address, self.id);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Stream for PeerTransport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Item = Result<Incoming, ReceiveError<Codec>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let me = &mut *self;
+        let mut shared = me.shared.lock().unwrap();
+        shared.poll_receive();
+        let idle_timeout = shared.idle_timeout;
+        let terminated = shared.terminated;
+        let peer = match shared.peers.get_mut(&me.address) {
+            Some(peer) if peer.id == me.id => peer,
+            _ => return Poll::Ready(None),
+        };
+        if let Some(message) = peer.incoming.pop_front() {
+            return Poll::Ready(Some(Ok(message)));
+        }
+        let deadline = peer.last_seen + idle_timeout;
+        if terminated || Instant::now() >= deadline {
+            shared.forget(&me.address, me.id);
+            return Poll::Ready(None);
+        }
+        shared.receivers.register(me.id, cx.waker());
+        me.sleep.as_mut().reset(deadline);
+        if me.sleep.as_mut().poll(cx).is_ready() {
+            shared.forget(&me.address, me.id);
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> FusedStream for PeerTransport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    fn is_terminated(&self) -> bool {
+        self.shared
+            .lock()
+            .unwrap()
+            .peer(&self.address, self.id)
+            .is_none()
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Drop for PeerTransport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.forget(&self.address, self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::{net::UdpSocket, time::timeout};
+
+    use crate::{PeerDemux, Transport};
+
+    #[tokio::test]
+    async fn test_peer_demux() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_address = server.local_addr().unwrap();
+        let mut demux: PeerDemux<UdpSocket, Codec, u32, String> = PeerDemux::new(
+            Transport::new(server, Codec::default()).into_unconnected(),
+            Duration::from_millis(500),
+        );
+
+        let mut clients = vec![];
+        let mut addresses = vec![];
+        for _ in 0..2 {
+            let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            client.connect(server_address).await.unwrap();
+            addresses.push(client.local_addr().unwrap());
+            let client: Transport<UdpSocket, Codec, String, u32> =
+                Transport::new(client, Codec::default());
+            clients.push(client);
+        }
+
+        clients[0].send(1).await.unwrap();
+        let mut first = demux.next().await.unwrap().unwrap();
+        assert_eq!(first.address(), addresses[0]);
+        clients[1].send(2).await.unwrap();
+        let mut second = demux.next().await.unwrap().unwrap();
+        assert_eq!(second.address(), addresses[1]);
+        assert_eq!(demux.peer_count(), 2);
+
+        // message streams are isolated
+        clients[0].send(11).await.unwrap();
+        clients[1].send(22).await.unwrap();
+        assert_eq!(second.receive().await.unwrap(), 2);
+        assert_eq!(second.receive().await.unwrap(), 22);
+        assert_eq!(first.receive().await.unwrap(), 1);
+        assert_eq!(first.receive().await.unwrap(), 11);
+
+        first.send("first".to_string()).await.unwrap();
+        second.send("second".to_string()).await.unwrap();
+        assert_eq!(clients[0].receive().await.unwrap(), "first");
+        assert_eq!(clients[1].receive().await.unwrap(), "second");
+
+        // idle peers are evicted
+        let evicted = timeout(Duration::from_secs(5), first.next()).await.unwrap();
+        assert!(evicted.is_none());
+        assert!(first.is_evicted());
+        assert!(futures::stream::FusedStream::is_terminated(&first));
+        assert!(matches!(
+            first.send("evicted".to_string()).await,
+            Err(mezzenger::Error::Closed)
+        ));
+        let evicted = timeout(Duration::from_secs(5), second.next())
+            .await
+            .unwrap();
+        assert!(evicted.is_none());
+        assert_eq!(demux.peer_count(), 0);
+
+        // evicted peer is announced again
+        clients[0].send(3).await.unwrap();
+        let mut first = demux.next().await.unwrap().unwrap();
+        assert_eq!(first.address(), addresses[0]);
+        assert_eq!(first.receive().await.unwrap(), 3);
+    }
+}
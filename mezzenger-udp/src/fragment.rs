@@ -0,0 +1,539 @@
+//! Splitting messages larger than a datagram into fragments and reassembling them.
+//!
+//! Enabled with [Builder::fragmentation](crate::Builder::fragmentation) - both peers
+//! have to enable it, as every datagram is prefixed with a fragment header:
+//!
+//! | bytes | content                           |
+//! |-------|-----------------------------------|
+//! | 0..4  | message id (big endian `u32`)     |
+//! | 4..6  | fragment index (big endian `u16`) |
+//! | 6..8  | fragment count (big endian `u16`) |
+//!
+//! Fragments of a message are reassembled in any order (duplicates are ignored).<br>
+//! Message is delivered once all its fragments arrived - messages with fragments
+//! missing for longer than [reassembly timeout](Fragmentation::reassembly_timeout)
+//! are discarded.<br>
+//! Memory held by partially received messages is bounded - see
+//! [max partial messages](Fragmentation::max_partial_messages),
+//! [per peer](Fragmentation::max_partial_messages_per_peer) and
+//! [max partial bytes](Fragmentation::max_partial_bytes).
+
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Size of header prefixed to every fragment.
+pub const FRAGMENT_HEADER_SIZE: usize = 8;
+
+/// Default size of fragments (including header) - fits in a datagram on most paths.
+pub const DEFAULT_FRAGMENT_SIZE: usize = 1200;
+
+/// Default time after which partially received messages are discarded.
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default maximum number of partially received messages (from all peers).
+pub const DEFAULT_MAX_PARTIAL_MESSAGES: usize = 256;
+
+/// Default maximum number of partially received messages from a single peer.
+pub const DEFAULT_MAX_PARTIAL_MESSAGES_PER_PEER: usize = 16;
+
+/// Default maximum number of bytes buffered in partially received messages.
+pub const DEFAULT_MAX_PARTIAL_BYTES: usize = 16 * 1024 * 1024;
+
+/// Fragmentation options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fragmentation {
+    /// Maximum size of sent datagrams (including [fragment header](FRAGMENT_HEADER_SIZE)).
+    ///
+    /// Messages that don't fit are split into many fragments.
+    pub fragment_size: usize,
+    /// Time after which partially received messages are discarded.
+    pub reassembly_timeout: Duration,
+    /// Maximum number of partially received messages (from all peers).
+    ///
+    /// The oldest partial message is discarded to make room for a new one.
+    pub max_partial_messages: usize,
+    /// Maximum number of partially received messages from a single peer.
+    ///
+    /// Fragments starting new messages from a peer over the limit are dropped.
+    pub max_partial_messages_per_peer: usize,
+    /// Maximum number of bytes buffered in partially received messages (from all peers).
+    ///
+    /// The oldest partial messages are discarded to make room for new fragments.
+    pub max_partial_bytes: usize,
+}
+
+impl Default for Fragmentation {
+    fn default() -> Self {
+        Fragmentation {
+            fragment_size: DEFAULT_FRAGMENT_SIZE,
+            reassembly_timeout: DEFAULT_REASSEMBLY_TIMEOUT,
+            max_partial_messages: DEFAULT_MAX_PARTIAL_MESSAGES,
+            max_partial_messages_per_peer: DEFAULT_MAX_PARTIAL_MESSAGES_PER_PEER,
+            max_partial_bytes: DEFAULT_MAX_PARTIAL_BYTES,
+        }
+    }
+}
+
+type MessageKey = (SocketAddr, u32);
+
+struct Partial {
+    /// Received fragments by index - allocated as they arrive.
+    fragments: HashMap<u16, Vec<u8>>,
+    count: u16,
+    bytes: usize,
+    /// Distinguishes partial message from earlier ones with the same key.
+    generation: u64,
+}
+
+/// Fragmentation state of a transport.
+pub(crate) struct Fragmenter {
+    options: Fragmentation,
+    next_message_id: u32,
+    partial: HashMap<MessageKey, Partial>,
+    /// Partial messages in order they were started (entries of already removed ones
+    /// are skipped once they reach the front).
+    started: VecDeque<(Instant, MessageKey, u64)>,
+    partial_per_peer: HashMap<SocketAddr, usize>,
+    partial_bytes: usize,
+    next_generation: u64,
+}
+
+impl Fragmenter {
+    pub(crate) fn new(options: Fragmentation) -> Self {
+        assert!(
+            options.fragment_size > FRAGMENT_HEADER_SIZE,
+            "fragment size has to be larger than fragment header"
+        );
+        assert!(
+            options.max_partial_messages > 0 && options.max_partial_messages_per_peer > 0,
+            "partial message limits have to be non-zero"
+        );
+        Fragmenter {
+            options,
+            next_message_id: 0,
+            partial: HashMap::new(),
+            started: VecDeque::new(),
+            partial_per_peer: HashMap::new(),
+            partial_bytes: 0,
+            next_generation: 0,
+        }
+    }
+
+    pub(crate) fn options(&self) -> Fragmentation {
+        self.options
+    }
+
+    /// Size of the largest message that can be split into fragments.
+    pub(crate) fn max_message_size(&self) -> usize {
+        u16::MAX as usize * (self.options.fragment_size - FRAGMENT_HEADER_SIZE)
+    }
+
+    /// Number of partially received messages.
+    pub(crate) fn partial_messages(&self) -> usize {
+        self.partial.len()
+    }
+
+    /// Split encoded message into datagrams.
+    ///
+    /// Returns `None` if message requires more than [u16::MAX] fragments.
+    pub(crate) fn split(&mut self, message: &[u8]) -> Option<VecDeque<Vec<u8>>> {
+        let payload_size = self.options.fragment_size - FRAGMENT_HEADER_SIZE;
+        let count = message.len().div_ceil(payload_size).max(1);
+        let count = u16::try_from(count).ok()?;
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+
+        let mut chunks = message.chunks(payload_size);
+        Some(
+            (0..count)
+                .map(|index| {
+                    let chunk = chunks.next().unwrap_or_default();
+                    let mut fragment = Vec::with_capacity(FRAGMENT_HEADER_SIZE + chunk.len());
+                    fragment.extend_from_slice(&message_id.to_be_bytes());
+                    fragment.extend_from_slice(&index.to_be_bytes());
+                    fragment.extend_from_slice(&count.to_be_bytes());
+                    fragment.extend_from_slice(chunk);
+                    fragment
+                })
+                .collect(),
+        )
+    }
+
+    /// Add received datagram.
+    ///
+    /// Returns reassembled message once all its fragments arrived
+    /// or `Err(())` if datagram isn't a valid fragment.
+    pub(crate) fn reassemble(
+        &mut self,
+        address: SocketAddr,
+        datagram: &[u8],
+    ) -> Result<Option<Vec<u8>>, ()> {
+        self.reassemble_at(address, datagram, Instant::now())
+    }
+
+    fn reassemble_at(
+        &mut self,
+        address: SocketAddr,
+        datagram: &[u8],
+        now: Instant,
+    ) -> Result<Option<Vec<u8>>, ()> {
+        self.evict_expired(now);
+
+        if datagram.len() < FRAGMENT_HEADER_SIZE {
+            return Err(());
+        }
+        let (header, payload) = datagram.split_at(FRAGMENT_HEADER_SIZE);
+        let message_id = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let index = u16::from_be_bytes([header[4], header[5]]);
+        let count = u16::from_be_bytes([header[6], header[7]]);
+        if index >= count {
+            return Err(());
+        }
+        if count == 1 {
+            return Ok(Some(payload.to_vec()));
+        }
+
+        let key = (address, message_id);
+        match self.partial.get(&key) {
+            Some(partial) if partial.count != count => return Err(()),
+            Some(partial) if partial.fragments.contains_key(&index) => return Ok(None),
+            Some(_) => {}
+            None => {
+                let peer_partial = self.partial_per_peer.get(&address).copied();
+                if peer_partial.unwrap_or_default() >= self.options.max_partial_messages_per_peer {
+                    return Ok(None);
+                }
+                while self.partial.len() >= self.options.max_partial_messages {
+                    self.evict_oldest();
+                }
+            }
+        }
+        while self.partial_bytes + payload.len() > self.options.max_partial_bytes {
+            if !self.evict_oldest() {
+                // fragment doesn't fit even with nothing else buffered
+                self.remove(&key);
+                return Ok(None);
+            }
+        }
+
+        let partial = match self.partial.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let generation = self.next_generation;
+                self.next_generation += 1;
+                self.started.push_back((now, key, generation));
+                *self.partial_per_peer.entry(address).or_default() += 1;
+                entry.insert(Partial {
+                    fragments: HashMap::new(),
+                    count,
+                    bytes: 0,
+                    generation,
+                })
+            }
+        };
+        partial.fragments.insert(index, payload.to_vec());
+        partial.bytes += payload.len();
+        self.partial_bytes += payload.len();
+        if partial.fragments.len() < count as usize {
+            return Ok(None);
+        }
+
+        let mut partial = self.remove(&key).unwrap();
+        let message = (0..count)
+            .flat_map(|index| partial.fragments.remove(&index).unwrap())
+            .collect();
+        Ok(Some(message))
+    }
+
+    /// Discard partial messages started longer than reassembly timeout ago.
+    fn evict_expired(&mut self, now: Instant) {
+        let timeout = self.options.reassembly_timeout;
+        while let Some(&(started, key, generation)) = self.started.front() {
+            if now.saturating_duration_since(started) < timeout {
+                break;
+            }
+            self.started.pop_front();
+            self.remove_generation(&key, generation);
+        }
+    }
+
+    /// Discard the oldest partial message.
+    ///
+    /// Returns `false` if there were no partial messages.
+    fn evict_oldest(&mut self) -> bool {
+        while let Some((_, key, generation)) = self.started.pop_front() {
+            if self.remove_generation(&key, generation).is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn remove_generation(&mut self, key: &MessageKey, generation: u64) -> Option<Partial> {
+        match self.partial.get(key) {
+            Some(partial) if partial.generation == generation => self.remove(key),
+            _ => None,
+        }
+    }
+
+    fn remove(&mut self, key: &MessageKey) -> Option<Partial> {
+        let partial = self.partial.remove(key)?;
+        self.partial_bytes -= partial.bytes;
+        if let Entry::Occupied(mut entry) = self.partial_per_peer.entry(key.0) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+        Some(partial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        time::{Duration, Instant},
+    };
+
+    use futures::SinkExt;
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use socket2::SockRef;
+    use tokio::{net::UdpSocket, time::timeout};
+
+    use super::{Fragmentation, Fragmenter, FRAGMENT_HEADER_SIZE};
+    use crate::{Builder, Transport};
+
+    fn fragmenter(fragment_size: usize) -> Fragmenter {
+        Fragmenter::new(Fragmentation {
+            fragment_size,
+            reassembly_timeout: Duration::from_secs(1),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let address: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        let other: SocketAddr = "127.0.0.1:4321".parse().unwrap();
+        let mut sender = fragmenter(FRAGMENT_HEADER_SIZE + 4);
+        let mut receiver = fragmenter(FRAGMENT_HEADER_SIZE + 4);
+
+        let message: Vec<u8> = (0..10).collect();
+        let fragments = sender.split(&message).unwrap();
+        assert_eq!(fragments.len(), 3);
+        assert_eq!(&fragments[2][..], &[0, 0, 0, 0, 0, 2, 0, 3, 8, 9]);
+
+        // out of order, duplicated, interleaved with other peer
+        let now = Instant::now();
+        for (peer, index) in [(address, 2), (other, 0), (address, 0), (address, 2)] {
+            assert_eq!(
+                receiver.reassemble_at(peer, &fragments[index], now),
+                Ok(None)
+            );
+        }
+        assert_eq!(receiver.partial_messages(), 2);
+        assert_eq!(
+            receiver.reassemble_at(address, &fragments[1], now),
+            Ok(Some(message))
+        );
+        assert_eq!(receiver.partial_messages(), 1);
+
+        // partial messages are evicted after timeout
+        let later = now + Duration::from_secs(2);
+        let single = sender.split(&[42]).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(
+            receiver.reassemble_at(other, &single[0], later),
+            Ok(Some(vec![42]))
+        );
+        assert_eq!(receiver.partial_messages(), 0);
+
+        assert_eq!(receiver.reassemble_at(address, &[0; 4], now), Err(()));
+        assert_eq!(
+            receiver.reassemble_at(address, &[0, 0, 0, 0, 0, 3, 0, 3], now),
+            Err(())
+        );
+        assert!(sender.split(&vec![0; 4 * u16::MAX as usize + 1]).is_none());
+    }
+
+    fn fragment(message_id: u32, index: u16, count: u16, payload: &[u8]) -> Vec<u8> {
+        let mut fragment = message_id.to_be_bytes().to_vec();
+        fragment.extend_from_slice(&index.to_be_bytes());
+        fragment.extend_from_slice(&count.to_be_bytes());
+        fragment.extend_from_slice(payload);
+        fragment
+    }
+
+    #[test]
+    fn test_reassembly_limits() {
+        let peers: Vec<SocketAddr> = (1..=3)
+            .map(|port| SocketAddr::from(([127, 0, 0, 1], port)))
+            .collect();
+        let mut receiver = Fragmenter::new(Fragmentation {
+            fragment_size: FRAGMENT_HEADER_SIZE + 4,
+            reassembly_timeout: Duration::from_secs(1),
+            max_partial_messages: 3,
+            max_partial_messages_per_peer: 2,
+            max_partial_bytes: 16,
+        });
+        let now = Instant::now();
+
+        // fragments are stored as they arrive - not preallocated for the whole message
+        for message_id in 0..2 {
+            let fragment = fragment(message_id, 0, u16::MAX, &[1; 4]);
+            assert_eq!(receiver.reassemble_at(peers[0], &fragment, now), Ok(None));
+        }
+        assert_eq!(receiver.partial_messages(), 2);
+        assert_eq!(receiver.partial_bytes, 8);
+
+        // over the per-peer limit
+        let dropped = fragment(2, 0, 2, &[2; 4]);
+        assert_eq!(receiver.reassemble_at(peers[0], &dropped, now), Ok(None));
+        assert_eq!(receiver.partial_messages(), 2);
+        assert_eq!(receiver.partial_bytes, 8);
+
+        // over the total limit - the oldest partial message is discarded
+        let later = now + Duration::from_millis(100);
+        let fragment_1 = fragment(0, 0, 3, &[3; 4]);
+        assert_eq!(
+            receiver.reassemble_at(peers[1], &fragment_1, later),
+            Ok(None)
+        );
+        let fragment_2 = fragment(0, 0, 2, &[4; 4]);
+        assert_eq!(
+            receiver.reassemble_at(peers[2], &fragment_2, later),
+            Ok(None)
+        );
+        assert_eq!(receiver.partial_messages(), 3);
+        assert!(!receiver.partial.contains_key(&(peers[0], 0)));
+        assert_eq!(receiver.partial_bytes, 12);
+
+        // over the bytes limit - the oldest partial messages are discarded
+        let fragment_3 = fragment(0, 1, 3, &[3; 8]);
+        assert_eq!(
+            receiver.reassemble_at(peers[1], &fragment_3, later),
+            Ok(None)
+        );
+        assert_eq!(receiver.partial_messages(), 2);
+        assert!(!receiver.partial.contains_key(&(peers[0], 1)));
+        assert_eq!(receiver.partial_bytes, 16);
+
+        // completing message makes room by discarding the oldest one too
+        let fragment_4 = fragment(0, 1, 2, &[5; 2]);
+        assert_eq!(
+            receiver.reassemble_at(peers[2], &fragment_4, later),
+            Ok(Some(vec![4, 4, 4, 4, 5, 5]))
+        );
+        assert_eq!(receiver.partial_messages(), 0);
+        assert_eq!(receiver.partial_bytes, 0);
+
+        // fragment that can't fit at all
+        assert_eq!(
+            receiver.reassemble_at(peers[1], &fragment_1, later),
+            Ok(None)
+        );
+        let oversized = fragment(1, 0, 2, &[6; 17]);
+        assert_eq!(
+            receiver.reassemble_at(peers[2], &oversized, later),
+            Ok(None)
+        );
+        assert_eq!(receiver.partial_messages(), 0);
+        assert_eq!(receiver.partial_bytes, 0);
+        assert!(receiver.partial_per_peer.is_empty());
+
+        // expired partial messages are discarded oldest first
+        assert_eq!(receiver.reassemble_at(peers[0], &dropped, now), Ok(None));
+        assert_eq!(
+            receiver.reassemble_at(peers[1], &fragment_1, later),
+            Ok(None)
+        );
+        let expired = now + Duration::from_secs(1);
+        assert_eq!(
+            receiver.reassemble_at(peers[2], &fragment_2, expired),
+            Ok(None)
+        );
+        assert_eq!(receiver.partial_messages(), 2);
+        assert!(!receiver.partial.contains_key(&(peers[0], 2)));
+        assert_eq!(receiver.partial_per_peer.get(&peers[0]), None);
+        assert_eq!(receiver.partial_bytes, 8);
+    }
+
+    /// Forward datagrams to `target`, each as many times as `copies` returns for its index.
+    async fn spawn_relay(target: SocketAddr, copies: fn(usize) -> usize) -> SocketAddr {
+        let relay = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let _ = SockRef::from(&relay).set_recv_buffer_size(4 * 1024 * 1024);
+        let address = relay.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 65536];
+            for index in 0.. {
+                let size = relay.recv(&mut buffer).await.unwrap();
+                for _ in 0..copies(index) {
+                    relay.send_to(&buffer[..size], target).await.unwrap();
+                }
+            }
+        });
+        address
+    }
+
+    async fn pair(
+        copies: fn(usize) -> usize,
+    ) -> (
+        Transport<UdpSocket, Codec, (), Vec<u8>>,
+        Transport<UdpSocket, Codec, Vec<u8>, ()>,
+    ) {
+        let options = Fragmentation {
+            fragment_size: 8 * 1024,
+            reassembly_timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let _ = SockRef::from(&right).set_recv_buffer_size(4 * 1024 * 1024);
+        let relay = spawn_relay(right.local_addr().unwrap(), copies).await;
+        left.connect(relay).await.unwrap();
+        (
+            Builder::new(left, Codec::default())
+                .fragmentation(options)
+                .build(),
+            Builder::new(right, Codec::default())
+                .fragmentation(options)
+                .build(),
+        )
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fragmentation() {
+        // every fragment duplicated
+        let (mut left, mut right) = pair(|_| 2).await;
+        let receiver = tokio::spawn(async move {
+            let message = right.receive().await.unwrap();
+            (right, message)
+        });
+        let message: Vec<u8> = (0..200 * 1024).map(|i| i as u8).collect();
+        left.send(message.clone()).await.unwrap();
+        let (right, received) = receiver.await.unwrap();
+        assert_eq!(received, message);
+        assert_eq!(right.stats().messages_received, 1);
+        assert_eq!(right.partial_messages(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lost_fragment() {
+        // third fragment of first message lost
+        let (mut left, mut right) = pair(|index| if index == 2 { 0 } else { 1 }).await;
+        left.send(vec![1; 200 * 1024]).await.unwrap();
+        assert!(timeout(Duration::from_millis(500), right.receive())
+            .await
+            .is_err());
+        assert_eq!(right.partial_messages(), 1);
+
+        left.send(vec![2; 16]).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), vec![2; 16]);
+        assert_eq!(right.partial_messages(), 0);
+    }
+}
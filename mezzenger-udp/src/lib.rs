@@ -4,9 +4,9 @@
 //! **NOTE**: This transport inherits UDP properties:
 //! - it is **unreliable** - messages are not guaranteed to reach destination,
 //! - it is **unordered** - messages may arrive at destination out of order, also they
-//! may be duplicated (the same message may arrive at destination twice or more times).
+//!   may be duplicated (the same message may arrive at destination twice or more times).
 //! - message size is limited to datagram size - sending may result in error if encoded
-//! message is too large.
+//!   message is too large.
 //!
 //! See [repository](https://github.com/zduny/mezzenger) for more info.
 //!
@@ -26,7 +26,7 @@
 //! transport.send("Hello World!".to_string()).await?;
 //! ```
 
-use futures::{future::poll_fn, stream::FusedStream, Sink, SinkExt, Stream};
+use futures::{future::poll_fn, stream::FusedStream, Sink, Stream};
 use kodec::{Decode, Encode};
 use pin_project::pin_project;
 use serde::Serialize;
@@ -38,19 +38,68 @@ use std::{
     marker::PhantomData,
     net::SocketAddr,
     pin::Pin,
-    task::{Context, Poll},
+    sync::Arc,
+    task::{ready, Context, Poll},
 };
 use tokio::{
     io::ReadBuf,
-    net::{ToSocketAddrs, UdpSocket},
+    net::{lookup_host, ToSocketAddrs, UdpSocket},
 };
 
+#[cfg(feature = "broadcast")]
+pub mod broadcast;
+mod buffer;
+use buffer::ReceiveBuffer;
+pub use buffer::{SharedReceiveBuffer, DEFAULT_RECEIVE_BUFFER_SIZE};
+pub mod coalesce;
+use coalesce::Coalescer;
+pub use coalesce::Coalescing;
+pub mod demux;
+pub use demux::{PeerDemux, PeerTransport};
+pub mod fragment;
+pub use fragment::Fragmentation;
+use fragment::Fragmenter;
+pub mod multicast;
+mod qos;
+pub use qos::{Capabilities, Ecn, Received, DSCP_EXPEDITED_FORWARDING};
+mod reuseport;
+pub use reuseport::{ReuseportError, ReuseportPool};
+mod stats;
+pub use stats::{Stats, TransportStats};
+mod unconnected;
+pub use unconnected::Unconnected;
+
+/// Largest possible size of UDP datagram payload (over IPv4) - default limit of sent datagrams
+/// (see [Builder::max_datagram_size]).
+pub const MAX_DATAGRAM_SIZE: usize = 65507;
+
 #[derive(Debug)]
 pub enum Error<SerializationError, DeserializationError> {
+    /// Datagram wasn't sent whole for other (unexpected) reason.
     SendingError,
     SerializationError(SerializationError),
     DeserializationError(DeserializationError),
+    #[cfg(feature = "type-tag")]
+    TypeMismatch {
+        expected: &'static str,
+        got: String,
+    },
     IoError(tokio::io::Error),
+    /// Datagram was too large to be sent whole (for example exceeding path MTU or
+    /// socket limits) - consider enabling [fragmentation](Builder::fragmentation).
+    DatagramTooLarge {
+        attempted: usize,
+        sent: usize,
+    },
+    MessageTooLarge {
+        size: usize,
+        max: usize,
+    },
+    InvalidFragment,
+    InvalidCoalescedDatagram,
+    DatagramTruncated {
+        buffer_size: usize,
+    },
 }
 
 impl<SerializationError, DeserializationError> Display
@@ -66,7 +115,27 @@ where
             Error::DeserializationError(error) => {
                 write!(f, "failed to deserialize message: {error}")
             }
+            #[cfg(feature = "type-tag")]
+            Error::TypeMismatch { expected, got } => {
+                write!(f, "message type mismatch (expected {expected}, got {got})")
+            }
             Error::IoError(error) => write!(f, "IO error occurred: {error}"),
+            Error::DatagramTooLarge { attempted, sent } => write!(
+                f,
+                "datagram was too large to be sent ({sent} of {attempted} bytes sent)"
+            ),
+            Error::MessageTooLarge { size, max } => write!(
+                f,
+                "message was too large to be sent ({size} bytes, at most {max} bytes allowed)"
+            ),
+            Error::InvalidFragment => write!(f, "received invalid fragment"),
+            Error::InvalidCoalescedDatagram => {
+                write!(f, "received invalid datagram of coalesced messages")
+            }
+            Error::DatagramTruncated { buffer_size } => write!(
+                f,
+                "received datagram didn't fit in receive buffer ({buffer_size} bytes)"
+            ),
         }
     }
 }
@@ -79,6 +148,18 @@ where
 {
 }
 
+#[cfg(feature = "type-tag")]
+impl<SerializationError, DeserializationError> From<mezzenger::type_tag::TypeMismatch>
+    for Error<SerializationError, DeserializationError>
+{
+    fn from(mismatch: mezzenger::type_tag::TypeMismatch) -> Self {
+        Error::TypeMismatch {
+            expected: mismatch.expected,
+            got: mismatch.got,
+        }
+    }
+}
+
 /// Transport over [tokio](https://tokio.rs/)'s UDP implementation.
 ///
 /// Wraps over [tokio::net::UdpSocket].
@@ -86,9 +167,18 @@ where
 /// **NOTE**: This transport inherits UDP properties:
 /// - it is **unreliable** - messages are NOT guaranteed to reach destination,
 /// - it is **unordered** - messages may arrive at destination out of order, also they
-/// may be duplicated (the same message may arrive at destination twice or more times).
+///   may be duplicated (the same message may arrive at destination twice or more times).
 /// - message size is limited to datagram size - sending may result in error if encoded
-/// message is too large.
+///   message is too large (unless [fragmentation](Builder::fragmentation) is enabled).
+///
+/// Many small messages can share a datagram if [coalescing](Builder::coalescing) is enabled.
+///
+/// Messages encoded into datagrams larger than [max datagram size](Builder::max_datagram_size)
+/// aren't sent - sending them results in [Error::MessageTooLarge] (transport keeps working,
+/// following messages are sent).
+///
+/// Datagrams larger than [receive buffer](Transport::receive_buffer_size) are not decoded -
+/// receiving them results in [Error::DatagramTruncated] (transport keeps working).
 #[pin_project]
 pub struct Transport<U, Codec, Incoming, Outgoing>
 where
@@ -99,10 +189,24 @@ where
 {
     udp_socket: Option<U>,
     codec: Codec,
-    send_queue: VecDeque<Outgoing>,
+    send_queue: VecDeque<(Outgoing, Option<SocketAddr>)>,
     send_buffer: Vec<u8>,
     message_pending: bool,
-    receive_buffer: Vec<u8>,
+    pending_target: Option<SocketAddr>,
+    fragmenter: Option<Fragmenter>,
+    /// Remaining fragments of message being sent.
+    fragments: VecDeque<Vec<u8>>,
+    coalescer: Option<Coalescer>,
+    max_datagram_size: usize,
+    multicast_group: Option<SocketAddr>,
+    /// Remaining messages of received datagram of coalesced messages.
+    #[allow(clippy::type_complexity)]
+    unpacked: VecDeque<
+        Result<Received<Incoming>, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>,
+    >,
+    receive_buffer: ReceiveBuffer,
+    capabilities: Capabilities,
+    stats: Arc<TransportStats>,
     _incoming: PhantomData<Incoming>,
 }
 
@@ -115,17 +219,108 @@ where
 {
     /// Create new transport wrapping a provided `[tokio::net::UdpSocket]`.
     pub fn new(udp_socket: U, codec: Codec) -> Self {
+        Transport::new_with_receive_buffer_size(udp_socket, codec, DEFAULT_RECEIVE_BUFFER_SIZE)
+    }
+
+    /// Create new transport wrapping a provided `[tokio::net::UdpSocket]`,
+    /// receiving datagrams up to `size` bytes.
+    ///
+    /// Receiving larger datagram results in [Error::DatagramTruncated].
+    pub fn new_with_receive_buffer_size(udp_socket: U, codec: Codec, size: usize) -> Self {
+        Transport::new_with_receive_buffer(udp_socket, codec, ReceiveBuffer::new(size))
+    }
+
+    /// Create new transport wrapping a provided `[tokio::net::UdpSocket]`,
+    /// receiving datagrams into a buffer shared with other transports.
+    pub fn new_with_shared_receive_buffer(
+        udp_socket: U,
+        codec: Codec,
+        receive_buffer: SharedReceiveBuffer,
+    ) -> Self {
+        Transport::new_with_receive_buffer(udp_socket, codec, ReceiveBuffer::Shared(receive_buffer))
+    }
+
+    fn new_with_receive_buffer(udp_socket: U, codec: Codec, receive_buffer: ReceiveBuffer) -> Self {
         Transport {
             udp_socket: Some(udp_socket),
             codec,
             send_queue: VecDeque::new(),
             send_buffer: vec![],
             message_pending: false,
-            receive_buffer: vec![0; 65536],
+            pending_target: None,
+            fragmenter: None,
+            fragments: VecDeque::new(),
+            coalescer: None,
+            max_datagram_size: MAX_DATAGRAM_SIZE,
+            multicast_group: None,
+            unpacked: VecDeque::new(),
+            receive_buffer,
+            capabilities: Capabilities::default(),
+            stats: Arc::new(TransportStats::default()),
             _incoming: PhantomData,
         }
     }
 
+    /// Socket options applied when the transport was created with [Builder].
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Fragmentation options if it was enabled with [Builder::fragmentation].
+    pub fn fragmentation(&self) -> Option<Fragmentation> {
+        self.fragmenter.as_ref().map(Fragmenter::options)
+    }
+
+    /// Coalescing options if it was enabled with [Builder::coalescing].
+    pub fn coalescing(&self) -> Option<Coalescing> {
+        self.coalescer.as_ref().map(Coalescer::options)
+    }
+
+    /// Size of the largest datagram transport sends.
+    pub fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+
+    /// Set size of the largest datagram transport sends (see [Builder::max_datagram_size]).
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+
+    /// Number of messages with fragments still missing.
+    pub fn partial_messages(&self) -> usize {
+        self.fragmenter
+            .as_ref()
+            .map_or(0, Fragmenter::partial_messages)
+    }
+
+    /// Size of the largest datagram that can be received.
+    pub fn receive_buffer_size(&self) -> usize {
+        self.receive_buffer.size()
+    }
+
+    /// Codec used to encode outgoing messages and decode incoming ones.
+    pub fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    /// Mutable reference to codec used to encode outgoing messages and decode incoming ones.
+    pub fn codec_mut(&mut self) -> &mut Codec {
+        &mut self.codec
+    }
+
+    /// Snapshot of transport statistics.
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// Handle to live transport statistics.
+    ///
+    /// Can be sent to other tasks (for example metrics collector)
+    /// while the transport is owned elsewhere.
+    pub fn stats_handle(&self) -> Arc<TransportStats> {
+        self.stats.clone()
+    }
+
     /// Send message to address.
     pub async fn send_to<A: ToSocketAddrs>(
         &mut self,
@@ -133,21 +328,77 @@ where
         target: A,
     ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
     {
-        self.flush().await?;
+        self.force_flush().await?;
+        let result = self.send_datagrams_to(message, target).await;
+        if let Err(mezzenger::Error::Other(error)) = &result {
+            self.stats.send_failed(error);
+        }
+        result
+    }
+
+    async fn send_datagrams_to<A: ToSocketAddrs>(
+        &mut self,
+        message: Outgoing,
+        target: A,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
         if let Some(udp_socket) = &self.udp_socket {
+            #[cfg(feature = "type-tag")]
+            self.send_buffer
+                .extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
             self.codec
                 .encode(&mut self.send_buffer, &message)
                 .map_err(
                     Error::<<Codec as Encode>::Error, <Codec as Decode>::Error>::SerializationError,
                 )
                 .map_err(mezzenger::Error::Other)?;
-            udp_socket
-                .borrow()
-                .send_to(&self.send_buffer, target)
+            let datagrams = match (&mut self.fragmenter, &self.coalescer) {
+                (Some(fragmenter), _) => {
+                    let datagrams = fragmenter.split(&self.send_buffer);
+                    let size = self.send_buffer.len();
+                    self.send_buffer.clear();
+                    datagrams.ok_or(mezzenger::Error::Other(Error::MessageTooLarge {
+                        size,
+                        max: fragmenter.max_message_size(),
+                    }))?
+                }
+                (None, Some(coalescer)) => {
+                    let datagram = coalescer.single(&self.send_buffer);
+                    let size = self.send_buffer.len();
+                    self.send_buffer.clear();
+                    VecDeque::from([datagram.ok_or(mezzenger::Error::Other(
+                        Error::MessageTooLarge {
+                            size,
+                            max: coalescer.max_message_size(),
+                        },
+                    ))?])
+                }
+                (None, None) => VecDeque::from([std::mem::take(&mut self.send_buffer)]),
+            };
+            if let Some(datagram) = datagrams
+                .iter()
+                .find(|datagram| datagram.len() > self.max_datagram_size)
+            {
+                return Err(mezzenger::Error::Other(Error::MessageTooLarge {
+                    size: datagram.len(),
+                    max: self.max_datagram_size,
+                }));
+            }
+            let targets: Vec<SocketAddr> = lookup_host(target)
                 .await
                 .map_err(Error::<<Codec as Encode>::Error, <Codec as Decode>::Error>::IoError)
-                .map_err(mezzenger::Error::Other)?;
-            self.send_buffer.clear();
+                .map_err(mezzenger::Error::Other)?
+                .collect();
+            for datagram in datagrams {
+                let sent = udp_socket
+                    .borrow()
+                    .send_to(&datagram, &targets[..])
+                    .await
+                    .map_err(|error| map_send_error(error, datagram.len()))
+                    .and_then(|sent| check_sent(datagram.len(), sent))
+                    .map_err(mezzenger::Error::Other)?;
+                self.stats.datagram_sent(sent);
+            }
             Ok(())
         } else {
             Err(mezzenger::Error::Closed)
@@ -175,6 +426,36 @@ where
         }
     }
 
+    /// Receive single message along with extra datagram information.
+    ///
+    /// ECN codepoint is reported only if [Capabilities::ecn_receive] is set.
+    pub async fn receive_extended(
+        &mut self,
+    ) -> Result<
+        Received<Incoming>,
+        mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>,
+    > {
+        if self.udp_socket.is_some() {
+            let result = poll_fn(|cx| self.poll_recv_extended(cx)).await;
+            if let Some(result) = result {
+                result.map_err(mezzenger::Error::Other)
+            } else {
+                Err(mezzenger::Error::Closed)
+            }
+        } else {
+            Err(mezzenger::Error::Closed)
+        }
+    }
+
+    /// Flush all queued messages, including partially filled datagram held back
+    /// by [coalescing](Builder::coalescing).
+    pub async fn force_flush(
+        &mut self,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        poll_fn(|cx| Pin::new(&mut *self).poll_flush_queue(cx, true)).await
+    }
+
     #[allow(clippy::type_complexity)]
     fn poll_recv_from(
         &mut self,
@@ -187,77 +468,183 @@ where
             >,
         >,
     > {
-        if let Some(udp_socket) = &self.udp_socket {
-            let mut buf = ReadBuf::new(&mut self.receive_buffer);
-            match udp_socket.borrow().poll_recv_from(cx, &mut buf) {
-                Poll::Ready(result) => match result {
-                    Ok(address) => {
-                        let result: Result<Incoming, _> = self.codec.decode(buf.filled());
-                        match result {
-                            Ok(message) => Poll::Ready(Some(Ok((message, address)))),
-                            Err(error) => {
-                                Poll::Ready(Some(Err(Error::DeserializationError(error))))
+        let result = ready!(self.poll_recv_extended(cx));
+        Poll::Ready(
+            result.map(|result| result.map(|received| (received.message, received.address))),
+        )
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll_recv_extended(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<
+        Option<
+            Result<Received<Incoming>, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>,
+        >,
+    > {
+        if let Some(result) = self.unpacked.pop_front() {
+            return Poll::Ready(Some(result));
+        }
+        while let Some(udp_socket) = &self.udp_socket {
+            let mut receive_buffer = self.receive_buffer.lock();
+            let result = ready!(poll_recv_datagram(
+                udp_socket.borrow(),
+                cx,
+                &mut receive_buffer,
+                self.capabilities.ecn_receive,
+            ));
+            return match result {
+                Ok((size, address, ecn)) => {
+                    // Buffer is one byte larger than its size - datagram filling it
+                    // was truncated.
+                    let buffer_size = receive_buffer.len() - 1;
+                    if size > buffer_size {
+                        self.stats.datagram_received(size, false);
+                        return Poll::Ready(Some(Err(Error::DatagramTruncated { buffer_size })));
+                    }
+                    let datagram = &receive_buffer[..size];
+                    if self.coalescer.is_some() {
+                        let Ok(messages) = coalesce::unpack(datagram) else {
+                            self.stats.datagram_received(size, false);
+                            return Poll::Ready(Some(Err(Error::InvalidCoalescedDatagram)));
+                        };
+                        self.unpacked = messages
+                            .into_iter()
+                            .map(|message| {
+                                decode::<_, _, Outgoing>(&self.codec, message).map(|message| {
+                                    Received {
+                                        message,
+                                        address,
+                                        ecn,
+                                    }
+                                })
+                            })
+                            .collect();
+                        let decoded = self.unpacked.iter().filter(|result| result.is_ok());
+                        self.stats
+                            .coalesced_received(size, decoded.count(), self.unpacked.len());
+                        return Poll::Ready(self.unpacked.pop_front());
+                    }
+                    let reassembled = match &mut self.fragmenter {
+                        Some(fragmenter) => match fragmenter.reassemble(address, datagram) {
+                            Ok(Some(message)) => Some(message),
+                            Ok(None) => {
+                                self.stats.fragment_received(size);
+                                continue;
                             }
-                        }
+                            Err(()) => {
+                                self.stats.datagram_received(size, false);
+                                return Poll::Ready(Some(Err(Error::InvalidFragment)));
+                            }
+                        },
+                        None => None,
+                    };
+                    let result = decode::<_, _, Outgoing>(
+                        &self.codec,
+                        reassembled.as_deref().unwrap_or(datagram),
+                    );
+                    self.stats.datagram_received(size, result.is_ok());
+                    match result {
+                        Ok(message) => Poll::Ready(Some(Ok(Received {
+                            message,
+                            address,
+                            ecn,
+                        }))),
+                        Err(error) => Poll::Ready(Some(Err(error))),
                     }
-                    Err(error) => match error.kind() {
-                        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => {
-                            self.udp_socket = None;
-                            Poll::Ready(None)
-                        }
-                        _ => Poll::Ready(Some(Err(Error::IoError(error)))),
-                    },
+                }
+                Err(error) => match error.kind() {
+                    ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => {
+                        self.udp_socket = None;
+                        Poll::Ready(None)
+                    }
+                    _ => Poll::Ready(Some(Err(Error::IoError(error)))),
                 },
-                Poll::Pending => Poll::Pending,
-            }
-        } else {
-            Poll::Ready(None)
+            };
         }
+        Poll::Ready(None)
     }
-}
 
-impl<U, Codec, Incoming, Outgoing> Sink<Outgoing> for Transport<U, Codec, Incoming, Outgoing>
-where
-    U: Borrow<UdpSocket>,
-    Codec: kodec::Codec,
-    for<'de> Incoming: serde::de::Deserialize<'de>,
-    Outgoing: Serialize,
-{
-    type Error = mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
-
-    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
-    }
-
-    fn start_send(mut self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
-        if self.udp_socket.is_some() {
-            self.send_queue.push_back(item);
-            Ok(())
-        } else {
-            Err(mezzenger::Error::Closed)
+    /// Send queued messages (to their target addresses if provided).
+    ///
+    /// Partially filled datagram of coalesced messages is sent only once its delay
+    /// passed, unless `force` is set.
+    #[allow(clippy::type_complexity)]
+    fn poll_flush_queue(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        force: bool,
+    ) -> Poll<Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>
+    {
+        let result = ready!(self.as_mut().poll_send_queue(cx, force));
+        if let Err(mezzenger::Error::Other(error)) = &result {
+            self.stats.send_failed(error);
         }
+        Poll::Ready(result)
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    #[allow(clippy::type_complexity)]
+    fn poll_send_queue(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        force: bool,
+    ) -> Poll<Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>
+    {
         let me = self.project();
-        if me.send_queue.is_empty() && !*me.message_pending {
+        let holding = me.coalescer.as_ref().is_some_and(Coalescer::is_holding);
+        if me.send_queue.is_empty() && !*me.message_pending && !holding {
             return Poll::Ready(Ok(()));
         }
         if let Some(udp_socket) = &me.udp_socket {
             loop {
                 if *me.message_pending {
                     let bytes_to_send = me.send_buffer.len();
-                    let result = udp_socket.borrow().poll_send(cx, me.send_buffer);
+                    if bytes_to_send > *me.max_datagram_size {
+                        // drop the message (with its remaining fragments),
+                        // following messages are still sent
+                        *me.message_pending = false;
+                        me.send_buffer.clear();
+                        me.fragments.clear();
+                        return Poll::Ready(Err(mezzenger::Error::Other(Error::MessageTooLarge {
+                            size: bytes_to_send,
+                            max: *me.max_datagram_size,
+                        })));
+                    }
+                    let udp_socket = udp_socket.borrow();
+                    let result = match me.pending_target {
+                        Some(target) => udp_socket.poll_send_to(cx, me.send_buffer, *target),
+                        None => udp_socket.poll_send(cx, me.send_buffer),
+                    };
                     match result {
+                        Poll::Ready(Err(error)) if error.kind() == ErrorKind::WouldBlock => {
+                            // Full socket send buffer is normally reported by tokio as `Pending`,
+                            // but `WouldBlock` can still leak through - on Windows (`WSAEWOULDBLOCK`
+                            // when the send buffer fills up between readiness checks), on BSDs and macOS
+                            // (`EAGAIN` when the interface queue is full while the socket stays writable).
+                            // Treat it as backpressure: keep the datagram pending and try again
+                            // once the socket becomes writable.
+                            if poll_send_ready_after_would_block(udp_socket, cx).is_pending() {
+                                return Poll::Pending;
+                            }
+                            continue;
+                        }
                         Poll::Ready(result) => {
                             *me.message_pending = false;
                             me.send_buffer.clear();
+                            if result.is_err() {
+                                me.fragments.clear();
+                            }
                             match result {
                                 Ok(bytes_written) => {
-                                    if bytes_written != bytes_to_send {
-                                        return Poll::Ready(Err(mezzenger::Error::Other(
-                                            Error::SendingError,
-                                        )));
+                                    if let Err(error) = check_sent(bytes_to_send, bytes_written) {
+                                        me.fragments.clear();
+                                        return Poll::Ready(Err(mezzenger::Error::Other(error)));
+                                    }
+                                    me.stats.datagram_sent(bytes_written);
+                                    if let Some(fragment) = me.fragments.pop_front() {
+                                        *me.send_buffer = fragment;
+                                        *me.message_pending = true;
                                     }
                                 }
                                 Err(error) => match error.kind() {
@@ -267,7 +654,7 @@ where
                                     }
                                     _ => {
                                         return Poll::Ready(Err(mezzenger::Error::Other(
-                                            Error::IoError(error),
+                                            map_send_error(error, bytes_to_send),
                                         )))
                                     }
                                 },
@@ -275,16 +662,66 @@ where
                         }
                         Poll::Pending => return Poll::Pending,
                     }
-                } else if let Some(message) = me.send_queue.pop_front() {
+                } else if let Some((message, target)) = me.send_queue.pop_front() {
+                    #[cfg(feature = "type-tag")]
+                    me.send_buffer
+                        .extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
                     let result = me.codec.encode(&mut *me.send_buffer, &message);
                     if let Err(error) = result {
                         me.send_buffer.clear();
                         return Poll::Ready(Err(mezzenger::Error::Other(
                             Error::SerializationError(error),
                         )));
-                    } else {
-                        *me.message_pending = true;
                     }
+                    if let Some(coalescer) = me.coalescer {
+                        match coalescer.push(me.send_buffer, target) {
+                            Ok(Some((datagram, target))) => {
+                                *me.send_buffer = datagram;
+                                *me.message_pending = true;
+                                *me.pending_target = target;
+                            }
+                            Ok(None) => me.send_buffer.clear(),
+                            Err(()) => {
+                                let size = me.send_buffer.len();
+                                me.send_buffer.clear();
+                                return Poll::Ready(Err(mezzenger::Error::Other(
+                                    Error::MessageTooLarge {
+                                        size,
+                                        max: coalescer.max_message_size(),
+                                    },
+                                )));
+                            }
+                        }
+                        continue;
+                    }
+                    if let Some(fragmenter) = me.fragmenter {
+                        match fragmenter.split(me.send_buffer) {
+                            Some(mut fragments) => {
+                                *me.send_buffer = fragments.pop_front().unwrap();
+                                *me.fragments = fragments;
+                            }
+                            None => {
+                                let size = me.send_buffer.len();
+                                me.send_buffer.clear();
+                                return Poll::Ready(Err(mezzenger::Error::Other(
+                                    Error::MessageTooLarge {
+                                        size,
+                                        max: fragmenter.max_message_size(),
+                                    },
+                                )));
+                            }
+                        }
+                    }
+                    *me.message_pending = true;
+                    *me.pending_target = target;
+                } else if let Some((datagram, target)) = me
+                    .coalescer
+                    .as_mut()
+                    .and_then(|coalescer| coalescer.take(force))
+                {
+                    *me.send_buffer = datagram;
+                    *me.message_pending = true;
+                    *me.pending_target = target;
                 } else {
                     return Poll::Ready(Ok(()));
                 }
@@ -293,9 +730,284 @@ where
             Poll::Ready(Err(mezzenger::Error::Closed))
         }
     }
+}
+
+/// Decode received datagram (checking its [type tag](mezzenger::type_tag)
+/// if `type-tag` feature is enabled).
+#[allow(clippy::type_complexity)]
+#[cfg_attr(not(feature = "type-tag"), allow(clippy::extra_unused_type_parameters))]
+fn decode<Codec, Incoming, Outgoing>(
+    codec: &Codec,
+    datagram: &[u8],
+) -> Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    #[cfg(feature = "type-tag")]
+    let datagram = mezzenger::type_tag::check::<Incoming, Outgoing>(datagram)?;
+    codec.decode(datagram).map_err(Error::DeserializationError)
+}
+
+/// Check whether the whole datagram was sent.
+///
+/// Datagram sent only partially was truncated - it was too large to be sent whole.
+fn check_sent<SerializationError, DeserializationError>(
+    attempted: usize,
+    sent: usize,
+) -> Result<usize, Error<SerializationError, DeserializationError>> {
+    if sent == attempted {
+        Ok(sent)
+    } else if 0 < sent && sent < attempted {
+        Err(Error::DatagramTooLarge { attempted, sent })
+    } else {
+        Err(Error::SendingError)
+    }
+}
+
+/// Map error of sending a datagram of `attempted` size - datagrams rejected for being
+/// too large (`EMSGSIZE`) result in [Error::DatagramTooLarge].
+fn map_send_error<SerializationError, DeserializationError>(
+    error: tokio::io::Error,
+    attempted: usize,
+) -> Error<SerializationError, DeserializationError> {
+    #[cfg(unix)]
+    const EMSGSIZE: i32 = libc::EMSGSIZE;
+    #[cfg(windows)]
+    const EMSGSIZE: i32 = windows_sys::Win32::Networking::WinSock::WSAEMSGSIZE;
+
+    #[cfg(any(unix, windows))]
+    if error.raw_os_error() == Some(EMSGSIZE) {
+        return Error::DatagramTooLarge { attempted, sent: 0 };
+    }
+    #[cfg(not(any(unix, windows)))]
+    let _ = attempted;
+    Error::IoError(error)
+}
+
+fn poll_recv_datagram(
+    udp_socket: &UdpSocket,
+    cx: &mut Context<'_>,
+    buffer: &mut [u8],
+    receive_ecn: bool,
+) -> Poll<tokio::io::Result<(usize, SocketAddr, Option<Ecn>)>> {
+    #[cfg(all(feature = "ecn", target_os = "linux"))]
+    if receive_ecn {
+        loop {
+            ready!(udp_socket.poll_recv_ready(cx))?;
+            let result = udp_socket.try_io(tokio::io::Interest::READABLE, || {
+                qos::recv_with_traffic_class(udp_socket, buffer)
+            });
+            match result {
+                Ok((size, address, traffic_class)) => {
+                    return Poll::Ready(Ok((size, address, traffic_class.map(Ecn::from_tos))))
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => continue,
+                Err(error) => return Poll::Ready(Err(error)),
+            }
+        }
+    }
+    #[cfg(not(all(feature = "ecn", target_os = "linux")))]
+    let _ = receive_ecn;
+
+    let mut buf = ReadBuf::new(buffer);
+    let address = ready!(udp_socket.poll_recv_from(cx, &mut buf))?;
+    Poll::Ready(Ok((buf.filled().len(), address, None)))
+}
+
+/// Wait for socket to become writable again after sending failed with leaked `WouldBlock`.
+///
+/// Tokio clears write readiness only when it observes `WouldBlock` itself - it's cleared
+/// explicitly here, so the task is woken by the reactor instead of being polled again right away.
+fn poll_send_ready_after_would_block(
+    udp_socket: &UdpSocket,
+    cx: &mut Context<'_>,
+) -> Poll<tokio::io::Result<()>> {
+    let _ = udp_socket.try_io(tokio::io::Interest::WRITABLE, || {
+        Err::<(), _>(ErrorKind::WouldBlock.into())
+    });
+    udp_socket.poll_send_ready(cx)
+}
+
+/// Builder of [Transport] applying quality of service socket options.
+///
+/// **NOTE**: Options are applied to the wrapped socket itself - if the socket is
+/// shared (borrowed) they affect all its users.
+pub struct Builder<U, Codec>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+{
+    udp_socket: U,
+    codec: Codec,
+    dscp: Option<u8>,
+    ecn: bool,
+    fragmentation: Option<Fragmentation>,
+    coalescing: Option<Coalescing>,
+    max_datagram_size: usize,
+    receive_buffer: Option<ReceiveBuffer>,
+}
+
+impl<U, Codec> Builder<U, Codec>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+{
+    /// Create new builder for transport wrapping a provided `[tokio::net::UdpSocket]`.
+    pub fn new(udp_socket: U, codec: Codec) -> Self {
+        Builder {
+            udp_socket,
+            codec,
+            dscp: None,
+            ecn: false,
+            fragmentation: None,
+            coalescing: None,
+            max_datagram_size: MAX_DATAGRAM_SIZE,
+            receive_buffer: None,
+        }
+    }
+
+    /// Mark outgoing datagrams with provided
+    /// [DSCP](https://en.wikipedia.org/wiki/Differentiated_services) value
+    /// (only lower 6 bits are used).
+    ///
+    /// See [DSCP_EXPEDITED_FORWARDING].
+    pub fn dscp(mut self, dscp: u8) -> Self {
+        self.dscp = Some(dscp & 0b111111);
+        self
+    }
+
+    /// Mark outgoing datagrams as ECN-capable and report ECN codepoints
+    /// of incoming datagrams (requires `ecn` feature and Linux).
+    pub fn ecn(mut self, ecn: bool) -> Self {
+        self.ecn = ecn;
+        self
+    }
+
+    /// Split messages larger than [fragment size](Fragmentation::fragment_size) into
+    /// many datagrams and reassemble them on receipt.
+    ///
+    /// Peer has to enable fragmentation too - see [fragment] module for details.<br>
+    /// Message is lost if any of its fragments is lost.
+    ///
+    /// Panics if fragment size isn't larger than [fragment header](fragment::FRAGMENT_HEADER_SIZE).
+    pub fn fragmentation(mut self, fragmentation: Fragmentation) -> Self {
+        self.fragmentation = Some(fragmentation);
+        self
+    }
+
+    /// Pack many small messages into a single datagram up to
+    /// [max datagram size](Coalescing::max_datagram_size).
+    ///
+    /// Peer has to enable coalescing too - see [coalesce] module for details.<br>
+    /// Can't be combined with [fragmentation](Builder::fragmentation).
+    ///
+    /// Panics if max datagram size isn't larger than
+    /// [length prefix](coalesce::LENGTH_PREFIX_SIZE) or doesn't fit in a UDP datagram.
+    pub fn coalescing(mut self, coalescing: Coalescing) -> Self {
+        self.coalescing = Some(coalescing);
+        self
+    }
+
+    /// Send datagrams up to `size` bytes (by default [MAX_DATAGRAM_SIZE]).
+    ///
+    /// Size is checked after encoding (and fragmenting or coalescing) messages -
+    /// sending larger datagram fails with [Error::MessageTooLarge] without handing it
+    /// to the socket (which would fail with an opaque IO error).<br>
+    /// Keep [fragment size](Fragmentation::fragment_size) and
+    /// [coalesced datagram size](Coalescing::max_datagram_size) within the limit.
+    pub fn max_datagram_size(mut self, size: usize) -> Self {
+        self.max_datagram_size = size;
+        self
+    }
+
+    /// Receive datagrams up to `size` bytes (by default [DEFAULT_RECEIVE_BUFFER_SIZE]).
+    ///
+    /// See [Transport::new_with_receive_buffer_size].
+    pub fn receive_buffer_size(mut self, size: usize) -> Self {
+        self.receive_buffer = Some(ReceiveBuffer::new(size));
+        self
+    }
+
+    /// Receive datagrams into a buffer shared with other transports.
+    ///
+    /// See [Transport::new_with_shared_receive_buffer].
+    pub fn shared_receive_buffer(mut self, receive_buffer: SharedReceiveBuffer) -> Self {
+        self.receive_buffer = Some(ReceiveBuffer::Shared(receive_buffer));
+        self
+    }
+
+    /// Build transport.
+    ///
+    /// Options unsupported on current platform are skipped,
+    /// see [Transport::capabilities].
+    ///
+    /// Panics if both [fragmentation](Builder::fragmentation) and
+    /// [coalescing](Builder::coalescing) are enabled.
+    pub fn build<Incoming, Outgoing>(self) -> Transport<U, Codec, Incoming, Outgoing>
+    where
+        for<'de> Incoming: serde::de::Deserialize<'de>,
+        Outgoing: Serialize,
+    {
+        assert!(
+            self.fragmentation.is_none() || self.coalescing.is_none(),
+            "fragmentation can't be combined with coalescing"
+        );
+        let mut capabilities = Capabilities::default();
+        let udp_socket = self.udp_socket.borrow();
+        if self.dscp.is_some() || self.ecn {
+            let ecn = if self.ecn { 0b10 } else { 0b00 };
+            let traffic_class = (self.dscp.unwrap_or(0) << 2) | ecn;
+            if qos::set_traffic_class(udp_socket, traffic_class).is_ok() {
+                capabilities.dscp = self.dscp.is_some();
+                capabilities.ecn = self.ecn;
+            }
+        }
+        if self.ecn {
+            capabilities.ecn_receive = qos::enable_receive_traffic_class(udp_socket).is_ok();
+        }
+
+        let receive_buffer = self
+            .receive_buffer
+            .unwrap_or_else(|| ReceiveBuffer::new(DEFAULT_RECEIVE_BUFFER_SIZE));
+        let mut transport =
+            Transport::new_with_receive_buffer(self.udp_socket, self.codec, receive_buffer);
+        transport.capabilities = capabilities;
+        transport.fragmenter = self.fragmentation.map(Fragmenter::new);
+        transport.coalescer = self.coalescing.map(Coalescer::new);
+        transport.max_datagram_size = self.max_datagram_size;
+        transport
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Sink<Outgoing> for Transport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Error = mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        if self.udp_socket.is_some() {
+            self.send_queue.push_back((item, None));
+            Ok(())
+        } else {
+            Err(mezzenger::Error::Closed)
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush_queue(cx, false)
+    }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        match self.poll_flush_unpin(cx) {
+        match self.as_mut().poll_flush_queue(cx, true) {
             Poll::Ready(_) => {
                 self.udp_socket = None;
                 Poll::Ready(Ok(()))
@@ -342,9 +1054,10 @@ mod tests {
     use futures::SinkExt;
     use kodec::binary::Codec;
     use mezzenger::Receive;
+    use socket2::SockRef;
     use tokio::net::UdpSocket;
 
-    use crate::Transport;
+    use crate::{Builder, Ecn, Transport, DSCP_EXPEDITED_FORWARDING};
 
     #[tokio::test]
     async fn test_transport() {
@@ -398,4 +1111,438 @@ mod tests {
         assert_eq!(left.receive().await.unwrap(), ());
         assert_eq!(left.receive().await.unwrap(), ());
     }
+
+    #[tokio::test]
+    async fn test_dscp() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let transport: Transport<&UdpSocket, Codec, (), ()> =
+            Builder::new(&socket, Codec::default())
+                .dscp(DSCP_EXPEDITED_FORWARDING)
+                .ecn(true)
+                .build();
+
+        let capabilities = transport.capabilities();
+        assert!(capabilities.dscp);
+        assert!(capabilities.ecn);
+        assert_eq!(SockRef::from(&socket).tos().unwrap(), 0b10111010);
+    }
+
+    #[tokio::test]
+    async fn test_receive_extended() {
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        left.connect(right.local_addr().unwrap()).await.unwrap();
+        let left_address = left.local_addr().unwrap();
+
+        let mut left: Transport<UdpSocket, Codec, (), String> =
+            Builder::new(left, Codec::default())
+                .dscp(DSCP_EXPEDITED_FORWARDING)
+                .ecn(true)
+                .build();
+        let mut right: Transport<UdpSocket, Codec, String, ()> =
+            Builder::new(right, Codec::default()).ecn(true).build();
+
+        left.send("Hello World!".to_string()).await.unwrap();
+        left.send("Hello World again!".to_string()).await.unwrap();
+
+        let expected_ecn = if right.capabilities().ecn_receive {
+            Some(Ecn::Ect0)
+        } else {
+            None
+        };
+
+        let received = right.receive_extended().await.unwrap();
+        assert_eq!(received.message, "Hello World!");
+        assert_eq!(received.address, left_address);
+        assert_eq!(received.ecn, expected_ecn);
+
+        assert_eq!(right.receive().await.unwrap(), "Hello World again!");
+    }
+
+    #[tokio::test]
+    async fn test_receive_buffer_size() {
+        use crate::{Error, SharedReceiveBuffer, DEFAULT_RECEIVE_BUFFER_SIZE};
+
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        left.connect(right.local_addr().unwrap()).await.unwrap();
+
+        let mut left: Transport<UdpSocket, Codec, (), Vec<u8>> =
+            Transport::new(left, Codec::default());
+        assert_eq!(left.receive_buffer_size(), DEFAULT_RECEIVE_BUFFER_SIZE);
+        left.send(vec![1; 100]).await.unwrap();
+        let size = left.stats().bytes_sent as usize;
+
+        // datagram exactly at configured size fits
+        let mut right: Transport<UdpSocket, Codec, Vec<u8>, ()> =
+            Transport::new_with_receive_buffer_size(right, Codec::default(), size);
+        assert_eq!(right.receive_buffer_size(), size);
+        assert_eq!(right.receive().await.unwrap(), vec![1; 100]);
+
+        left.send(vec![2; 101]).await.unwrap();
+        match right.receive().await {
+            Err(mezzenger::Error::Other(error @ Error::DatagramTruncated { buffer_size })) => {
+                assert_eq!(buffer_size, size);
+                assert_eq!(
+                    error.to_string(),
+                    format!("received datagram didn't fit in receive buffer ({size} bytes)")
+                );
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert_eq!(right.stats().decode_errors, 1);
+
+        left.send(vec![3; 10]).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), vec![3; 10]);
+
+        // transports sharing a single buffer
+        let buffer = SharedReceiveBuffer::new(size);
+        let mut receivers = vec![];
+        for _ in 0..2 {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let address = socket.local_addr().unwrap();
+            let receiver: Transport<UdpSocket, Codec, Vec<u8>, ()> =
+                Builder::new(socket, Codec::default())
+                    .shared_receive_buffer(buffer.clone())
+                    .build();
+            assert_eq!(receiver.receive_buffer_size(), size);
+            receivers.push((receiver, address));
+        }
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut sender: Transport<UdpSocket, Codec, (), Vec<u8>> =
+            Transport::new(sender, Codec::default());
+        for (index, (_, address)) in receivers.iter().enumerate() {
+            sender
+                .send_to(vec![index as u8; 100], *address)
+                .await
+                .unwrap();
+        }
+        for (index, (receiver, _)) in receivers.iter_mut().enumerate() {
+            assert_eq!(receiver.receive().await.unwrap(), vec![index as u8; 100]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncated_datagram() {
+        use std::time::Duration;
+
+        use crate::{Error, Fragmentation};
+
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        left.connect(right.local_addr().unwrap()).await.unwrap();
+
+        // fragments larger than receive buffer are reported as truncated too
+        let fragmentation = Fragmentation {
+            fragment_size: 64,
+            reassembly_timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let mut left: Transport<UdpSocket, Codec, (), String> =
+            Builder::new(left, Codec::default())
+                .fragmentation(fragmentation)
+                .build();
+        let mut right: Transport<UdpSocket, Codec, String, ()> =
+            Builder::new(right, Codec::default())
+                .fragmentation(fragmentation)
+                .receive_buffer_size(16)
+                .build();
+
+        left.send("x".repeat(200)).await.unwrap();
+        match right.receive().await {
+            Err(mezzenger::Error::Other(Error::DatagramTruncated { buffer_size: 16 })) => {}
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert_eq!(right.partial_messages(), 0);
+        assert_eq!(right.stats().decode_errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_datagram_too_large() {
+        use crate::{check_sent, Error};
+
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right_address = right.local_addr().unwrap();
+        left.connect(right_address).await.unwrap();
+
+        // lift transport's own limit to let the socket reject the datagram
+        let mut left: Transport<UdpSocket, Codec, (), Vec<u8>> =
+            Builder::new(left, Codec::default())
+                .max_datagram_size(usize::MAX)
+                .build();
+        let mut right: Transport<UdpSocket, Codec, Vec<u8>, ()> =
+            Transport::new(right, Codec::default());
+
+        left.feed(vec![1; 70000]).await.unwrap();
+        left.feed(vec![2; 10]).await.unwrap();
+        match left.flush().await {
+            Err(mezzenger::Error::Other(Error::DatagramTooLarge { attempted, sent: 0 })) => {
+                assert!(attempted > 70000)
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+        left.flush().await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), vec![2; 10]);
+
+        assert!(matches!(
+            left.send_to(vec![1; 70000], right_address).await,
+            Err(mezzenger::Error::Other(Error::DatagramTooLarge {
+                sent: 0,
+                ..
+            }))
+        ));
+
+        assert_eq!(check_sent::<(), ()>(100, 100).unwrap(), 100);
+        assert!(matches!(
+            check_sent::<(), ()>(100, 60),
+            Err(Error::DatagramTooLarge {
+                attempted: 100,
+                sent: 60
+            })
+        ));
+        assert!(matches!(
+            check_sent::<(), ()>(100, 0),
+            Err(Error::SendingError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        use crate::{Error, Stats};
+
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right_address = right.local_addr().unwrap();
+        let left_address = left.local_addr().unwrap();
+        left.connect(right_address).await.unwrap();
+
+        let mut left: Transport<UdpSocket, Codec, String, String> =
+            Builder::new(left, Codec::default())
+                .max_datagram_size(100)
+                .build();
+        let mut right: Transport<UdpSocket, Codec, String, String> =
+            Transport::new(right, Codec::default());
+        let handle = right.stats_handle();
+
+        left.send("Hello".to_string()).await.unwrap();
+        left.send("World".to_string()).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "Hello");
+        assert_eq!(right.receive().await.unwrap(), "World");
+        right.send_to("Hi".to_string(), left_address).await.unwrap();
+        assert_eq!(left.receive().await.unwrap(), "Hi");
+
+        // undecodable datagram written directly to the socket
+        let raw = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        #[cfg(feature = "type-tag")]
+        let undecodable = [&mezzenger::type_tag::tag::<String>()[..], &[0xff]].concat();
+        #[cfg(not(feature = "type-tag"))]
+        let undecodable = vec![0xff];
+        raw.send_to(&undecodable, right_address).await.unwrap();
+        assert!(matches!(
+            right.receive_from().await,
+            Err(mezzenger::Error::Other(Error::DeserializationError(_)))
+        ));
+
+        assert!(matches!(
+            left.send("a".repeat(200)).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge { .. }))
+        ));
+        assert!(matches!(
+            left.send_to("a".repeat(200), right_address).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge { .. }))
+        ));
+        left.stats.send_failed(&Error::<(), ()>::SendingError);
+        left.stats.send_failed(&Error::<(), ()>::DatagramTooLarge {
+            attempted: 100,
+            sent: 60,
+        });
+
+        let left_stats = left.stats();
+        assert_eq!(left_stats.datagrams_sent, 2);
+        assert_eq!(left_stats.messages_received, 1);
+        assert_eq!(left_stats.datagrams_received, 1);
+        assert_eq!(left_stats.oversized_rejections, 2);
+        assert_eq!(left_stats.partial_sends, 2);
+
+        let right_stats = handle.snapshot();
+        assert_eq!(right_stats, right.stats());
+        assert_eq!(right_stats.datagrams_sent, 1);
+        assert_eq!(right_stats.bytes_sent, left_stats.bytes_received);
+        assert_eq!(
+            right_stats.bytes_received,
+            left_stats.bytes_sent + undecodable.len() as u64
+        );
+        assert_eq!(right_stats.messages_received, 2);
+        assert_eq!(right_stats.datagrams_received, 3);
+        assert_eq!(right_stats.decode_errors, 1);
+        assert_eq!(right_stats.oversized_rejections, 0);
+
+        handle.reset();
+        assert_eq!(right.stats(), Stats::default());
+    }
+
+    #[tokio::test]
+    async fn test_max_datagram_size() {
+        use crate::{Error, MAX_DATAGRAM_SIZE};
+
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right_address = right.local_addr().unwrap();
+        left.connect(right_address).await.unwrap();
+
+        let mut left: Transport<UdpSocket, Codec, (), String> =
+            Builder::new(left, Codec::default())
+                .max_datagram_size(64)
+                .build();
+        let mut right: Transport<UdpSocket, Codec, String, ()> =
+            Transport::new(right, Codec::default());
+        assert_eq!(left.max_datagram_size(), 64);
+        assert_eq!(right.max_datagram_size(), MAX_DATAGRAM_SIZE);
+
+        // oversized message is dropped, following messages are still sent
+        left.feed("small".to_string()).await.unwrap();
+        left.feed("x".repeat(100)).await.unwrap();
+        left.feed("small again".to_string()).await.unwrap();
+        match left.flush().await {
+            Err(mezzenger::Error::Other(Error::MessageTooLarge { size, max: 64 })) => {
+                assert!(size > 100)
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+        left.flush().await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), "small");
+        assert_eq!(right.receive().await.unwrap(), "small again");
+        assert_eq!(left.stats().datagrams_sent, 2);
+
+        assert!(matches!(
+            left.send_to("x".repeat(100), right_address).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge {
+                max: 64,
+                ..
+            }))
+        ));
+        left.send_to("small".to_string(), right_address)
+            .await
+            .unwrap();
+        assert_eq!(right.receive().await.unwrap(), "small");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_reuseport_pool() {
+        use std::time::Duration;
+
+        use crate::ReuseportPool;
+
+        let pool: ReuseportPool<Codec, u32, ()> =
+            ReuseportPool::new("127.0.0.1:0".parse().unwrap(), 2, Codec::default()).unwrap();
+        let address = pool.local_addr();
+        assert_ne!(address.port(), 0);
+
+        const CLIENTS: u32 = 64;
+        for i in 0..CLIENTS {
+            let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            client.connect(address).await.unwrap();
+            let mut client: Transport<UdpSocket, Codec, (), u32> =
+                Transport::new(client, Codec::default());
+            client.send(i).await.unwrap();
+        }
+
+        let mut received = vec![];
+        for mut transport in pool.into_transports() {
+            while let Ok(message) =
+                tokio::time::timeout(Duration::from_millis(200), transport.receive()).await
+            {
+                received.push(message.unwrap());
+            }
+            assert!(transport.stats().messages_received > 0);
+        }
+
+        received.sort();
+        assert_eq!(received, (0..CLIENTS).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "type-tag")]
+    #[tokio::test]
+    async fn test_type_mismatch() {
+        use crate::Error;
+
+        let left = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let right = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        left.connect(right.local_addr().unwrap()).await.unwrap();
+        right.connect(left.local_addr().unwrap()).await.unwrap();
+
+        // deliberately mismatched - right should expect `String`
+        let mut left: Transport<UdpSocket, Codec, u32, String> =
+            Transport::new(left, Codec::default());
+        let mut right: Transport<UdpSocket, Codec, u32, String> =
+            Transport::new(right, Codec::default());
+
+        left.send("Hello World!".to_string()).await.unwrap();
+        match right.receive().await {
+            Err(mezzenger::Error::Other(error @ Error::TypeMismatch { .. })) => assert_eq!(
+                error.to_string(),
+                "message type mismatch (expected u32, got alloc::string::String)"
+            ),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert_eq!(right.stats().decode_errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_would_block_backpressure() {
+        use std::{
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            task::Context,
+            time::Duration,
+        };
+
+        use futures::task::{waker, ArcWake};
+
+        use crate::poll_send_ready_after_would_block;
+
+        #[derive(Default)]
+        struct Counter(AtomicUsize);
+
+        impl ArcWake for Counter {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let other = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.writable().await.unwrap();
+
+        let counter = Arc::new(Counter::default());
+        let waker = waker(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        // socket reported as full - task waits for the reactor instead of being woken right away
+        assert!(poll_send_ready_after_would_block(&socket, &mut cx).is_pending());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+        // next socket event reports it writable again
+        other
+            .send_to(&[1], socket.local_addr().unwrap())
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while counter.0.load(Ordering::SeqCst) == 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert!(socket.poll_send_ready(&mut cx).is_ready());
+    }
 }
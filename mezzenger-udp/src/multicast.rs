@@ -0,0 +1,297 @@
+//! Sending to (and receiving from) multicast groups.
+//!
+//! Sockets bound with [bind] (or [bind_v4], [bind_v6]) are bound with `SO_REUSEADDR`
+//! (and `SO_REUSEPORT` where available), so many receivers on the same host can join
+//! the same group.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! let group: SocketAddr = "239.255.0.1:1234".parse()?;
+//! let mut transport: Transport<_, Codec, Position, Position> =
+//!     Transport::bind_multicast(group, Codec::default())?;
+//! transport.send_to_group(position).await?;
+//!
+//! // on other host (or in other process)
+//! let position = transport.receive().await?;
+//! ```
+
+use std::{
+    borrow::Borrow,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+};
+
+use kodec::{Decode, Encode};
+use serde::Serialize;
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use tokio::net::UdpSocket;
+
+use crate::{Error, Transport};
+
+type TransportError<Codec> = Error<<Codec as Encode>::Error, <Codec as Decode>::Error>;
+
+/// Bind socket to port of provided multicast `group` (on all interfaces) and join the group
+/// on the default interface.
+///
+/// Must be called from within [tokio](https://tokio.rs/) runtime.
+pub fn bind(group: SocketAddr) -> io::Result<UdpSocket> {
+    match group {
+        SocketAddr::V4(group) => bind_v4(group, Ipv4Addr::UNSPECIFIED),
+        SocketAddr::V6(group) => bind_v6(group, 0),
+    }
+}
+
+/// Bind socket to port of provided IPv4 multicast `group` (on all interfaces) and join the group
+/// on `interface` (identified by its address, [Ipv4Addr::UNSPECIFIED] for the default interface).
+///
+/// Datagrams sent to the group leave through the same interface.
+///
+/// Must be called from within [tokio](https://tokio.rs/) runtime.
+pub fn bind_v4(group: SocketAddrV4, interface: Ipv4Addr) -> io::Result<UdpSocket> {
+    let socket = bind_reusable(SocketAddr::from((Ipv4Addr::UNSPECIFIED, group.port())))?;
+    socket.set_multicast_if_v4(&interface)?;
+    socket.join_multicast_v4(group.ip(), &interface)?;
+    socket.set_multicast_loop_v4(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Bind socket to port of provided IPv6 multicast `group` (on all interfaces) and join the group
+/// on `interface` (identified by its index, `0` for the default interface).
+///
+/// Datagrams sent to the group leave through the same interface.
+///
+/// Must be called from within [tokio](https://tokio.rs/) runtime.
+pub fn bind_v6(group: SocketAddrV6, interface: u32) -> io::Result<UdpSocket> {
+    let socket = bind_reusable(SocketAddr::from((Ipv6Addr::UNSPECIFIED, group.port())))?;
+    socket.set_multicast_if_v6(interface)?;
+    socket.join_multicast_v6(group.ip(), interface)?;
+    socket.set_multicast_loop_v6(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+fn bind_reusable(address: SocketAddr) -> io::Result<Socket> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    if address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    // BSDs and macOS allow many sockets bound to the same multicast port only with `SO_REUSEPORT`
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    Ok(socket)
+}
+
+impl<Codec, Incoming, Outgoing> Transport<UdpSocket, Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Create new transport over a socket bound to port of provided multicast `group`
+    /// (see [bind]), sending to the group with [send_to_group](Transport::send_to_group).
+    ///
+    /// Must be called from within [tokio](https://tokio.rs/) runtime.
+    pub fn bind_multicast(group: SocketAddr, codec: Codec) -> io::Result<Self> {
+        let udp_socket = bind(group)?;
+        let mut transport = Transport::new(udp_socket, codec);
+        transport.set_multicast_group(Some(group));
+        Ok(transport)
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Transport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Multicast group messages are sent to with [send_to_group](Transport::send_to_group).
+    pub fn multicast_group(&self) -> Option<SocketAddr> {
+        self.multicast_group
+    }
+
+    /// Set multicast group messages are sent to with [send_to_group](Transport::send_to_group).
+    pub fn set_multicast_group(&mut self, group: Option<SocketAddr>) {
+        self.multicast_group = group;
+    }
+
+    /// Send message to the [multicast group](Transport::set_multicast_group).
+    ///
+    /// Results in [Error::IoError] if multicast group is not set.
+    pub async fn send_to_group(
+        &mut self,
+        message: Outgoing,
+    ) -> Result<(), mezzenger::Error<TransportError<Codec>>> {
+        let group = self.multicast_group.ok_or_else(|| {
+            mezzenger::Error::Other(Error::IoError(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "multicast group is not set",
+            )))
+        })?;
+        self.send_to(message, group).await
+    }
+
+    /// Join IPv4 multicast group on `interface` (identified by its address,
+    /// [Ipv4Addr::UNSPECIFIED] for the default interface).
+    pub fn join_multicast_v4(
+        &self,
+        group: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), TransportError<Codec>> {
+        socket(&self.udp_socket)?
+            .join_multicast_v4(group, interface)
+            .map_err(Error::IoError)
+    }
+
+    /// Join IPv6 multicast group on `interface` (identified by its index,
+    /// `0` for the default interface).
+    pub fn join_multicast_v6(
+        &self,
+        group: &Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), TransportError<Codec>> {
+        socket(&self.udp_socket)?
+            .join_multicast_v6(group, interface)
+            .map_err(Error::IoError)
+    }
+
+    /// Leave IPv4 multicast group joined with [join_multicast_v4](Transport::join_multicast_v4).
+    pub fn leave_multicast_v4(
+        &self,
+        group: Ipv4Addr,
+        interface: Ipv4Addr,
+    ) -> Result<(), TransportError<Codec>> {
+        socket(&self.udp_socket)?
+            .leave_multicast_v4(group, interface)
+            .map_err(Error::IoError)
+    }
+
+    /// Leave IPv6 multicast group joined with [join_multicast_v6](Transport::join_multicast_v6).
+    pub fn leave_multicast_v6(
+        &self,
+        group: &Ipv6Addr,
+        interface: u32,
+    ) -> Result<(), TransportError<Codec>> {
+        socket(&self.udp_socket)?
+            .leave_multicast_v6(group, interface)
+            .map_err(Error::IoError)
+    }
+
+    /// Set whether datagrams sent to multicast groups are looped back to sockets
+    /// on this host (enabled by default).
+    pub fn set_multicast_loop(&self, enabled: bool) -> Result<(), TransportError<Codec>> {
+        let udp_socket = socket(&self.udp_socket)?;
+        match udp_socket.local_addr().map_err(Error::IoError)?.ip() {
+            IpAddr::V4(_) => udp_socket.set_multicast_loop_v4(enabled),
+            IpAddr::V6(_) => udp_socket.set_multicast_loop_v6(enabled),
+        }
+        .map_err(Error::IoError)
+    }
+
+    /// Set time-to-live (hop limit for IPv6) of datagrams sent to multicast groups.
+    ///
+    /// Default is `1` - datagrams don't leave local network.
+    pub fn set_multicast_ttl(&self, ttl: u32) -> Result<(), TransportError<Codec>> {
+        let udp_socket = socket(&self.udp_socket)?;
+        match udp_socket.local_addr().map_err(Error::IoError)?.ip() {
+            IpAddr::V4(_) => udp_socket.set_multicast_ttl_v4(ttl),
+            IpAddr::V6(_) => SockRef::from(udp_socket).set_multicast_hops_v6(ttl),
+        }
+        .map_err(Error::IoError)
+    }
+}
+
+fn socket<U, SerializationError, DeserializationError>(
+    udp_socket: &Option<U>,
+) -> Result<&UdpSocket, Error<SerializationError, DeserializationError>>
+where
+    U: Borrow<UdpSocket>,
+{
+    udp_socket
+        .as_ref()
+        .map(|udp_socket| udp_socket.borrow())
+        .ok_or_else(|| Error::IoError(io::Error::from(io::ErrorKind::NotConnected)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+        time::Duration,
+    };
+
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::{net::UdpSocket, time::timeout};
+
+    use crate::{Error, Transport};
+
+    const GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 77, 1);
+
+    type MulticastTransport = Transport<UdpSocket, Codec, String, String>;
+
+    fn bind(port: u16) -> MulticastTransport {
+        let group = SocketAddrV4::new(GROUP, port);
+        let udp_socket = super::bind_v4(group, Ipv4Addr::LOCALHOST).unwrap();
+        let mut transport = Transport::new(udp_socket, Codec::default());
+        transport.set_multicast_group(Some(group.into()));
+        transport
+    }
+
+    #[tokio::test]
+    async fn test_multicast() {
+        let mut sender = bind(0);
+        let port = sender
+            .udp_socket
+            .as_ref()
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        sender.set_multicast_group(Some(SocketAddr::from((GROUP, port))));
+        sender.set_multicast_ttl(1).unwrap();
+        sender.set_multicast_loop(true).unwrap();
+
+        let mut receivers = [bind(port), bind(port)];
+
+        sender.send_to_group("Hello".to_string()).await.unwrap();
+        for receiver in &mut receivers {
+            let message = timeout(Duration::from_secs(1), receiver.receive())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(message, "Hello");
+        }
+
+        receivers[0]
+            .leave_multicast_v4(GROUP, Ipv4Addr::LOCALHOST)
+            .unwrap();
+        sender.send_to_group("World".to_string()).await.unwrap();
+        let message = timeout(Duration::from_secs(1), receivers[1].receive())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message, "World");
+    }
+
+    #[tokio::test]
+    async fn test_group_not_set() {
+        let udp_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut transport: MulticastTransport = Transport::new(udp_socket, Codec::default());
+        assert_eq!(transport.multicast_group(), None);
+
+        let result = transport.send_to_group("Hello".to_string()).await;
+        assert!(matches!(
+            result,
+            Err(mezzenger::Error::Other(Error::IoError(_)))
+        ));
+    }
+}
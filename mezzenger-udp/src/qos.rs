@@ -0,0 +1,170 @@
+//! DSCP marking and ECN support.
+
+use std::{io, net::SocketAddr};
+
+use socket2::SockRef;
+use tokio::net::UdpSocket;
+
+/// DSCP value of Expedited Forwarding class (recommended for real-time traffic).
+pub const DSCP_EXPEDITED_FORWARDING: u8 = 46;
+
+/// [Explicit Congestion Notification](https://en.wikipedia.org/wiki/Explicit_Congestion_Notification)
+/// codepoint of a received datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ecn {
+    /// Not ECN-capable transport.
+    NotEct,
+    /// ECN-capable transport (1).
+    Ect1,
+    /// ECN-capable transport (0).
+    Ect0,
+    /// Congestion experienced.
+    Ce,
+}
+
+impl Ecn {
+    /// Extract ECN codepoint from TOS/traffic class byte.
+    pub fn from_tos(tos: u8) -> Self {
+        match tos & 0b11 {
+            0b00 => Ecn::NotEct,
+            0b01 => Ecn::Ect1,
+            0b10 => Ecn::Ect0,
+            _ => Ecn::Ce,
+        }
+    }
+}
+
+/// Report of socket options successfully applied by [Builder](crate::Builder).
+///
+/// Unsupported options are silently skipped - check this report to find out
+/// whether they are in effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    /// Outgoing datagrams carry requested DSCP marking.
+    pub dscp: bool,
+    /// Outgoing datagrams are marked as ECN-capable.
+    pub ecn: bool,
+    /// ECN codepoints of incoming datagrams are reported by
+    /// [receive_extended](crate::Transport::receive_extended).
+    ///
+    /// Requires `ecn` feature and Linux.
+    pub ecn_receive: bool,
+}
+
+/// Datagram received with [receive_extended](crate::Transport::receive_extended).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Received<Incoming> {
+    /// Received message.
+    pub message: Incoming,
+    /// Origin address.
+    pub address: SocketAddr,
+    /// ECN codepoint of the datagram, `None` if unknown.
+    pub ecn: Option<Ecn>,
+}
+
+pub(crate) fn set_traffic_class(udp_socket: &UdpSocket, traffic_class: u8) -> io::Result<()> {
+    let socket = SockRef::from(udp_socket);
+    if udp_socket.local_addr()?.is_ipv4() {
+        socket.set_tos(traffic_class as u32)
+    } else {
+        set_traffic_class_v6(&socket, traffic_class)
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos"
+))]
+fn set_traffic_class_v6(socket: &SockRef, traffic_class: u8) -> io::Result<()> {
+    socket.set_tclass_v6(traffic_class as u32)
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos"
+)))]
+fn set_traffic_class_v6(_socket: &SockRef, _traffic_class: u8) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(all(feature = "ecn", target_os = "linux"))]
+pub(crate) use linux::{enable_receive_traffic_class, recv_with_traffic_class};
+
+#[cfg(not(all(feature = "ecn", target_os = "linux")))]
+pub(crate) fn enable_receive_traffic_class(_udp_socket: &UdpSocket) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(all(feature = "ecn", target_os = "linux"))]
+mod linux {
+    use std::{io, mem::size_of_val, net::SocketAddr, os::fd::AsRawFd, ptr};
+
+    use socket2::{SockAddr, SockRef};
+    use tokio::net::UdpSocket;
+
+    pub(crate) fn enable_receive_traffic_class(udp_socket: &UdpSocket) -> io::Result<()> {
+        let socket = SockRef::from(udp_socket);
+        if udp_socket.local_addr()?.is_ipv4() {
+            socket.set_recv_tos(true)
+        } else {
+            socket.set_recv_tclass_v6(true)
+        }
+    }
+
+    /// Receive a datagram along with its TOS/traffic class byte using `recvmsg`.
+    pub(crate) fn recv_with_traffic_class(
+        udp_socket: &UdpSocket,
+        buffer: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Option<u8>)> {
+        let mut iov = libc::iovec {
+            iov_base: buffer.as_mut_ptr().cast(),
+            iov_len: buffer.len(),
+        };
+        let mut control = [0u64; 8];
+        let mut traffic_class = None;
+
+        // SAFETY: all pointers stored in `message` point to buffers outliving the call
+        // and control messages are only read within bounds reported by the kernel.
+        let (bytes_read, address) = unsafe {
+            SockAddr::try_init(|storage, length| {
+                let mut message: libc::msghdr = std::mem::zeroed();
+                message.msg_name = storage.cast();
+                message.msg_namelen = *length;
+                message.msg_iov = &mut iov;
+                message.msg_iovlen = 1;
+                message.msg_control = control.as_mut_ptr().cast();
+                message.msg_controllen = size_of_val(&control) as _;
+
+                let result = libc::recvmsg(udp_socket.as_raw_fd(), &mut message, 0);
+                if result < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                *length = message.msg_namelen;
+
+                let mut header = libc::CMSG_FIRSTHDR(&message);
+                while !header.is_null() {
+                    let level = (*header).cmsg_level;
+                    let kind = (*header).cmsg_type;
+                    let data = libc::CMSG_DATA(header);
+                    if level == libc::IPPROTO_IP && kind == libc::IP_TOS {
+                        traffic_class = Some(*data);
+                    } else if level == libc::IPPROTO_IPV6 && kind == libc::IPV6_TCLASS {
+                        let value = ptr::read_unaligned(data as *const libc::c_int);
+                        traffic_class = Some(value as u8);
+                    }
+                    header = libc::CMSG_NXTHDR(&message, header);
+                }
+                Ok(result as usize)
+            })?
+        };
+
+        let address = address
+            .as_socket()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+        Ok((bytes_read, address, traffic_class))
+    }
+}
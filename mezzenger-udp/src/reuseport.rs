@@ -0,0 +1,172 @@
+//! Socket sharing with `SO_REUSEPORT`.
+
+use std::{borrow::Borrow, fmt::Display, io, net::SocketAddr};
+
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+use crate::{Stats, Transport};
+
+/// Error returned when binding transports with `SO_REUSEPORT`.
+#[derive(Debug)]
+pub enum ReuseportError {
+    /// `SO_REUSEPORT` is not supported on current platform.
+    Unsupported,
+    IoError(io::Error),
+}
+
+impl Display for ReuseportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReuseportError::Unsupported => {
+                write!(f, "SO_REUSEPORT is not supported on this platform")
+            }
+            ReuseportError::IoError(error) => write!(f, "IO error occurred: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ReuseportError {}
+
+impl From<io::Error> for ReuseportError {
+    fn from(error: io::Error) -> Self {
+        ReuseportError::IoError(error)
+    }
+}
+
+impl<Codec, Incoming, Outgoing> Transport<UdpSocket, Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Create new transport over a socket bound to provided address with `SO_REUSEPORT`.
+    ///
+    /// Other sockets (in this or other processes of the same user) may bind to
+    /// the same address - see [ReuseportPool] for load balancing caveats.
+    ///
+    /// Must be called from within [tokio](https://tokio.rs/) runtime.
+    ///
+    /// Returns [ReuseportError::Unsupported] on platforms without `SO_REUSEPORT`.
+    pub fn bind_reuseport(address: SocketAddr, codec: Codec) -> Result<Self, ReuseportError> {
+        let udp_socket = bind_reuseport(address)?;
+        Ok(Transport::new(udp_socket, codec))
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+fn bind_reuseport(address: SocketAddr) -> Result<UdpSocket, ReuseportError> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+fn bind_reuseport(_address: SocketAddr) -> Result<UdpSocket, ReuseportError> {
+    Err(ReuseportError::Unsupported)
+}
+
+/// Group of transports bound to the same address with `SO_REUSEPORT`.
+///
+/// The kernel spreads incoming datagrams between sockets in the group,
+/// so each transport can be owned by a separate worker task.
+///
+/// **NOTE**: Load balancing is performed by the kernel and has a few caveats:
+/// - on Linux datagrams are distributed by a hash of source and destination
+///   address - all datagrams from a single client land in the same socket,
+///   so with few clients (or clients behind a single NAT address) load may be
+///   heavily imbalanced,
+/// - when a socket in the group is closed the hash is recomputed and clients
+///   may be moved to different sockets,
+/// - on BSDs and macOS `SO_REUSEPORT` does not balance unicast traffic
+///   (typically only one socket receives it).
+///
+/// Use [stats](ReuseportPool::stats) to observe the imbalance.
+pub struct ReuseportPool<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    local_address: SocketAddr,
+    transports: Vec<Transport<UdpSocket, Codec, Incoming, Outgoing>>,
+}
+
+impl<Codec, Incoming, Outgoing> ReuseportPool<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec + Clone,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Bind `n` transports to provided address.
+    ///
+    /// If address port is `0` all transports share the port assigned to the first one.
+    ///
+    /// Must be called from within [tokio](https://tokio.rs/) runtime.
+    pub fn new(address: SocketAddr, n: usize, codec: Codec) -> Result<Self, ReuseportError> {
+        let mut local_address = address;
+        let mut transports = Vec::with_capacity(n);
+        for _ in 0..n {
+            let transport = Transport::bind_reuseport(local_address, codec.clone())?;
+            if local_address.port() == 0 {
+                local_address = transport.local_addr()?;
+            }
+            transports.push(transport);
+        }
+        Ok(ReuseportPool {
+            local_address,
+            transports,
+        })
+    }
+}
+
+impl<Codec, Incoming, Outgoing> ReuseportPool<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Address all transports in the pool are bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    /// Transports in the pool.
+    pub fn transports(&self) -> &[Transport<UdpSocket, Codec, Incoming, Outgoing>] {
+        &self.transports
+    }
+
+    /// Statistics snapshot of every transport in the pool.
+    pub fn stats(&self) -> Vec<Stats> {
+        self.transports.iter().map(Transport::stats).collect()
+    }
+
+    /// Take transports out of the pool (for example to move each to its own worker task).
+    pub fn into_transports(self) -> Vec<Transport<UdpSocket, Codec, Incoming, Outgoing>> {
+        self.transports
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Transport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.udp_socket
+            .as_ref()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?
+            .borrow()
+            .local_addr()
+    }
+}
@@ -0,0 +1,126 @@
+//! Transport statistics.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Error;
+
+/// Snapshot of transport statistics returned by [Transport::stats](crate::Transport::stats).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Number of sent datagrams (including fragments and coalesced datagrams).
+    pub datagrams_sent: u64,
+    /// Number of successfully received messages.
+    pub messages_received: u64,
+    /// Number of bytes sent.
+    pub bytes_sent: u64,
+    /// Number of bytes received.
+    pub bytes_received: u64,
+    /// Number of received datagrams that failed to deserialize.
+    pub decode_errors: u64,
+    /// Number of received datagrams (including fragments, coalesced and undecodable datagrams).
+    pub datagrams_received: u64,
+    /// Number of messages rejected for being too large to be sent
+    /// ([Error::MessageTooLarge] and [Error::DatagramTooLarge] reported by the OS).
+    pub oversized_rejections: u64,
+    /// Number of datagrams sent only partially or not at all
+    /// ([Error::SendingError] and truncated [Error::DatagramTooLarge]).
+    pub partial_sends: u64,
+}
+
+/// Live transport statistics that can be shared with other tasks.
+///
+/// Obtained with [Transport::stats_handle](crate::Transport::stats_handle).
+#[derive(Debug, Default)]
+pub struct TransportStats {
+    datagrams_sent: AtomicU64,
+    messages_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    decode_errors: AtomicU64,
+    datagrams_received: AtomicU64,
+    oversized_rejections: AtomicU64,
+    partial_sends: AtomicU64,
+}
+
+impl TransportStats {
+    /// Take a snapshot of current statistics.
+    pub fn snapshot(&self) -> Stats {
+        Stats {
+            datagrams_sent: self.datagrams_sent.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+            datagrams_received: self.datagrams_received.load(Ordering::Relaxed),
+            oversized_rejections: self.oversized_rejections.load(Ordering::Relaxed),
+            partial_sends: self.partial_sends.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reset all statistics.
+    pub fn reset(&self) {
+        for counter in [
+            &self.datagrams_sent,
+            &self.messages_received,
+            &self.bytes_sent,
+            &self.bytes_received,
+            &self.decode_errors,
+            &self.datagrams_received,
+            &self.oversized_rejections,
+            &self.partial_sends,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn datagram_sent(&self, bytes: usize) {
+        self.datagrams_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count failure of sending a message.
+    pub(crate) fn send_failed<SerializationError, DeserializationError>(
+        &self,
+        error: &Error<SerializationError, DeserializationError>,
+    ) {
+        match error {
+            Error::MessageTooLarge { .. } | Error::DatagramTooLarge { sent: 0, .. } => {
+                self.oversized_rejections.fetch_add(1, Ordering::Relaxed);
+            }
+            Error::DatagramTooLarge { .. } | Error::SendingError => {
+                self.partial_sends.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    /// Count received fragment of a message that isn't complete yet.
+    pub(crate) fn fragment_received(&self, bytes: usize) {
+        self.datagrams_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count received datagram carrying `messages` coalesced messages,
+    /// `decoded` of which were successfully decoded.
+    pub(crate) fn coalesced_received(&self, bytes: usize, decoded: usize, messages: usize) {
+        self.datagrams_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.messages_received
+            .fetch_add(decoded as u64, Ordering::Relaxed);
+        self.decode_errors
+            .fetch_add((messages - decoded) as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn datagram_received(&self, bytes: usize, decoded: bool) {
+        self.datagrams_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        if decoded {
+            self.messages_received.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.decode_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Transport exchanging messages with many peers over a single (unconnected) socket.
+
+use std::{
+    borrow::Borrow,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::{stream::FusedStream, Sink, Stream};
+use kodec::{Decode, Encode};
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+use crate::{Error, Stats, Transport, TransportStats};
+
+impl<U, Codec, Incoming, Outgoing> Transport<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Turn transport into [Unconnected] transport receiving messages along with
+    /// their origin address and sending messages to addresses provided with them
+    /// (for example for a server handling many peers with a single socket).
+    ///
+    /// Messages already queued are still sent to the connected address.
+    pub fn into_unconnected(self) -> Unconnected<U, Codec, Incoming, Outgoing> {
+        Unconnected { inner: self }
+    }
+}
+
+/// Transport over an unconnected [tokio::net::UdpSocket] exchanging messages with many peers.
+///
+/// Received messages are paired with their origin address,
+/// sent messages are paired with their target address.
+///
+/// Created with [Transport::into_unconnected].
+///
+/// **NOTE**: Like [Transport] it is **unreliable** and **unordered**.
+pub struct Unconnected<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    inner: Transport<U, Codec, Incoming, Outgoing>,
+}
+
+impl<U, Codec, Incoming, Outgoing> Unconnected<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Snapshot of transport statistics.
+    pub fn stats(&self) -> Stats {
+        self.inner.stats()
+    }
+
+    /// Handle to live transport statistics.
+    pub fn stats_handle(&self) -> Arc<TransportStats> {
+        self.inner.stats_handle()
+    }
+
+    /// Size of the largest datagram that can be received.
+    pub fn receive_buffer_size(&self) -> usize {
+        self.inner.receive_buffer_size()
+    }
+
+    /// Size of the largest datagram transport sends.
+    pub fn max_datagram_size(&self) -> usize {
+        self.inner.max_datagram_size()
+    }
+
+    /// Flush all queued messages, including partially filled datagrams held back
+    /// by [coalescing](crate::Builder::coalescing).
+    pub async fn force_flush(
+        &mut self,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        self.inner.force_flush().await
+    }
+
+    /// Turn back into [Transport] (messages not yet flushed are still sent to their targets).
+    pub fn into_inner(self) -> Transport<U, Codec, Incoming, Outgoing> {
+        self.inner
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Sink<(Outgoing, SocketAddr)>
+    for Unconnected<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Error = mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (item, target): (Outgoing, SocketAddr),
+    ) -> Result<(), Self::Error> {
+        if self.inner.udp_socket.is_some() {
+            self.inner.send_queue.push_back((item, Some(target)));
+            Ok(())
+        } else {
+            Err(mezzenger::Error::Closed)
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_flush_queue(cx, false)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<Outgoing>::poll_close(Pin::new(&mut self.inner), cx)
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> Stream for Unconnected<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    type Item =
+        Result<(Incoming, SocketAddr), Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_recv_from(cx)
+    }
+}
+
+impl<U, Codec, Incoming, Outgoing> FusedStream for Unconnected<U, Codec, Incoming, Outgoing>
+where
+    U: Borrow<UdpSocket>,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::net::UdpSocket;
+
+    use crate::{Transport, Unconnected};
+
+    #[tokio::test]
+    async fn test_unconnected() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_address = server.local_addr().unwrap();
+        let mut server: Unconnected<UdpSocket, Codec, u32, String> =
+            Transport::new(server, Codec::default()).into_unconnected();
+
+        let mut clients = vec![];
+        let mut addresses = vec![];
+        for _ in 0..2 {
+            let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            client.connect(server_address).await.unwrap();
+            addresses.push(client.local_addr().unwrap());
+            let client: Transport<UdpSocket, Codec, String, u32> =
+                Transport::new(client, Codec::default());
+            clients.push(client);
+        }
+
+        clients[0].send(1).await.unwrap();
+        assert_eq!(server.receive().await.unwrap(), (1, addresses[0]));
+        clients[1].send(2).await.unwrap();
+        assert_eq!(server.receive().await.unwrap(), (2, addresses[1]));
+
+        server
+            .feed(("second".to_string(), addresses[1]))
+            .await
+            .unwrap();
+        server
+            .feed(("first".to_string(), addresses[0]))
+            .await
+            .unwrap();
+        server.flush().await.unwrap();
+        assert_eq!(clients[0].receive().await.unwrap(), "first");
+        assert_eq!(clients[1].receive().await.unwrap(), "second");
+        assert_eq!(server.stats().datagrams_sent, 2);
+        assert_eq!(server.stats().messages_received, 2);
+
+        server.close().await.unwrap();
+        assert!(futures::stream::FusedStream::is_terminated(&server));
+        assert!(matches!(
+            server.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert!(matches!(
+            server.send(("closed".to_string(), addresses[0])).await,
+            Err(mezzenger::Error::Closed)
+        ));
+    }
+}
@@ -0,0 +1,100 @@
+//! Compares encoding broadcast message once (with [SharedEncoded] and [SharedNumbered])
+//! with encoding it separately for every client.
+//!
+//! Run with:
+//! ```text
+//! cargo bench -p mezzenger-utils --bench shared
+//! ```
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use kodec::{binary::Codec, Encode};
+use mezzenger_utils::{numbered::Wrapper, SharedEncoded, SharedNumbered};
+use serde::Serialize;
+
+const CLIENTS: usize = 1000;
+const ROUNDS: usize = 20;
+
+#[derive(Serialize)]
+struct Entity {
+    id: u64,
+    position: (f32, f32, f32),
+    velocity: (f32, f32, f32),
+    name: String,
+}
+
+fn state() -> Vec<Entity> {
+    (0..500)
+        .map(|id| Entity {
+            id,
+            position: (id as f32, 0.0, 1.0),
+            velocity: (0.5, 0.5, id as f32),
+            name: format!("entity {id}"),
+        })
+        .collect()
+}
+
+fn measure(name: &str, mut broadcast: impl FnMut() -> usize) -> Duration {
+    let start = Instant::now();
+    let mut bytes = 0;
+    for _ in 0..ROUNDS {
+        bytes += black_box(broadcast());
+    }
+    let elapsed = start.elapsed() / ROUNDS as u32;
+    println!(
+        "{name:<24} {:>10.3} ms per broadcast to {CLIENTS} clients ({} bytes per client)",
+        elapsed.as_secs_f64() * 1000.0,
+        bytes / ROUNDS / CLIENTS
+    );
+    elapsed
+}
+
+fn main() {
+    let codec = Codec::default();
+    let state = state();
+
+    let per_client = measure("per-client encoding", || {
+        let mut bytes = 0;
+        for _ in 0..CLIENTS {
+            let mut buffer = vec![];
+            codec.encode(&mut buffer, &state).unwrap();
+            bytes += black_box(buffer).len();
+        }
+        bytes
+    });
+
+    let shared = measure("shared", || {
+        let shared = SharedEncoded::new(&codec, &state).unwrap();
+        (0..CLIENTS).map(|_| black_box(shared.clone()).len()).sum()
+    });
+
+    let per_client_numbered = measure("per-client numbered", || {
+        let mut bytes = 0;
+        for number in 0..CLIENTS as u64 {
+            let mut buffer = vec![];
+            let message = Wrapper {
+                number,
+                wrapped: &state,
+            };
+            codec.encode(&mut buffer, &message).unwrap();
+            bytes += black_box(buffer).len();
+        }
+        bytes
+    });
+
+    let shared_numbered = measure("shared numbered", || {
+        let shared = SharedNumbered::<u64, _>::new(&codec, &state).unwrap();
+        (0..CLIENTS as u64)
+            .map(|number| black_box(shared.with_number(&codec, &number).unwrap()).len())
+            .sum()
+    });
+
+    println!(
+        "\nshared: {:.1}x faster, shared numbered: {:.1}x faster",
+        per_client.as_secs_f64() / shared.as_secs_f64(),
+        per_client_numbered.as_secs_f64() / shared_numbered.as_secs_f64()
+    );
+}
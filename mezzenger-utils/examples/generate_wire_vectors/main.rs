@@ -0,0 +1,10 @@
+//! Regenerate wire format conformance vectors (see `mezzenger_utils::wire_vectors`).
+
+use mezzenger_utils::wire_vectors::{render, vectors, FIXTURE_PATH};
+
+fn main() -> std::io::Result<()> {
+    let path = format!("{}/{FIXTURE_PATH}", env!("CARGO_MANIFEST_DIR"));
+    std::fs::write(&path, render(&vectors()))?;
+    println!("Wire vectors written to {path}.");
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! Request-response over channel transports.
+//!
+//! Uses tokio runtime, so it runs natively only.
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+use futures::{SinkExt, StreamExt};
+use mezzenger::Receive;
+use mezzenger_channel::transports;
+use mezzenger_utils::{
+    numbered::Numbered,
+    rpc::{requester, Reply},
+};
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::spawn;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Request {
+    Add(i64, i64),
+    Multiply(i64, i64),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Notification {
+    RequestsHandled(usize),
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[tokio::main]
+async fn main() {
+    let (client, server) = transports();
+
+    let server = spawn(async move {
+        let mut server = Numbered::new_usize(server);
+        let mut handled = 0;
+        while let Ok(request) = server.receive().await {
+            let response = match request.wrapped {
+                Request::Add(a, b) => a + b,
+                Request::Multiply(a, b) => a * b,
+            };
+            handled += 1;
+            let reply = Reply::Response {
+                request: request.number,
+                response,
+            };
+            if server.send(reply).await.is_err() {
+                break;
+            }
+            let notification = Reply::Message(Notification::RequestsHandled(handled));
+            if server.send(notification).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let (requester, mut receiver) = requester::<usize, _, _, Request, i64, Notification>(client);
+    // receiver dispatches responses - keep polling it
+    let notifications = spawn(async move {
+        while let Some(Ok(notification)) = receiver.next().await {
+            println!("Notification: {notification:?}");
+        }
+    });
+
+    println!(
+        "2 + 3 = {}",
+        requester.request(Request::Add(2, 3)).await.unwrap()
+    );
+    println!(
+        "4 * 5 = {}",
+        requester.request(Request::Multiply(4, 5)).await.unwrap()
+    );
+
+    drop(requester);
+    notifications.abort();
+    let _ = server.await;
+}
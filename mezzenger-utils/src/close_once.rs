@@ -0,0 +1,190 @@
+//! Helper making closing of wrapper transports idempotent.
+//!
+//! Wrappers provided by this crate guarantee that once closing their inner transport
+//! completed successfully, subsequent [poll_close] calls return `Ok(())` immediately
+//! without re-driving the inner transport.
+//!
+//! [poll_close]: futures::Sink::poll_close
+
+use std::task::{ready, Poll};
+
+/// Helper tracking whether transport was already closed.
+///
+/// Use it in custom wrappers to make them follow the same close contract as
+/// wrappers provided by this crate.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CloseOnce {
+    closed: bool,
+}
+
+impl CloseOnce {
+    /// Create new helper (in not closed state).
+    pub fn new() -> Self {
+        CloseOnce { closed: false }
+    }
+
+    /// Was closing completed.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Drive closing with provided function unless closing was already completed.
+    ///
+    /// Closing is considered completed once `poll` returns `Ok(())`.
+    pub fn poll_close<E>(
+        &mut self,
+        poll: impl FnOnce() -> Poll<Result<(), E>>,
+    ) -> Poll<Result<(), E>> {
+        if self.closed {
+            return Poll::Ready(Ok(()));
+        }
+        let result = ready!(poll());
+        if result.is_ok() {
+            self.closed = true;
+        }
+        Poll::Ready(result)
+    }
+
+    /// Mark closing as completed.
+    ///
+    /// Returns `true` if it wasn't completed before - useful in [Drop]
+    /// implementations closing the transport.
+    pub fn mark_closed(&mut self) -> bool {
+        !std::mem::replace(&mut self.closed, true)
+    }
+}
+
+#[cfg(all(test, feature = "last_only"))]
+mod tests {
+    use std::{
+        cell::Cell,
+        marker::PhantomData,
+        pin::Pin,
+        rc::Rc,
+        task::{Context, Poll},
+    };
+
+    use futures::{Sink, SinkExt, Stream};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use super::CloseOnce;
+    use crate::{
+        numbered::{Numbered, Wrapper},
+        LatestOnly,
+    };
+
+    /// Transport counting how many times it was closed.
+    struct Counting<Incoming, Outgoing> {
+        closes: Rc<Cell<usize>>,
+        _incoming: PhantomData<Incoming>,
+        _outgoing: PhantomData<Outgoing>,
+    }
+
+    impl<Incoming, Outgoing> Sink<Outgoing> for Counting<Incoming, Outgoing> {
+        type Error = mezzenger::Error<()>;
+
+        fn poll_ready(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(self: Pin<&mut Self>, _item: Outgoing) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.closes.set(self.closes.get() + 1);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<Incoming, Outgoing> Stream for Counting<Incoming, Outgoing> {
+        type Item = Result<Incoming, ()>;
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(None)
+        }
+    }
+
+    type Layer<T> = Wrapper<u32, T>;
+
+    async fn test_close_stack_inner() {
+        let closes = Rc::new(Cell::new(0));
+        let transport: Counting<Layer<Layer<i32>>, Layer<Layer<i32>>> = Counting {
+            closes: closes.clone(),
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        };
+        let transport = Numbered::new_u32(Numbered::new_u32(transport));
+        let mut transport = LatestOnly::new(transport);
+
+        transport.send(1).await.unwrap();
+        transport.close().await.unwrap();
+        transport.close().await.unwrap();
+        assert_eq!(closes.get(), 1);
+
+        drop(transport);
+        assert_eq!(closes.get(), 1);
+    }
+
+    #[test]
+    fn test_mark_closed() {
+        let mut close_once = CloseOnce::new();
+        assert!(!close_once.is_closed());
+        assert!(close_once.mark_closed());
+        assert!(!close_once.mark_closed());
+        assert!(close_once.is_closed());
+        assert_eq!(
+            close_once.poll_close(|| -> Poll<Result<(), ()>> { unreachable!() }),
+            Poll::Ready(Ok(()))
+        );
+    }
+
+    #[test]
+    fn test_failed_close() {
+        let mut close_once = CloseOnce::new();
+        assert_eq!(
+            close_once.poll_close(|| Poll::Ready(Err(()))),
+            Poll::Ready(Err(()))
+        );
+        assert!(!close_once.is_closed());
+        assert_eq!(
+            close_once.poll_close(|| Poll::<Result<(), ()>>::Pending),
+            Poll::Pending
+        );
+        assert!(!close_once.is_closed());
+        assert_eq!(
+            close_once.poll_close(|| Poll::<Result<(), ()>>::Ready(Ok(()))),
+            Poll::Ready(Ok(()))
+        );
+        assert!(close_once.is_closed());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_close_stack() {
+        test_close_stack_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_close_stack() {
+        test_close_stack_inner().await
+    }
+}
@@ -0,0 +1,312 @@
+//! Wrapper transport encrypting messages with a pre-shared key.
+//!
+//! Every message is encrypted and authenticated with
+//! [ChaCha20-Poly1305](https://datatracker.ietf.org/doc/html/rfc8439)
+//! using a random nonce prepended to the ciphertext.
+//!
+//! [`Encrypted`] operates on already encoded messages (`Vec<u8>`), so it can wrap
+//! any transport sending byte vectors (TCP, UDP, websocket...).
+//!
+//! **NOTE**: Wrapper provides confidentiality and integrity of single messages only -
+//! there is no protection against replayed, reordered or dropped messages
+//! and no key exchange (both peers have to know the key upfront).<br>
+//! Because nonces are random, a single key shouldn't be used to encrypt
+//! more than 2<sup>32</sup> messages.
+
+use std::{
+    fmt::{Debug, Display},
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Nonce,
+};
+use futures::{stream::FusedStream, Sink, Stream};
+use pin_project::pin_project;
+
+use crate::CloseOnce;
+
+/// Size of encryption key in bytes.
+pub const KEY_SIZE: usize = 32;
+
+/// Size of nonce prepended to every message.
+pub const NONCE_SIZE: usize = 12;
+
+/// Size of authentication tag appended to every message.
+pub const TAG_SIZE: usize = 16;
+
+/// Error of [`Encrypted`] transport.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Message couldn't be encrypted.
+    EncryptionFailed,
+    /// Received message was tampered with, truncated or encrypted with a different key.
+    DecryptionFailed,
+    /// Inner transport error.
+    Other(E),
+}
+
+impl<E> Display for Error<E>
+where
+    E: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::EncryptionFailed => write!(f, "failed to encrypt message"),
+            Error::DecryptionFailed => write!(f, "failed to decrypt message"),
+            Error::Other(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl<E> std::error::Error for Error<E> where E: Debug + Display {}
+
+/// Wrapper transport encrypting sent messages and decrypting received ones
+/// with a pre-shared key.
+///
+/// See [module documentation](self) for security caveats.
+#[pin_project]
+pub struct Encrypted<T, E>
+where
+    T: mezzenger::Transport<Vec<u8>, Vec<u8>, E>,
+{
+    #[pin]
+    inner: T,
+    cipher: ChaCha20Poly1305,
+    close_once: CloseOnce,
+    _error: PhantomData<E>,
+}
+
+impl<T, E> Encrypted<T, E>
+where
+    T: mezzenger::Transport<Vec<u8>, Vec<u8>, E>,
+{
+    /// Create new encrypted transport wrapping provided transport.
+    ///
+    /// Both peers have to use the same key.
+    pub fn new(transport: T, key: &[u8; KEY_SIZE]) -> Self {
+        Encrypted {
+            inner: transport,
+            cipher: ChaCha20Poly1305::new(key.into()),
+            close_once: CloseOnce::new(),
+            _error: PhantomData,
+        }
+    }
+
+    /// Get reference to inner transport.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get mutable reference to inner transport.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+fn encrypt(cipher: &ChaCha20Poly1305, message: &[u8]) -> Option<Vec<u8>> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, message).ok()?;
+    let mut encrypted = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+    encrypted.extend_from_slice(&nonce);
+    encrypted.extend_from_slice(&ciphertext);
+    Some(encrypted)
+}
+
+fn decrypt(cipher: &ChaCha20Poly1305, message: &[u8]) -> Option<Vec<u8>> {
+    if message.len() < NONCE_SIZE + TAG_SIZE {
+        return None;
+    }
+    let (nonce, ciphertext) = message.split_at(NONCE_SIZE);
+    cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+}
+
+impl<T, E> Sink<Vec<u8>> for Encrypted<T, E>
+where
+    T: mezzenger::Transport<Vec<u8>, Vec<u8>, E>,
+{
+    type Error = mezzenger::Error<Error<E>>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_ready(cx).map_err(map_error)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), Self::Error> {
+        let me = self.project();
+        let encrypted =
+            encrypt(me.cipher, &item).ok_or(mezzenger::Error::Other(Error::EncryptionFailed))?;
+        me.inner.start_send(encrypted).map_err(map_error)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_flush(cx).map_err(map_error)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.close_once
+            .poll_close(|| me.inner.poll_close(cx).map_err(map_error))
+    }
+}
+
+fn map_error<E>(error: mezzenger::Error<E>) -> mezzenger::Error<Error<E>> {
+    match error {
+        mezzenger::Error::Closed => mezzenger::Error::Closed,
+        mezzenger::Error::Other(error) => mezzenger::Error::Other(Error::Other(error)),
+    }
+}
+
+impl<T, E> Stream for Encrypted<T, E>
+where
+    T: mezzenger::Transport<Vec<u8>, Vec<u8>, E>,
+{
+    type Item = Result<Vec<u8>, Error<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let me = self.project();
+        me.inner.poll_next(cx).map(|item| {
+            item.map(|result| match result {
+                Ok(message) => decrypt(me.cipher, &message).ok_or(Error::DecryptionFailed),
+                Err(error) => Err(Error::Other(error)),
+            })
+        })
+    }
+}
+
+impl<T, E> FusedStream for Encrypted<T, E>
+where
+    T: mezzenger::Transport<Vec<u8>, Vec<u8>, E> + FusedStream,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+impl<T, E> mezzenger::Order for Encrypted<T, E> where
+    T: mezzenger::Transport<Vec<u8>, Vec<u8>, E> + mezzenger::Order
+{
+}
+
+impl<T, E> mezzenger::Reliable for Encrypted<T, E> where
+    T: mezzenger::Transport<Vec<u8>, Vec<u8>, E> + mezzenger::Reliable
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+    use mezzenger::Receive;
+    use mezzenger_channel::transports;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use super::{Encrypted, Error, NONCE_SIZE, TAG_SIZE};
+
+    const KEY: [u8; 32] = [7; 32];
+
+    async fn test_transport_inner() {
+        let (left, right) = transports();
+        let mut left = Encrypted::new(left, &KEY);
+        let mut right = Encrypted::new(right, &KEY);
+
+        left.send(b"hello".to_vec()).await.unwrap();
+        left.send(vec![]).await.unwrap();
+        right.send(b"world".to_vec()).await.unwrap();
+
+        assert_eq!(right.receive().await.unwrap(), b"hello");
+        assert_eq!(right.receive().await.unwrap(), b"");
+        assert_eq!(left.receive().await.unwrap(), b"world");
+    }
+
+    async fn test_ciphertext_inner() {
+        let (left, mut right) = transports();
+        let mut left = Encrypted::new(left, &KEY);
+
+        left.send(b"secret".to_vec()).await.unwrap();
+        left.send(b"secret".to_vec()).await.unwrap();
+
+        let first: Vec<u8> = right.receive().await.unwrap();
+        let second: Vec<u8> = right.receive().await.unwrap();
+        assert_eq!(first.len(), NONCE_SIZE + b"secret".len() + TAG_SIZE);
+        assert!(!first.windows(6).any(|window| window == b"secret"));
+        // Every message uses different nonce.
+        assert_ne!(first, second);
+    }
+
+    async fn test_tampered_inner() {
+        let (mut left, right) = transports();
+        let mut right = Encrypted::new(right, &KEY);
+
+        let (plain_left, mut plain_right) = transports();
+        let mut encrypting = Encrypted::new(plain_left, &KEY);
+        encrypting.send(b"message".to_vec()).await.unwrap();
+        let encrypted: Vec<u8> = plain_right.receive().await.unwrap();
+
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        left.send(tampered).await.unwrap();
+        left.send(encrypted[..NONCE_SIZE].to_vec()).await.unwrap();
+        left.send(encrypted).await.unwrap();
+
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::DecryptionFailed))
+        ));
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::DecryptionFailed))
+        ));
+        assert_eq!(right.receive().await.unwrap(), b"message");
+
+        let mut other_key = Encrypted::new(left, &[8; 32]);
+        other_key.send(b"message".to_vec()).await.unwrap();
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Other(Error::DecryptionFailed))
+        ));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_transport() {
+        test_transport_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_transport() {
+        test_transport_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_ciphertext() {
+        test_ciphertext_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_ciphertext() {
+        test_ciphertext_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_tampered() {
+        test_tampered_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_tampered() {
+        test_tampered_inner().await
+    }
+}
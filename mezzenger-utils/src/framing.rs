@@ -0,0 +1,477 @@
+//! Framings for talking to peers that don't use mezzenger.
+//!
+//! [`JsonLines`] turns any [AsyncRead] + [AsyncWrite] stream (for example
+//! [TcpStream](https://docs.rs/tokio/latest/tokio/net/struct.TcpStream.html))
+//! into a transport exchanging messages encoded as
+//! [newline-delimited JSON](https://github.com/ndjson/ndjson-spec):
+//! - every sent message is serialized with [serde_json] and followed by `\n`,
+//! - every received line is parsed as a single message - `\r\n` line endings
+//!   are accepted too and empty lines are skipped.
+//!
+//! Lines longer than the maximum line length are skipped (without buffering them)
+//! and reported with [Error::MessageTooLarge], lines that can't be parsed are reported
+//! with [Error::DeserializationError] - receiving continues with the next line in both cases.
+
+use std::{
+    fmt::{Debug, Display},
+    io::ErrorKind,
+    marker::PhantomData,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures::{stream::FusedStream, Sink, Stream};
+use pin_project::pin_project;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Default maximum line length (1 MiB).
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+/// Size of buffered outgoing data after which sending waits until it's written.
+const WRITE_BUFFER_LIMIT: usize = 8 * 1024;
+
+/// Size of chunks read from the stream.
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Error of [`JsonLines`] transport.
+#[derive(Debug)]
+pub enum Error {
+    /// Line (without line ending) was longer than maximum line length.
+    MessageTooLarge(usize),
+    /// Message couldn't be serialized.
+    SerializationError(serde_json::Error),
+    /// Received line couldn't be parsed.
+    DeserializationError(serde_json::Error),
+    /// Underlying stream error.
+    IoError(std::io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MessageTooLarge(size) => write!(f, "line was too long ({size} bytes)"),
+            Error::SerializationError(error) => write!(f, "failed to serialize message: {error}"),
+            Error::DeserializationError(error) => {
+                write!(f, "failed to deserialize message: {error}")
+            }
+            Error::IoError(error) => write!(f, "IO error occurred: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Lines received from the stream.
+struct Lines {
+    buffer: Vec<u8>,
+    /// Length of the buffer prefix already searched for line ending.
+    scanned: usize,
+    max_line_length: usize,
+    /// Length of the over-length line being skipped.
+    skipped: Option<usize>,
+    closed: bool,
+}
+
+impl Lines {
+    /// Take next complete line out of the read buffer.
+    fn next_line<Incoming>(&mut self) -> Option<Result<Incoming, Error>>
+    where
+        Incoming: DeserializeOwned,
+    {
+        while let Some(offset) = self.buffer[self.scanned..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+        {
+            let position = self.scanned + offset;
+            self.scanned = 0;
+            let line: Vec<u8> = self.buffer.drain(..=position).collect();
+            if let Some(result) = self.parse_line(&line[..position]) {
+                return Some(result);
+            }
+        }
+        self.scanned = self.buffer.len();
+        None
+    }
+
+    fn parse_line<Incoming>(&mut self, line: &[u8]) -> Option<Result<Incoming, Error>>
+    where
+        Incoming: DeserializeOwned,
+    {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if let Some(skipped) = self.skipped.take() {
+            return Some(Err(Error::MessageTooLarge(skipped + line.len())));
+        }
+        if line.len() > self.max_line_length {
+            return Some(Err(Error::MessageTooLarge(line.len())));
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+        Some(serde_json::from_slice(line).map_err(Error::DeserializationError))
+    }
+
+    /// Skip over-length line instead of buffering it.
+    fn skip_long_line(&mut self) {
+        let pending = self.buffer.strip_suffix(b"\r").unwrap_or(&self.buffer);
+        if pending.len() > self.max_line_length {
+            self.skipped = Some(self.skipped.unwrap_or_default() + pending.len());
+            let carriage_return = pending.len() < self.buffer.len();
+            self.buffer.clear();
+            if carriage_return {
+                self.buffer.push(b'\r');
+            }
+            self.scanned = self.buffer.len();
+        }
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.closed && self.buffer.is_empty() && self.skipped.is_none()
+    }
+}
+
+/// Transport exchanging newline-delimited JSON messages over a byte stream.
+///
+/// See [module documentation](self) for details.
+#[pin_project]
+pub struct JsonLines<T, Incoming, Outgoing>
+where
+    T: AsyncRead + AsyncWrite,
+    Incoming: DeserializeOwned,
+    Outgoing: Serialize,
+{
+    #[pin]
+    inner: T,
+    lines: Lines,
+    write_buffer: Vec<u8>,
+    written: usize,
+    write_closed: bool,
+    _incoming: PhantomData<Incoming>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<T, Incoming, Outgoing> JsonLines<T, Incoming, Outgoing>
+where
+    T: AsyncRead + AsyncWrite,
+    Incoming: DeserializeOwned,
+    Outgoing: Serialize,
+{
+    /// Create new transport wrapping provided stream.
+    ///
+    /// **NOTE**: Lines are limited to [DEFAULT_MAX_LINE_LENGTH].
+    pub fn new(inner: T) -> Self {
+        JsonLines::new_with_max_line_length(inner, DEFAULT_MAX_LINE_LENGTH)
+    }
+
+    /// Create new transport wrapping provided stream with lines limited to `max_line_length`
+    /// bytes (not counting line ending).
+    ///
+    /// Sending or receiving longer lines results in [Error::MessageTooLarge].
+    pub fn new_with_max_line_length(inner: T, max_line_length: usize) -> Self {
+        JsonLines {
+            inner,
+            lines: Lines {
+                buffer: vec![],
+                scanned: 0,
+                max_line_length,
+                skipped: None,
+                closed: false,
+            },
+            write_buffer: vec![],
+            written: 0,
+            write_closed: false,
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// Maximum line length (not counting line ending).
+    pub fn max_line_length(&self) -> usize {
+        self.lines.max_line_length
+    }
+
+    /// Get reference to wrapped stream.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    fn poll_write_buffer(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), mezzenger::Error<Error>>> {
+        let mut me = self.project();
+        while *me.written < me.write_buffer.len() {
+            let written = ready!(me
+                .inner
+                .as_mut()
+                .poll_write(cx, &me.write_buffer[*me.written..]))
+            .map_err(map_error)?;
+            if written == 0 {
+                return Poll::Ready(Err(mezzenger::Error::Closed));
+            }
+            *me.written += written;
+        }
+        me.write_buffer.clear();
+        *me.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn map_error(error: std::io::Error) -> mezzenger::Error<Error> {
+    match error.kind() {
+        ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted
+        | ErrorKind::BrokenPipe
+        | ErrorKind::NotConnected => mezzenger::Error::Closed,
+        _ => mezzenger::Error::Other(Error::IoError(error)),
+    }
+}
+
+impl<T, Incoming, Outgoing> Sink<Outgoing> for JsonLines<T, Incoming, Outgoing>
+where
+    T: AsyncRead + AsyncWrite,
+    Incoming: DeserializeOwned,
+    Outgoing: Serialize,
+{
+    type Error = mezzenger::Error<Error>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.write_closed {
+            return Poll::Ready(Err(mezzenger::Error::Closed));
+        }
+        if self.write_buffer.len() >= WRITE_BUFFER_LIMIT {
+            self.poll_write_buffer(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let me = self.project();
+        if *me.write_closed {
+            return Err(mezzenger::Error::Closed);
+        }
+        let line = serde_json::to_vec(&item)
+            .map_err(|error| mezzenger::Error::Other(Error::SerializationError(error)))?;
+        if line.len() > me.lines.max_line_length {
+            return Err(mezzenger::Error::Other(Error::MessageTooLarge(line.len())));
+        }
+        me.write_buffer.extend_from_slice(&line);
+        me.write_buffer.push(b'\n');
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.write_closed {
+            return Poll::Ready(Err(mezzenger::Error::Closed));
+        }
+        ready!(self.as_mut().poll_write_buffer(cx))?;
+        self.project().inner.poll_flush(cx).map_err(map_error)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.write_closed {
+            return Poll::Ready(Ok(()));
+        }
+        ready!(self.as_mut().poll_write_buffer(cx))?;
+        let me = self.project();
+        ready!(me.inner.poll_shutdown(cx)).map_err(map_error)?;
+        *me.write_closed = true;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T, Incoming, Outgoing> Stream for JsonLines<T, Incoming, Outgoing>
+where
+    T: AsyncRead + AsyncWrite,
+    Incoming: DeserializeOwned,
+    Outgoing: Serialize,
+{
+    type Item = Result<Incoming, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut me = self.project();
+        loop {
+            if let Some(result) = me.lines.next_line() {
+                return Poll::Ready(Some(result));
+            }
+            if me.lines.closed {
+                // last line doesn't have to be terminated
+                let line = std::mem::take(&mut me.lines.buffer);
+                me.lines.scanned = 0;
+                if line.is_empty() && me.lines.skipped.is_none() {
+                    return Poll::Ready(None);
+                }
+                if let Some(result) = me.lines.parse_line(&line) {
+                    return Poll::Ready(Some(result));
+                }
+                continue;
+            }
+
+            me.lines.skip_long_line();
+            let mut chunk = [0; READ_CHUNK_SIZE];
+            let mut read_buffer = ReadBuf::new(&mut chunk);
+            match ready!(me.inner.as_mut().poll_read(cx, &mut read_buffer)) {
+                Ok(()) => {
+                    if read_buffer.filled().is_empty() {
+                        me.lines.closed = true;
+                    } else {
+                        me.lines.buffer.extend_from_slice(read_buffer.filled());
+                    }
+                }
+                Err(error) => {
+                    me.lines.closed = true;
+                    return Poll::Ready(Some(Err(Error::IoError(error))));
+                }
+            }
+        }
+    }
+}
+
+impl<T, Incoming, Outgoing> FusedStream for JsonLines<T, Incoming, Outgoing>
+where
+    T: AsyncRead + AsyncWrite,
+    Incoming: DeserializeOwned,
+    Outgoing: Serialize,
+{
+    fn is_terminated(&self) -> bool {
+        self.lines.is_terminated()
+    }
+}
+
+impl<T, Incoming, Outgoing> mezzenger::Reliable for JsonLines<T, Incoming, Outgoing>
+where
+    T: AsyncRead + AsyncWrite,
+    Incoming: DeserializeOwned,
+    Outgoing: Serialize,
+{
+}
+
+impl<T, Incoming, Outgoing> mezzenger::Order for JsonLines<T, Incoming, Outgoing>
+where
+    T: AsyncRead + AsyncWrite,
+    Incoming: DeserializeOwned,
+    Outgoing: Serialize,
+{
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use futures::SinkExt;
+    use mezzenger::Receive;
+    use serde::{Deserialize, Serialize};
+    use tokio::io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    use super::{Error, JsonLines, Lines};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Message {
+        id: u32,
+        text: String,
+    }
+
+    fn message(id: u32, text: &str) -> Message {
+        Message {
+            id,
+            text: text.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_lines() {
+        let (stream, peer) = duplex(1024);
+        let mut transport: JsonLines<_, Message, Message> =
+            JsonLines::new_with_max_line_length(stream, 64);
+        let (peer_reader, mut peer_writer) = tokio::io::split(peer);
+        let mut peer_lines = BufReader::new(peer_reader).lines();
+
+        transport.send(message(1, "Hello")).await.unwrap();
+        transport.send(message(2, "World")).await.unwrap();
+        assert_eq!(
+            peer_lines.next_line().await.unwrap().unwrap(),
+            r#"{"id":1,"text":"Hello"}"#
+        );
+        assert_eq!(
+            peer_lines.next_line().await.unwrap().unwrap(),
+            r#"{"id":2,"text":"World"}"#
+        );
+        assert!(matches!(
+            transport.send(message(3, &"x".repeat(64))).await,
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(82)))
+        ));
+
+        let too_long = format!(r#"{{"id":3,"text":"{}"}}"#, "x".repeat(2000));
+        let script = format!(
+            "{}\r\n\n{}\n{{not json}}\n{}\r\n{}",
+            r#"{"id":1,"text":"Hi"}"#,
+            too_long,
+            r#"{"id":4,"text":"after"}"#,
+            r#"{"id":5,"text":"last"}"#,
+        );
+        // script is larger than duplex buffer - over-length line arrives in chunks
+        tokio::spawn(async move {
+            peer_writer.write_all(script.as_bytes()).await.unwrap();
+            peer_writer.shutdown().await.unwrap();
+        });
+
+        assert_eq!(transport.receive().await.unwrap(), message(1, "Hi"));
+        match transport.receive().await {
+            Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) => {
+                assert_eq!(size, too_long.len())
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert!(matches!(
+            transport.receive().await,
+            Err(mezzenger::Error::Other(Error::DeserializationError(_)))
+        ));
+        assert_eq!(transport.receive().await.unwrap(), message(4, "after"));
+        assert_eq!(transport.receive().await.unwrap(), message(5, "last"));
+        assert!(matches!(
+            transport.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert!(futures::stream::FusedStream::is_terminated(&transport));
+
+        transport.close().await.unwrap();
+        assert!(peer_lines.next_line().await.unwrap().is_none());
+        assert!(matches!(
+            transport.send(message(6, "closed")).await,
+            Err(mezzenger::Error::Closed)
+        ));
+    }
+
+    #[test]
+    fn test_line_in_chunks() {
+        let mut lines = Lines {
+            buffer: vec![],
+            scanned: 0,
+            max_line_length: 64,
+            skipped: None,
+            closed: false,
+        };
+
+        let line = format!(
+            "{}\n{}\n",
+            r#"{"id":1,"text":"Hi"}"#, r#"{"id":2,"text":"Ho"}"#
+        );
+        let (first, second) = line.as_bytes().split_at(line.find('\n').unwrap());
+        for byte in first {
+            lines.buffer.push(*byte);
+            assert!(lines.next_line::<Message>().is_none());
+            // already searched bytes aren't searched again
+            assert_eq!(lines.scanned, lines.buffer.len());
+        }
+        lines.buffer.extend_from_slice(second);
+        assert_eq!(
+            lines.next_line::<Message>().unwrap().unwrap(),
+            message(1, "Hi")
+        );
+        assert_eq!(
+            lines.next_line::<Message>().unwrap().unwrap(),
+            message(2, "Ho")
+        );
+        assert!(lines.next_line::<Message>().is_none());
+        assert_eq!(lines.scanned, 0);
+    }
+}
@@ -6,10 +6,23 @@
 //! Potentially useful when user doesn't care about stale messages
 //! (for example - multiplayer video games).
 //!
+//! When message numbers can't be trusted (for example counters restarting along with the sender)
+//! use [LatestByKeyFn] deciding freshness with a comparator over messages themselves
+//! (comparing timestamps, versions...).
+//!
+//! Transports carrying many logical streams (for example positions of many entities)
+//! can use [KeyedLatestOnly] discarding old messages separately for every stream.
+//!
+//! Transports running long enough for message numbers to wrap around
+//! should use [LatestOnly::new_wrapping].
+//!
 //! [numbered]: crate::numbered::Number
 //! [ordered]: mezzenger::Order
 
 use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    hash::Hash,
     marker::PhantomData,
     pin::Pin,
     task::{ready, Context, Poll},
@@ -18,7 +31,10 @@ use std::{
 use futures::{stream::FusedStream, Sink, Stream, StreamExt};
 use pin_project::pin_project;
 
-use crate::numbered::{Number, Unwrap};
+use crate::{
+    numbered::{Number, SequenceNumber, Unwrap},
+    CloseOnce,
+};
 
 /// Wrapper transport turning a [numbered] (but not necessarily [ordered]) transport
 /// into an [ordered] transport, discarding old messages (polling a transport for
@@ -37,6 +53,9 @@ where
     #[pin]
     inner: T,
     last_number: Option<N>,
+    newer: fn(&N, &N) -> bool,
+    latest: Latest<E>,
+    close_once: CloseOnce,
     _error: PhantomData<E>,
     _incoming: PhantomData<Incoming>,
     _outgoing: PhantomData<Outgoing>,
@@ -57,12 +76,31 @@ where
         LatestOnly {
             inner: transport,
             last_number: None,
+            newer: greater,
+            latest: Latest::new(),
+            close_once: CloseOnce::new(),
             _error: PhantomData,
             _incoming: PhantomData,
             _outgoing: PhantomData,
         }
     }
 
+    /// Like [new](LatestOnly::new) but comparing message numbers with [SequenceNumber],
+    /// tolerating their wraparound - so long-running transports numbering messages with
+    /// types like [u32] keep working after numbers wrap around.
+    ///
+    /// Messages numbered more than half of the number range apart are no longer compared
+    /// correctly (see [SequenceNumber]).
+    pub fn new_wrapping(transport: T) -> Self
+    where
+        N: SequenceNumber,
+    {
+        LatestOnly {
+            newer: |number, last_number| number.is_newer_than(last_number),
+            ..LatestOnly::new(transport)
+        }
+    }
+
     /// Return an [unwrapping] transport.
     ///
     /// By default [`LatestOnly`] stream returns messages in their original form
@@ -79,6 +117,42 @@ where
     {
         LatestOnlyUnwrapping { inner: self }
     }
+
+    /// Number of the latest received message (if any message was received yet).
+    pub fn last_number(&self) -> Option<&N> {
+        self.last_number.as_ref()
+    }
+
+    /// Unwrap inner transport (for example to rebuild a leaner transport stack
+    /// mid-connection).
+    ///
+    /// [`LatestOnly`] doesn't buffer messages - messages not yet received are still queued
+    /// in the inner transport, none are lost.<br>
+    /// State left over is returned along with the inner transport - see [Leftover].
+    pub fn into_inner(self) -> (T, Leftover<N, E>) {
+        let leftover = Leftover {
+            last_number: self.last_number,
+            pending_error: self.latest.pending_error,
+            terminated: self.latest.terminated,
+        };
+        (self.inner, leftover)
+    }
+}
+
+/// State left over after unwrapping inner transport of [`LatestOnly`]
+/// (see [LatestOnly::into_inner]).
+#[derive(Debug, PartialEq, Eq)]
+pub struct Leftover<N, E> {
+    /// Number of the latest received message - messages still queued in the inner
+    /// transport numbered lower (or equal) are stale.
+    pub last_number: Option<N>,
+
+    /// Error received from the inner transport right after the latest message, not yet
+    /// returned to the user.
+    pub pending_error: Option<E>,
+
+    /// Whether inner transport's stream has ended.
+    pub terminated: bool,
 }
 
 impl<T, E, N, Incoming, Outgoing> Sink<Outgoing> for LatestOnly<T, E, N, Incoming, Outgoing>
@@ -106,7 +180,7 @@ where
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         let me = self.project();
-        me.inner.poll_close(cx)
+        me.close_once.poll_close(|| me.inner.poll_close(cx))
     }
 }
 
@@ -119,31 +193,18 @@ where
     type Item = Result<Incoming, E>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let mut me = self.project();
-        let mut latest = None;
-        loop {
-            match me.inner.poll_next_unpin(cx) {
-                Poll::Ready(item) => {
-                    if let Some(item) = item {
-                        let item = item?;
-                        let number = item.number();
-                        if me.last_number.is_none() || &number > me.last_number.as_ref().unwrap() {
-                            latest = Some(item);
-                            *me.last_number = Some(number);
-                        }
-                    } else {
-                        return Poll::Ready(None);
-                    }
-                }
-                Poll::Pending => {
-                    return if let Some(latest) = latest {
-                        Poll::Ready(Some(Ok(latest)))
-                    } else {
-                        Poll::Pending
-                    }
-                }
+        let me = self.project();
+        let last_number = me.last_number;
+        let newer = *me.newer;
+        me.latest.poll_next(me.inner, cx, |item| {
+            let number = item.number();
+            if last_number.is_none() || newer(&number, last_number.as_ref().unwrap()) {
+                *last_number = Some(number);
+                true
+            } else {
+                false
             }
-        }
+        })
     }
 }
 
@@ -154,7 +215,7 @@ where
     for<'a> &'a N: PartialOrd,
 {
     fn is_terminated(&self) -> bool {
-        self.inner.is_terminated()
+        self.latest.is_terminated(&self.inner)
     }
 }
 
@@ -178,6 +239,20 @@ where
     inner: LatestOnly<T, E, N, I, Outgoing>,
 }
 
+impl<T, E, N, I, Incoming, Outgoing> LatestOnlyUnwrapping<T, E, N, I, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<I, Outgoing, E>,
+    I: Number<Output = N> + Unwrap<Output = Incoming>,
+    for<'a> &'a N: PartialOrd,
+{
+    /// Unwrap inner transport.
+    ///
+    /// See [LatestOnly::into_inner].
+    pub fn into_inner(self) -> (T, Leftover<N, E>) {
+        self.inner.into_inner()
+    }
+}
+
 impl<T, E, N, I, Incoming, Outgoing> Sink<Outgoing>
     for LatestOnlyUnwrapping<T, E, N, I, Incoming, Outgoing>
 where
@@ -244,9 +319,522 @@ where
 {
 }
 
+/// Wrapper transport turning a transport into an [ordered] transport, discarding
+/// old messages - like [LatestOnly] but with message freshness decided by the `compare`
+/// function instead of message numbers.
+///
+/// `compare(a, b)` returns [Ordering::Greater] if message `a` is fresher than message `b`.
+/// Received message is delivered only if it's fresher than every message received before it.
+///
+/// [ordered]: mezzenger::Order
+#[pin_project]
+pub struct LatestByKeyFn<T, F, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    F: Fn(&Incoming, &Incoming) -> Ordering,
+    Incoming: Clone,
+{
+    #[pin]
+    inner: T,
+    compare: F,
+    last: Option<Incoming>,
+    latest: Latest<E>,
+    close_once: CloseOnce,
+    _error: PhantomData<E>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<T, F, E, Incoming, Outgoing> LatestByKeyFn<T, F, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    F: Fn(&Incoming, &Incoming) -> Ordering,
+    Incoming: Clone,
+{
+    /// Wrap a provided transport turning it into [ordered] transport returning the freshest
+    /// message (according to `compare`) when polling it for the next received message.
+    ///
+    /// [ordered]: mezzenger::Order
+    pub fn new(transport: T, compare: F) -> Self {
+        LatestByKeyFn {
+            inner: transport,
+            compare,
+            last: None,
+            latest: Latest::new(),
+            close_once: CloseOnce::new(),
+            _error: PhantomData,
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// The freshest message received so far (if any message was received yet).
+    pub fn last(&self) -> Option<&Incoming> {
+        self.last.as_ref()
+    }
+}
+
+impl<T, F, E, Incoming, Outgoing> Sink<Outgoing> for LatestByKeyFn<T, F, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    F: Fn(&Incoming, &Incoming) -> Ordering,
+    Incoming: Clone,
+{
+    type Error = mezzenger::Error<E>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let me = self.project();
+        me.inner.start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.close_once.poll_close(|| me.inner.poll_close(cx))
+    }
+}
+
+impl<T, F, E, Incoming, Outgoing> Stream for LatestByKeyFn<T, F, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    F: Fn(&Incoming, &Incoming) -> Ordering,
+    Incoming: Clone,
+{
+    type Item = Result<Incoming, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let me = self.project();
+        let compare = me.compare;
+        let last = me.last;
+        me.latest.poll_next(me.inner, cx, |item| {
+            if last.is_none() || compare(item, last.as_ref().unwrap()) == Ordering::Greater {
+                *last = Some(item.clone());
+                true
+            } else {
+                false
+            }
+        })
+    }
+}
+
+impl<T, F, E, Incoming, Outgoing> FusedStream for LatestByKeyFn<T, F, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + FusedStream,
+    F: Fn(&Incoming, &Incoming) -> Ordering,
+    Incoming: Clone,
+{
+    fn is_terminated(&self) -> bool {
+        self.latest.is_terminated(&self.inner)
+    }
+}
+
+impl<T, F, E, Incoming, Outgoing> mezzenger::Order for LatestByKeyFn<T, F, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    F: Fn(&Incoming, &Incoming) -> Ordering,
+    Incoming: Clone,
+{
+}
+
+/// Wrapper transport turning a [numbered] (but not necessarily [ordered]) transport
+/// carrying many logical streams into an [ordered] transport, discarding old messages
+/// of every stream - like [LatestOnly] but message freshness is tracked separately
+/// for every key extracted from messages with the `key` function.
+///
+/// Messages with different keys don't suppress each other - polling the transport
+/// returns the latest received message of every key (one at a time, in order of their arrival).
+///
+/// [numbered]: crate::numbered::Number
+/// [ordered]: mezzenger::Order
+#[pin_project]
+pub struct KeyedLatestOnly<T, K, F, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&Incoming) -> K,
+    Incoming: Number<Output = N>,
+    for<'a> &'a N: PartialOrd,
+{
+    #[pin]
+    inner: T,
+    key: F,
+    last_numbers: HashMap<K, N>,
+    newer: fn(&N, &N) -> bool,
+    pending: VecDeque<(K, Incoming)>,
+    pending_error: Option<E>,
+    terminated: bool,
+    close_once: CloseOnce,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<T, K, F, E, N, Incoming, Outgoing> KeyedLatestOnly<T, K, F, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&Incoming) -> K,
+    Incoming: Number<Output = N>,
+    for<'a> &'a N: PartialOrd,
+{
+    /// Wrap a provided [numbered] transport turning it into [ordered] transport returning
+    /// latest message of every key (extracted with `key`) when polling it for the next
+    /// received message.
+    ///
+    /// [numbered]: crate::numbered::Number
+    /// [ordered]: mezzenger::Order
+    pub fn new(transport: T, key: F) -> Self {
+        KeyedLatestOnly {
+            inner: transport,
+            key,
+            last_numbers: HashMap::new(),
+            newer: greater,
+            pending: VecDeque::new(),
+            pending_error: None,
+            terminated: false,
+            close_once: CloseOnce::new(),
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// Like [new](KeyedLatestOnly::new) but comparing message numbers with [SequenceNumber],
+    /// tolerating their wraparound (see [LatestOnly::new_wrapping]).
+    pub fn new_wrapping(transport: T, key: F) -> Self
+    where
+        N: SequenceNumber,
+    {
+        KeyedLatestOnly {
+            newer: |number, last_number| number.is_newer_than(last_number),
+            ..KeyedLatestOnly::new(transport, key)
+        }
+    }
+
+    /// Return an [unwrapping] transport.
+    ///
+    /// See [LatestOnly::into_unwrapping].
+    ///
+    /// [unwrapping]: crate::numbered::Unwrap
+    pub fn into_unwrapping<I>(
+        self,
+    ) -> KeyedLatestOnlyUnwrapping<T, K, F, E, N, Incoming, I, Outgoing>
+    where
+        Incoming: Unwrap<Output = I>,
+    {
+        KeyedLatestOnlyUnwrapping { inner: self }
+    }
+
+    /// Number of the latest received message with provided key
+    /// (if any message with that key was received yet).
+    pub fn last_number(&self, key: &K) -> Option<&N> {
+        self.last_numbers.get(key)
+    }
+}
+
+impl<T, K, F, E, N, Incoming, Outgoing> Sink<Outgoing>
+    for KeyedLatestOnly<T, K, F, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&Incoming) -> K,
+    Incoming: Number<Output = N>,
+    for<'a> &'a N: PartialOrd,
+{
+    type Error = mezzenger::Error<E>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let me = self.project();
+        me.inner.start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.close_once.poll_close(|| me.inner.poll_close(cx))
+    }
+}
+
+impl<T, K, F, E, N, Incoming, Outgoing> Stream
+    for KeyedLatestOnly<T, K, F, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&Incoming) -> K,
+    Incoming: Number<Output = N>,
+    for<'a> &'a N: PartialOrd,
+{
+    type Item = Result<Incoming, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut me = self.project();
+        while me.pending_error.is_none() && !*me.terminated {
+            match me.inner.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(item))) => {
+                    let key = (me.key)(&item);
+                    let number = item.number();
+                    let fresher = match me.last_numbers.get(&key) {
+                        Some(last_number) => (me.newer)(&number, last_number),
+                        None => true,
+                    };
+                    if fresher {
+                        me.last_numbers.insert(key.clone(), number);
+                        me.pending.retain(|(pending_key, _)| pending_key != &key);
+                        me.pending.push_back((key, item));
+                    }
+                }
+                // Messages received before error or closing must still be delivered.
+                Poll::Ready(Some(Err(error))) => {
+                    if me.pending.is_empty() {
+                        return Poll::Ready(Some(Err(error)));
+                    }
+                    *me.pending_error = Some(error);
+                }
+                Poll::Ready(None) => *me.terminated = true,
+                Poll::Pending => break,
+            }
+        }
+
+        if let Some((_, item)) = me.pending.pop_front() {
+            Poll::Ready(Some(Ok(item)))
+        } else if let Some(error) = me.pending_error.take() {
+            Poll::Ready(Some(Err(error)))
+        } else if *me.terminated {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T, K, F, E, N, Incoming, Outgoing> FusedStream
+    for KeyedLatestOnly<T, K, F, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + FusedStream,
+    K: Eq + Hash + Clone,
+    F: Fn(&Incoming) -> K,
+    Incoming: Number<Output = N>,
+    for<'a> &'a N: PartialOrd,
+{
+    fn is_terminated(&self) -> bool {
+        self.pending.is_empty()
+            && self.pending_error.is_none()
+            && (self.terminated || self.inner.is_terminated())
+    }
+}
+
+impl<T, K, F, E, N, Incoming, Outgoing> mezzenger::Order
+    for KeyedLatestOnly<T, K, F, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&Incoming) -> K,
+    Incoming: Number<Output = N>,
+    for<'a> &'a N: PartialOrd,
+{
+}
+
+/// Transport returned by [`KeyedLatestOnly::into_unwrapping`] method.
+#[pin_project]
+pub struct KeyedLatestOnlyUnwrapping<T, K, F, E, N, I, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<I, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&I) -> K,
+    I: Number<Output = N> + Unwrap<Output = Incoming>,
+    for<'a> &'a N: PartialOrd,
+{
+    #[pin]
+    inner: KeyedLatestOnly<T, K, F, E, N, I, Outgoing>,
+}
+
+impl<T, K, F, E, N, I, Incoming, Outgoing>
+    KeyedLatestOnlyUnwrapping<T, K, F, E, N, I, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<I, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&I) -> K,
+    I: Number<Output = N> + Unwrap<Output = Incoming>,
+    for<'a> &'a N: PartialOrd,
+{
+    /// Number of the latest received message with provided key.
+    ///
+    /// See [KeyedLatestOnly::last_number].
+    pub fn last_number(&self, key: &K) -> Option<&N> {
+        self.inner.last_number(key)
+    }
+}
+
+impl<T, K, F, E, N, I, Incoming, Outgoing> Sink<Outgoing>
+    for KeyedLatestOnlyUnwrapping<T, K, F, E, N, I, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<I, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&I) -> K,
+    I: Number<Output = N> + Unwrap<Output = Incoming>,
+    for<'a> &'a N: PartialOrd,
+{
+    type Error = mezzenger::Error<E>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let me = self.project();
+        me.inner.start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_close(cx)
+    }
+}
+
+impl<T, K, F, E, N, I, Incoming, Outgoing> Stream
+    for KeyedLatestOnlyUnwrapping<T, K, F, E, N, I, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<I, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&I) -> K,
+    I: Number<Output = N> + Unwrap<Output = Incoming>,
+    for<'a> &'a N: PartialOrd,
+{
+    type Item = Result<Incoming, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let me = self.project();
+        let result = ready!(me.inner.poll_next(cx));
+        Poll::Ready(result.map(|result| result.map(|result| result.unwrap())))
+    }
+}
+
+impl<T, K, F, E, N, I, Incoming, Outgoing> FusedStream
+    for KeyedLatestOnlyUnwrapping<T, K, F, E, N, I, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<I, Outgoing, E> + FusedStream,
+    K: Eq + Hash + Clone,
+    F: Fn(&I) -> K,
+    I: Number<Output = N> + Unwrap<Output = Incoming>,
+    for<'a> &'a N: PartialOrd,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+impl<T, K, F, E, N, I, Incoming, Outgoing> mezzenger::Order
+    for KeyedLatestOnlyUnwrapping<T, K, F, E, N, I, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<I, Outgoing, E>,
+    K: Eq + Hash + Clone,
+    F: Fn(&I) -> K,
+    I: Number<Output = N> + Unwrap<Output = Incoming>,
+    for<'a> &'a N: PartialOrd,
+{
+}
+
+fn greater<N>(number: &N, last_number: &N) -> bool
+where
+    for<'a> &'a N: PartialOrd,
+{
+    number > last_number
+}
+
+/// Receiving state shared by [LatestOnly] and [LatestByKeyFn].
+struct Latest<E> {
+    pending_error: Option<E>,
+    terminated: bool,
+}
+
+impl<E> Latest<E> {
+    fn new() -> Self {
+        Latest {
+            pending_error: None,
+            terminated: false,
+        }
+    }
+
+    /// Receive all messages ready in `inner` stream, returning the freshest one.
+    ///
+    /// `fresher` is called for every received message in order - it decides whether
+    /// the message is fresher than all messages before it (and remembers it if so).
+    fn poll_next<S, Item>(
+        &mut self,
+        mut inner: Pin<&mut S>,
+        cx: &mut Context<'_>,
+        mut fresher: impl FnMut(&Item) -> bool,
+    ) -> Poll<Option<Result<Item, E>>>
+    where
+        S: Stream<Item = Result<Item, E>>,
+    {
+        if let Some(error) = self.pending_error.take() {
+            return Poll::Ready(Some(Err(error)));
+        }
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let mut latest = None;
+        loop {
+            match inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(item))) => {
+                    if fresher(&item) {
+                        latest = Some(item);
+                    }
+                }
+                // Messages received before error or closing must still be delivered.
+                Poll::Ready(Some(Err(error))) => {
+                    return if let Some(latest) = latest {
+                        self.pending_error = Some(error);
+                        Poll::Ready(Some(Ok(latest)))
+                    } else {
+                        Poll::Ready(Some(Err(error)))
+                    };
+                }
+                Poll::Ready(None) => {
+                    self.terminated = true;
+                    return Poll::Ready(latest.map(Ok));
+                }
+                Poll::Pending => {
+                    return if let Some(latest) = latest {
+                        Poll::Ready(Some(Ok(latest)))
+                    } else {
+                        Poll::Pending
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_terminated<S>(&self, inner: &S) -> bool
+    where
+        S: FusedStream,
+    {
+        self.pending_error.is_none() && (self.terminated || inner.is_terminated())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use futures::SinkExt;
+    use futures::{stream::FusedStream, SinkExt};
     use mezzenger::Receive;
     use mezzenger_channel::transports;
 
@@ -256,10 +844,30 @@ mod tests {
     wasm_bindgen_test_configure!(run_in_browser);
 
     use crate::{
+        latest_only::{KeyedLatestOnly, LatestByKeyFn, Leftover},
         numbered::{Numbered, Unwrap, Wrapper},
         LatestOnly,
     };
 
+    /// Sensor reading with counter restarting along with the sensor.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Reading {
+        counter: u32,
+        timestamp: u64,
+    }
+
+    fn reading(counter: u32, timestamp: u64) -> Reading {
+        Reading { counter, timestamp }
+    }
+
+    /// Position update of an entity identified with a character.
+    fn position(number: u32, entity: char, position: i32) -> Wrapper<u32, (char, i32)> {
+        Wrapper {
+            number,
+            wrapped: (entity, position),
+        }
+    }
+
     async fn test_transport_inner() {
         let (left, right) = transports();
 
@@ -314,6 +922,30 @@ mod tests {
         assert_eq!(left.receive().await.unwrap().unwrap(), 1);
     }
 
+    async fn test_last_message_before_close_inner() {
+        let (left, right) = transports::<Wrapper<usize, i32>, Wrapper<usize, i32>>();
+
+        let left = Numbered::new_usize(left);
+        let right = Numbered::new_usize(right);
+
+        let mut left = LatestOnly::new(left);
+        let mut right = LatestOnly::new(right);
+
+        left.send(1).await.unwrap();
+        left.send(2).await.unwrap();
+        left.send(3).await.unwrap();
+        left.close().await.unwrap();
+        drop(left);
+
+        assert!(!right.is_terminated());
+        assert_eq!(right.receive().await.unwrap().unwrap(), 3);
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert!(right.is_terminated());
+    }
+
     async fn test_unwrapping_inner() {
         let (left, right) = transports();
 
@@ -336,6 +968,218 @@ mod tests {
         assert_eq!(left.receive().await.unwrap(), 3);
     }
 
+    async fn test_into_inner_inner() {
+        let (left, right) = transports();
+
+        let left = Numbered::new_usize(left);
+        let right = Numbered::new_usize(right);
+
+        let mut left = LatestOnly::new(left).into_unwrapping();
+        let mut right = LatestOnly::new(right).into_unwrapping();
+
+        left.send(1).await.unwrap();
+        left.send(2).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), 2);
+        right.send(1).await.unwrap();
+        left.send(3).await.unwrap();
+
+        // strip both layers mid-conversation
+        let (left, leftover) = left.into_inner();
+        let (mut left, left_number) = left.into_inner();
+        let Leftover {
+            last_number,
+            pending_error,
+            terminated,
+        } = leftover;
+        assert_eq!(last_number, None);
+        assert!(pending_error.is_none());
+        assert!(!terminated);
+        assert_eq!(left_number, 3);
+
+        let (right, leftover) = right.into_inner();
+        let (mut right, right_number) = right.into_inner();
+        assert_eq!(leftover.last_number, Some(1));
+        assert_eq!(right_number, 1);
+
+        // messages sent before unwrapping are still queued in the inner transports
+        assert_eq!(
+            right.receive().await.unwrap(),
+            Wrapper {
+                number: 2,
+                wrapped: 3,
+            }
+        );
+        assert_eq!(
+            left.receive().await.unwrap(),
+            Wrapper {
+                number: 0,
+                wrapped: 1,
+            }
+        );
+
+        for wrapped in 4..7 {
+            left.send(Wrapper {
+                number: wrapped - 1,
+                wrapped,
+            })
+            .await
+            .unwrap();
+        }
+        for wrapped in 4..7 {
+            assert_eq!(right.receive().await.unwrap().unwrap(), wrapped);
+        }
+        right
+            .send(Wrapper {
+                number: right_number,
+                wrapped: 2,
+            })
+            .await
+            .unwrap();
+        assert_eq!(left.receive().await.unwrap().number, 1);
+    }
+
+    async fn test_wrapping_inner() {
+        let (mut left, right) = transports::<(), Wrapper<u32, u32>>();
+        let mut right = LatestOnly::new_wrapping(right).into_unwrapping();
+
+        let batches = [
+            (vec![u32::MAX - 2], u32::MAX - 2),
+            (vec![u32::MAX, u32::MAX - 1], u32::MAX),
+            // freshly wrapped numbers are newer than numbers near maximum
+            (vec![0], 0),
+            (vec![u32::MAX, 2, 1], 2),
+            // numbers from before wrapping are stale
+            (vec![u32::MAX - 1, 3], 3),
+        ];
+        for (numbers, latest) in batches {
+            for number in numbers {
+                left.send(Wrapper {
+                    number,
+                    wrapped: number,
+                })
+                .await
+                .unwrap();
+            }
+            assert_eq!(right.receive().await.unwrap(), latest);
+        }
+        left.close().await.unwrap();
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+
+        // plain comparison treats wrapped numbers as stale
+        let (mut left, right) = transports::<(), Wrapper<u32, u32>>();
+        let mut right = LatestOnly::new(right);
+        for number in [u32::MAX, 0, 1] {
+            left.send(Wrapper {
+                number,
+                wrapped: number,
+            })
+            .await
+            .unwrap();
+        }
+        assert_eq!(right.receive().await.unwrap().number, u32::MAX);
+        assert_eq!(right.last_number(), Some(&u32::MAX));
+    }
+
+    async fn test_by_key_fn_inner() {
+        let (mut left, right) = transports::<(), Reading>();
+        let mut right = LatestByKeyFn::new(right, |a: &Reading, b: &Reading| {
+            a.timestamp.cmp(&b.timestamp)
+        });
+
+        left.send(reading(5, 100)).await.unwrap();
+        left.send(reading(6, 110)).await.unwrap();
+        // sensor restarted - counter starts over
+        left.send(reading(0, 120)).await.unwrap();
+        left.send(reading(1, 130)).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), reading(1, 130));
+
+        // delayed reading from before the restart is stale despite its higher counter
+        left.send(reading(7, 105)).await.unwrap();
+        left.send(reading(2, 140)).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), reading(2, 140));
+
+        // stale readings alone are never delivered
+        left.send(reading(8, 90)).await.unwrap();
+        left.send(reading(3, 150)).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), reading(3, 150));
+        assert_eq!(right.last(), Some(&reading(3, 150)));
+
+        left.send(reading(4, 160)).await.unwrap();
+        left.send(reading(9, 155)).await.unwrap();
+        left.close().await.unwrap();
+        drop(left);
+
+        assert!(!right.is_terminated());
+        assert_eq!(right.receive().await.unwrap(), reading(4, 160));
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert!(right.is_terminated());
+    }
+
+    async fn test_keyed_inner() {
+        let (mut left, right) = transports::<(), Wrapper<u32, (char, i32)>>();
+        let mut right = KeyedLatestOnly::new(right, |message: &Wrapper<u32, (char, i32)>| {
+            message.wrapped.0
+        });
+
+        // every entity numbers its own updates
+        for (number, entity, value) in [(1, 'a', 10), (1, 'b', 20), (2, 'a', 11), (2, 'b', 21)] {
+            left.send(position(number, entity, value)).await.unwrap();
+        }
+        left.send(position(3, 'a', 12)).await.unwrap();
+        // latest message of every key, in order of arrival
+        assert_eq!(right.receive().await.unwrap(), position(2, 'b', 21));
+        assert_eq!(right.receive().await.unwrap(), position(3, 'a', 12));
+        assert_eq!(right.last_number(&'a'), Some(&3));
+        assert_eq!(right.last_number(&'c'), None);
+
+        // stale messages are discarded per key
+        left.send(position(1, 'b', 19)).await.unwrap();
+        left.send(position(4, 'a', 13)).await.unwrap();
+        assert_eq!(right.receive().await.unwrap(), position(4, 'a', 13));
+
+        // messages of different keys don't suppress each other
+        left.send(position(10, 'a', 14)).await.unwrap();
+        left.send(position(3, 'b', 22)).await.unwrap();
+        left.send(position(2, 'a', 9)).await.unwrap();
+        left.send(position(1, 'c', 30)).await.unwrap();
+        left.close().await.unwrap();
+        drop(left);
+
+        assert!(!right.is_terminated());
+        assert_eq!(right.receive().await.unwrap(), position(10, 'a', 14));
+        assert_eq!(right.receive().await.unwrap(), position(3, 'b', 22));
+        assert_eq!(right.receive().await.unwrap(), position(1, 'c', 30));
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert!(right.is_terminated());
+    }
+
+    async fn test_keyed_unwrapping_inner() {
+        let (left, right) = transports::<Wrapper<u32, ()>, Wrapper<u32, (char, i32)>>();
+
+        let mut left = Numbered::new_starting_at(left, 0u32);
+        let mut right =
+            KeyedLatestOnly::new_wrapping(right, |message: &Wrapper<u32, (char, i32)>| {
+                message.wrapped.0
+            })
+            .into_unwrapping();
+
+        for (entity, value) in [('a', 1), ('b', 2), ('a', 3), ('b', 4), ('a', 5)] {
+            left.send((entity, value)).await.unwrap();
+        }
+        assert_eq!(right.receive().await.unwrap(), ('b', 4));
+        assert_eq!(right.receive().await.unwrap(), ('a', 5));
+        assert_eq!(right.last_number(&'b'), Some(&3));
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[tokio::test]
     async fn test_transport() {
@@ -348,6 +1192,18 @@ mod tests {
         test_transport_inner().await
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_last_message_before_close() {
+        test_last_message_before_close_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_last_message_before_close() {
+        test_last_message_before_close_inner().await
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[tokio::test]
     async fn test_unwrapping() {
@@ -371,4 +1227,64 @@ mod tests {
     async fn test_order() {
         test_order_inner().await
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_into_inner() {
+        test_into_inner_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_into_inner() {
+        test_into_inner_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_wrapping() {
+        test_wrapping_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_wrapping() {
+        test_wrapping_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_by_key_fn() {
+        test_by_key_fn_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_by_key_fn() {
+        test_by_key_fn_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_keyed() {
+        test_keyed_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_keyed() {
+        test_keyed_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_keyed_unwrapping() {
+        test_keyed_unwrapping_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_keyed_unwrapping() {
+        test_keyed_unwrapping_inner().await
+    }
 }
@@ -1,13 +1,20 @@
 //! Utilities for [mezzenger](https://github.com/zduny/mezzenger).
 
+pub mod close_once;
+pub use close_once::CloseOnce;
+
 #[cfg(feature = "inspector")]
 pub mod inspector;
 
-#[cfg(feature = "splitter")]
+#[cfg(feature = "split")]
 pub mod split;
+#[cfg(feature = "split")]
+pub use split::Splitter;
 
-#[cfg(feature = "merger")]
+#[cfg(feature = "merge")]
 pub mod merge;
+#[cfg(feature = "merge")]
+pub use merge::Merger;
 
 #[cfg(feature = "numbered")]
 pub mod numbered;
@@ -23,4 +30,41 @@ pub mod reliable;
 #[cfg(feature = "last_only")]
 pub mod latest_only;
 #[cfg(feature = "last_only")]
-pub use latest_only::LatestOnly;
+pub use latest_only::{KeyedLatestOnly, LatestByKeyFn, LatestOnly};
+
+#[cfg(feature = "loss")]
+pub mod loss;
+#[cfg(feature = "loss")]
+pub use loss::LossAccounting;
+
+#[cfg(feature = "rpc")]
+pub mod rpc;
+
+#[cfg(feature = "shared")]
+pub mod shared;
+#[cfg(feature = "shared")]
+pub use shared::{SharedEncoded, SharedNumbered};
+
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "crypto")]
+pub use crypto::Encrypted;
+
+#[cfg(feature = "multiplex")]
+pub mod multiplex;
+#[cfg(feature = "multiplex")]
+pub use multiplex::{ChannelTransport, Multiplexer};
+
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
+#[cfg(feature = "watchdog")]
+pub use watchdog::Watchdog;
+
+#[cfg(feature = "framing")]
+pub mod framing;
+#[cfg(feature = "framing")]
+pub use framing::JsonLines;
+
+pub mod wire;
+#[cfg(feature = "wire-vectors")]
+pub mod wire_vectors;
@@ -0,0 +1,700 @@
+//! Wrapper transport estimating message loss of a [numbered] transport.
+//!
+//! Number of messages sent by the other side is inferred from the range of received
+//! message numbers (taking their wraparound into account, see [SequenceDistance]) and
+//! compared with the number of messages actually received - duplicates are counted only once.
+//!
+//! Senders restarting their message counter (for example after reconnecting) can be
+//! told apart with a generation extracted from messages (see [LossAccounting::new_with_generation]).
+//!
+//! Accounting doesn't alter received messages, so it composes with other wrappers -
+//! wrapping it with [LatestOnly] estimates loss of all messages, before old ones are discarded:
+//!
+//! ```ignore
+//! let mut transport = LossAccounting::new(Numbered::new_u32(transport));
+//! let mut summaries = transport.summaries(100);
+//! let transport = LatestOnly::new_wrapping(transport);
+//! ```
+//!
+//! [numbered]: crate::numbered::Number
+//! [LatestOnly]: crate::LatestOnly
+
+use std::{
+    collections::BTreeSet,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{channel::mpsc, stream::FusedStream, Sink, Stream};
+use pin_project::pin_project;
+
+use crate::{
+    numbered::{Number, SequenceDistance},
+    CloseOnce,
+};
+
+/// Default number of most recent message numbers recent loss is computed over
+/// (see [LossAccounting::set_window]).
+pub const DEFAULT_WINDOW: u64 = 1024;
+
+/// Snapshot of loss accounting (see [LossAccounting::summary]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LossSummary {
+    /// Generation of the latest accounted message.
+    pub generation: u64,
+
+    /// Number of distinct messages received in time (excluding duplicates and late messages).
+    pub received: u64,
+
+    /// Number of messages inferred to be sent by the other side.
+    pub expected: u64,
+
+    /// Number of duplicated messages received.
+    pub duplicates: u64,
+
+    /// Number of messages received too late to be accounted for - numbered lower than
+    /// the window of most recent numbers or belonging to an older generation.<br>
+    /// Late messages are counted as lost.
+    pub late: u64,
+
+    /// Number of distinct messages received in time within the window of most recent
+    /// message numbers.
+    pub recent_received: u64,
+
+    /// Number of messages inferred to be sent within the window of most recent message numbers.
+    pub recent_expected: u64,
+}
+
+impl LossSummary {
+    /// Number of messages inferred to be lost.
+    pub fn lost(&self) -> u64 {
+        self.expected.saturating_sub(self.received)
+    }
+
+    /// Ratio of lost messages to all messages inferred to be sent (`0.0` if none was received yet).
+    pub fn loss_ratio(&self) -> f64 {
+        ratio(self.received, self.expected)
+    }
+
+    /// Like [loss_ratio](LossSummary::loss_ratio) but only within the window of most recent
+    /// message numbers.
+    pub fn recent_loss_ratio(&self) -> f64 {
+        ratio(self.recent_received, self.recent_expected)
+    }
+}
+
+fn ratio(received: u64, expected: u64) -> f64 {
+    if expected == 0 {
+        0.0
+    } else {
+        expected.saturating_sub(received) as f64 / expected as f64
+    }
+}
+
+/// Wrapper transport estimating message loss of a [numbered] transport.
+///
+/// Received messages are returned unchanged.
+///
+/// [numbered]: crate::numbered::Number
+#[pin_project]
+pub struct LossAccounting<T, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    Incoming: Number<Output = N>,
+    N: SequenceDistance,
+{
+    #[pin]
+    inner: T,
+    generation: fn(&Incoming) -> u64,
+    step: u64,
+    window: u64,
+    accounting: Accounting<N>,
+    summaries: Option<Summaries>,
+    close_once: CloseOnce,
+    _error: PhantomData<E>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<T, E, N, Incoming, Outgoing> LossAccounting<T, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    Incoming: Number<Output = N>,
+    N: SequenceDistance,
+{
+    /// Wrap a provided [numbered] transport estimating its message loss.
+    ///
+    /// [numbered]: crate::numbered::Number
+    pub fn new(transport: T) -> Self {
+        LossAccounting {
+            inner: transport,
+            generation: |_| 0,
+            step: 1,
+            window: DEFAULT_WINDOW,
+            accounting: Accounting::new(),
+            summaries: None,
+            close_once: CloseOnce::new(),
+            _error: PhantomData,
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// Like [new](LossAccounting::new) but telling apart senders restarting their message
+    /// counter with a generation extracted from messages.
+    ///
+    /// Message of a higher generation starts accounting anew (counts accumulated so far are kept),
+    /// messages of older generations are counted as late.
+    pub fn new_with_generation(transport: T, generation: fn(&Incoming) -> u64) -> Self {
+        LossAccounting {
+            generation,
+            ..LossAccounting::new(transport)
+        }
+    }
+
+    /// Set step between consecutive message numbers (see [Numbered::set_step]).
+    ///
+    /// Default step is `1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is zero.
+    ///
+    /// [Numbered::set_step]: crate::Numbered::set_step
+    pub fn set_step(&mut self, step: u64) {
+        assert!(step > 0, "step must be greater than zero");
+        self.step = step;
+    }
+
+    /// Set number of most recent message numbers recent loss is computed over.
+    ///
+    /// Duplicates are detected only within the window - messages numbered lower are counted as late.
+    ///
+    /// Default window is [DEFAULT_WINDOW].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn set_window(&mut self, window: u64) {
+        assert!(window > 0, "window must be greater than zero");
+        self.window = window;
+        self.accounting.prune(window);
+    }
+
+    /// Return a stream of [summaries](LossSummary), one every `period` received messages
+    /// (duplicates and late messages included).
+    ///
+    /// A final summary is sent once inner transport's stream ends - the summary stream ends right after.<br>
+    /// Calling this method again replaces previously returned stream (ending it).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn summaries(&mut self, period: u64) -> mpsc::UnboundedReceiver<LossSummary> {
+        assert!(period > 0, "period must be greater than zero");
+        let (sender, receiver) = mpsc::unbounded();
+        self.summaries = Some(Summaries {
+            sender,
+            period,
+            counter: 0,
+        });
+        receiver
+    }
+
+    /// Current loss accounting summary.
+    pub fn summary(&self) -> LossSummary {
+        self.accounting.summary(self.window)
+    }
+
+    /// Ratio of lost messages to all messages inferred to be sent (`0.0` if none was received yet).
+    pub fn loss_ratio(&self) -> f64 {
+        self.summary().loss_ratio()
+    }
+
+    /// Ratio of lost messages within the window of most recent message numbers
+    /// (see [set_window](LossAccounting::set_window)).
+    pub fn recent_loss_ratio(&self) -> f64 {
+        self.summary().recent_loss_ratio()
+    }
+
+    /// Number of distinct messages received in time.
+    pub fn received(&self) -> u64 {
+        self.summary().received
+    }
+
+    /// Number of messages inferred to be sent by the other side.
+    pub fn expected(&self) -> u64 {
+        self.summary().expected
+    }
+
+    /// Number of duplicated messages received.
+    pub fn duplicates(&self) -> u64 {
+        self.accounting.duplicates
+    }
+
+    /// Unwrap inner transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+struct Summaries {
+    sender: mpsc::UnboundedSender<LossSummary>,
+    period: u64,
+    counter: u64,
+}
+
+struct Accounting<N> {
+    generation: u64,
+    highest_number: Option<N>,
+    highest: i64,
+    lowest: i64,
+    seen: BTreeSet<i64>,
+    received: u64,
+    past_received: u64,
+    past_expected: u64,
+    duplicates: u64,
+    late: u64,
+}
+
+impl<N> Accounting<N>
+where
+    N: SequenceDistance,
+{
+    fn new() -> Self {
+        Accounting {
+            generation: 0,
+            highest_number: None,
+            highest: 0,
+            lowest: 0,
+            seen: BTreeSet::new(),
+            received: 0,
+            past_received: 0,
+            past_expected: 0,
+            duplicates: 0,
+            late: 0,
+        }
+    }
+
+    fn expected(&self) -> u64 {
+        if self.highest_number.is_some() {
+            self.highest.abs_diff(self.lowest) + 1
+        } else {
+            0
+        }
+    }
+
+    fn restart(&mut self, generation: u64) {
+        self.past_received += self.received;
+        self.past_expected += self.expected();
+        self.generation = generation;
+        self.highest_number = None;
+        self.highest = 0;
+        self.lowest = 0;
+        self.seen.clear();
+        self.received = 0;
+    }
+
+    fn record(&mut self, number: N, generation: u64, step: u64, window: u64) {
+        if generation < self.generation {
+            self.late += 1;
+            return;
+        }
+        if generation > self.generation {
+            self.restart(generation);
+        }
+
+        let offset = match &self.highest_number {
+            Some(highest_number) => {
+                let distance = number.distance_from(highest_number) / step as i64;
+                self.highest.saturating_add(distance)
+            }
+            None => {
+                self.highest_number = Some(number);
+                self.seen.insert(0);
+                self.received += 1;
+                return;
+            }
+        };
+
+        if offset <= self.highest.saturating_sub_unsigned(window) {
+            self.late += 1;
+            return;
+        }
+        if !self.seen.insert(offset) {
+            self.duplicates += 1;
+            return;
+        }
+
+        self.received += 1;
+        if offset > self.highest {
+            self.highest = offset;
+            self.highest_number = Some(number);
+            self.prune(window);
+        }
+        self.lowest = self.lowest.min(offset);
+    }
+
+    fn prune(&mut self, window: u64) {
+        let oldest = self.highest.saturating_sub_unsigned(window);
+        self.seen = self.seen.split_off(&oldest.saturating_add(1));
+    }
+
+    fn summary(&self, window: u64) -> LossSummary {
+        LossSummary {
+            generation: self.generation,
+            received: self.past_received + self.received,
+            expected: self.past_expected + self.expected(),
+            duplicates: self.duplicates,
+            late: self.late,
+            recent_received: self.seen.len() as u64,
+            recent_expected: self.expected().min(window),
+        }
+    }
+}
+
+impl<T, E, N, Incoming, Outgoing> Sink<Outgoing> for LossAccounting<T, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    Incoming: Number<Output = N>,
+    N: SequenceDistance,
+{
+    type Error = mezzenger::Error<E>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let me = self.project();
+        me.inner.start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let me = self.project();
+        me.close_once.poll_close(|| me.inner.poll_close(cx))
+    }
+}
+
+impl<T, E, N, Incoming, Outgoing> Stream for LossAccounting<T, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+    Incoming: Number<Output = N>,
+    N: SequenceDistance,
+{
+    type Item = Result<Incoming, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let me = self.project();
+        let item = me.inner.poll_next(cx);
+        match &item {
+            Poll::Ready(Some(Ok(message))) => {
+                let generation = (me.generation)(message);
+                me.accounting
+                    .record(message.number(), generation, *me.step, *me.window);
+                if let Some(summaries) = me.summaries {
+                    summaries.counter += 1;
+                    if summaries.counter == summaries.period {
+                        summaries.counter = 0;
+                        let _ = summaries
+                            .sender
+                            .unbounded_send(me.accounting.summary(*me.window));
+                    }
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(summaries) = me.summaries.take() {
+                    let _ = summaries
+                        .sender
+                        .unbounded_send(me.accounting.summary(*me.window));
+                }
+            }
+            _ => {}
+        }
+        item
+    }
+}
+
+impl<T, E, N, Incoming, Outgoing> FusedStream for LossAccounting<T, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + FusedStream,
+    Incoming: Number<Output = N>,
+    N: SequenceDistance,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+impl<T, E, N, Incoming, Outgoing> mezzenger::Order for LossAccounting<T, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + mezzenger::Order,
+    Incoming: Number<Output = N>,
+    N: SequenceDistance,
+{
+}
+
+impl<T, E, N, Incoming, Outgoing> mezzenger::Reliable
+    for LossAccounting<T, E, N, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + mezzenger::Reliable,
+    Incoming: Number<Output = N>,
+    N: SequenceDistance,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+    use mezzenger::Receive;
+    use mezzenger_channel::transports;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use crate::{loss::LossSummary, numbered::Wrapper, LossAccounting};
+
+    fn numbered<N>(number: N) -> Wrapper<N, ()> {
+        Wrapper {
+            number,
+            wrapped: (),
+        }
+    }
+
+    async fn test_loss_inner() {
+        let (mut left, right) = transports::<(), _>();
+        let mut right = LossAccounting::new(right);
+
+        for number in [0u32, 1, 3, 3, 4, 7, 6, 9] {
+            left.send(numbered(number)).await.unwrap();
+        }
+        for _ in 0..8 {
+            right.receive().await.unwrap();
+        }
+
+        assert_eq!(right.expected(), 10);
+        assert_eq!(right.received(), 7);
+        assert_eq!(right.duplicates(), 1);
+        assert_eq!(right.summary().lost(), 3);
+        assert!((right.loss_ratio() - 0.3).abs() < f64::EPSILON);
+
+        // reordered message arriving late fills the gap
+        left.send(numbered(2)).await.unwrap();
+        left.send(numbered(2)).await.unwrap();
+        right.receive().await.unwrap();
+        right.receive().await.unwrap();
+
+        assert_eq!(right.expected(), 10);
+        assert_eq!(right.received(), 8);
+        assert_eq!(right.duplicates(), 2);
+        assert!((right.loss_ratio() - 0.2).abs() < f64::EPSILON);
+    }
+
+    async fn test_wraparound_inner() {
+        let (mut left, right) = transports::<(), _>();
+        let mut right = LossAccounting::new(right);
+
+        for number in [250u8, 252, 253, 255, 0, 0, 2, 3] {
+            left.send(numbered(number)).await.unwrap();
+        }
+        for _ in 0..8 {
+            right.receive().await.unwrap();
+        }
+
+        assert_eq!(right.expected(), 10);
+        assert_eq!(right.received(), 7);
+        assert_eq!(right.duplicates(), 1);
+    }
+
+    async fn test_step_inner() {
+        let (mut left, right) = transports::<(), _>();
+        let mut right = LossAccounting::new(right);
+        right.set_step(2);
+
+        for number in [0u32, 2, 6, 8] {
+            left.send(numbered(number)).await.unwrap();
+        }
+        for _ in 0..4 {
+            right.receive().await.unwrap();
+        }
+
+        assert_eq!(right.expected(), 5);
+        assert_eq!(right.received(), 4);
+    }
+
+    async fn test_generation_inner() {
+        let (mut left, right) = transports::<(), _>();
+        let mut right =
+            LossAccounting::new_with_generation(right, |message: &Wrapper<u32, u64>| {
+                message.wrapped
+            });
+
+        let message = |number, generation| Wrapper {
+            number,
+            wrapped: generation,
+        };
+
+        // sender restarting its counter
+        for (number, generation) in [(0u32, 0u64), (1, 0), (3, 0), (0, 1), (2, 1), (4, 0)] {
+            left.send(message(number, generation)).await.unwrap();
+        }
+        for _ in 0..6 {
+            right.receive().await.unwrap();
+        }
+
+        assert_eq!(
+            right.summary(),
+            LossSummary {
+                generation: 1,
+                received: 5,
+                expected: 7,
+                duplicates: 0,
+                late: 1,
+                recent_received: 2,
+                recent_expected: 3,
+            }
+        );
+    }
+
+    async fn test_window_inner() {
+        let (mut left, right) = transports::<(), _>();
+        let mut right = LossAccounting::new(right);
+        right.set_window(4);
+
+        for number in [0u32, 1, 2, 3, 4, 5, 6, 7, 9, 1, 8] {
+            left.send(numbered(number)).await.unwrap();
+        }
+        for _ in 0..11 {
+            right.receive().await.unwrap();
+        }
+
+        let summary = right.summary();
+        assert_eq!(summary.expected, 10);
+        assert_eq!(summary.received, 10);
+        assert_eq!(summary.late, 1);
+        assert_eq!(summary.duplicates, 0);
+        assert_eq!(summary.loss_ratio(), 0.0);
+
+        for number in [11u32, 13, 14] {
+            left.send(numbered(number)).await.unwrap();
+        }
+        for _ in 0..3 {
+            right.receive().await.unwrap();
+        }
+
+        let summary = right.summary();
+        assert_eq!(summary.expected, 15);
+        assert_eq!(summary.received, 13);
+        assert_eq!(summary.recent_expected, 4);
+        assert_eq!(summary.recent_received, 3);
+        assert!((summary.recent_loss_ratio() - 0.25).abs() < f64::EPSILON);
+    }
+
+    #[cfg(feature = "last_only")]
+    async fn test_latest_only_inner() {
+        use futures::StreamExt;
+
+        use crate::LatestOnly;
+
+        let (mut left, right) = transports::<(), _>();
+        let mut right = LossAccounting::new(right);
+        let summaries = right.summaries(3);
+        let mut right = LatestOnly::new_wrapping(right);
+
+        for number in [0u32, 2, 2, 1, 4, 5] {
+            left.send(numbered(number)).await.unwrap();
+        }
+
+        assert_eq!(right.receive().await.unwrap().number, 5);
+        left.close().await.unwrap();
+        assert!(right.next().await.is_none());
+
+        let summaries: Vec<_> = summaries.collect().await;
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].expected, 3);
+        assert_eq!(summaries[0].received, 2);
+        assert_eq!(summaries[1].expected, 6);
+        assert_eq!(summaries[1].received, 5);
+        assert_eq!(summaries[1].duplicates, 1);
+        assert_eq!(summaries[2], summaries[1]);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_loss() {
+        test_loss_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_loss() {
+        test_loss_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_wraparound() {
+        test_wraparound_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_wraparound() {
+        test_wraparound_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_step() {
+        test_step_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_step() {
+        test_step_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_generation() {
+        test_generation_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_generation() {
+        test_generation_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_window() {
+        test_window_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_window() {
+        test_window_inner().await
+    }
+
+    #[cfg(all(feature = "last_only", not(target_arch = "wasm32")))]
+    #[tokio::test]
+    async fn test_latest_only() {
+        test_latest_only_inner().await
+    }
+
+    #[cfg(all(feature = "last_only", target_arch = "wasm32"))]
+    #[wasm_bindgen_test]
+    async fn test_latest_only() {
+        test_latest_only_inner().await
+    }
+}
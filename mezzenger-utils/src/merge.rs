@@ -1 +1,401 @@
-//! *Work in progress*.
+//! Combining many transports sharing the same `Incoming` type into a single transport.
+//!
+//! [Merger] receives messages of all its transports - polling them in round-robin
+//! fashion, so a transport flooded with messages doesn't starve others.<br>
+//! Merged stream ends once all transports end.
+//!
+//! Sent messages (if `Outgoing` is [Clone]) are fanned out to all transports.
+
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures::{stream::FusedStream, Sink, SinkExt, Stream, StreamExt};
+
+/// Transport receiving messages of many transports.
+///
+/// Transports are polled fairly - every poll starts with the transport following
+/// the one that provided the previous message.<br>
+/// Transport whose stream ended is skipped, merged stream ends once all transports end.
+///
+/// Messages sent through it are sent to all transports (requires `Outgoing` to be [Clone]).
+pub struct Merger<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+{
+    transports: Vec<T>,
+    terminated: Vec<bool>,
+    next: usize,
+    _error: PhantomData<E>,
+    _incoming: PhantomData<Incoming>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+// Transports are never pinned - message and error types don't matter.
+impl<T, E, Incoming, Outgoing> Unpin for Merger<T, E, Incoming, Outgoing> where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin
+{
+}
+
+impl<T, E, Incoming, Outgoing> Merger<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+{
+    /// Merge provided transports.
+    pub fn new(transports: Vec<T>) -> Self {
+        let terminated = vec![false; transports.len()];
+        Merger {
+            transports,
+            terminated,
+            next: 0,
+            _error: PhantomData,
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// Return a [tagged] transport.
+    ///
+    /// By default [`Merger`] stream returns messages in their original form.<br>
+    /// [`MergerTagged`] returns messages (and errors) along with index of the transport
+    /// they were received from.
+    ///
+    /// [tagged]: MergerTagged
+    pub fn into_tagged(self) -> MergerTagged<T, E, Incoming, Outgoing> {
+        MergerTagged { inner: self }
+    }
+
+    /// Number of merged transports (including ones whose stream ended).
+    pub fn len(&self) -> usize {
+        self.transports.len()
+    }
+
+    /// Whether there are no merged transports.
+    pub fn is_empty(&self) -> bool {
+        self.transports.is_empty()
+    }
+
+    /// Number of transports whose stream didn't end yet.
+    pub fn active(&self) -> usize {
+        self.terminated
+            .iter()
+            .filter(|terminated| !**terminated)
+            .count()
+    }
+
+    /// Get reference to transport with provided index.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.transports.get(index)
+    }
+
+    /// Get mutable reference to transport with provided index
+    /// (for example to send a message to that transport only).
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.transports.get_mut(index)
+    }
+
+    /// Unwrap merged transports.
+    pub fn into_inner(self) -> Vec<T> {
+        self.transports
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll_next_tagged(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<(usize, Incoming), (usize, E)>>> {
+        let count = self.transports.len();
+        let start = self.next;
+        for offset in 0..count {
+            let index = (start + offset) % count;
+            if self.terminated[index] {
+                continue;
+            }
+            match self.transports[index].poll_next_unpin(cx) {
+                Poll::Ready(Some(result)) => {
+                    self.next = (index + 1) % count;
+                    return Poll::Ready(Some(
+                        result
+                            .map(|message| (index, message))
+                            .map_err(|error| (index, error)),
+                    ));
+                }
+                Poll::Ready(None) => self.terminated[index] = true,
+                Poll::Pending => {}
+            }
+        }
+        if self.active() == 0 {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Poll all transports with `poll`, returning first error.
+    fn poll_all(
+        &mut self,
+        mut poll: impl FnMut(&mut T) -> Poll<Result<(), mezzenger::Error<E>>>,
+    ) -> Poll<Result<(), mezzenger::Error<E>>> {
+        let mut ready = true;
+        for transport in self.transports.iter_mut() {
+            match poll(transport) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => ready = false,
+            }
+        }
+        if ready {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T, E, Incoming, Outgoing> Sink<Outgoing> for Merger<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+    Outgoing: Clone,
+{
+    type Error = mezzenger::Error<E>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut()
+            .poll_all(|transport| transport.poll_ready_unpin(cx))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let me = self.get_mut();
+        if let Some((last, transports)) = me.transports.split_last_mut() {
+            for transport in transports {
+                transport.start_send_unpin(item.clone())?;
+            }
+            last.start_send_unpin(item)?;
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut()
+            .poll_all(|transport| transport.poll_flush_unpin(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut()
+            .poll_all(|transport| transport.poll_close_unpin(cx))
+    }
+}
+
+impl<T, E, Incoming, Outgoing> Stream for Merger<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+{
+    type Item = Result<Incoming, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let result = ready!(self.get_mut().poll_next_tagged(cx));
+        Poll::Ready(result.map(|result| {
+            result
+                .map(|(_, message)| message)
+                .map_err(|(_, error)| error)
+        }))
+    }
+}
+
+impl<T, E, Incoming, Outgoing> FusedStream for Merger<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+{
+    fn is_terminated(&self) -> bool {
+        self.active() == 0
+    }
+}
+
+impl<T, E, Incoming, Outgoing> mezzenger::Reliable for Merger<T, E, Incoming, Outgoing> where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + mezzenger::Reliable + Unpin
+{
+}
+
+/// Transport returned by [`Merger::into_tagged`] method.
+///
+/// Received messages and errors are paired with index of the transport
+/// they were received from.
+pub struct MergerTagged<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+{
+    inner: Merger<T, E, Incoming, Outgoing>,
+}
+
+impl<T, E, Incoming, Outgoing> MergerTagged<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+{
+    /// Number of transports whose stream didn't end yet.
+    pub fn active(&self) -> usize {
+        self.inner.active()
+    }
+
+    /// Get mutable reference to transport with provided index
+    /// (for example to send a message to that transport only).
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.inner.get_mut(index)
+    }
+
+    /// Unwrap merged transports.
+    pub fn into_inner(self) -> Vec<T> {
+        self.inner.into_inner()
+    }
+}
+
+impl<T, E, Incoming, Outgoing> Sink<Outgoing> for MergerTagged<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+    Outgoing: Clone,
+{
+    type Error = mezzenger::Error<E>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        self.inner.start_send_unpin(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+}
+
+impl<T, E, Incoming, Outgoing> Stream for MergerTagged<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+{
+    type Item = Result<(usize, Incoming), (usize, E)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_tagged(cx)
+    }
+}
+
+impl<T, E, Incoming, Outgoing> FusedStream for MergerTagged<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + Unpin,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+impl<T, E, Incoming, Outgoing> mezzenger::Reliable for MergerTagged<T, E, Incoming, Outgoing> where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + mezzenger::Reliable + Unpin
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{stream::FusedStream, FutureExt, SinkExt, StreamExt};
+    use mezzenger::Receive;
+    use mezzenger_channel::transports;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use super::Merger;
+
+    async fn test_merger_inner() {
+        let (mut senders, receivers): (Vec<_>, Vec<_>) =
+            (0..3).map(|_| transports::<String, u32>()).unzip();
+        let mut merger = Merger::new(receivers);
+        assert_eq!(merger.len(), 3);
+
+        // flooding transport doesn't starve others
+        for message in 0..10 {
+            senders[0].send(message).await.unwrap();
+        }
+        senders[1].send(100).await.unwrap();
+        senders[2].send(200).await.unwrap();
+        let mut received = vec![];
+        for _ in 0..4 {
+            received.push(merger.receive().await.unwrap());
+        }
+        assert_eq!(received, vec![0, 100, 200, 1]);
+
+        // fan out
+        merger.send("Hello".to_string()).await.unwrap();
+        for sender in senders.iter_mut() {
+            assert_eq!(sender.receive().await.unwrap(), "Hello");
+        }
+
+        // ended transports are skipped
+        let mut first = senders.remove(0);
+        first.close().await.unwrap();
+        drop(first);
+        for message in 2..10 {
+            assert_eq!(merger.receive().await.unwrap(), message);
+        }
+        senders[1].send(201).await.unwrap();
+        assert_eq!(merger.receive().await.unwrap(), 201);
+        assert!(merger.next().now_or_never().is_none());
+        assert_eq!(merger.active(), 2);
+
+        drop(senders);
+        assert!(!merger.is_terminated());
+        assert!(matches!(
+            merger.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert_eq!(merger.active(), 0);
+        assert!(merger.is_terminated());
+    }
+
+    async fn test_tagged_inner() {
+        let (mut senders, receivers): (Vec<_>, Vec<_>) =
+            (0..2).map(|_| transports::<(), u32>()).unzip();
+        let mut merger = Merger::new(receivers).into_tagged();
+
+        senders[1].send(1).await.unwrap();
+        senders[1].send(2).await.unwrap();
+        senders[0].send(3).await.unwrap();
+        assert_eq!(merger.receive().await.unwrap(), (0, 3));
+        assert_eq!(merger.receive().await.unwrap(), (1, 1));
+        assert_eq!(merger.receive().await.unwrap(), (1, 2));
+
+        merger.get_mut(1).unwrap().send(()).await.unwrap();
+        assert_eq!(senders[1].receive().await.unwrap(), ());
+        assert!(senders[0].next().now_or_never().is_none());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_merger() {
+        test_merger_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_merger() {
+        test_merger_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_tagged() {
+        test_tagged_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_tagged() {
+        test_tagged_inner().await
+    }
+}
@@ -0,0 +1,634 @@
+//! Independent logical channels multiplexed over a single transport.
+//!
+//! [`Multiplexer`] wraps a transport sending [`Channeled`] envelopes and opens
+//! [`ChannelTransport`] handles, each with its own message types (encoded
+//! into envelope payload with provided codec).
+//!
+//! Incoming messages are queued per channel (up to [CHANNEL_BUFFER] messages),
+//! so a channel that isn't polled doesn't block other channels.<br>
+//! Messages arriving for a channel with full queue are discarded - its stream
+//! yields [Error::Overflow] (after already queued messages) to signal the loss.<br>
+//! Outgoing messages are queued per channel (up to [CHANNEL_BUFFER] messages)
+//! and written to the transport round-robin, so a channel sending bulk data
+//! doesn't starve other channels.
+//!
+//! Messages arriving for a channel that wasn't opened yet or was closed (or dropped)
+//! are discarded - open channels on both sides before using them.
+//!
+//! **NOTE**: Transport is read by whichever channel is polled - incoming messages
+//! are dispatched only while at least one channel is polled.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{Debug, Display},
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use futures::{
+    future::poll_fn, ready, stream::FusedStream, task::waker, Sink, SinkExt, Stream, StreamExt,
+};
+use kodec::{Decode, Encode};
+use mezzenger::WakerSet;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of messages queued by a single channel in each direction.
+///
+/// Channel stops accepting new outgoing messages and discards new incoming
+/// messages once its queue is full.
+pub const CHANNEL_BUFFER: usize = 16;
+
+/// Envelope sent through multiplexed transport.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Channeled {
+    /// Channel id.
+    pub channel: u16,
+
+    /// Message encoded with channel's codec.
+    pub payload: Vec<u8>,
+}
+
+/// Error of [`ChannelTransport`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<E, SerializationError, DeserializationError> {
+    /// Message couldn't be encoded.
+    SerializationError(SerializationError),
+    /// Received message couldn't be decoded.
+    DeserializationError(DeserializationError),
+    /// Incoming messages were discarded because channel's queue was full.
+    Overflow,
+    /// Inner transport error.
+    Other(E),
+}
+
+impl<E, SerializationError, DeserializationError> Display
+    for Error<E, SerializationError, DeserializationError>
+where
+    E: Display,
+    SerializationError: Display,
+    DeserializationError: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::SerializationError(error) => write!(f, "failed to serialize message: {error}"),
+            Error::DeserializationError(error) => {
+                write!(f, "failed to deserialize message: {error}")
+            }
+            Error::Overflow => write!(f, "incoming messages discarded due to full queue"),
+            Error::Other(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl<E, SerializationError, DeserializationError> std::error::Error
+    for Error<E, SerializationError, DeserializationError>
+where
+    E: Debug + Display,
+    SerializationError: Debug + Display,
+    DeserializationError: Debug + Display,
+{
+}
+
+/// Error of [`ChannelTransport`] using provided codec.
+pub type ChannelError<E, Codec> = Error<E, <Codec as Encode>::Error, <Codec as Decode>::Error>;
+
+#[derive(Default)]
+struct Channel {
+    incoming: VecDeque<Vec<u8>>,
+    outgoing: VecDeque<Vec<u8>>,
+    /// Were incoming messages discarded since the last reported overflow.
+    overflowed: bool,
+    opened: bool,
+    closed: bool,
+}
+
+struct Shared<T, E>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E>,
+{
+    transport: T,
+    channels: HashMap<u16, Channel>,
+    /// Channels with queued outgoing messages, in order they will be sent.
+    schedule: VecDeque<u16>,
+    receivers: Arc<WakerSet<u16>>,
+    receive_waker: Waker,
+    senders: Arc<WakerSet<u16>>,
+    send_waker: Waker,
+    closed: bool,
+    _error: PhantomData<E>,
+}
+
+impl<T, E> Shared<T, E>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+{
+    fn close(&mut self) {
+        self.closed = true;
+        self.receivers.wake_all();
+        self.senders.wake_all();
+    }
+
+    fn is_closed(&self, channel: u16) -> bool {
+        self.closed || self.channels[&channel].closed
+    }
+
+    /// Write queued messages to the transport, round-robin.
+    ///
+    /// Stops once `channel`'s queue is empty (or all queues are empty if `None`).
+    fn poll_send(&mut self, channel: Option<u16>) -> Poll<Result<(), mezzenger::Error<E>>> {
+        let send_waker = self.send_waker.clone();
+        let mut cx = Context::from_waker(&send_waker);
+        let mut progress = false;
+        let result = loop {
+            let done = match channel {
+                Some(channel) => self.channels[&channel].outgoing.is_empty(),
+                None => self.schedule.is_empty(),
+            };
+            if done {
+                break Poll::Ready(Ok(()));
+            }
+            match self.transport.poll_ready_unpin(&mut cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(error)) => break Poll::Ready(Err(error)),
+                Poll::Pending => break Poll::Pending,
+            }
+            let id = self.schedule.pop_front().expect("schedule is empty");
+            let queued = self.channels.get_mut(&id).unwrap();
+            let payload = queued
+                .outgoing
+                .pop_front()
+                .expect("channel has no messages");
+            if !queued.outgoing.is_empty() {
+                self.schedule.push_back(id);
+            }
+            progress = true;
+            let message = Channeled {
+                channel: id,
+                payload,
+            };
+            if let Err(error) = self.transport.start_send_unpin(message) {
+                break Poll::Ready(Err(error));
+            }
+        };
+        if progress {
+            self.senders.wake_all();
+        }
+        result
+    }
+}
+
+type SharedRef<T, E> = Arc<Mutex<Shared<T, E>>>;
+
+/// Multiplexer of logical channels over a single transport.
+///
+/// See [module documentation](self) for details.
+///
+/// **NOTE**: Dropping multiplexer doesn't close opened channels,
+/// use [Multiplexer::close] to close the transport and terminate all of them.
+pub struct Multiplexer<T, E, Codec>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E>,
+{
+    shared: SharedRef<T, E>,
+    codec: Codec,
+}
+
+impl<T, E, Codec> Multiplexer<T, E, Codec>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+    Codec: kodec::Codec + Clone,
+{
+    /// Create new multiplexer over provided transport.
+    ///
+    /// Channel messages are encoded with provided codec.
+    pub fn new(transport: T, codec: Codec) -> Self {
+        let receivers = Arc::new(WakerSet::default());
+        let senders = Arc::new(WakerSet::default());
+        let shared = Shared {
+            transport,
+            channels: HashMap::new(),
+            schedule: VecDeque::new(),
+            receive_waker: waker(receivers.clone()),
+            receivers,
+            send_waker: waker(senders.clone()),
+            senders,
+            closed: false,
+            _error: PhantomData,
+        };
+        Multiplexer {
+            shared: Arc::new(Mutex::new(shared)),
+            codec,
+        }
+    }
+
+    /// Open channel with provided id.
+    ///
+    /// Both peers have to use the same message types for a channel
+    /// (with incoming and outgoing types swapped).
+    ///
+    /// Panics if channel with provided id was already opened.
+    pub fn open_channel<Incoming, Outgoing>(
+        &self,
+        id: u16,
+    ) -> ChannelTransport<T, E, Codec, Incoming, Outgoing> {
+        let mut shared = self.shared.lock().unwrap();
+        let channel = shared.channels.entry(id).or_default();
+        assert!(!channel.opened, "channel {id} already opened");
+        channel.opened = true;
+        ChannelTransport {
+            shared: self.shared.clone(),
+            codec: self.codec.clone(),
+            id,
+            _message: PhantomData,
+        }
+    }
+
+    /// Was transport closed.
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().unwrap().closed
+    }
+
+    /// Close the transport.
+    ///
+    /// All channel streams terminate (after yielding already received messages)
+    /// and channel sinks fail with [mezzenger::Error::Closed].
+    pub async fn close(&self) -> Result<(), mezzenger::Error<E>> {
+        poll_fn(|cx| {
+            let mut shared = self.shared.lock().unwrap();
+            if !shared.closed {
+                shared.close();
+            }
+            shared.transport.poll_close_unpin(cx)
+        })
+        .await
+    }
+}
+
+/// Logical channel of a [`Multiplexer`].
+///
+/// Closing (or dropping) channel doesn't affect other channels
+/// and isn't signalled to the peer.
+pub struct ChannelTransport<T, E, Codec, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+{
+    shared: SharedRef<T, E>,
+    codec: Codec,
+    id: u16,
+    _message: PhantomData<(Incoming, Outgoing)>,
+}
+
+impl<T, E, Codec, Incoming, Outgoing> ChannelTransport<T, E, Codec, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+{
+    /// Channel id.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Number of outgoing messages queued by this channel.
+    pub fn queued(&self) -> usize {
+        self.shared.lock().unwrap().channels[&self.id]
+            .outgoing
+            .len()
+    }
+}
+
+impl<T, E, Codec, Incoming, Outgoing> Sink<Outgoing>
+    for ChannelTransport<T, E, Codec, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+    Codec: kodec::Codec,
+    Outgoing: Serialize,
+{
+    type Error = mezzenger::Error<ChannelError<E, Codec>>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.is_closed(self.id) {
+            return Poll::Ready(Err(mezzenger::Error::Closed));
+        }
+        shared.senders.register(self.id, cx.waker());
+        if let Poll::Ready(Err(error)) = shared.poll_send(None) {
+            return Poll::Ready(Err(map_error(error)));
+        }
+        if shared.channels[&self.id].outgoing.len() < CHANNEL_BUFFER {
+            shared.senders.remove(&self.id);
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let mut payload = vec![];
+        self.codec
+            .encode(&mut payload, &item)
+            .map_err(|error| mezzenger::Error::Other(Error::SerializationError(error)))?;
+        let mut shared = self.shared.lock().unwrap();
+        if shared.is_closed(self.id) {
+            return Err(mezzenger::Error::Closed);
+        }
+        let channel = shared.channels.get_mut(&self.id).unwrap();
+        channel.outgoing.push_back(payload);
+        if channel.outgoing.len() == 1 {
+            shared.schedule.push_back(self.id);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.is_closed(self.id) {
+            return Poll::Ready(Err(mezzenger::Error::Closed));
+        }
+        shared.senders.register(self.id, cx.waker());
+        ready!(shared.poll_send(Some(self.id))).map_err(map_error)?;
+        let send_waker = shared.send_waker.clone();
+        let result = ready!(shared
+            .transport
+            .poll_flush_unpin(&mut Context::from_waker(&send_waker)));
+        shared.senders.remove(&self.id);
+        Poll::Ready(result.map_err(map_error))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.shared.lock().unwrap().is_closed(self.id) {
+            return Poll::Ready(Ok(()));
+        }
+        ready!(self.as_mut().poll_flush(cx))?;
+        let mut shared = self.shared.lock().unwrap();
+        let channel = shared.channels.get_mut(&self.id).unwrap();
+        channel.closed = true;
+        channel.incoming.clear();
+        channel.overflowed = false;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T, E, Codec, Incoming, Outgoing> Stream for ChannelTransport<T, E, Codec, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+    Codec: kodec::Codec,
+    for<'de> Incoming: Deserialize<'de>,
+{
+    type Item = Result<Incoming, ChannelError<E, Codec>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap();
+        loop {
+            let channel = shared.channels.get_mut(&self.id).unwrap();
+            if let Some(payload) = channel.incoming.pop_front() {
+                return Poll::Ready(Some(self.decode(payload)));
+            }
+            if channel.overflowed {
+                channel.overflowed = false;
+                return Poll::Ready(Some(Err(Error::Overflow)));
+            }
+            if channel.closed || shared.closed {
+                shared.receivers.remove(&self.id);
+                return Poll::Ready(None);
+            }
+            shared.receivers.register(self.id, cx.waker());
+            let receive_waker = shared.receive_waker.clone();
+            let mut receive_cx = Context::from_waker(&receive_waker);
+            match shared.transport.poll_next_unpin(&mut receive_cx) {
+                Poll::Ready(Some(Ok(message))) => {
+                    if message.channel == self.id {
+                        shared.receivers.remove(&self.id);
+                        return Poll::Ready(Some(self.decode(message.payload)));
+                    }
+                    if let Some(channel) = shared.channels.get_mut(&message.channel) {
+                        if !channel.closed {
+                            if channel.incoming.len() < CHANNEL_BUFFER {
+                                channel.incoming.push_back(message.payload);
+                            } else {
+                                channel.overflowed = true;
+                            }
+                            shared.receivers.wake_one(&message.channel);
+                        }
+                    }
+                }
+                Poll::Ready(Some(Err(error))) => {
+                    shared.receivers.remove(&self.id);
+                    return Poll::Ready(Some(Err(Error::Other(error))));
+                }
+                Poll::Ready(None) => shared.close(),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T, E, Codec, Incoming, Outgoing> FusedStream
+    for ChannelTransport<T, E, Codec, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+    Codec: kodec::Codec,
+    for<'de> Incoming: Deserialize<'de>,
+{
+    fn is_terminated(&self) -> bool {
+        let shared = self.shared.lock().unwrap();
+        let channel = &shared.channels[&self.id];
+        shared.is_closed(self.id) && channel.incoming.is_empty() && !channel.overflowed
+    }
+}
+
+impl<T, E, Codec, Incoming, Outgoing> ChannelTransport<T, E, Codec, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+    Codec: kodec::Codec,
+    for<'de> Incoming: Deserialize<'de>,
+{
+    fn decode(&self, payload: Vec<u8>) -> Result<Incoming, ChannelError<E, Codec>> {
+        self.codec
+            .decode(&payload[..])
+            .map_err(Error::DeserializationError)
+    }
+}
+
+impl<T, E, Codec, Incoming, Outgoing> Drop for ChannelTransport<T, E, Codec, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Channeled, Channeled, E> + Unpin,
+{
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            let channel = shared.channels.get_mut(&self.id).unwrap();
+            channel.closed = true;
+            channel.incoming.clear();
+            channel.overflowed = false;
+            shared.receivers.remove(&self.id);
+            shared.senders.remove(&self.id);
+        }
+    }
+}
+
+fn map_error<E, SerializationError, DeserializationError>(
+    error: mezzenger::Error<E>,
+) -> mezzenger::Error<Error<E, SerializationError, DeserializationError>> {
+    match error {
+        mezzenger::Error::Closed => mezzenger::Error::Closed,
+        mezzenger::Error::Other(error) => mezzenger::Error::Other(Error::Other(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{join, stream::FusedStream, SinkExt, StreamExt};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use mezzenger_channel::transports;
+    use serde::{Deserialize, Serialize};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use crate::multiplex::{Error, Multiplexer, CHANNEL_BUFFER};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    enum Control {
+        Start,
+        Stop,
+    }
+
+    async fn test_channels_inner() {
+        let (left, right) = transports();
+        let left = Multiplexer::new(left, Codec::default());
+        let right = Multiplexer::new(right, Codec::default());
+
+        let mut left_control = left.open_channel::<Control, Control>(0);
+        let mut left_bulk = left.open_channel::<(), Vec<u8>>(1);
+        let mut right_control = right.open_channel::<Control, Control>(0);
+        let mut right_bulk = right.open_channel::<Vec<u8>, ()>(1);
+
+        let bulk = async {
+            for i in 0..100u8 {
+                left_bulk.send(vec![i; 1000]).await.unwrap();
+            }
+        };
+        let control = async {
+            left_control.send(Control::Start).await.unwrap();
+            assert_eq!(left_control.receive().await.unwrap(), Control::Stop);
+        };
+        let received_bulk = async {
+            for i in 0..100u8 {
+                assert_eq!(right_bulk.receive().await.unwrap(), vec![i; 1000]);
+            }
+        };
+        let received_control = async {
+            assert_eq!(right_control.receive().await.unwrap(), Control::Start);
+            right_control.send(Control::Stop).await.unwrap();
+        };
+        join!(bulk, control, received_bulk, received_control);
+
+        // message arriving before channel is opened is discarded
+        let mut right_late = right.open_channel::<String, String>(2);
+        right_late.send("early".to_string()).await.unwrap();
+        right_control.send(Control::Stop).await.unwrap();
+        assert_eq!(left_control.receive().await.unwrap(), Control::Stop);
+        let mut late = left.open_channel::<String, String>(2);
+        right_late.send("late".to_string()).await.unwrap();
+        assert_eq!(late.receive().await.unwrap(), "late");
+    }
+
+    async fn test_flood_inner() {
+        let (left, right) = transports();
+        let left = Multiplexer::new(left, Codec::default());
+        let right = Multiplexer::new(right, Codec::default());
+
+        let mut left_control = left.open_channel::<Control, Control>(0);
+        let mut left_flooded = left.open_channel::<u32, u32>(1);
+        let mut right_control = right.open_channel::<Control, Control>(0);
+        let mut right_flooded = right.open_channel::<u32, u32>(1);
+        let mut right_unopened = right.open_channel::<u32, u32>(2);
+
+        for i in 0..(CHANNEL_BUFFER as u32 * 10) {
+            right_flooded.send(i).await.unwrap();
+            right_unopened.send(i).await.unwrap();
+        }
+        right_control.send(Control::Stop).await.unwrap();
+        assert_eq!(left_control.receive().await.unwrap(), Control::Stop);
+
+        {
+            let shared = left.shared.lock().unwrap();
+            assert_eq!(shared.channels[&1].incoming.len(), CHANNEL_BUFFER);
+            assert!(!shared.channels.contains_key(&2));
+        }
+
+        for i in 0..CHANNEL_BUFFER as u32 {
+            assert_eq!(left_flooded.next().await.unwrap().unwrap(), i);
+        }
+        assert!(matches!(
+            left_flooded.next().await.unwrap(),
+            Err(Error::Overflow)
+        ));
+        right_flooded.send(1234).await.unwrap();
+        assert_eq!(left_flooded.next().await.unwrap().unwrap(), 1234);
+    }
+
+    async fn test_close_inner() {
+        let (left, right) = transports();
+        let left = Multiplexer::new(left, Codec::default());
+        let right = Multiplexer::new(right, Codec::default());
+
+        let mut left_first = left.open_channel::<u32, u32>(0);
+        let mut left_second = left.open_channel::<String, String>(1);
+        let mut right_first = right.open_channel::<u32, u32>(0);
+        let mut right_second = right.open_channel::<String, String>(1);
+
+        right_first.send(1).await.unwrap();
+        assert_eq!(left_first.receive().await.unwrap(), 1);
+
+        left.close().await.unwrap();
+        assert!(left.is_closed());
+        assert!(left_first.next().await.is_none());
+        assert!(left_second.next().await.is_none());
+        assert!(left_first.is_terminated());
+        assert!(left_second.send("Hello".to_string()).await.is_err());
+
+        assert!(right_first.next().await.is_none());
+        assert!(right_second.next().await.is_none());
+        assert!(right.is_closed());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_channels() {
+        test_channels_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_channels() {
+        test_channels_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_flood() {
+        test_flood_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_flood() {
+        test_flood_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_close() {
+        test_close_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_close() {
+        test_close_inner().await
+    }
+}
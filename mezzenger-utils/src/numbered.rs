@@ -9,6 +9,8 @@ use std::{
 use futures::{stream::FusedStream, Sink, Stream};
 use num::{traits::WrappingAdd, One, Zero};
 use pin_project::pin_project;
+
+use crate::CloseOnce;
 use serde::{Deserialize, Serialize};
 
 /// Trait implemented by messages with attached number.
@@ -20,6 +22,48 @@ pub trait Number {
     fn number(&self) -> Self::Output;
 }
 
+/// Message numbers comparable across wraparound (serial number arithmetic, see
+/// [RFC 1982](https://www.rfc-editor.org/rfc/rfc1982)).
+///
+/// Number `a` is considered newer than `b` if it's ahead of `b` by less than half
+/// of the number range - so freshly wrapped `0` is newer than `u32::MAX`.<br>
+/// Comparison is correct as long as compared numbers are less than half of the range apart.
+pub trait SequenceNumber {
+    /// Is this number newer than `other` (taking wraparound into account).
+    fn is_newer_than(&self, other: &Self) -> bool;
+}
+
+/// Distance between message numbers taking wraparound into account
+/// (see [SequenceNumber]).
+pub trait SequenceDistance {
+    /// Signed distance from `other` to this number - positive if this number is newer.
+    ///
+    /// Distances exceeding [i64] range are clamped.
+    fn distance_from(&self, other: &Self) -> i64;
+}
+
+macro_rules! impl_sequence_number {
+    ($($unsigned:ty => $signed:ty),*) => {
+        $(
+            impl SequenceNumber for $unsigned {
+                fn is_newer_than(&self, other: &Self) -> bool {
+                    (self.wrapping_sub(*other) as $signed) > 0
+                }
+            }
+
+            impl SequenceDistance for $unsigned {
+                fn distance_from(&self, other: &Self) -> i64 {
+                    let distance = self.wrapping_sub(*other) as $signed;
+                    i64::try_from(distance)
+                        .unwrap_or(if distance > 0 { i64::MAX } else { i64::MIN })
+                }
+            }
+        )*
+    };
+}
+
+impl_sequence_number!(u8 => i8, u16 => i16, u32 => i32, u64 => i64, u128 => i128, usize => isize);
+
 /// Trait implemented by numbered messages that can be unwrapped into their original form.
 pub trait Unwrap {
     /// Type of wrapped message.
@@ -62,10 +106,12 @@ impl<N, T> Unwrap for Wrapper<N, T> {
 ///
 /// Received messages are of [`Wrapper`] type.
 ///
-/// First message number is [zero].<br>
-/// Next message number = previous message number + [one].
+/// First message number is [zero] (unless created with [new_starting_at](Numbered::new_starting_at)).<br>
+/// Next message number = previous message number + [one] (unless changed with
+/// [set_step](Numbered::set_step)).
 ///
-/// Message numbers will [wrap] when reaching maximum value.
+/// Message numbers will [wrap] when reaching maximum value - compare them
+/// with [SequenceNumber] (like `LatestOnly::new_wrapping` does).
 ///
 /// [zero]: num::Zero
 /// [one]: num::One
@@ -79,6 +125,8 @@ where
     #[pin]
     inner: T,
     current_number: N,
+    step: N,
+    close_once: CloseOnce,
     _error: PhantomData<E>,
     _number: PhantomData<N>,
     _incoming: PhantomData<Incoming>,
@@ -94,9 +142,23 @@ where
     ///
     /// [numbered]: self::Number
     pub fn new(transport: T) -> Self {
+        Numbered::new_starting_at(transport, N::zero())
+    }
+
+    /// Create new [numbered] transport wrapping provided transport,
+    /// numbering messages starting with `start`.
+    ///
+    /// Useful for resuming a session after reconnecting - pass
+    /// [current number](Numbered::current_number) of the previous transport
+    /// so peer keeps seeing increasing numbers.
+    ///
+    /// [numbered]: self::Number
+    pub fn new_starting_at(transport: T, start: N) -> Self {
         Numbered {
             inner: transport,
-            current_number: N::zero(),
+            current_number: start,
+            step: N::one(),
+            close_once: CloseOnce::new(),
             _error: PhantomData,
             _number: PhantomData,
             _incoming: PhantomData,
@@ -108,6 +170,58 @@ where
     pub fn current_number(&self) -> N {
         self.current_number.clone()
     }
+
+    /// Set number that will be attached to the next sent message.
+    pub fn set_current_number(&mut self, number: N) {
+        self.current_number = number;
+    }
+
+    /// Difference between numbers of consecutive messages.
+    pub fn step(&self) -> N {
+        self.step.clone()
+    }
+
+    /// Set difference between numbers of consecutive messages (by default [one]).
+    ///
+    /// Numbers still [wrap] when reaching maximum value.
+    ///
+    /// [one]: num::One
+    /// [wrap]: num::traits::WrappingAdd
+    pub fn set_step(&mut self, step: N) {
+        self.step = step;
+    }
+
+    /// Take number for a message sent directly through inner transport
+    /// (for example [shared] message sent using transport's raw path).
+    ///
+    /// Returns [current number](Numbered::current_number) and advances it.
+    ///
+    /// [shared]: crate::shared::SharedNumbered
+    pub fn next_number(&mut self) -> N {
+        let number = self.current_number.clone();
+        self.current_number = self.current_number.wrapping_add(&self.step);
+        number
+    }
+
+    /// Get reference to inner transport.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get mutable reference to inner transport.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap inner transport (for example to rebuild a leaner transport stack
+    /// mid-connection).
+    ///
+    /// Returns inner transport along with the [current number](Numbered::current_number) -
+    /// messages sent directly through inner transport should continue numbering from it.<br>
+    /// [`Numbered`] doesn't buffer messages - none are lost.
+    pub fn into_inner(self) -> (T, N) {
+        (self.inner, self.current_number)
+    }
 }
 
 impl<T, E, Incoming, Outgoing> Numbered<usize, T, E, Incoming, Outgoing>
@@ -170,7 +284,7 @@ where
         };
         let result = me.inner.start_send(item);
         if result.is_ok() {
-            *me.current_number = me.current_number.wrapping_add(&One::one());
+            *me.current_number = me.current_number.wrapping_add(me.step);
         }
         result
     }
@@ -182,7 +296,7 @@ where
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         let me = self.project();
-        me.inner.poll_close(cx)
+        me.close_once.poll_close(|| me.inner.poll_close(cx))
     }
 }
 
@@ -220,7 +334,7 @@ mod tests {
     #[cfg(target_arch = "wasm32")]
     wasm_bindgen_test_configure!(run_in_browser);
 
-    use crate::numbered::{Numbered, Wrapper};
+    use crate::numbered::{Numbered, SequenceDistance, SequenceNumber, Wrapper};
 
     async fn test_transport_inner() {
         let (left, right) = transports();
@@ -281,6 +395,86 @@ mod tests {
         );
     }
 
+    async fn test_into_inner_inner() {
+        let (left, mut right) = transports();
+
+        let mut left: Numbered<usize, _, _, (), i32> = Numbered::new_usize(left);
+
+        left.send(1).await.unwrap();
+        left.send(2).await.unwrap();
+        let (mut left, number) = left.into_inner();
+        assert_eq!(number, 2);
+        left.send(Wrapper { number, wrapped: 3 }).await.unwrap();
+
+        for (number, wrapped) in [(0, 1), (1, 2), (2, 3)] {
+            assert_eq!(right.receive().await.unwrap(), Wrapper { number, wrapped });
+        }
+    }
+
+    async fn test_resume_inner() {
+        let (left, mut right) = transports();
+        let mut left: Numbered<u32, _, _, (), u32> = Numbered::new_u32(left);
+
+        left.send(1).await.unwrap();
+        left.send(2).await.unwrap();
+        let mut last_number = None;
+        for _ in 0..2 {
+            last_number = Some(right.receive().await.unwrap().number);
+        }
+        assert_eq!(last_number, Some(1));
+
+        // reconnect, keep numbering where previous transport left off
+        let number = left.current_number();
+        drop(left);
+        let (left, mut right) = transports();
+        let mut left: Numbered<u32, _, _, (), u32> = Numbered::new_starting_at(left, number);
+        assert_eq!(left.current_number(), 2);
+
+        left.send(3).await.unwrap();
+        left.send(4).await.unwrap();
+        for (number, wrapped) in [(2, 3), (3, 4)] {
+            let message: Wrapper<u32, u32> = right.receive().await.unwrap();
+            assert!(message.number.is_newer_than(&last_number.unwrap()));
+            last_number = Some(message.number);
+            assert_eq!(message, Wrapper { number, wrapped });
+        }
+        assert_eq!(left.current_number(), 4);
+
+        // custom start, step and wrapping
+        let (left, mut right) = transports();
+        let mut left: Numbered<u8, _, _, (), u32> = Numbered::new_starting_at(left, 250);
+        left.set_step(3);
+        assert_eq!(left.step(), 3);
+        for message in 0..3 {
+            left.send(message).await.unwrap();
+        }
+        assert_eq!(left.current_number(), 3);
+        left.set_current_number(100);
+        left.send(3).await.unwrap();
+        for (number, wrapped) in [(250, 0), (253, 1), (0, 2), (100, 3)] {
+            assert_eq!(right.receive().await.unwrap(), Wrapper { number, wrapped });
+        }
+    }
+
+    #[test]
+    fn test_sequence_number() {
+        assert!(1u32.is_newer_than(&0));
+        assert!(!0u32.is_newer_than(&1));
+        assert!(!5u32.is_newer_than(&5));
+        assert!(0u32.is_newer_than(&u32::MAX));
+        assert!(2u32.is_newer_than(&(u32::MAX - 2)));
+        assert!(!u32::MAX.is_newer_than(&0));
+        assert!(0u8.is_newer_than(&200));
+        assert!(!0u8.is_newer_than(&100));
+        assert!(0usize.is_newer_than(&usize::MAX));
+
+        assert_eq!(5u32.distance_from(&2), 3);
+        assert_eq!(2u32.distance_from(&5), -3);
+        assert_eq!(2u32.distance_from(&(u32::MAX - 2)), 5);
+        assert_eq!(3u8.distance_from(&250), 9);
+        assert_eq!((u128::MAX / 4).distance_from(&0), i64::MAX);
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[tokio::test]
     async fn test_transport() {
@@ -292,4 +486,28 @@ mod tests {
     async fn test_transport() {
         test_transport_inner().await
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_resume() {
+        test_resume_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_resume() {
+        test_resume_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_into_inner() {
+        test_into_inner_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_into_inner() {
+        test_into_inner_inner().await
+    }
 }
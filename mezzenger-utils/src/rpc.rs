@@ -0,0 +1,533 @@
+//! Request-response correlation over a [numbered] transport.
+//!
+//! [`Requester`] attaches a number to every request (using [`Numbered`] transport)
+//! and resolves the request future once a [`Reply::Response`] referencing that number
+//! arrives.<br>
+//! All other incoming messages are forwarded to a separate [`Receiver`] stream.
+//!
+//! **NOTE**: [`Receiver`] dispatches responses to waiting requests, so it has to be
+//! polled (for example in a separate task) for requests to complete.
+//!
+//! [numbered]: crate::numbered::Number
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    hash::Hash,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::{
+    channel::oneshot,
+    future::{poll_fn, select, Either},
+    pin_mut, ready,
+    stream::FusedStream,
+    Future, SinkExt, Stream, StreamExt,
+};
+use num::{traits::WrappingAdd, One, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::numbered::{Numbered, Wrapper};
+
+/// Message received by the requesting side.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Reply<N, Response, Message> {
+    /// Response to a request.
+    Response {
+        /// Number of the request this is a response to.
+        request: N,
+
+        /// Response.
+        response: Response,
+    },
+
+    /// Unsolicited message.
+    Message(Message),
+}
+
+/// Request error.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Transport was closed before response arrived.
+    Closed,
+    /// Response didn't arrive in time.
+    TimedOut,
+    /// Next request number is still used by a request waiting for response
+    /// (number type wrapped around).
+    NumberInUse,
+    /// Transport error occurred while sending request.
+    Other(E),
+}
+
+impl<E> From<mezzenger::Error<E>> for Error<E> {
+    fn from(error: mezzenger::Error<E>) -> Self {
+        match error {
+            mezzenger::Error::Closed => Error::Closed,
+            mezzenger::Error::Other(error) => Error::Other(error),
+        }
+    }
+}
+
+impl<E> Display for Error<E>
+where
+    E: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Closed => write!(f, "transport closed"),
+            Error::TimedOut => write!(f, "request timed out"),
+            Error::NumberInUse => write!(f, "request number still in use"),
+            Error::Other(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl<E> std::error::Error for Error<E> where E: Debug + Display {}
+
+struct Shared<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>,
+    N: Clone + Zero + One + WrappingAdd,
+{
+    transport: Numbered<N, T, E, Reply<N, Response, Message>, Request>,
+    pending: HashMap<N, oneshot::Sender<Response>>,
+    closed: bool,
+}
+
+impl<N, T, E, Request, Response, Message> Shared<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>,
+    N: Clone + Zero + One + WrappingAdd,
+{
+    fn close(&mut self) {
+        self.closed = true;
+        // dropping senders resolves waiting requests with `Error::Closed`
+        self.pending.clear();
+    }
+}
+
+type SharedRef<N, T, E, Request, Response, Message> =
+    Arc<Mutex<Shared<N, T, E, Request, Response, Message>>>;
+
+/// Create a [`Requester`] and its [`Receiver`] over provided transport.
+///
+/// Outgoing requests are [numbered], incoming messages are expected to be
+/// numbered [`Reply`] messages.
+///
+/// [numbered]: crate::numbered::Numbered
+#[allow(clippy::type_complexity)]
+pub fn requester<N, T, E, Request, Response, Message>(
+    transport: T,
+) -> (
+    Requester<N, T, E, Request, Response, Message>,
+    Receiver<N, T, E, Request, Response, Message>,
+)
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>
+        + Unpin,
+    N: Clone + Zero + One + WrappingAdd + Hash + Eq,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        transport: Numbered::new(transport),
+        pending: HashMap::new(),
+        closed: false,
+    }));
+    let requester = Requester {
+        shared: shared.clone(),
+    };
+    let receiver = Receiver {
+        shared,
+        terminated: false,
+    };
+    (requester, receiver)
+}
+
+/// Handle used to send requests and await their responses.
+///
+/// Can be cloned to issue requests concurrently.
+pub struct Requester<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>,
+    N: Clone + Zero + One + WrappingAdd,
+{
+    shared: SharedRef<N, T, E, Request, Response, Message>,
+}
+
+impl<N, T, E, Request, Response, Message> Clone for Requester<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>,
+    N: Clone + Zero + One + WrappingAdd,
+{
+    fn clone(&self) -> Self {
+        Requester {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<N, T, E, Request, Response, Message> Requester<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>
+        + Unpin,
+    N: Clone + Zero + One + WrappingAdd + Hash + Eq,
+{
+    /// Send request and wait for the response.
+    ///
+    /// Returns [Error::NumberInUse] if request number (after wrapping around)
+    /// is still used by another waiting request.
+    pub async fn request(&self, request: Request) -> Result<Response, Error<E>> {
+        let mut pending = self.send_request(request).await?;
+        (&mut pending.receiver).await.map_err(|_| Error::Closed)
+    }
+
+    /// Send request and wait for the response until `delay` future completes.
+    ///
+    /// Returns [Error::TimedOut] if `delay` completes first
+    /// (pass for example `tokio::time::sleep(duration)`).
+    pub async fn request_with_timeout<D>(
+        &self,
+        request: Request,
+        delay: D,
+    ) -> Result<Response, Error<E>>
+    where
+        D: Future,
+    {
+        let mut pending = self.send_request(request).await?;
+        pin_mut!(delay);
+        match select(&mut pending.receiver, delay).await {
+            Either::Left((response, _)) => response.map_err(|_| Error::Closed),
+            Either::Right(_) => Err(Error::TimedOut),
+        }
+    }
+
+    /// Number of requests still waiting for response.
+    pub fn pending_requests(&self) -> usize {
+        self.shared.lock().unwrap().pending.len()
+    }
+
+    #[allow(clippy::type_complexity)]
+    async fn send_request(
+        &self,
+        request: Request,
+    ) -> Result<PendingRequest<'_, N, T, E, Request, Response, Message>, Error<E>> {
+        let mut request = Some(request);
+        let (number, receiver) = poll_fn(|cx| {
+            let mut shared = self.shared.lock().unwrap();
+            if shared.closed {
+                return Poll::Ready(Err(Error::Closed));
+            }
+            ready!(shared.transport.poll_ready_unpin(cx))?;
+            let number = shared.transport.current_number();
+            if shared.pending.contains_key(&number) {
+                return Poll::Ready(Err(Error::NumberInUse));
+            }
+            let (sender, receiver) = oneshot::channel();
+            shared.pending.insert(number.clone(), sender);
+            let request = request.take().expect("request sent twice");
+            if let Err(error) = shared.transport.start_send_unpin(request) {
+                shared.pending.remove(&number);
+                return Poll::Ready(Err(error.into()));
+            }
+            Poll::Ready(Ok((number, receiver)))
+        })
+        .await?;
+        let pending = PendingRequest {
+            shared: &self.shared,
+            number,
+            receiver,
+        };
+        poll_fn(|cx| self.shared.lock().unwrap().transport.poll_flush_unpin(cx)).await?;
+        Ok(pending)
+    }
+}
+
+/// Request waiting for response.
+///
+/// Removes its entry from pending requests when dropped - also when request
+/// future gets cancelled (dropped) before response arrives.
+struct PendingRequest<'a, N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>,
+    N: Clone + Zero + One + WrappingAdd + Hash + Eq,
+{
+    shared: &'a SharedRef<N, T, E, Request, Response, Message>,
+    number: N,
+    receiver: oneshot::Receiver<Response>,
+}
+
+impl<'a, N, T, E, Request, Response, Message> Drop
+    for PendingRequest<'a, N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>,
+    N: Clone + Zero + One + WrappingAdd + Hash + Eq,
+{
+    fn drop(&mut self) {
+        self.receiver.close();
+        if let Ok(mut shared) = self.shared.lock() {
+            // entry with the same number may already belong to another request
+            // (if response arrived and the number wrapped around)
+            let canceled = shared
+                .pending
+                .get(&self.number)
+                .map(|sender| sender.is_canceled())
+                .unwrap_or(false);
+            if canceled {
+                shared.pending.remove(&self.number);
+            }
+        }
+    }
+}
+
+/// Stream of unsolicited (non-response) messages.
+///
+/// Dispatches responses to waiting requests, so it has to be polled
+/// for requests to complete.<br>
+/// All waiting requests fail with [Error::Closed] when the transport gets
+/// closed or this receiver is dropped.
+pub struct Receiver<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>,
+    N: Clone + Zero + One + WrappingAdd,
+{
+    shared: SharedRef<N, T, E, Request, Response, Message>,
+    terminated: bool,
+}
+
+impl<N, T, E, Request, Response, Message> Stream for Receiver<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>
+        + Unpin,
+    N: Clone + Zero + One + WrappingAdd + Hash + Eq,
+{
+    type Item = Result<Message, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let mut shared = self.shared.lock().unwrap();
+        loop {
+            match ready!(shared.transport.poll_next_unpin(cx)) {
+                Some(Ok(message)) => match message.wrapped {
+                    Reply::Response { request, response } => {
+                        if let Some(sender) = shared.pending.remove(&request) {
+                            let _ = sender.send(response);
+                        }
+                    }
+                    Reply::Message(message) => return Poll::Ready(Some(Ok(message))),
+                },
+                Some(Err(error)) => return Poll::Ready(Some(Err(error))),
+                None => {
+                    shared.close();
+                    drop(shared);
+                    self.terminated = true;
+                    return Poll::Ready(None);
+                }
+            }
+        }
+    }
+}
+
+impl<N, T, E, Request, Response, Message> FusedStream
+    for Receiver<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>
+        + Unpin,
+    N: Clone + Zero + One + WrappingAdd + Hash + Eq,
+{
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+impl<N, T, E, Request, Response, Message> Drop for Receiver<N, T, E, Request, Response, Message>
+where
+    T: mezzenger::Transport<Wrapper<N, Reply<N, Response, Message>>, Wrapper<N, Request>, E>,
+    N: Clone + Zero + One + WrappingAdd,
+{
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{future, join, poll, FutureExt, SinkExt, StreamExt};
+    use mezzenger::Receive;
+    use mezzenger_channel::transports;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use crate::{
+        numbered::Numbered,
+        rpc::{requester, Error, Reply},
+    };
+
+    async fn test_request_inner() {
+        let (left, right) = transports();
+
+        let (requester, mut receiver) = requester::<usize, _, _, u32, u32, String>(left);
+        let mut responder = Numbered::new_usize(right);
+
+        let requests = async {
+            let (first, second) = join!(requester.request(2), requester.request(3));
+            assert_eq!(first.unwrap(), 4);
+            assert_eq!(second.unwrap(), 9);
+            assert_eq!(requester.pending_requests(), 0);
+        };
+        let responses = async {
+            responder
+                .send(Reply::Message("Hello".to_string()))
+                .await
+                .unwrap();
+            for _ in 0..2 {
+                let request = responder.receive().await.unwrap();
+                responder
+                    .send(Reply::Response {
+                        request: request.number,
+                        response: request.wrapped * request.wrapped,
+                    })
+                    .await
+                    .unwrap();
+            }
+            responder
+                .send(Reply::Message("World".to_string()))
+                .await
+                .unwrap();
+        };
+        let messages = async {
+            assert_eq!(receiver.next().await.unwrap().unwrap(), "Hello");
+            assert_eq!(receiver.next().await.unwrap().unwrap(), "World");
+        };
+        join!(requests, responses, messages);
+    }
+
+    async fn test_timeout_inner() {
+        let (left, _right) = transports();
+
+        let (requester, _receiver) = requester::<usize, _, _, u32, u32, ()>(left);
+
+        assert!(matches!(
+            requester.request_with_timeout(1, future::ready(())).await,
+            Err(Error::TimedOut)
+        ));
+        assert_eq!(requester.pending_requests(), 0);
+    }
+
+    async fn test_cancelled_request_inner() {
+        let (left, _right) = transports();
+
+        let (requester, _receiver) = requester::<usize, _, _, u32, u32, ()>(left);
+
+        assert!(requester.request(1).now_or_never().is_none());
+        assert_eq!(requester.pending_requests(), 0);
+    }
+
+    async fn test_number_in_use_inner() {
+        let (left, _right) = transports();
+
+        let (requester, _receiver) = requester::<u8, _, _, u32, u32, ()>(left);
+
+        let mut waiting: Vec<_> = (0..=u8::MAX)
+            .map(|i| Box::pin(requester.request(i as u32)))
+            .collect();
+        for request in waiting.iter_mut() {
+            assert!(poll!(request).is_pending());
+        }
+        assert_eq!(requester.pending_requests(), 256);
+        assert!(matches!(
+            requester.request(1234).await,
+            Err(Error::NumberInUse)
+        ));
+        assert_eq!(requester.pending_requests(), 256);
+
+        // number is released once request waiting for it is dropped
+        drop(waiting.remove(0));
+        assert_eq!(requester.pending_requests(), 255);
+        let mut request = Box::pin(requester.request(1234));
+        assert!(poll!(&mut request).is_pending());
+        assert_eq!(requester.pending_requests(), 256);
+    }
+
+    async fn test_close_inner() {
+        let (left, right) = transports();
+
+        let (requester, mut receiver) = requester::<usize, _, _, u32, u32, ()>(left);
+
+        let request = requester.request(1);
+        let close = async {
+            drop(right);
+            assert!(receiver.next().await.is_none());
+        };
+        let (result, _) = join!(request, close);
+        assert!(matches!(result, Err(Error::Closed)));
+        assert!(matches!(requester.request(2).await, Err(Error::Closed)));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_request() {
+        test_request_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_request() {
+        test_request_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_timeout() {
+        test_timeout_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_timeout() {
+        test_timeout_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_cancelled_request() {
+        test_cancelled_request_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_cancelled_request() {
+        test_cancelled_request_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_number_in_use() {
+        test_number_in_use_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_number_in_use() {
+        test_number_in_use_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_close() {
+        test_close_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_close() {
+        test_close_inner().await
+    }
+}
@@ -0,0 +1,364 @@
+//! Messages encoded once and sent through many transports.
+//!
+//! Servers broadcasting the same message to many clients normally encode it
+//! separately for every client.<br>
+//! [`SharedEncoded`] holds a message encoded once, cheap to clone and to hand over
+//! to every client's transport raw sending path (for example `send_encoded` of
+//! [mezzenger-tcp](https://crates.io/crates/mezzenger-tcp) and
+//! [mezzenger-websocket](https://crates.io/crates/mezzenger-websocket) native transports).
+//!
+//! [`SharedNumbered`] does the same for clients using [`Numbered`] transport -
+//! message is encoded once and only message number is patched per client.
+//!
+//! **NOTE**: Message has to be encoded with the same codec transport uses.
+//!
+//! [`Numbered`]: crate::Numbered
+
+use std::{
+    fmt::{Debug, Display},
+    marker::PhantomData,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use kodec::Encode;
+use num::Zero;
+use serde::Serialize;
+
+use crate::numbered::Wrapper;
+
+/// Error returned when encoding shared message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Codec failed to encode message.
+    EncodingError(E),
+    /// Codec doesn't encode [`Wrapper`] as message number followed by message
+    /// or message numbers aren't encoded with fixed size.
+    UnsupportedCodec,
+}
+
+impl<E> Display for Error<E>
+where
+    E: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::EncodingError(error) => write!(f, "failed to encode message: {error}"),
+            Error::UnsupportedCodec => {
+                write!(
+                    f,
+                    "codec doesn't encode message numbers in fixed-size prefix"
+                )
+            }
+        }
+    }
+}
+
+impl<E> std::error::Error for Error<E> where E: Debug + Display {}
+
+/// Message encoded once, sendable through many transports.
+///
+/// Cloning is cheap - encoded bytes are shared.
+#[derive(Debug)]
+pub struct SharedEncoded<T> {
+    bytes: Bytes,
+    _message: PhantomData<fn(&T)>,
+}
+
+impl<T> SharedEncoded<T>
+where
+    T: Serialize,
+{
+    /// Encode message with provided codec.
+    pub fn new<Codec>(codec: &Codec, message: &T) -> Result<Self, Codec::Error>
+    where
+        Codec: Encode,
+    {
+        let mut buffer = BytesMut::new().writer();
+        codec.encode(&mut buffer, message)?;
+        Ok(SharedEncoded::from_bytes(buffer.into_inner().freeze()))
+    }
+}
+
+impl<T> SharedEncoded<T> {
+    fn from_bytes(bytes: Bytes) -> Self {
+        SharedEncoded {
+            bytes,
+            _message: PhantomData,
+        }
+    }
+
+    /// Encoded message.
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// Unwrap encoded message.
+    pub fn into_bytes(self) -> Bytes {
+        self.bytes
+    }
+
+    /// Size of encoded message.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Is encoded message empty.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl<T> Clone for SharedEncoded<T> {
+    fn clone(&self) -> Self {
+        SharedEncoded::from_bytes(self.bytes.clone())
+    }
+}
+
+impl<T> AsRef<[u8]> for SharedEncoded<T> {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Message encoded once, sendable through many [numbered] transports.
+///
+/// Message is encoded once, following message number prefix, which is patched
+/// for every client with [`with_number`](SharedNumbered::with_number) (without re-encoding
+/// the message).
+///
+/// Requires codec encoding [`Wrapper`] as message number followed by message, with
+/// message numbers of fixed size (like [kodec](https://crates.io/crates/kodec)'s
+/// binary codec does).
+///
+/// [numbered]: crate::Numbered
+#[derive(Debug)]
+pub struct SharedNumbered<N, T> {
+    template: Bytes,
+    prefix_size: usize,
+    _number: PhantomData<fn(&N)>,
+    _message: PhantomData<fn(&T)>,
+}
+
+impl<N, T> SharedNumbered<N, T>
+where
+    N: Serialize + Zero,
+    T: Serialize,
+{
+    /// Encode message with provided codec.
+    ///
+    /// Returns [`Error::UnsupportedCodec`] if codec doesn't encode message number
+    /// as a prefix.
+    pub fn new<Codec>(codec: &Codec, message: &T) -> Result<Self, Error<Codec::Error>>
+    where
+        Codec: Encode,
+    {
+        let mut buffer = BytesMut::new().writer();
+        codec
+            .encode(&mut buffer, &N::zero())
+            .map_err(Error::EncodingError)?;
+        let prefix_size = buffer.get_ref().len();
+        codec
+            .encode(&mut buffer, message)
+            .map_err(Error::EncodingError)?;
+        let template = buffer.into_inner().freeze();
+
+        let mut wrapped = Vec::with_capacity(template.len());
+        codec
+            .encode(
+                &mut wrapped,
+                &Wrapper {
+                    number: N::zero(),
+                    wrapped: message,
+                },
+            )
+            .map_err(Error::EncodingError)?;
+        if wrapped[..] != template[..] {
+            return Err(Error::UnsupportedCodec);
+        }
+
+        Ok(SharedNumbered {
+            template,
+            prefix_size,
+            _number: PhantomData,
+            _message: PhantomData,
+        })
+    }
+
+    /// Encoded message with provided message number.
+    ///
+    /// Copies encoded message and patches its number prefix.<br>
+    /// Returns [`Error::UnsupportedCodec`] if provided number is encoded with different
+    /// size than zero.
+    pub fn with_number<Codec>(
+        &self,
+        codec: &Codec,
+        number: &N,
+    ) -> Result<SharedEncoded<Wrapper<N, T>>, Error<Codec::Error>>
+    where
+        Codec: Encode,
+    {
+        let mut buffer = BytesMut::with_capacity(self.template.len()).writer();
+        codec
+            .encode(&mut buffer, number)
+            .map_err(Error::EncodingError)?;
+        let mut buffer = buffer.into_inner();
+        if buffer.len() != self.prefix_size {
+            return Err(Error::UnsupportedCodec);
+        }
+        buffer.extend_from_slice(&self.template[self.prefix_size..]);
+        Ok(SharedEncoded::from_bytes(buffer.freeze()))
+    }
+}
+
+impl<N, T> SharedNumbered<N, T> {
+    /// Size of encoded message (including number prefix).
+    pub fn len(&self) -> usize {
+        self.template.len()
+    }
+
+    /// Is encoded message empty.
+    pub fn is_empty(&self) -> bool {
+        self.template.is_empty()
+    }
+}
+
+impl<N, T> Clone for SharedNumbered<N, T> {
+    fn clone(&self) -> Self {
+        SharedNumbered {
+            template: self.template.clone(),
+            prefix_size: self.prefix_size,
+            _number: PhantomData,
+            _message: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+    use kodec::{binary::Codec, Decode, Encode};
+    use mezzenger::Receive;
+    use mezzenger_channel::transports;
+    use serde::{Deserialize, Serialize};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use crate::{
+        numbered::Wrapper,
+        shared::{Error, SharedEncoded, SharedNumbered},
+        Numbered,
+    };
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct State {
+        tick: u64,
+        positions: Vec<(f32, f32)>,
+        name: String,
+    }
+
+    fn state() -> State {
+        State {
+            tick: 42,
+            positions: vec![(1.0, 2.0), (3.0, 4.0)],
+            name: "Hello World!".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_shared_encoded() {
+        let codec = Codec::default();
+        let shared = SharedEncoded::new(&codec, &state()).unwrap();
+
+        let mut expected = vec![];
+        codec.encode(&mut expected, &state()).unwrap();
+        assert_eq!(&shared.bytes()[..], &expected[..]);
+
+        let clones: Vec<_> = (0..3).map(|_| shared.clone()).collect();
+        for clone in clones {
+            assert_eq!(clone.bytes().as_ptr(), shared.bytes().as_ptr());
+            let decoded: State = codec.decode(clone.as_ref()).unwrap();
+            assert_eq!(decoded, state());
+        }
+    }
+
+    #[test]
+    fn test_unsupported_codec() {
+        struct LengthPrefixed;
+
+        impl Encode for LengthPrefixed {
+            type Error = std::io::Error;
+
+            fn encode<W, T>(&self, mut writer: W, message: &T) -> Result<(), Self::Error>
+            where
+                W: std::io::Write,
+                T: Serialize,
+            {
+                let encoded = encode_binary(message);
+                writer.write_all(&(encoded.len() as u8).to_be_bytes())?;
+                writer.write_all(&encoded)
+            }
+        }
+
+        fn encode_binary<T: Serialize>(message: &T) -> Vec<u8> {
+            let mut buffer = vec![];
+            Codec::default().encode(&mut buffer, message).unwrap();
+            buffer
+        }
+
+        assert!(matches!(
+            SharedNumbered::<u64, State>::new(&LengthPrefixed, &state()),
+            Err(Error::UnsupportedCodec)
+        ));
+    }
+
+    async fn test_shared_numbered_inner() {
+        let codec = Codec::default();
+        let shared = SharedNumbered::<u64, State>::new(&codec, &state()).unwrap();
+
+        let mut clients = vec![];
+        for _ in 0..3 {
+            let (server, client) = transports::<Wrapper<u64, ()>, Wrapper<u64, State>>();
+            clients.push((Numbered::new_u64(server), client));
+        }
+
+        // clients received different number of messages before
+        for (index, (server, client)) in clients.iter_mut().enumerate() {
+            for _ in 0..index {
+                server.send(state()).await.unwrap();
+                client.receive().await.unwrap();
+            }
+        }
+
+        for (index, (server, client)) in clients.iter_mut().enumerate() {
+            let number = server.next_number();
+            assert_eq!(number, index as u64);
+            let encoded = shared.with_number(&codec, &number).unwrap();
+
+            // simulate sending through raw path of transport
+            let decoded: Wrapper<u64, State> = codec.decode(encoded.as_ref()).unwrap();
+            server.inner_mut().send(decoded).await.unwrap();
+
+            let received = client.receive().await.unwrap();
+            assert_eq!(received.number, index as u64);
+            assert_eq!(received.wrapped, state());
+
+            // numbering continues after shared message
+            server.send(state()).await.unwrap();
+            assert_eq!(client.receive().await.unwrap().number, index as u64 + 1);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_shared_numbered() {
+        test_shared_numbered_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_shared_numbered() {
+        test_shared_numbered_inner().await
+    }
+}
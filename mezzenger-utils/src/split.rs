@@ -1 +1,368 @@
-//! *Work in progress*.
+//! Fanning messages out to many transports.
+//!
+//! [Splitter] sends every message to all its transports (for example mirroring traffic
+//! to a primary transport and a logging sink).
+//!
+//! *Splitting a transport into two with different message types is work in progress*.
+
+use std::{
+    fmt::{Debug, Display},
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Sink, SinkExt};
+
+/// How [Splitter] handles errors of its transports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Return error of the first failing transport right away - transports following it
+    /// are not polled (nor sent the message) by the failed call.
+    ///
+    /// This is the default.
+    #[default]
+    FailFast,
+    /// Poll (and send the message to) all transports, returning errors of all failing ones.
+    Collect,
+}
+
+/// Errors of transports wrapped by [Splitter].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error<E> {
+    /// Indices of failing transports along with their errors.
+    ///
+    /// Contains single error if [ErrorPolicy::FailFast] is used.
+    pub errors: Vec<(usize, E)>,
+}
+
+impl<E> Display for Error<E>
+where
+    E: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "splitter transport error")?;
+        for (index, error) in &self.errors {
+            write!(f, "; transport {index}: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E> std::error::Error for Error<E> where E: Debug + Display {}
+
+/// Sink sending every message to all its transports (requires `Outgoing` to be [Clone]).
+///
+/// Calls are ready (or flushed) only once all transports are ready (or flushed).<br>
+/// Errors are handled according to [ErrorPolicy].
+///
+/// Transport reporting it's [closed](mezzenger::Error::Closed) is skipped from then on -
+/// remaining transports keep receiving messages. Once all transports are closed
+/// splitter reports it's closed too (see [Splitter::is_open]).
+pub struct Splitter<T, E, Outgoing>
+where
+    T: Sink<Outgoing, Error = mezzenger::Error<E>> + Unpin,
+    Outgoing: Clone,
+{
+    transports: Vec<T>,
+    closed: Vec<bool>,
+    error_policy: ErrorPolicy,
+    _error: PhantomData<E>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+// Transports are never pinned - message and error types don't matter.
+impl<T, E, Outgoing> Unpin for Splitter<T, E, Outgoing>
+where
+    T: Sink<Outgoing, Error = mezzenger::Error<E>> + Unpin,
+    Outgoing: Clone,
+{
+}
+
+impl<T, E, Outgoing> Splitter<T, E, Outgoing>
+where
+    T: Sink<Outgoing, Error = mezzenger::Error<E>> + Unpin,
+    Outgoing: Clone,
+{
+    /// Fan messages out to provided transports, failing fast on errors.
+    pub fn new(transports: Vec<T>) -> Self {
+        Splitter::new_with_error_policy(transports, ErrorPolicy::default())
+    }
+
+    /// Fan messages out to provided transports, handling errors with provided policy.
+    pub fn new_with_error_policy(transports: Vec<T>, error_policy: ErrorPolicy) -> Self {
+        let closed = vec![false; transports.len()];
+        Splitter {
+            transports,
+            closed,
+            error_policy,
+            _error: PhantomData,
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// Policy of handling errors of transports.
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+
+    /// Number of wrapped transports (including closed ones).
+    pub fn len(&self) -> usize {
+        self.transports.len()
+    }
+
+    /// Whether there are no wrapped transports.
+    pub fn is_empty(&self) -> bool {
+        self.transports.is_empty()
+    }
+
+    /// Whether any transport is still open.
+    pub fn is_open(&self) -> bool {
+        self.closed.iter().any(|closed| !closed)
+    }
+
+    /// Whether transport with provided index is closed (messages are no longer sent to it).
+    pub fn is_closed(&self, index: usize) -> bool {
+        self.closed.get(index).copied().unwrap_or(true)
+    }
+
+    /// Get reference to transport with provided index.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.transports.get(index)
+    }
+
+    /// Get mutable reference to transport with provided index.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.transports.get_mut(index)
+    }
+
+    /// Unwrap wrapped transports.
+    pub fn into_inner(self) -> Vec<T> {
+        self.transports
+    }
+
+    /// Call `call` for all open transports, handling their errors according to error policy.
+    ///
+    /// Transports successfully closed are marked closed if `closing` is set.
+    fn call_all(
+        &mut self,
+        closing: bool,
+        mut call: impl FnMut(&mut T) -> Poll<Result<(), mezzenger::Error<E>>>,
+    ) -> Poll<Result<(), mezzenger::Error<Error<E>>>> {
+        if !self.is_open() {
+            return Poll::Ready(if closing {
+                Ok(())
+            } else {
+                Err(mezzenger::Error::Closed)
+            });
+        }
+        let mut ready = true;
+        let mut errors = vec![];
+        for (index, transport) in self.transports.iter_mut().enumerate() {
+            if self.closed[index] {
+                continue;
+            }
+            match call(transport) {
+                Poll::Ready(Ok(())) => self.closed[index] = closing,
+                Poll::Ready(Err(mezzenger::Error::Closed)) => self.closed[index] = true,
+                Poll::Ready(Err(mezzenger::Error::Other(error))) => {
+                    errors.push((index, error));
+                    if self.error_policy == ErrorPolicy::FailFast {
+                        break;
+                    }
+                }
+                Poll::Pending => ready = false,
+            }
+        }
+        if !errors.is_empty() {
+            Poll::Ready(Err(mezzenger::Error::Other(Error { errors })))
+        } else if !closing && !self.is_open() {
+            Poll::Ready(Err(mezzenger::Error::Closed))
+        } else if ready {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T, E, Outgoing> Sink<Outgoing> for Splitter<T, E, Outgoing>
+where
+    T: Sink<Outgoing, Error = mezzenger::Error<E>> + Unpin,
+    Outgoing: Clone,
+{
+    type Error = mezzenger::Error<Error<E>>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut()
+            .call_all(false, |transport| transport.poll_ready_unpin(cx))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        let result = self.get_mut().call_all(false, |transport| {
+            Poll::Ready(transport.start_send_unpin(item.clone()))
+        });
+        match result {
+            Poll::Ready(result) => result,
+            Poll::Pending => unreachable!("start_send is never pending"),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut()
+            .call_all(false, |transport| transport.poll_flush_unpin(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut()
+            .call_all(true, |transport| transport.poll_close_unpin(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures::{Sink, SinkExt};
+    use mezzenger::Receive;
+    use mezzenger_channel::transports;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use super::{Error, ErrorPolicy, Splitter};
+
+    /// Sink collecting messages or failing to accept them.
+    struct Collect {
+        messages: Vec<u32>,
+        broken: bool,
+    }
+
+    fn collect(broken: bool) -> Collect {
+        Collect {
+            messages: vec![],
+            broken,
+        }
+    }
+
+    impl Sink<u32> for Collect {
+        type Error = mezzenger::Error<&'static str>;
+
+        fn poll_ready(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: u32) -> Result<(), Self::Error> {
+            if self.broken {
+                Err(mezzenger::Error::Other("broken"))
+            } else {
+                self.messages.push(item);
+                Ok(())
+            }
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    async fn test_splitter_inner() {
+        let (senders, mut receivers): (Vec<_>, Vec<_>) =
+            (0..3).map(|_| transports::<(), u32>()).unzip();
+        let mut splitter = Splitter::new(senders);
+        assert_eq!(splitter.len(), 3);
+
+        splitter.send(1).await.unwrap();
+        for receiver in receivers.iter_mut() {
+            assert_eq!(receiver.receive().await.unwrap(), 1);
+        }
+
+        // closed transport is skipped, others keep receiving
+        drop(receivers.remove(1));
+        splitter.send(2).await.unwrap();
+        assert!(splitter.is_closed(1));
+        assert!(!splitter.is_closed(0));
+        for receiver in receivers.iter_mut() {
+            assert_eq!(receiver.receive().await.unwrap(), 2);
+        }
+
+        splitter.close().await.unwrap();
+        assert!(!splitter.is_open());
+        assert!(matches!(
+            splitter.send(3).await,
+            Err(mezzenger::Error::Closed)
+        ));
+        for receiver in receivers.iter_mut() {
+            assert!(matches!(
+                receiver.receive().await,
+                Err(mezzenger::Error::Closed)
+            ));
+        }
+    }
+
+    async fn test_error_policy_inner() {
+        let mut splitter = Splitter::new(vec![collect(true), collect(false), collect(true)]);
+        assert_eq!(splitter.error_policy(), ErrorPolicy::FailFast);
+        assert_eq!(
+            splitter.send(1).await,
+            Err(mezzenger::Error::Other(Error {
+                errors: vec![(0, "broken")]
+            }))
+        );
+        assert!(splitter.get(1).unwrap().messages.is_empty());
+
+        let mut splitter = Splitter::new_with_error_policy(
+            vec![collect(true), collect(false), collect(true)],
+            ErrorPolicy::Collect,
+        );
+        let error = splitter.send(1).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "splitter transport error; transport 0: broken; transport 2: broken"
+        );
+        assert_eq!(splitter.get(1).unwrap().messages, vec![1]);
+        assert!(splitter.is_open());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_splitter() {
+        test_splitter_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_splitter() {
+        test_splitter_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_error_policy() {
+        test_error_policy_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_error_policy() {
+        test_error_policy_inner().await
+    }
+}
@@ -0,0 +1,631 @@
+//! Last-resort watchdog force-closing stuck transports.
+//!
+//! [`Watchdog`] tracks progress of the wrapped transport - a message accepted for sending,
+//! a completed flush or a received message. If no progress is made for the configured
+//! duration while there is **pending work**, inner transport is forcibly closed and
+//! [Error::WatchdogTriggered] is returned to the user.
+//!
+//! Pending work is:
+//! - messages accepted for sending but not flushed yet,
+//! - a sender waiting for the transport to become ready (or to finish flushing or closing),
+//! - a consumer waiting for a message - only if enabled with
+//!   [set_watch_receive](Watchdog::set_watch_receive), as idle connections
+//!   normally don't receive anything.
+//!
+//! Waiting senders and consumers count only while they are being polled - a cancelled
+//! (dropped) send or receive doesn't leave pending work behind.
+//!
+//! Time is measured with [mezzenger::rt] timers (tokio natively, browser timers on `wasm32`).
+//! It only fires while the transport is being polled.
+
+use std::{
+    fmt::{Debug, Display},
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{ready, stream::FusedStream, Future, Sink, Stream};
+use mezzenger::rt::{sleep, Sleep};
+use pin_project::pin_project;
+
+use crate::CloseOnce;
+
+/// Error of [`Watchdog`] transport.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Transport made no progress for too long while there was pending work
+    /// and was forcibly closed.
+    WatchdogTriggered,
+    /// Inner transport error.
+    Other(E),
+}
+
+impl<E> Display for Error<E>
+where
+    E: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::WatchdogTriggered => {
+                write!(f, "transport made no progress and was closed by watchdog")
+            }
+            Error::Other(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl<E> std::error::Error for Error<E> where E: Debug + Display {}
+
+/// Diagnostic snapshot of [`Watchdog`] state recorded when it was triggered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    /// Configured timeout.
+    pub timeout: Duration,
+    /// Number of messages accepted for sending but not flushed.
+    pub unflushed: usize,
+    /// Was it triggered by a sender waiting for the inner transport.
+    pub sending: bool,
+    /// Was it triggered by a consumer waiting for a message.
+    pub receiving: bool,
+}
+
+/// Wrapper transport force-closing inner transport that made no progress
+/// for too long while there was pending work.
+///
+/// See [module documentation](self) for what is considered pending work.
+#[pin_project]
+pub struct Watchdog<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+{
+    #[pin]
+    inner: T,
+    timeout: Duration,
+    delay: Option<Sleep>,
+    watch_receive: bool,
+    unflushed: usize,
+    report: Option<Report>,
+    error_returned: bool,
+    close_once: CloseOnce,
+    _error: PhantomData<E>,
+    _incoming: PhantomData<Incoming>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<T, E, Incoming, Outgoing> Watchdog<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+{
+    /// Create new watchdog wrapping provided transport.
+    ///
+    /// **NOTE**: Natively it has to be polled within tokio runtime.
+    pub fn new(transport: T, timeout: Duration) -> Self {
+        Watchdog {
+            inner: transport,
+            timeout,
+            delay: None,
+            watch_receive: false,
+            unflushed: 0,
+            report: None,
+            error_returned: false,
+            close_once: CloseOnce::new(),
+            _error: PhantomData,
+            _incoming: PhantomData,
+            _outgoing: PhantomData,
+        }
+    }
+
+    /// Set whether a consumer waiting for a message is considered pending work
+    /// (disabled by default).
+    ///
+    /// Enable it only if the peer is expected to send messages regularly
+    /// (for example heartbeats).
+    pub fn set_watch_receive(&mut self, watch_receive: bool) {
+        self.watch_receive = watch_receive;
+    }
+
+    /// Was the watchdog triggered.
+    pub fn is_triggered(&self) -> bool {
+        self.report.is_some()
+    }
+
+    /// State recorded when the watchdog was triggered.
+    pub fn report(&self) -> Option<&Report> {
+        self.report.as_ref()
+    }
+
+    /// Get reference to inner transport.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get mutable reference to inner transport.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consume wrapper returning inner transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T, E, Incoming, Outgoing> Watchdog<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+{
+    /// Record progress - restart measuring time.
+    fn progress(self: Pin<&mut Self>) {
+        *self.project().delay = None;
+    }
+
+    /// Trigger the watchdog if there is pending work and the timeout elapsed
+    /// since last progress.
+    ///
+    /// Called when inner transport didn't complete an operation - `sending` or `receiving`
+    /// tells which one is currently waiting.
+    fn poll_triggered(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        sending: bool,
+        receiving: bool,
+    ) -> bool {
+        let me = self.as_mut().project();
+        if me.report.is_none() {
+            let pending = *me.unflushed > 0 || sending || (*me.watch_receive && receiving);
+            if !pending {
+                *me.delay = None;
+                return false;
+            }
+            let delay = me.delay.get_or_insert_with(|| sleep(*me.timeout));
+            if Pin::new(delay).poll(cx).is_pending() {
+                return false;
+            }
+            *me.delay = None;
+            *me.report = Some(Report {
+                timeout: *me.timeout,
+                unflushed: *me.unflushed,
+                sending,
+                receiving,
+            });
+        }
+        self.poll_closing(cx)
+    }
+
+    /// Check whether the watchdog was triggered.
+    ///
+    /// Once triggered, inner transport is closed - closing is driven
+    /// (ignoring its result) every time this is called until it completes.
+    fn poll_closing(self: Pin<&mut Self>, cx: &mut Context<'_>) -> bool {
+        let me = self.project();
+        if me.report.is_none() {
+            return false;
+        }
+        if !me.close_once.is_closed() && me.inner.poll_close(cx).is_ready() {
+            me.close_once.mark_closed();
+        }
+        true
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll_sending(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        poll: impl FnOnce(Pin<&mut T>, &mut Context<'_>) -> Poll<Result<(), mezzenger::Error<E>>>,
+    ) -> Poll<Result<(), mezzenger::Error<Error<E>>>> {
+        if self.as_mut().poll_closing(cx) {
+            return Poll::Ready(Err(mezzenger::Error::Other(Error::WatchdogTriggered)));
+        }
+        let result = poll(self.as_mut().project().inner, cx);
+        if result.is_pending() && self.as_mut().poll_triggered(cx, true, false) {
+            return Poll::Ready(Err(mezzenger::Error::Other(Error::WatchdogTriggered)));
+        }
+        result.map_err(map_error)
+    }
+}
+
+fn map_error<E>(error: mezzenger::Error<E>) -> mezzenger::Error<Error<E>> {
+    match error {
+        mezzenger::Error::Closed => mezzenger::Error::Closed,
+        mezzenger::Error::Other(error) => mezzenger::Error::Other(Error::Other(error)),
+    }
+}
+
+impl<T, E, Incoming, Outgoing> Sink<Outgoing> for Watchdog<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+{
+    type Error = mezzenger::Error<Error<E>>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_sending(cx, |inner, cx| inner.poll_ready(cx))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        if self.report.is_some() {
+            return Err(mezzenger::Error::Other(Error::WatchdogTriggered));
+        }
+        self.as_mut()
+            .project()
+            .inner
+            .start_send(item)
+            .map_err(map_error)?;
+        *self.as_mut().project().unflushed += 1;
+        self.progress();
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let result = ready!(self
+            .as_mut()
+            .poll_sending(cx, |inner, cx| inner.poll_flush(cx)));
+        if result.is_ok() {
+            *self.as_mut().project().unflushed = 0;
+            self.progress();
+        }
+        Poll::Ready(result)
+    }
+
+    /// Close inner transport.
+    ///
+    /// Returns `Ok(())` right away if the watchdog was triggered - inner transport
+    /// (possibly stuck) was already closed on a best effort basis.
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.as_mut().poll_closing(cx) {
+            return Poll::Ready(Ok(()));
+        }
+        if self.close_once.is_closed() {
+            return Poll::Ready(Ok(()));
+        }
+        let result = ready!(self
+            .as_mut()
+            .poll_sending(cx, |inner, cx| inner.poll_close(cx)));
+        match result {
+            Err(mezzenger::Error::Other(Error::WatchdogTriggered)) => Poll::Ready(Ok(())),
+            result => {
+                let me = self.project();
+                if result.is_ok() {
+                    me.close_once.mark_closed();
+                    *me.unflushed = 0;
+                }
+                Poll::Ready(result)
+            }
+        }
+    }
+}
+
+impl<T, E, Incoming, Outgoing> Stream for Watchdog<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E>,
+{
+    type Item = Result<Incoming, Error<E>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if !self.as_mut().poll_closing(cx) {
+            let item = self.as_mut().project().inner.poll_next(cx);
+            match item {
+                Poll::Ready(Some(Ok(message))) => {
+                    self.progress();
+                    return Poll::Ready(Some(Ok(message)));
+                }
+                Poll::Ready(item) => {
+                    return Poll::Ready(item.map(|result| result.map_err(Error::Other)))
+                }
+                Poll::Pending => {
+                    if !self.as_mut().poll_triggered(cx, false, true) {
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
+        let me = self.project();
+        if std::mem::replace(me.error_returned, true) {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(Err(Error::WatchdogTriggered)))
+        }
+    }
+}
+
+impl<T, E, Incoming, Outgoing> FusedStream for Watchdog<T, E, Incoming, Outgoing>
+where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + FusedStream,
+{
+    fn is_terminated(&self) -> bool {
+        self.error_returned || self.inner.is_terminated()
+    }
+}
+
+impl<T, E, Incoming, Outgoing> mezzenger::Order for Watchdog<T, E, Incoming, Outgoing> where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + mezzenger::Order
+{
+}
+
+impl<T, E, Incoming, Outgoing> mezzenger::Reliable for Watchdog<T, E, Incoming, Outgoing> where
+    T: mezzenger::Transport<Incoming, Outgoing, E> + mezzenger::Reliable
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        pin::Pin,
+        rc::Rc,
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    use futures::{poll, Sink, SinkExt, Stream, StreamExt};
+    use mezzenger::Receive;
+    use mezzenger_channel::transports;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    use super::{Error, Report, Watchdog};
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    /// Transport that never sends, flushes or receives anything.
+    struct Wedged {
+        /// Accept messages (but never flush them).
+        accepting: bool,
+        closes: Rc<Cell<usize>>,
+    }
+
+    impl Wedged {
+        fn new(accepting: bool) -> (Self, Rc<Cell<usize>>) {
+            let closes = Rc::new(Cell::new(0));
+            let wedged = Wedged {
+                accepting,
+                closes: closes.clone(),
+            };
+            (wedged, closes)
+        }
+    }
+
+    impl Sink<i32> for Wedged {
+        type Error = mezzenger::Error<()>;
+
+        fn poll_ready(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            if self.accepting {
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        }
+
+        fn start_send(self: Pin<&mut Self>, _item: i32) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Pending
+        }
+
+        fn poll_close(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.closes.set(self.closes.get() + 1);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Stream for Wedged {
+        type Item = Result<i32, ()>;
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Pending
+        }
+    }
+
+    async fn test_idle_inner() {
+        let (wedged, closes) = Wedged::new(false);
+        let mut watchdog = Watchdog::new(wedged, TIMEOUT);
+
+        // consumer waiting on an idle connection isn't pending work
+        for _ in 0..3 {
+            assert!(poll!(watchdog.next()).is_pending());
+        }
+        assert!(!watchdog.is_triggered());
+        assert_eq!(closes.get(), 0);
+    }
+
+    async fn test_stuck_send_inner() {
+        let (wedged, closes) = Wedged::new(false);
+        let mut watchdog = Watchdog::new(wedged, TIMEOUT);
+
+        assert_eq!(
+            watchdog.send(1).await,
+            Err(mezzenger::Error::Other(Error::WatchdogTriggered))
+        );
+        assert_eq!(
+            watchdog.report(),
+            Some(&Report {
+                timeout: TIMEOUT,
+                unflushed: 0,
+                sending: true,
+                receiving: false,
+            })
+        );
+        assert_eq!(closes.get(), 1);
+
+        assert_eq!(
+            watchdog.send(2).await,
+            Err(mezzenger::Error::Other(Error::WatchdogTriggered))
+        );
+        assert_eq!(watchdog.next().await, Some(Err(Error::WatchdogTriggered)));
+        assert_eq!(watchdog.next().await, None);
+        watchdog.close().await.unwrap();
+        assert_eq!(closes.get(), 1);
+    }
+
+    async fn test_stuck_flush_inner() {
+        let (wedged, closes) = Wedged::new(true);
+        let mut watchdog = Watchdog::new(wedged, TIMEOUT);
+
+        watchdog.feed(1).await.unwrap();
+        watchdog.feed(2).await.unwrap();
+        assert!(!watchdog.is_triggered());
+
+        // consumer gets notified too, even though receiving isn't watched
+        assert!(matches!(
+            watchdog.receive().await,
+            Err(mezzenger::Error::Other(Error::WatchdogTriggered))
+        ));
+        assert_eq!(watchdog.report().unwrap().unflushed, 2);
+        assert!(watchdog.report().unwrap().receiving);
+        assert_eq!(closes.get(), 1);
+        assert_eq!(
+            watchdog.flush().await,
+            Err(mezzenger::Error::Other(Error::WatchdogTriggered))
+        );
+    }
+
+    async fn test_watch_receive_inner() {
+        let (wedged, closes) = Wedged::new(false);
+        let mut watchdog = Watchdog::new(wedged, TIMEOUT);
+        watchdog.set_watch_receive(true);
+
+        assert_eq!(watchdog.next().await, Some(Err(Error::WatchdogTriggered)));
+        assert!(watchdog.report().unwrap().receiving);
+        assert!(!watchdog.report().unwrap().sending);
+        assert_eq!(closes.get(), 1);
+    }
+
+    async fn test_progress_inner() {
+        let (left, right) = transports::<i32, i32>();
+        let mut left = Watchdog::new(left, TIMEOUT);
+        let mut right = Watchdog::new(right, TIMEOUT);
+        right.set_watch_receive(true);
+
+        for i in 0..10 {
+            left.send(i).await.unwrap();
+            assert_eq!(right.receive().await.unwrap(), i);
+        }
+        assert!(!left.is_triggered());
+        assert!(!right.is_triggered());
+
+        left.close().await.unwrap();
+        assert!(!left.is_triggered());
+        assert!(matches!(
+            right.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert!(!right.is_triggered());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_idle() {
+        test_idle_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_idle() {
+        test_idle_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_stuck_send() {
+        test_stuck_send_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_stuck_send() {
+        test_stuck_send_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_stuck_flush() {
+        test_stuck_flush_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_stuck_flush() {
+        test_stuck_flush_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_receive() {
+        test_watch_receive_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_watch_receive() {
+        test_watch_receive_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_progress() {
+        test_progress_inner().await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    async fn test_progress() {
+        test_progress_inner().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_send() {
+        let (wedged, closes) = Wedged::new(false);
+        let mut watchdog = Watchdog::new(wedged, TIMEOUT);
+
+        // send given up on (for example by a timeout) while the transport wasn't ready
+        {
+            let send = watchdog.send(1);
+            futures::pin_mut!(send);
+            assert!(poll!(send).is_pending());
+        }
+
+        // idle connection isn't killed afterwards
+        let result = tokio::time::timeout(TIMEOUT * 3, watchdog.next()).await;
+        assert!(result.is_err());
+        assert!(!watchdog.is_triggered());
+        assert_eq!(closes.get(), 0);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_timer() {
+        use tokio::time::{sleep, Instant};
+
+        let (wedged, closes) = Wedged::new(true);
+        let mut watchdog = Watchdog::new(wedged, TIMEOUT);
+        let start = Instant::now();
+
+        // accepting a message is progress - restarts measuring time
+        watchdog.feed(1).await.unwrap();
+        assert!(poll!(watchdog.flush()).is_pending());
+        sleep(TIMEOUT / 2).await;
+        watchdog.feed(2).await.unwrap();
+        assert_eq!(
+            watchdog.flush().await,
+            Err(mezzenger::Error::Other(Error::WatchdogTriggered))
+        );
+        assert_eq!(start.elapsed(), TIMEOUT / 2 + TIMEOUT);
+        assert_eq!(closes.get(), 1);
+    }
+}
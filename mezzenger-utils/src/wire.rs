@@ -0,0 +1,29 @@
+//! Envelope types put on the wire by wrapper transports, nameable from one place.
+//!
+//! Envelopes are plain [serde](https://serde.rs) types encoded with the codec
+//! of the underlying transport - implementations in other languages have to
+//! follow serde's data model for the codec in use:
+//!
+//! - [NumberedWrapper] (sent by [Numbered](crate::Numbered) transports and
+//!   [Requester](crate::rpc::Requester) requests) - struct with `number` followed
+//!   by `wrapped` message,
+//! - [RpcReply] (received by [Requester](crate::rpc::Requester)) - enum with
+//!   `Response { request, response }` (variant 0) and `Message(message)` (variant 1),
+//! - [Channeled] (sent by [Multiplexer](crate::Multiplexer)) - struct with `channel` (`u16`)
+//!   followed by `payload` (channel message encoded as byte vector).
+//!
+//! For example with `kodec`'s binary codec (bincode: fixed-size little endian integers,
+//! enum variants as `u32`, lengths as `u64`) `NumberedWrapper { number: 7u8, wrapped: 42u32 }`
+//! is encoded as `07 2a 00 00 00`, while with its JSON codec as `{"number":7,"wrapped":42}`.
+//!
+//! See `wire_vectors` module (enabled with `wire-vectors` feature) for conformance vectors
+//! of every envelope.
+
+#[cfg(feature = "numbered")]
+pub use crate::numbered::Wrapper as NumberedWrapper;
+
+#[cfg(feature = "rpc")]
+pub use crate::rpc::Reply as RpcReply;
+
+#[cfg(feature = "multiplex")]
+pub use crate::multiplex::Channeled;
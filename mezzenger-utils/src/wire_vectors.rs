@@ -0,0 +1,138 @@
+//! Conformance vectors of the [wire format](crate::wire) of envelope types.
+//!
+//! Canonical envelope values encoded with `kodec`'s binary and JSON codecs.<br>
+//! Vectors are checked into the repository (`fixtures/wire_vectors.txt`) and tested
+//! for byte-for-byte stability, so any unintentional wire format change fails tests.
+//! Implementations in other languages can use the fixture file to test their conformance.
+//!
+//! Deliberate changes have to regenerate the fixture file:
+//!
+//! ```text
+//! cargo run -p mezzenger-utils --example generate_wire_vectors --features wire-vectors
+//! ```
+
+use std::fmt::Write;
+
+use kodec::Encode;
+use serde::Serialize;
+
+use crate::wire::{NumberedWrapper, RpcReply};
+
+/// Path of the fixture file relative to crate's root.
+pub const FIXTURE_PATH: &str = "fixtures/wire_vectors.txt";
+
+/// Single envelope value encoded with supported codecs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vector {
+    /// Name of the vector.
+    pub name: &'static str,
+    /// Value encoded with `kodec`'s binary codec.
+    pub binary: Vec<u8>,
+    /// Value encoded with `kodec`'s JSON codec.
+    pub json: String,
+}
+
+impl Vector {
+    fn new<T: Serialize>(name: &'static str, value: T) -> Self {
+        let mut binary = vec![];
+        kodec::binary::Codec::default()
+            .encode(&mut binary, &value)
+            .unwrap();
+        let mut json = vec![];
+        kodec::json::Codec::default()
+            .encode(&mut json, &value)
+            .unwrap();
+        Vector {
+            name,
+            binary,
+            json: String::from_utf8(json).unwrap(),
+        }
+    }
+}
+
+/// Canonical set of envelope values.
+pub fn vectors() -> Vec<Vector> {
+    vec![
+        Vector::new(
+            "numbered_u8",
+            NumberedWrapper {
+                number: 7u8,
+                wrapped: 42u32,
+            },
+        ),
+        Vector::new(
+            "numbered_u16",
+            NumberedWrapper {
+                number: 258u16,
+                wrapped: "hi".to_string(),
+            },
+        ),
+        Vector::new(
+            "numbered_u32_max",
+            NumberedWrapper {
+                number: u32::MAX,
+                wrapped: true,
+            },
+        ),
+        Vector::new(
+            "numbered_u64",
+            NumberedWrapper {
+                number: 1u64 << 40,
+                wrapped: (),
+            },
+        ),
+        Vector::new(
+            "numbered_bytes",
+            NumberedWrapper {
+                number: 0u8,
+                wrapped: vec![1u8, 2, 3],
+            },
+        ),
+        Vector::new(
+            "rpc_request",
+            NumberedWrapper {
+                number: 3u32,
+                wrapped: "ping".to_string(),
+            },
+        ),
+        Vector::new(
+            "rpc_response",
+            RpcReply::<u32, String, u8>::Response {
+                request: 5,
+                response: "ok".to_string(),
+            },
+        ),
+        Vector::new("rpc_message", RpcReply::<u32, String, u8>::Message(9)),
+    ]
+}
+
+/// Render vectors in the fixture file format.
+///
+/// Every vector occupies a single line: name, binary encoding (hex)
+/// and JSON encoding separated by spaces.
+pub fn render(vectors: &[Vector]) -> String {
+    let mut rendered = String::new();
+    for vector in vectors {
+        let binary: String = vector
+            .binary
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        writeln!(rendered, "{} {} {}", vector.name, binary, vector.json).unwrap();
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, vectors};
+
+    #[test]
+    fn test_wire_vectors() {
+        let fixture = include_str!("../fixtures/wire_vectors.txt");
+        for (expected, actual) in fixture.lines().zip(render(&vectors()).lines()) {
+            assert_eq!(actual, expected, "wire format changed");
+        }
+        assert_eq!(render(&vectors()), fixture);
+    }
+}
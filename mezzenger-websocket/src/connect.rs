@@ -0,0 +1,438 @@
+//! Connecting to servers resolving to multiple addresses.
+//!
+//! Connection attempts are raced according to
+//! [Happy Eyeballs](https://www.rfc-editor.org/rfc/rfc8305) - addresses are tried
+//! alternating between IPv6 and IPv4, next attempt starts once the previous one failed
+//! or didn't succeed within [attempt delay](ConnectOptions::attempt_delay).<br>
+//! First established TCP connection is used for the WebSocket handshake, other attempts
+//! are aborted.
+//!
+//! **NOTE**: Only plain (`ws://`) connections are supported - `tokio-tungstenite` is built
+//! without TLS support.
+
+use std::{fmt::Display, future::Future, io, net::SocketAddr, time::Duration};
+
+use futures::{stream::FuturesUnordered, StreamExt};
+use serde::Serialize;
+use tokio::{
+    net::{lookup_host, TcpStream},
+    time::timeout,
+};
+use tokio_tungstenite::{client_async, MaybeTlsStream, WebSocketStream};
+use tungstenite::{client::IntoClientRequest, error::UrlError, handshake::client::Request};
+
+use crate::Transport;
+
+/// Default delay between connection attempts (recommended by RFC 8305).
+pub const DEFAULT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Options of [Transport::connect_with_options].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectOptions {
+    /// Delay after which next connection attempt starts if previous ones are still pending.
+    pub attempt_delay: Duration,
+}
+
+impl Default for ConnectOptions {
+    /// Attempts staggered by [DEFAULT_ATTEMPT_DELAY].
+    fn default() -> Self {
+        ConnectOptions {
+            attempt_delay: DEFAULT_ATTEMPT_DELAY,
+        }
+    }
+}
+
+/// Information about established connection.
+#[derive(Debug)]
+pub struct HandshakeInfo {
+    /// Address connection was established with.
+    pub address: SocketAddr,
+    /// Addresses tried before (or in parallel to) the winning one that failed,
+    /// along with their errors.
+    ///
+    /// Attempts aborted once connection was established are not included.
+    pub failed: Vec<(SocketAddr, io::Error)>,
+}
+
+/// Error of [Transport::connect] (and its variants).
+#[derive(Debug)]
+pub enum ConnectError {
+    /// Invalid request (for example URL without host).
+    InvalidRequest(tungstenite::Error),
+    /// Failed to resolve host.
+    ResolveError(io::Error),
+    /// Host resolved to no addresses.
+    NoAddresses,
+    /// Connection attempts to all addresses failed.
+    AllFailed(Vec<(SocketAddr, io::Error)>),
+    /// TCP connection was established but WebSocket handshake failed.
+    HandshakeError {
+        address: SocketAddr,
+        error: tungstenite::Error,
+    },
+}
+
+impl Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectError::InvalidRequest(error) => write!(f, "invalid request: {error}"),
+            ConnectError::ResolveError(error) => write!(f, "failed to resolve host: {error}"),
+            ConnectError::NoAddresses => write!(f, "host resolved to no addresses"),
+            ConnectError::AllFailed(errors) => {
+                write!(f, "all connection attempts failed")?;
+                for (address, error) in errors {
+                    write!(f, "; {address}: {error}")?;
+                }
+                Ok(())
+            }
+            ConnectError::HandshakeError { address, error } => {
+                write!(f, "websocket handshake with {address} failed: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+impl<Codec, Incoming, Outgoing>
+    Transport<WebSocketStream<MaybeTlsStream<TcpStream>>, Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    /// Connect to WebSocket server racing connection attempts to all its addresses.
+    ///
+    /// Addresses are tried alternating between IPv6 and IPv4, next attempt starts once
+    /// the previous one failed or [attempt delay](ConnectOptions::attempt_delay) passed.
+    /// First established TCP connection is used for the handshake, other attempts are aborted.
+    ///
+    /// Like [new_with_response](Transport::new_with_response) transport keeps response
+    /// headers of the handshake.
+    ///
+    /// Secure (`wss://`) requests result in [ConnectError::InvalidRequest]
+    /// (TLS isn't supported).
+    ///
+    /// ## Example
+    ///
+    /// ```ignore
+    /// let (transport, info): (Transport<_, Codec, i32, String>, _) =
+    ///     Transport::connect("ws://localhost:8080", Codec::default()).await?;
+    /// println!("connected to {}", info.address);
+    /// ```
+    pub async fn connect<R>(request: R, codec: Codec) -> Result<(Self, HandshakeInfo), ConnectError>
+    where
+        R: IntoClientRequest,
+    {
+        Transport::connect_with_options(request, codec, ConnectOptions::default()).await
+    }
+
+    /// Connect to WebSocket server using provided options.
+    ///
+    /// See [connect](Transport::connect).
+    pub async fn connect_with_options<R>(
+        request: R,
+        codec: Codec,
+        options: ConnectOptions,
+    ) -> Result<(Self, HandshakeInfo), ConnectError>
+    where
+        R: IntoClientRequest,
+    {
+        Transport::connect_with_resolver(request, codec, options, |host, port| async move {
+            Ok(lookup_host((host.as_str(), port)).await?.collect())
+        })
+        .await
+    }
+
+    /// Connect to WebSocket server resolving its host with `resolve`
+    /// (called with host and port of the request).
+    ///
+    /// See [connect](Transport::connect).
+    pub async fn connect_with_resolver<R, F, Fut>(
+        request: R,
+        codec: Codec,
+        options: ConnectOptions,
+        resolve: F,
+    ) -> Result<(Self, HandshakeInfo), ConnectError>
+    where
+        R: IntoClientRequest,
+        F: FnOnce(String, u16) -> Fut,
+        Fut: Future<Output = io::Result<Vec<SocketAddr>>>,
+    {
+        let request = request
+            .into_client_request()
+            .map_err(ConnectError::InvalidRequest)?;
+        if request.uri().scheme_str() == Some("wss") {
+            return Err(ConnectError::InvalidRequest(tungstenite::Error::Url(
+                UrlError::TlsFeatureNotEnabled,
+            )));
+        }
+        let (host, port) = host_and_port(&request).ok_or(ConnectError::InvalidRequest(
+            tungstenite::Error::Url(UrlError::NoHostName),
+        ))?;
+        let addresses = resolve(host, port)
+            .await
+            .map_err(ConnectError::ResolveError)?;
+        let (stream, address, failed) =
+            race(addresses, options.attempt_delay, TcpStream::connect).await?;
+        let connection = client_async(request, MaybeTlsStream::Plain(stream))
+            .await
+            .map_err(|error| ConnectError::HandshakeError { address, error })?;
+        Ok((
+            Transport::new_with_response(connection, codec),
+            HandshakeInfo { address, failed },
+        ))
+    }
+}
+
+/// Host (without brackets of IPv6 literals) and port of the request,
+/// `None` if it has no host.
+fn host_and_port(request: &Request) -> Option<(String, u16)> {
+    let uri = request.uri();
+    let host = uri.host()?;
+    // IPv6 literals are enclosed in brackets
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = uri.port_u16().unwrap_or(80);
+    Some((host, port))
+}
+
+/// Order addresses alternating between address families,
+/// starting with family of the first address.
+fn interleave(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addresses.first() else {
+        return addresses;
+    };
+    let first_ipv6 = first.is_ipv6();
+    let (preferred, other): (Vec<_>, Vec<_>) = addresses
+        .into_iter()
+        .partition(|address| address.is_ipv6() == first_ipv6);
+    let mut interleaved = Vec::with_capacity(preferred.len() + other.len());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return interleaved,
+            (address, other_address) => {
+                interleaved.extend(address.into_iter().chain(other_address))
+            }
+        }
+    }
+}
+
+/// Race connection attempts to provided addresses, returning the first established
+/// connection along with its address and errors of attempts that failed before.
+async fn race<F, Fut, S>(
+    addresses: Vec<SocketAddr>,
+    attempt_delay: Duration,
+    connect: F,
+) -> Result<(S, SocketAddr, Vec<(SocketAddr, io::Error)>), ConnectError>
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+{
+    if addresses.is_empty() {
+        return Err(ConnectError::NoAddresses);
+    }
+    let start = |address| {
+        let connecting = connect(address);
+        async move { (address, connecting.await) }
+    };
+    let mut pending = interleave(addresses).into_iter().peekable();
+    let mut attempts = FuturesUnordered::new();
+    let mut failed = vec![];
+    loop {
+        if attempts.is_empty() {
+            match pending.next() {
+                Some(address) => attempts.push(start(address)),
+                None => return Err(ConnectError::AllFailed(failed)),
+            }
+        }
+        let finished = if pending.peek().is_some() {
+            match timeout(attempt_delay, attempts.next()).await {
+                Ok(finished) => finished,
+                Err(_) => {
+                    attempts.extend(pending.next().map(start));
+                    continue;
+                }
+            }
+        } else {
+            attempts.next().await
+        };
+        match finished {
+            Some((address, Ok(stream))) => return Ok((stream, address, failed)),
+            Some((address, Err(error))) => {
+                failed.push((address, error));
+                // failed attempt doesn't wait for the delay
+                attempts.extend(pending.next().map(start));
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, net::SocketAddr, time::Duration};
+
+    use futures::{future::pending, SinkExt};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::{net::TcpListener, time::Instant};
+    use tokio_tungstenite::accept_async;
+    use tungstenite::error::UrlError;
+
+    use super::{interleave, race, ConnectError, ConnectOptions};
+    use crate::Transport;
+
+    fn address(address: &str) -> SocketAddr {
+        address.parse().unwrap()
+    }
+
+    async fn unused_address() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_interleave() {
+        let addresses = vec![
+            address("[::1]:1"),
+            address("[::1]:2"),
+            address("[::1]:3"),
+            address("127.0.0.1:4"),
+            address("127.0.0.1:5"),
+        ];
+        assert_eq!(
+            interleave(addresses),
+            vec![
+                address("[::1]:1"),
+                address("127.0.0.1:4"),
+                address("[::1]:2"),
+                address("127.0.0.1:5"),
+                address("[::1]:3"),
+            ]
+        );
+        assert!(interleave(vec![]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_race_staggered() {
+        let dead = address("[::1]:1");
+        let live = address("127.0.0.1:2");
+        let start = Instant::now();
+        let (connected, winner, failed) = race(
+            vec![dead, live],
+            Duration::from_millis(100),
+            |address| async move {
+                if address == dead {
+                    pending::<()>().await;
+                }
+                Ok::<_, io::Error>(address)
+            },
+        )
+        .await
+        .unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_secs(5));
+        assert_eq!(connected, live);
+        assert_eq!(winner, live);
+        assert!(failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let live = listener.local_addr().unwrap();
+        let dead = address(&format!("[::1]:{}", unused_address().await.port()));
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut transport: Transport<_, Codec, String, String> =
+                Transport::new(accept_async(stream).await.unwrap(), Codec::default());
+            transport.send("Hello World!".to_string()).await.unwrap();
+        });
+
+        // failed attempt starts the next one without waiting for the delay
+        let options = ConnectOptions {
+            attempt_delay: Duration::from_secs(60),
+        };
+        let start = Instant::now();
+        let (mut transport, info): (Transport<_, Codec, String, String>, _) =
+            Transport::connect_with_resolver(
+                "ws://localhost/",
+                Codec::default(),
+                options,
+                |host, port| async move {
+                    assert_eq!((host.as_str(), port), ("localhost", 80));
+                    Ok(vec![dead, live])
+                },
+            )
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(info.address, live);
+        assert_eq!(info.failed.len(), 1);
+        assert_eq!(info.failed[0].0, dead);
+        assert_eq!(transport.receive().await.unwrap(), "Hello World!");
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_all_failed() {
+        let dead = [unused_address().await, unused_address().await];
+        let result: Result<(Transport<_, Codec, String, String>, _), _> =
+            Transport::connect_with_resolver(
+                "ws://example.com:8080/",
+                Codec::default(),
+                ConnectOptions::default(),
+                |_, _| async move { Ok(dead.to_vec()) },
+            )
+            .await;
+        let Err(ConnectError::AllFailed(errors)) = result else {
+            panic!("expected all attempts to fail");
+        };
+        let mut addresses: Vec<_> = errors.iter().map(|(address, _)| *address).collect();
+        addresses.sort();
+        let mut expected = dead.to_vec();
+        expected.sort();
+        assert_eq!(addresses, expected);
+        assert!(errors
+            .iter()
+            .all(|(_, error)| error.kind() == io::ErrorKind::ConnectionRefused));
+
+        let result: Result<(Transport<_, Codec, String, String>, _), _> =
+            Transport::connect_with_resolver(
+                "ws://example.com/",
+                Codec::default(),
+                ConnectOptions::default(),
+                |_, _| async move { Ok(vec![]) },
+            )
+            .await;
+        assert!(matches!(result, Err(ConnectError::NoAddresses)));
+    }
+
+    #[tokio::test]
+    async fn test_connect_tls_not_supported() {
+        let result: Result<(Transport<_, Codec, String, String>, _), _> =
+            Transport::connect_with_resolver(
+                "wss://example.com/",
+                Codec::default(),
+                ConnectOptions::default(),
+                |_, _| async move { panic!("secure request shouldn't be resolved") },
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(ConnectError::InvalidRequest(tungstenite::Error::Url(
+                UrlError::TlsFeatureNotEnabled
+            )))
+        ));
+    }
+}
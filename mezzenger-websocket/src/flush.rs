@@ -0,0 +1,33 @@
+//! Batching of outgoing messages.
+
+/// When flushing the transport (for example with [SinkExt::send](futures::SinkExt::send))
+/// actually flushes the underlying stream.
+///
+/// Flushing every message separately produces a write (and often a TCP segment)
+/// per message - to send many messages at once prefer `send_batch`
+/// (or [feed](futures::SinkExt::feed) messages and flush once).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Every flush is forwarded to the underlying stream.
+    ///
+    /// This is the default.
+    #[default]
+    EveryMessage,
+    /// Flushes are ignored - messages are flushed only with `force_flush`
+    /// (or when the underlying stream decides to).
+    Manual,
+    /// Flushes are ignored until at least this many bytes of messages
+    /// were sent since last flush.
+    Bytes(usize),
+}
+
+impl FlushPolicy {
+    /// Should flush be forwarded with `unflushed_bytes` sent since last flush.
+    pub(crate) fn should_flush(&self, unflushed_bytes: usize) -> bool {
+        match self {
+            FlushPolicy::EveryMessage => true,
+            FlushPolicy::Manual => false,
+            FlushPolicy::Bytes(bytes) => unflushed_bytes >= *bytes,
+        }
+    }
+}
@@ -8,6 +8,16 @@
 //!
 //! See [repository](https://github.com/zduny/mezzenger) for more info.
 
+/// Default maximum size of a serialized message (16 MiB).
+///
+/// Sending or receiving larger messages results in `Error::MessageTooLarge`.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+#[cfg(all(any(feature = "native", feature = "warp"), not(target_arch = "wasm32")))]
+mod flush;
+#[cfg(all(any(feature = "native", feature = "warp"), not(target_arch = "wasm32")))]
+pub use flush::FlushPolicy;
+
 #[cfg(all(feature = "warp", not(target_arch = "wasm32")))]
 pub mod warp;
 
@@ -15,6 +25,10 @@ pub mod warp;
 mod native;
 #[cfg(all(feature = "native", not(target_arch = "wasm32")))]
 pub use native::*;
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+mod connect;
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+pub use connect::*;
 
 #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 mod wasm;
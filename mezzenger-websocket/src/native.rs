@@ -5,21 +5,46 @@
 use std::{
     fmt::{Debug, Display},
     marker::PhantomData,
+    mem::ManuallyDrop,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
-use futures::{stream::FusedStream, Sink, SinkExt, Stream, StreamExt};
+use futures::{
+    future::poll_fn, stream::FusedStream, task::noop_waker_ref, Sink, SinkExt, Stream, StreamExt,
+};
 use kodec::{Decode, Encode};
-use pin_project::pin_project;
+use mezzenger::FlushOnDrop;
+use pin_project::{pin_project, pinned_drop};
 use serde::Serialize;
-use tungstenite::Message;
+use tokio::{
+    runtime::Handle,
+    time::{timeout_at, Instant},
+};
+use tungstenite::{
+    handshake::client::Response,
+    http::{
+        header::{SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_PROTOCOL},
+        HeaderMap,
+    },
+    Message,
+};
+
+use crate::{FlushPolicy, DEFAULT_MAX_MESSAGE_SIZE};
 
 #[derive(Debug)]
 pub enum Error<SerializationError, DeserializationError> {
+    MessageTooLarge(usize),
     SerializationError(SerializationError),
     DeserializationError(DeserializationError),
+    #[cfg(feature = "type-tag")]
+    TypeMismatch {
+        expected: &'static str,
+        got: String,
+    },
     TungsteniteError(tungstenite::Error),
+    UnflushedData,
 }
 
 impl<SerializationError, DeserializationError> Display
@@ -30,11 +55,17 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Error::MessageTooLarge(size) => write!(f, "message was too large ({size} bytes)"),
             Error::SerializationError(error) => write!(f, "failed to serialize message: {error}"),
             Error::DeserializationError(error) => {
                 write!(f, "failed to deserialize message: {error}")
             }
+            #[cfg(feature = "type-tag")]
+            Error::TypeMismatch { expected, got } => {
+                write!(f, "message type mismatch (expected {expected}, got {got})")
+            }
             Error::TungsteniteError(error) => write!(f, "tungstenite error occurred: {error}"),
+            Error::UnflushedData => write!(f, "timed out before pending messages were flushed"),
         }
     }
 }
@@ -47,12 +78,24 @@ where
 {
 }
 
+#[cfg(feature = "type-tag")]
+impl<SerializationError, DeserializationError> From<mezzenger::type_tag::TypeMismatch>
+    for Error<SerializationError, DeserializationError>
+{
+    fn from(mismatch: mezzenger::type_tag::TypeMismatch) -> Self {
+        Error::TypeMismatch {
+            expected: mismatch.expected,
+            got: mismatch.got,
+        }
+    }
+}
+
 /// Web Socket transport for [tokio-tungstenite](https://github.com/snapview/tokio-tungstenite).
 ///
 /// Wraps around [tokio_tungstenite::WebSocketStream].
 ///
 /// **NOTE**: This transport's receiving stream ignores all non-binary (text, ping, pong, close) messages.
-#[pin_project]
+#[pin_project(PinnedDrop)]
 pub struct Transport<T, Codec, Incoming, Outgoing>
 where
     T: Sink<Message, Error = tungstenite::Error>
@@ -62,14 +105,26 @@ where
     for<'de> Incoming: serde::de::Deserialize<'de>,
     Outgoing: Serialize,
 {
-    #[pin]
-    inner: T,
+    inner: ManuallyDrop<T>,
     codec: Codec,
+    receive_codec: Option<Codec>,
     terminated: bool,
+    flush_policy: FlushPolicy,
+    unflushed_bytes: usize,
+    max_message_size: usize,
+    response_headers: Option<HeaderMap>,
+    flush_on_drop: Option<DropFlush<T>>,
     _incoming: PhantomData<Incoming>,
     _outgoing: PhantomData<Outgoing>,
 }
 
+/// Runtime handle captured at construction along with function spawning
+/// a task flushing and closing the stream on it.
+struct DropFlush<T> {
+    handle: Handle,
+    spawn: fn(&Handle, T),
+}
+
 impl<T, Codec, Incoming, Outgoing> Transport<T, Codec, Incoming, Outgoing>
 where
     T: Sink<Message, Error = tungstenite::Error>
@@ -80,15 +135,321 @@ where
     Outgoing: Serialize,
 {
     /// Create new transport wrapping a provided `[tokio_tungstenite::WebSocketStream]`.
+    ///
+    /// **NOTE**: By default serialized message size is limited to [DEFAULT_MAX_MESSAGE_SIZE].<br>
+    /// Sending or receiving messages of larger size will result in [Error::MessageTooLarge].
     pub fn new(stream: T, codec: Codec) -> Self {
+        Transport::new_with_max_message_size(stream, codec, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Create new transport wrapping a provided `[tokio_tungstenite::WebSocketStream]`.
+    ///
+    /// Serialized message size will be limited to `max_message_size`.<br>
+    /// Sending or receiving messages of larger size will result in [Error::MessageTooLarge].
+    ///
+    /// **NOTE**: Incoming messages are checked once the underlying stream received them
+    /// in full - limit size of messages accepted by the stream itself too (see
+    /// [WebSocketConfig](tungstenite::protocol::WebSocketConfig)) to bound memory used
+    /// while receiving them.
+    pub fn new_with_max_message_size(stream: T, codec: Codec, max_message_size: usize) -> Self {
         Transport {
-            inner: stream,
+            inner: ManuallyDrop::new(stream),
             codec,
+            receive_codec: None,
             terminated: false,
+            flush_policy: FlushPolicy::default(),
+            unflushed_bytes: 0,
+            max_message_size,
+            response_headers: None,
+            flush_on_drop: None,
             _incoming: PhantomData,
             _outgoing: PhantomData,
         }
     }
+
+    /// Create new transport wrapping a stream along with server's handshake response -
+    /// pair returned by [tokio_tungstenite::connect_async] (and its variants).
+    ///
+    /// Unlike [new](Transport::new) it keeps response headers, so subprotocol
+    /// and extensions negotiated during the handshake can be queried.
+    ///
+    /// ## Example
+    ///
+    /// ```ignore
+    /// let mut request = "ws://127.0.0.1:8080".into_client_request()?;
+    /// request
+    ///     .headers_mut()
+    ///     .insert("Sec-WebSocket-Protocol", "v2, v1".parse()?);
+    /// let transport: Transport<_, Codec, i32, String> =
+    ///     Transport::new_with_response(connect_async(request).await?, Codec::default());
+    /// match transport.selected_protocol() {
+    ///     Some("v2") => { /* ... */ }
+    ///     _ => { /* ... */ }
+    /// }
+    /// ```
+    pub fn new_with_response((stream, response): (T, Response), codec: Codec) -> Self {
+        let mut transport = Transport::new(stream, codec);
+        transport.response_headers = Some(response.into_parts().0.headers);
+        transport
+    }
+
+    /// Create new transport wrapping a provided `[tokio_tungstenite::WebSocketStream]`
+    /// handling pending messages on drop according to `flush_on_drop`.
+    ///
+    /// With [FlushOnDrop::Detached] dropped transport flushes pending messages and
+    /// closes the stream in a task spawned on the runtime this constructor was called
+    /// within (if there was none the option has no effect).<br>
+    /// It's best-effort - prefer [close_gracefully](Transport::close_gracefully)
+    /// where the outcome matters.
+    pub fn new_with_flush_on_drop(stream: T, codec: Codec, flush_on_drop: FlushOnDrop) -> Self
+    where
+        T: Send + 'static,
+    {
+        let mut transport = Transport::new(stream, codec);
+        if flush_on_drop == FlushOnDrop::Detached {
+            transport.flush_on_drop = Handle::try_current().ok().map(|handle| DropFlush {
+                handle,
+                spawn: spawn_flush::<T>,
+            });
+        }
+        transport
+    }
+
+    /// Headers of server's handshake response.
+    ///
+    /// `None` unless transport was created with [new_with_response](Transport::new_with_response).
+    pub fn response_headers(&self) -> Option<&HeaderMap> {
+        self.response_headers.as_ref()
+    }
+
+    /// Subprotocol selected by the server during the handshake
+    /// (`Sec-WebSocket-Protocol` response header).
+    ///
+    /// `None` if the server didn't select any subprotocol or transport wasn't created with
+    /// [new_with_response](Transport::new_with_response).
+    pub fn selected_protocol(&self) -> Option<&str> {
+        self.response_headers
+            .as_ref()?
+            .get(SEC_WEBSOCKET_PROTOCOL)?
+            .to_str()
+            .ok()
+    }
+
+    /// Extensions (along with their parameters, for example
+    /// `permessage-deflate; client_max_window_bits=15`) accepted by the server during
+    /// the handshake (`Sec-WebSocket-Extensions` response headers).
+    ///
+    /// Empty if no extension was accepted or transport wasn't created with
+    /// [new_with_response](Transport::new_with_response).
+    pub fn extensions(&self) -> Vec<&str> {
+        self.response_headers
+            .iter()
+            .flat_map(|headers| headers.get_all(SEC_WEBSOCKET_EXTENSIONS))
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|extension| !extension.is_empty())
+            .collect()
+    }
+
+    /// Codec used to encode outgoing messages.
+    ///
+    /// It's also used to decode incoming messages unless a separate receiving
+    /// codec was set with [swap_receive_codec](Transport::swap_receive_codec)
+    /// or [swap_send_codec](Transport::swap_send_codec).
+    pub fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    /// Mutable reference to codec used to encode outgoing messages.
+    ///
+    /// See [codec](Transport::codec).
+    pub fn codec_mut(&mut self) -> &mut Codec {
+        &mut self.codec
+    }
+
+    /// Replace codec used in both directions.
+    ///
+    /// Messages sent after this call are encoded with the new codec,
+    /// next received message is decoded with the new codec (even if it was already
+    /// received by the underlying stream).
+    ///
+    /// Useful for protocol version negotiation: exchange first messages using bootstrap codec,
+    /// then switch both ends to codec of agreed version.
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.swap_codec(codec);
+    }
+
+    /// Replace codec used in both directions, returning the current (sending) one.
+    ///
+    /// Messages sent after this call are encoded with the new codec,
+    /// next received message is decoded with the new codec.
+    pub fn swap_codec(&mut self, codec: Codec) -> Codec {
+        self.receive_codec = None;
+        std::mem::replace(&mut self.codec, codec)
+    }
+
+    /// Replace codec used to encode outgoing messages.
+    ///
+    /// Messages sent before this call (even if not flushed yet) keep
+    /// using the old codec. Received messages are still decoded with
+    /// the old codec until [swap_receive_codec](Transport::swap_receive_codec) is called.
+    pub fn swap_send_codec(&mut self, codec: Codec) {
+        let old = std::mem::replace(&mut self.codec, codec);
+        self.receive_codec.get_or_insert(old);
+    }
+
+    /// Replace codec used to decode incoming messages.
+    ///
+    /// Takes effect starting with the next received message.
+    pub fn swap_receive_codec(&mut self, codec: Codec) {
+        self.receive_codec = Some(codec);
+    }
+
+    /// Send message already encoded with transport's codec and flush it.
+    ///
+    /// Useful for sending the same message to many clients without encoding it
+    /// for each of them separately (see `SharedEncoded` of
+    /// [mezzenger-utils](https://crates.io/crates/mezzenger-utils)).
+    ///
+    /// **NOTE**: Transport can't verify message was encoded as `Outgoing` with its codec -
+    /// otherwise the peer fails to decode it.
+    pub async fn send_encoded(
+        &mut self,
+        message: impl Into<Vec<u8>>,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        let message = message.into();
+        if message.len() > self.max_message_size {
+            return Err(mezzenger::Error::Other(Error::MessageTooLarge(
+                message.len(),
+            )));
+        }
+        self.inner
+            .send(Message::binary(message))
+            .await
+            .map_err(map_error)
+    }
+
+    /// Maximum serialized message size.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Change maximum serialized message size.
+    ///
+    /// Applies to messages sent and received after this call.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Current flush policy.
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.flush_policy
+    }
+
+    /// Set when flushing the transport actually flushes the underlying stream.
+    ///
+    /// See [FlushPolicy].
+    pub fn set_flush_policy(&mut self, flush_policy: FlushPolicy) {
+        self.flush_policy = flush_policy;
+    }
+
+    /// Send all messages and flush them once.
+    ///
+    /// Preferred over sending (and flushing) messages one by one, which results
+    /// in a write per message.
+    pub async fn send_batch(
+        &mut self,
+        messages: impl IntoIterator<Item = Outgoing>,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        for message in messages {
+            self.feed(message).await?;
+        }
+        self.force_flush().await
+    }
+
+    /// Flush all pending messages regardless of [flush policy](Transport::set_flush_policy).
+    pub async fn force_flush(
+        &mut self,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        poll_fn(|cx| self.poll_force_flush(cx)).await
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll_force_flush(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>
+    {
+        let result = futures::ready!(self.inner.poll_flush_unpin(cx)).map_err(map_error);
+        if result.is_ok() {
+            self.unflushed_bytes = 0;
+        }
+        Poll::Ready(result)
+    }
+
+    /// Try to flush all pending messages and send close frame without waiting,
+    /// consuming the transport.
+    ///
+    /// Useful when the underlying stream accepts data immediately (for example in tests) -
+    /// otherwise prefer [close_gracefully](Transport::close_gracefully) or
+    /// [flush on drop](Transport::new_with_flush_on_drop).<br>
+    /// If pending messages couldn't be written right away [Error::UnflushedData]
+    /// is returned (they are lost).
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    pub fn try_flush_sync_on_drop(
+        mut self,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        match self.inner.poll_close_unpin(&mut cx) {
+            Poll::Ready(Ok(())) => Ok(()),
+            Poll::Ready(Err(error)) => Err(map_error(error)),
+            Poll::Pending => Err(mezzenger::Error::Other(Error::UnflushedData)),
+        }
+    }
+
+    /// Flush all pending messages, send close frame and wait for the peer
+    /// to acknowledge it.
+    ///
+    /// Returns once the peer acknowledged closing or `timeout` elapsed.<br>
+    /// If pending messages (or close frame) couldn't be flushed before `timeout` elapsed
+    /// [Error::UnflushedData] is returned.
+    ///
+    /// **NOTE**: Messages received while waiting for the peer are discarded.
+    pub async fn close_gracefully(
+        mut self,
+        timeout: Duration,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        let deadline = Instant::now() + timeout;
+
+        match timeout_at(deadline, self.inner.flush()).await {
+            Ok(result) => result.map_err(map_error)?,
+            Err(_) => return Err(mezzenger::Error::Other(Error::UnflushedData)),
+        }
+
+        match timeout_at(deadline, self.inner.close()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => {
+                return match map_error(error) {
+                    mezzenger::Error::Closed => Ok(()),
+                    error => Err(error),
+                }
+            }
+            Err(_) => return Err(mezzenger::Error::Other(Error::UnflushedData)),
+        }
+
+        let _ = timeout_at(deadline, async {
+            while let Some(Ok(_)) = self.inner.next().await {}
+        })
+        .await;
+
+        Ok(())
+    }
 }
 
 impl<T, Codec, Incoming, Outgoing> Sink<Outgoing> for Transport<T, Codec, Incoming, Outgoing>
@@ -108,16 +469,29 @@ where
 
     fn start_send(mut self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
         let mut buffer = vec![];
+        #[cfg(feature = "type-tag")]
+        buffer.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
         self.codec
             .encode(&mut buffer, &item)
             .map_err(Error::SerializationError)
             .map_err(mezzenger::Error::Other)?;
+        if buffer.len() > self.max_message_size {
+            return Err(mezzenger::Error::Other(Error::MessageTooLarge(
+                buffer.len(),
+            )));
+        }
+        self.unflushed_bytes += buffer.len();
         let message = Message::binary(buffer);
         self.inner.start_send_unpin(message).map_err(map_error)
     }
 
+    /// Flush pending messages if [flush policy](Transport::set_flush_policy) allows it.
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_flush_unpin(cx).map_err(map_error)
+        if self.flush_policy.should_flush(self.unflushed_bytes) {
+            self.poll_force_flush(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -125,6 +499,35 @@ where
     }
 }
 
+#[pinned_drop]
+impl<T, Codec, Incoming, Outgoing> PinnedDrop for Transport<T, Codec, Incoming, Outgoing>
+where
+    T: Sink<Message, Error = tungstenite::Error>
+        + Stream<Item = Result<Message, tungstenite::Error>>
+        + Unpin,
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+    Outgoing: Serialize,
+{
+    fn drop(self: Pin<&mut Self>) {
+        let me = self.project();
+        // SAFETY: `inner` isn't used after this point.
+        let inner = unsafe { ManuallyDrop::take(me.inner) };
+        if let Some(DropFlush { handle, spawn }) = me.flush_on_drop.take() {
+            spawn(&handle, inner);
+        }
+    }
+}
+
+fn spawn_flush<T>(handle: &Handle, mut stream: T)
+where
+    T: Sink<Message, Error = tungstenite::Error> + Unpin + Send + 'static,
+{
+    handle.spawn(async move {
+        let _ = stream.close().await;
+    });
+}
+
 fn map_error<SerializationError, DeserializationError>(
     tungstenite_error: tungstenite::Error,
 ) -> mezzenger::Error<Error<SerializationError, DeserializationError>> {
@@ -156,7 +559,23 @@ where
                     match item {
                         Ok(message) => match message {
                             Message::Binary(message) => {
-                                let result: Result<Incoming, _> = self.codec.decode(&message[..]);
+                                if message.len() > self.max_message_size {
+                                    return Poll::Ready(Some(Err(Error::MessageTooLarge(
+                                        message.len(),
+                                    ))));
+                                }
+                                let codec = self.receive_codec.as_ref().unwrap_or(&self.codec);
+                                let message = &message[..];
+                                #[cfg(feature = "type-tag")]
+                                let message =
+                                    match mezzenger::type_tag::check::<Incoming, Outgoing>(message)
+                                    {
+                                        Ok(message) => message,
+                                        Err(mismatch) => {
+                                            return Poll::Ready(Some(Err(mismatch.into())))
+                                        }
+                                    };
+                                let result: Result<Incoming, _> = codec.decode(message);
                                 match result {
                                     Ok(message) => Poll::Ready(Some(Ok(message))),
                                     Err(error) => {
@@ -225,3 +644,339 @@ where
     Outgoing: Serialize,
 {
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures::{
+        channel::mpsc::{unbounded, UnboundedSender},
+        executor::block_on,
+        Sink, SinkExt, Stream, StreamExt,
+    };
+    use kodec::binary::Codec;
+    use mezzenger::FlushOnDrop;
+    use tungstenite::{http, Message};
+
+    use crate::{FlushPolicy, Transport};
+
+    /// Size of type tag prepended to every message.
+    #[cfg(feature = "type-tag")]
+    const TAG_SIZE: usize = mezzenger::type_tag::TAG_SIZE;
+    #[cfg(not(feature = "type-tag"))]
+    const TAG_SIZE: usize = 0;
+
+    /// Sink counting flushes and recording flushed messages,
+    /// stream echoes flushed messages back.
+    #[derive(Default)]
+    struct CountingSink {
+        buffered: Vec<Message>,
+        flushed: Vec<Message>,
+        flushes: usize,
+    }
+
+    impl Sink<Message> for CountingSink {
+        type Error = tungstenite::Error;
+
+        fn poll_ready(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+            self.buffered.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            let me = &mut *self;
+            me.flushes += 1;
+            me.flushed.append(&mut me.buffered);
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    impl Stream for CountingSink {
+        type Item = Result<Message, tungstenite::Error>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            if self.flushed.is_empty() {
+                Poll::Ready(None)
+            } else {
+                Poll::Ready(Some(Ok(self.flushed.remove(0))))
+            }
+        }
+    }
+
+    fn transport(flush_policy: FlushPolicy) -> Transport<CountingSink, Codec, (), u32> {
+        let mut transport = Transport::new(CountingSink::default(), Codec::default());
+        transport.set_flush_policy(flush_policy);
+        transport
+    }
+
+    fn flushed(transport: &Transport<CountingSink, Codec, (), u32>) -> Vec<Message> {
+        transport.inner.flushed.clone()
+    }
+
+    fn encoded(messages: impl IntoIterator<Item = u32>) -> Vec<Message> {
+        messages
+            .into_iter()
+            .map(|message| {
+                let mut encoded = vec![];
+                #[cfg(feature = "type-tag")]
+                encoded.extend_from_slice(&mezzenger::type_tag::tag::<u32>());
+                encoded.extend_from_slice(&message.to_le_bytes());
+                Message::binary(encoded)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_every_message_flush_policy() {
+        block_on(async {
+            let mut transport = transport(FlushPolicy::EveryMessage);
+            for i in 0..50 {
+                transport.send(i).await.unwrap();
+            }
+            assert_eq!(transport.inner.flushes, 50);
+            assert_eq!(flushed(&transport), encoded(0..50));
+
+            transport.send_batch(50..100).await.unwrap();
+            assert_eq!(transport.inner.flushes, 51);
+            assert_eq!(flushed(&transport), encoded(0..100));
+        });
+    }
+
+    #[test]
+    fn test_manual_flush_policy() {
+        block_on(async {
+            let mut transport = transport(FlushPolicy::Manual);
+            for i in 0..50 {
+                transport.send(i).await.unwrap();
+            }
+            assert_eq!(transport.inner.flushes, 0);
+            assert!(flushed(&transport).is_empty());
+
+            transport.force_flush().await.unwrap();
+            assert_eq!(transport.inner.flushes, 1);
+            assert_eq!(flushed(&transport), encoded(0..50));
+
+            transport.send_batch(50..60).await.unwrap();
+            assert_eq!(transport.inner.flushes, 2);
+            assert_eq!(flushed(&transport), encoded(0..60));
+        });
+    }
+
+    #[test]
+    fn test_bytes_flush_policy() {
+        block_on(async {
+            // Every message is encoded as 4 bytes (+ type tag).
+            let mut transport = transport(FlushPolicy::Bytes(4 * (4 + TAG_SIZE)));
+            for i in 0..10 {
+                transport.send(i).await.unwrap();
+            }
+            assert_eq!(transport.inner.flushes, 2);
+            assert_eq!(flushed(&transport), encoded(0..8));
+
+            transport.force_flush().await.unwrap();
+            assert_eq!(transport.inner.flushes, 3);
+            assert_eq!(flushed(&transport), encoded(0..10));
+
+            transport.close().await.unwrap();
+            assert_eq!(flushed(&transport), encoded(0..10));
+        });
+    }
+
+    /// Sink forwarding flushed messages to a channel.
+    struct ChannelSink {
+        buffered: Vec<Message>,
+        sender: UnboundedSender<Message>,
+    }
+
+    impl Sink<Message> for ChannelSink {
+        type Error = tungstenite::Error;
+
+        fn poll_ready(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+            self.buffered.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            let me = &mut *self;
+            for message in me.buffered.drain(..) {
+                let _ = me.sender.unbounded_send(message);
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    impl Stream for ChannelSink {
+        type Item = Result<Message, tungstenite::Error>;
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_drop() {
+        for flush_on_drop in [FlushOnDrop::Detached, FlushOnDrop::Disabled] {
+            let (sender, receiver) = unbounded();
+            let sink = ChannelSink {
+                buffered: vec![],
+                sender,
+            };
+            let mut transport: Transport<_, Codec, (), u32> =
+                Transport::new_with_flush_on_drop(sink, Codec::default(), flush_on_drop);
+            transport.set_flush_policy(FlushPolicy::Manual);
+            for i in 0..10 {
+                transport.send(i).await.unwrap();
+            }
+            drop(transport);
+
+            let flushed: Vec<Message> = receiver.collect().await;
+            match flush_on_drop {
+                FlushOnDrop::Detached => assert_eq!(flushed, encoded(0..10)),
+                FlushOnDrop::Disabled => assert!(flushed.is_empty()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_flush_sync_on_drop() {
+        let (sender, receiver) = unbounded();
+        let sink = ChannelSink {
+            buffered: vec![],
+            sender,
+        };
+        let mut transport: Transport<_, Codec, (), u32> = Transport::new(sink, Codec::default());
+        transport.set_flush_policy(FlushPolicy::Manual);
+        block_on(async {
+            for i in 0..10 {
+                transport.send(i).await.unwrap();
+            }
+        });
+        transport.try_flush_sync_on_drop().unwrap();
+        assert_eq!(block_on(receiver.collect::<Vec<_>>()), encoded(0..10));
+    }
+
+    #[test]
+    fn test_handshake_response() {
+        let response = http::Response::builder()
+            .header("Sec-WebSocket-Protocol", "v2")
+            .header(
+                "Sec-WebSocket-Extensions",
+                "permessage-deflate; client_max_window_bits=15, x-custom",
+            )
+            .header("Sec-WebSocket-Extensions", "x-other")
+            .body(None)
+            .unwrap();
+        let connected: Transport<_, Codec, (), u32> =
+            Transport::new_with_response((CountingSink::default(), response), Codec::default());
+        assert_eq!(connected.selected_protocol(), Some("v2"));
+        assert_eq!(
+            connected.extensions(),
+            vec![
+                "permessage-deflate; client_max_window_bits=15",
+                "x-custom",
+                "x-other"
+            ]
+        );
+        assert!(connected.response_headers().is_some());
+
+        let transport = transport(FlushPolicy::EveryMessage);
+        assert_eq!(transport.selected_protocol(), None);
+        assert!(transport.extensions().is_empty());
+        assert!(transport.response_headers().is_none());
+    }
+
+    #[test]
+    fn test_max_message_size() {
+        use mezzenger::Receive;
+
+        use crate::Error;
+
+        block_on(async {
+            let mut transport: Transport<CountingSink, Codec, Vec<u8>, Vec<u8>> =
+                Transport::new_with_max_message_size(
+                    CountingSink::default(),
+                    Codec::default(),
+                    16 + TAG_SIZE,
+                );
+            assert_eq!(transport.max_message_size(), 16 + TAG_SIZE);
+
+            // every message is encoded as (type tag +) 8-byte length followed by its content
+            transport.send(vec![1; 8]).await.unwrap();
+            assert!(matches!(
+                transport.send(vec![1; 9]).await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 17 + TAG_SIZE
+            ));
+            assert!(matches!(
+                transport.send_encoded(vec![0; 17 + TAG_SIZE]).await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 17 + TAG_SIZE
+            ));
+            assert_eq!(transport.inner.flushed.len(), 1);
+
+            transport.send(vec![2; 4]).await.unwrap();
+            transport.set_max_message_size(12 + TAG_SIZE);
+            assert!(matches!(
+                transport.receive().await,
+                Err(mezzenger::Error::Other(Error::MessageTooLarge(size))) if size == 16 + TAG_SIZE
+            ));
+            assert_eq!(transport.receive().await.unwrap(), vec![2; 4]);
+        });
+    }
+
+    #[cfg(feature = "type-tag")]
+    #[test]
+    fn test_type_mismatch() {
+        use mezzenger::Receive;
+
+        use crate::Error;
+
+        block_on(async {
+            // echoed message is tagged as `String` - deliberately mismatched with `u32`
+            let mut transport: Transport<CountingSink, Codec, u32, String> =
+                Transport::new(CountingSink::default(), Codec::default());
+            transport.send("Hello World!".to_string()).await.unwrap();
+            match transport.receive().await {
+                Err(mezzenger::Error::Other(error @ Error::TypeMismatch { .. })) => assert_eq!(
+                    error.to_string(),
+                    "message type mismatch (expected u32, got alloc::string::String)"
+                ),
+                result => panic!("unexpected result: {result:?}"),
+            }
+
+            let mut transport: Transport<CountingSink, Codec, String, String> =
+                Transport::new(CountingSink::default(), Codec::default());
+            transport.send("Hello World!".to_string()).await.unwrap();
+            assert_eq!(transport.receive().await.unwrap(), "Hello World!");
+        });
+    }
+}
@@ -4,22 +4,35 @@
 
 use std::{
     fmt::{Debug, Display},
+    future::Future,
     marker::PhantomData,
     pin::Pin,
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
+    time::Duration,
 };
 
-use futures::{stream::FusedStream, Sink, SinkExt, Stream, StreamExt};
+use futures::{future::poll_fn, stream::FusedStream, Sink, SinkExt, Stream, StreamExt};
 use kodec::{Decode, Encode};
 use pin_project::pin_project;
 use serde::Serialize;
+use tokio::time::{sleep, timeout_at, Instant, Sleep};
 use warp::ws::Message;
 
+use crate::{FlushPolicy, DEFAULT_MAX_MESSAGE_SIZE};
+
 #[derive(Debug)]
 pub enum Error<SerializationError, DeserializationError> {
+    MessageTooLarge(usize),
     SerializationError(SerializationError),
     DeserializationError(DeserializationError),
+    #[cfg(feature = "type-tag")]
+    TypeMismatch {
+        expected: &'static str,
+        got: String,
+    },
     WarpError(warp::Error),
+    UnflushedData,
+    RateLimitExceeded,
 }
 
 impl<SerializationError, DeserializationError> Display
@@ -30,11 +43,18 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Error::MessageTooLarge(size) => write!(f, "message was too large ({size} bytes)"),
             Error::SerializationError(error) => write!(f, "failed to serialize message: {error}"),
             Error::DeserializationError(error) => {
                 write!(f, "failed to deserialize message: {error}")
             }
+            #[cfg(feature = "type-tag")]
+            Error::TypeMismatch { expected, got } => {
+                write!(f, "message type mismatch (expected {expected}, got {got})")
+            }
             Error::WarpError(error) => write!(f, "warp error occurred: {error}"),
+            Error::UnflushedData => write!(f, "timed out before pending messages were flushed"),
+            Error::RateLimitExceeded => write!(f, "incoming message rate limit exceeded"),
         }
     }
 }
@@ -47,6 +67,119 @@ where
 {
 }
 
+#[cfg(feature = "type-tag")]
+impl<SerializationError, DeserializationError> From<mezzenger::type_tag::TypeMismatch>
+    for Error<SerializationError, DeserializationError>
+{
+    fn from(mismatch: mezzenger::type_tag::TypeMismatch) -> Self {
+        Error::TypeMismatch {
+            expected: mismatch.expected,
+            got: mismatch.got,
+        }
+    }
+}
+
+/// Close code sent when peer exceeds [kill threshold](RateLimit::kill_threshold)
+/// (policy violation).
+pub const RATE_LIMIT_CLOSE_CODE: u16 = 1008;
+
+/// Limit of incoming message rate (see [Transport::set_max_incoming_rate]).
+///
+/// Implemented as a token bucket holding up to `burst` tokens, refilled with
+/// `messages_per_second` tokens per second - every received message takes one token.<br>
+/// When the bucket is empty the transport stops reading from the underlying stream
+/// until a token is available, so the peer is slowed down by TCP backpressure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Sustained number of messages per second (has to be greater than `0`).
+    pub messages_per_second: u32,
+    /// Number of messages that can be received at once after a period of inactivity
+    /// (at least one).
+    pub burst: u32,
+    /// Close the connection (with [RATE_LIMIT_CLOSE_CODE]) once more than this many
+    /// messages had to be delayed since the bucket was last full.
+    ///
+    /// `None` (the default) only delays messages.
+    pub kill_threshold: Option<u32>,
+}
+
+impl RateLimit {
+    /// Create new rate limit without kill threshold.
+    pub fn new(messages_per_second: u32, burst: u32) -> Self {
+        RateLimit {
+            messages_per_second,
+            burst,
+            kill_threshold: None,
+        }
+    }
+
+    /// Set [kill threshold](RateLimit::kill_threshold).
+    pub fn with_kill_threshold(mut self, kill_threshold: u32) -> Self {
+        self.kill_threshold = Some(kill_threshold);
+        self
+    }
+}
+
+/// Token bucket enforcing [RateLimit].
+struct RateLimiter {
+    limit: RateLimit,
+    tokens: f64,
+    refilled: Instant,
+    delayed: u32,
+    held: Option<Message>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    close_sent: bool,
+}
+
+impl RateLimiter {
+    fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            limit,
+            tokens: limit.burst.max(1) as f64,
+            refilled: Instant::now(),
+            delayed: 0,
+            held: None,
+            sleep: None,
+            close_sent: false,
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.refilled;
+        self.refilled = now;
+        let burst = self.limit.burst.max(1) as f64;
+        self.tokens =
+            burst.min(self.tokens + elapsed.as_secs_f64() * self.limit.messages_per_second as f64);
+        if self.tokens >= burst {
+            self.delayed = 0;
+        }
+    }
+
+    /// Take a token, waiting until one is available.
+    fn poll_acquire(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if let Some(sleep) = &mut self.sleep {
+                ready!(sleep.as_mut().poll(cx));
+                self.sleep = None;
+            }
+            self.refill();
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+                return Poll::Ready(());
+            }
+            let wait = (1.0 - self.tokens) / self.limit.messages_per_second as f64;
+            self.sleep = Some(Box::pin(sleep(Duration::from_secs_f64(wait))));
+        }
+    }
+
+    fn is_exceeded(&self) -> bool {
+        self.limit
+            .kill_threshold
+            .is_some_and(|threshold| self.delayed > threshold)
+    }
+}
+
 /// Web Socket transport for [warp](https://github.com/seanmonstar/warp).
 ///
 /// Wraps around [warp::filters::ws::WebSocket].
@@ -64,6 +197,10 @@ where
     inner: T,
     codec: Codec,
     terminated: bool,
+    flush_policy: FlushPolicy,
+    unflushed_bytes: usize,
+    max_message_size: usize,
+    rate_limiter: Option<RateLimiter>,
     _incoming: PhantomData<Incoming>,
     _outgoing: PhantomData<Outgoing>,
 }
@@ -76,15 +213,209 @@ where
     Outgoing: Serialize,
 {
     /// Create new transport wrapping a provided `[warp::filters::ws::WebSocket]`.
+    ///
+    /// **NOTE**: By default serialized message size is limited to [DEFAULT_MAX_MESSAGE_SIZE].<br>
+    /// Sending or receiving messages of larger size will result in [Error::MessageTooLarge].
     pub fn new(stream: T, codec: Codec) -> Self {
+        Transport::new_with_max_message_size(stream, codec, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Create new transport wrapping a provided `[warp::filters::ws::WebSocket]`.
+    ///
+    /// Serialized message size will be limited to `max_message_size`.<br>
+    /// Sending or receiving messages of larger size will result in [Error::MessageTooLarge].
+    ///
+    /// **NOTE**: Incoming messages are checked once the underlying stream received them
+    /// in full - limit size of messages accepted by the stream itself too (see
+    /// [Ws::max_message_size](warp::ws::Ws::max_message_size)) to bound memory used
+    /// while receiving them.
+    pub fn new_with_max_message_size(stream: T, codec: Codec, max_message_size: usize) -> Self {
         Transport {
             inner: stream,
             codec,
             terminated: false,
+            flush_policy: FlushPolicy::default(),
+            unflushed_bytes: 0,
+            max_message_size,
+            rate_limiter: None,
             _incoming: PhantomData,
             _outgoing: PhantomData,
         }
     }
+
+    /// Codec used to encode outgoing messages and decode incoming ones.
+    pub fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    /// Mutable reference to codec used to encode outgoing messages and decode incoming ones.
+    pub fn codec_mut(&mut self) -> &mut Codec {
+        &mut self.codec
+    }
+
+    /// Maximum serialized message size.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Change maximum serialized message size.
+    ///
+    /// Applies to messages sent and received after this call.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Limit rate of incoming messages (`None` disables the limit).
+    ///
+    /// See [RateLimit].<br>
+    /// Once the [kill threshold](RateLimit::kill_threshold) is exceeded the connection
+    /// is closed and receiving stream returns [Error::RateLimitExceeded] before ending.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if [messages_per_second](RateLimit::messages_per_second) is `0`.
+    pub fn set_max_incoming_rate(&mut self, rate_limit: Option<RateLimit>) {
+        if let Some(rate_limit) = &rate_limit {
+            assert!(
+                rate_limit.messages_per_second > 0,
+                "message rate has to be greater than 0"
+            );
+        }
+        self.rate_limiter = rate_limit.map(RateLimiter::new);
+    }
+
+    /// Current limit of incoming message rate.
+    pub fn max_incoming_rate(&self) -> Option<RateLimit> {
+        self.rate_limiter.as_ref().map(|limiter| limiter.limit)
+    }
+
+    /// Poll the underlying stream respecting [incoming rate limit](Transport::set_max_incoming_rate).
+    ///
+    /// Returns `None` after closing the connection when the limit was exceeded.
+    fn poll_inner(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Message, warp::Error>>> {
+        let Some(limiter) = &mut self.rate_limiter else {
+            return self.inner.poll_next_unpin(cx);
+        };
+        if limiter.is_exceeded() {
+            if !limiter.close_sent {
+                if ready!(self.inner.poll_ready_unpin(cx)).is_ok() {
+                    let close = Message::close_with(RATE_LIMIT_CLOSE_CODE, "rate limit exceeded");
+                    let _ = self.inner.start_send_unpin(close);
+                }
+                limiter.close_sent = true;
+            }
+            let _ = ready!(self.inner.poll_close_unpin(cx));
+            return Poll::Ready(None);
+        }
+        let fresh = limiter.held.is_none();
+        let message = match limiter.held.take() {
+            Some(message) => message,
+            None => match ready!(self.inner.poll_next_unpin(cx)) {
+                Some(Ok(message)) => message,
+                item => return Poll::Ready(item),
+            },
+        };
+        match limiter.poll_acquire(cx) {
+            Poll::Ready(()) => Poll::Ready(Some(Ok(message))),
+            Poll::Pending => {
+                if fresh {
+                    limiter.delayed += 1;
+                    if limiter.is_exceeded() {
+                        return self.poll_inner(cx);
+                    }
+                }
+                limiter.held = Some(message);
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Current flush policy.
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.flush_policy
+    }
+
+    /// Set when flushing the transport actually flushes the underlying stream.
+    ///
+    /// See [FlushPolicy].
+    pub fn set_flush_policy(&mut self, flush_policy: FlushPolicy) {
+        self.flush_policy = flush_policy;
+    }
+
+    /// Send all messages and flush them once.
+    ///
+    /// Preferred over sending (and flushing) messages one by one, which results
+    /// in a write per message.
+    pub async fn send_batch(
+        &mut self,
+        messages: impl IntoIterator<Item = Outgoing>,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        for message in messages {
+            self.feed(message).await?;
+        }
+        self.force_flush().await
+    }
+
+    /// Flush all pending messages regardless of [flush policy](Transport::set_flush_policy).
+    pub async fn force_flush(
+        &mut self,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        poll_fn(|cx| self.poll_force_flush(cx)).await
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll_force_flush(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>
+    {
+        let result = futures::ready!(self.inner.poll_flush_unpin(cx)).map_err(map_warp_error);
+        if result.is_ok() {
+            self.unflushed_bytes = 0;
+        }
+        Poll::Ready(result)
+    }
+
+    /// Flush all pending messages, send close frame and wait for the peer
+    /// to acknowledge it.
+    ///
+    /// Returns once the peer acknowledged closing or `timeout` elapsed.<br>
+    /// If pending messages (or close frame) couldn't be flushed before `timeout` elapsed
+    /// [Error::UnflushedData] is returned.
+    ///
+    /// **NOTE**: Messages received while waiting for the peer are discarded.
+    pub async fn close_gracefully(
+        mut self,
+        timeout: Duration,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        let deadline = Instant::now() + timeout;
+
+        match timeout_at(deadline, self.inner.flush()).await {
+            Ok(result) => result.map_err(map_warp_error)?,
+            Err(_) => return Err(mezzenger::Error::Other(Error::UnflushedData)),
+        }
+
+        match timeout_at(deadline, self.inner.close()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => {
+                return match map_warp_error(error) {
+                    mezzenger::Error::Closed => Ok(()),
+                    error => Err(error),
+                }
+            }
+            Err(_) => return Err(mezzenger::Error::Other(Error::UnflushedData)),
+        }
+
+        let _ = timeout_at(deadline, async {
+            while let Some(Ok(_)) = self.inner.next().await {}
+        })
+        .await;
+
+        Ok(())
+    }
 }
 
 impl<T, Codec, Incoming, Outgoing> Sink<Outgoing> for Transport<T, Codec, Incoming, Outgoing>
@@ -102,16 +433,29 @@ where
 
     fn start_send(mut self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
         let mut buffer = vec![];
+        #[cfg(feature = "type-tag")]
+        buffer.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
         self.codec
             .encode(&mut buffer, &item)
             .map_err(Error::SerializationError)
             .map_err(mezzenger::Error::Other)?;
+        if buffer.len() > self.max_message_size {
+            return Err(mezzenger::Error::Other(Error::MessageTooLarge(
+                buffer.len(),
+            )));
+        }
+        self.unflushed_bytes += buffer.len();
         let message = Message::binary(buffer);
         self.inner.start_send_unpin(message).map_err(map_warp_error)
     }
 
+    /// Flush pending messages if [flush policy](Transport::set_flush_policy) allows it.
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_flush_unpin(cx).map_err(map_warp_error)
+        if self.flush_policy.should_flush(self.unflushed_bytes) {
+            self.poll_force_flush(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -151,13 +495,29 @@ where
     type Item = Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.inner.poll_next_unpin(cx) {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        match self.poll_inner(cx) {
             Poll::Ready(item) => {
                 if let Some(item) = item {
                     match item {
                         Ok(message) => {
                             if message.is_binary() {
                                 let bytes = message.as_bytes();
+                                if bytes.len() > self.max_message_size {
+                                    return Poll::Ready(Some(Err(Error::MessageTooLarge(
+                                        bytes.len(),
+                                    ))));
+                                }
+                                #[cfg(feature = "type-tag")]
+                                let bytes =
+                                    match mezzenger::type_tag::check::<Incoming, Outgoing>(bytes) {
+                                        Ok(bytes) => bytes,
+                                        Err(mismatch) => {
+                                            return Poll::Ready(Some(Err(mismatch.into())))
+                                        }
+                                    };
                                 let result: Result<Incoming, _> = self.codec.decode(bytes);
                                 match result {
                                     Ok(message) => Poll::Ready(Some(Ok(message))),
@@ -203,7 +563,15 @@ where
                     }
                 } else {
                     self.terminated = true;
-                    Poll::Ready(None)
+                    let exceeded = self
+                        .rate_limiter
+                        .as_ref()
+                        .is_some_and(RateLimiter::is_exceeded);
+                    if exceeded {
+                        Poll::Ready(Some(Err(Error::RateLimitExceeded)))
+                    } else {
+                        Poll::Ready(None)
+                    }
                 }
             }
             Poll::Pending => Poll::Pending,
@@ -240,3 +608,139 @@ where
     Outgoing: Serialize,
 {
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    use futures::{Sink, Stream};
+    use kodec::binary::Codec;
+    use mezzenger::Receive;
+    use tokio::time::Instant;
+    use warp::ws::Message;
+
+    use super::{Error, RateLimit, Transport, RATE_LIMIT_CLOSE_CODE};
+
+    /// Client sending `remaining` messages as fast as possible,
+    /// recording messages sent to it.
+    struct ScriptedClient {
+        remaining: u32,
+        received: Vec<Message>,
+        closed: bool,
+    }
+
+    impl ScriptedClient {
+        fn new(messages: u32) -> Self {
+            ScriptedClient {
+                remaining: messages,
+                received: vec![],
+                closed: false,
+            }
+        }
+    }
+
+    impl Sink<Message> for ScriptedClient {
+        type Error = warp::Error;
+
+        fn poll_ready(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+            self.received.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Stream for ScriptedClient {
+        type Item = Result<Message, warp::Error>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            if self.closed || self.remaining == 0 {
+                Poll::Ready(None)
+            } else {
+                self.remaining -= 1;
+                let mut message = vec![];
+                #[cfg(feature = "type-tag")]
+                message.extend_from_slice(&mezzenger::type_tag::tag::<u32>());
+                message.extend_from_slice(&self.remaining.to_le_bytes());
+                Poll::Ready(Some(Ok(Message::binary(message))))
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_incoming_rate() {
+        let mut transport: Transport<_, Codec, u32, ()> =
+            Transport::new(ScriptedClient::new(60), Codec::default());
+        transport.set_max_incoming_rate(Some(RateLimit::new(20, 10)));
+        let start = Instant::now();
+
+        // burst is received immediately
+        for expected in (50..60).rev() {
+            assert_eq!(transport.receive().await.unwrap(), expected);
+        }
+        assert!(start.elapsed() < Duration::from_millis(1));
+
+        for expected in (0..50).rev() {
+            assert_eq!(transport.receive().await.unwrap(), expected);
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(2500));
+        assert!(elapsed < Duration::from_millis(3000));
+        assert!(matches!(
+            transport.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+        assert!(!transport.inner.closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_kill_threshold() {
+        let mut transport: Transport<_, Codec, u32, ()> =
+            Transport::new(ScriptedClient::new(100), Codec::default());
+        transport.set_max_incoming_rate(Some(RateLimit::new(20, 10).with_kill_threshold(5)));
+
+        // 10 messages of the burst and 5 delayed ones
+        for _ in 0..15 {
+            transport.receive().await.unwrap();
+        }
+        assert!(matches!(
+            transport.receive().await,
+            Err(mezzenger::Error::Other(Error::RateLimitExceeded))
+        ));
+        assert!(matches!(
+            transport.receive().await,
+            Err(mezzenger::Error::Closed)
+        ));
+
+        let client = &transport.inner;
+        assert!(client.closed);
+        assert_eq!(
+            client.received[0].close_frame(),
+            Some((RATE_LIMIT_CLOSE_CODE, "rate limit exceeded"))
+        );
+    }
+}
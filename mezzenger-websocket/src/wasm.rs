@@ -5,29 +5,41 @@ use std::{
     cell::RefCell,
     collections::VecDeque,
     fmt::{Debug, Display},
+    io::{self, BufReader, Read},
     marker::PhantomData,
     pin::Pin,
     rc::Rc,
-    task::{Context, Poll, Waker},
+    task::{ready, Context, Poll, Waker},
+    time::Duration,
 };
 
 use futures::{stream::FusedStream, Sink, Stream};
-use js_sys::Uint8Array;
+use js_sys::{ArrayBuffer, Uint8Array};
 use js_utils::{
     event::{EventListener, When},
     JsError, Queue,
 };
 use kodec::{Decode, Encode};
 use serde::Serialize;
+use wasm_bindgen::JsCast;
 use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
 
+use crate::DEFAULT_MAX_MESSAGE_SIZE;
+
 #[derive(Debug)]
 pub enum Error<SerializationError, DeserializationError> {
+    MessageTooLarge(usize),
     SendingError(JsError),
     ClosingError(JsError),
     SerializationError(SerializationError),
     DeserializationError(DeserializationError),
+    #[cfg(feature = "type-tag")]
+    TypeMismatch {
+        expected: &'static str,
+        got: String,
+    },
     WebSocketError(Event),
+    QueueFull,
 }
 
 impl<SerializationError, DeserializationError> Display
@@ -38,13 +50,19 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Error::MessageTooLarge(size) => write!(f, "message was too large ({size} bytes)"),
             Error::SendingError(error) => write!(f, "failed to send message: {error}"),
             Error::ClosingError(error) => write!(f, "failed to close transport: {error}"),
             Error::SerializationError(error) => write!(f, "failed to serialize message: {error}"),
             Error::DeserializationError(error) => {
                 write!(f, "failed to deserialize message: {error}")
             }
+            #[cfg(feature = "type-tag")]
+            Error::TypeMismatch { expected, got } => {
+                write!(f, "message type mismatch (expected {expected}, got {got})")
+            }
             Error::WebSocketError(error) => write!(f, "WebSocket error occurred: {error:?}"),
+            Error::QueueFull => write!(f, "too many messages queued while connecting"),
         }
     }
 }
@@ -57,10 +75,175 @@ where
 {
 }
 
+#[cfg(feature = "type-tag")]
+impl<SerializationError, DeserializationError> From<mezzenger::type_tag::TypeMismatch>
+    for Error<SerializationError, DeserializationError>
+{
+    fn from(mismatch: mezzenger::type_tag::TypeMismatch) -> Self {
+        Error::TypeMismatch {
+            expected: mismatch.expected,
+            got: mismatch.got,
+        }
+    }
+}
+
+/// Default maximum number of outgoing messages queued while WebSocket is connecting.
+///
+/// See [Transport::set_connecting_queue_limit].
+pub const DEFAULT_CONNECTING_QUEUE_LIMIT: usize = 1024;
+
+/// Default threshold of WebSocket's `bufferedAmount` above which sending waits.
+///
+/// See [Transport::set_buffered_amount_threshold].
+pub const DEFAULT_BUFFERED_AMOUNT_THRESHOLD: u32 = 1024 * 1024;
+
+/// Interval in which `bufferedAmount` is checked again while sending waits.
+const BUFFERED_AMOUNT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Wake task after [BUFFERED_AMOUNT_POLL_INTERVAL].
+///
+/// `bufferedAmount` decreases only once the browser gets to run its event loop,
+/// so the check is repeated in a new task (microtask would spin without yielding).
+fn wake_later(waker: Waker) {
+    mezzenger::rt::spawn_local(async move {
+        mezzenger::rt::sleep(BUFFERED_AMOUNT_POLL_INTERVAL).await;
+        waker.wake();
+    });
+}
+
+/// Size of chunks copied from JavaScript memory when decoding incoming messages.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Reader copying contents of JavaScript array into Rust memory on demand.
+///
+/// Lets codec decode incoming message without materializing its whole
+/// serialized form in Rust memory first.
+struct ArrayReader {
+    array: Uint8Array,
+    position: u32,
+}
+
+impl ArrayReader {
+    fn new(array: Uint8Array) -> Self {
+        ArrayReader { array, position: 0 }
+    }
+}
+
+impl Read for ArrayReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.array.length() - self.position;
+        let length = remaining.min(buffer.len().try_into().unwrap_or(u32::MAX));
+        if length > 0 {
+            let end = self.position + length;
+            self.array
+                .subarray(self.position, end)
+                .copy_to(&mut buffer[..length as usize]);
+            self.position = end;
+        }
+        Ok(length as usize)
+    }
+}
+
+fn array_reader(array: Uint8Array) -> BufReader<ArrayReader> {
+    BufReader::with_capacity(READ_CHUNK_SIZE, ArrayReader::new(array))
+}
+
+/// Check [type tag](mezzenger::type_tag) of incoming message, returning the message without it.
+#[cfg(feature = "type-tag")]
+fn untag<Incoming, Outgoing>(
+    array: Uint8Array,
+) -> Result<Uint8Array, mezzenger::type_tag::TypeMismatch> {
+    let length = array.length().min(mezzenger::type_tag::TAG_SIZE as u32);
+    let mut tag = [0; mezzenger::type_tag::TAG_SIZE];
+    array
+        .subarray(0, length)
+        .copy_to(&mut tag[..length as usize]);
+    mezzenger::type_tag::check::<Incoming, Outgoing>(&tag[..length as usize])?;
+    Ok(array.subarray(length, array.length()))
+}
+
+struct LargeMessageState {
+    threshold: Option<u32>,
+    incoming: VecDeque<ArrayBuffer>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl LargeMessageState {
+    fn new() -> Self {
+        LargeMessageState {
+            threshold: None,
+            incoming: VecDeque::new(),
+            waker: None,
+            closed: false,
+        }
+    }
+
+    fn is_large(&self, buffer: &ArrayBuffer) -> bool {
+        self.threshold
+            .map(|threshold| buffer.byte_length() > threshold)
+            .unwrap_or(false)
+    }
+
+    fn message(&mut self, buffer: ArrayBuffer) {
+        self.incoming.push_back(buffer);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Stream of raw incoming messages exceeding size threshold.
+///
+/// Obtained with [Transport::large_messages].
+///
+/// Ends when transport is closed or dropped.
+pub struct LargeMessages {
+    state: Rc<RefCell<LargeMessageState>>,
+}
+
+impl Stream for LargeMessages {
+    type Item = ArrayBuffer;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.state.borrow_mut();
+        if let Some(buffer) = state.incoming.pop_front() {
+            Poll::Ready(Some(buffer))
+        } else if state.closed {
+            debug_assert!(state.incoming.is_empty(), "stream ended with undelivered items");
+            Poll::Ready(None)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl FusedStream for LargeMessages {
+    fn is_terminated(&self) -> bool {
+        let state = self.state.borrow();
+        state.closed && state.incoming.is_empty()
+    }
+}
+
 struct State<Incoming, Error> {
     incoming: VecDeque<Result<Incoming, Error>>,
     waker: Option<Waker>,
     closed: bool,
+    close_event: Option<(u16, String)>,
+    large_messages: Rc<RefCell<LargeMessageState>>,
+    outgoing: VecDeque<Vec<u8>>,
+    outgoing_limit: usize,
+    outgoing_waker: Option<Waker>,
+    buffered_amount_threshold: Option<u32>,
+    max_message_size: usize,
 }
 
 impl<Incoming, Error> State<Incoming, Error> {
@@ -69,22 +252,48 @@ impl<Incoming, Error> State<Incoming, Error> {
             incoming: VecDeque::new(),
             waker: None,
             closed: false,
+            close_event: None,
+            large_messages: Rc::new(RefCell::new(LargeMessageState::new())),
+            outgoing: VecDeque::new(),
+            outgoing_limit: DEFAULT_CONNECTING_QUEUE_LIMIT,
+            outgoing_waker: None,
+            buffered_amount_threshold: Some(DEFAULT_BUFFERED_AMOUNT_THRESHOLD),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
         }
     }
 
+    // Items arriving after closing are dropped - otherwise a stream that
+    // already ended (or reported being terminated) could yield items again.
     fn message(&mut self, message: Incoming) {
-        self.incoming.push_back(Ok(message));
-        self.wake();
+        if !self.closed {
+            self.incoming.push_back(Ok(message));
+            self.wake();
+        }
     }
 
     fn error(&mut self, error: Error) {
-        self.incoming.push_back(Err(error));
-        self.wake();
+        if !self.closed {
+            self.incoming.push_back(Err(error));
+            self.wake();
+        }
     }
 
     fn close(&mut self) {
         self.closed = true;
+        self.large_messages.borrow_mut().close();
+        self.outgoing.clear();
         self.wake();
+        self.wake_outgoing();
+    }
+
+    fn is_outgoing_full(&self) -> bool {
+        self.outgoing_limit > 0 && self.outgoing.len() >= self.outgoing_limit
+    }
+
+    fn wake_outgoing(&mut self) {
+        if let Some(waker) = self.outgoing_waker.take() {
+            waker.wake();
+        }
     }
 
     fn update_waker_with(&mut self, other: &Waker) {
@@ -102,6 +311,23 @@ impl<Incoming, Error> State<Incoming, Error> {
             waker.wake();
         }
     }
+
+    /// Queued items are delivered before reporting closed transport.
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Incoming, Error>>> {
+        if let Some(item) = self.incoming.pop_front() {
+            Poll::Ready(Some(item))
+        } else if self.closed {
+            debug_assert!(self.incoming.is_empty(), "stream ended with undelivered items");
+            Poll::Ready(None)
+        } else {
+            self.update_waker_with(cx.waker());
+            Poll::Pending
+        }
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.closed && self.incoming.is_empty()
+    }
 }
 
 impl<Incoming, Error> Drop for State<Incoming, Error> {
@@ -124,6 +350,7 @@ where
     state: Rc<RefCell<State<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>,
     buffer: RefCell<Vec<u8>>,
     _message_listener: EventListener<WebSocket, MessageEvent>,
+    _open_listener: EventListener<WebSocket, Event>,
     _error_listener: EventListener<WebSocket, Event>,
     _close_listener: EventListener<WebSocket, CloseEvent>,
     _outgoing: PhantomData<Outgoing>,
@@ -139,6 +366,12 @@ where
     for<'de> Incoming: serde::de::Deserialize<'de>,
 {
     /// Create new transport for WebSocket without waiting for `open` event.
+    ///
+    /// Messages sent while WebSocket is still connecting are queued
+    /// and sent once it opens - flushing waits until then.<br>
+    /// If WebSocket fails to open queued messages are discarded and
+    /// flushing fails with [mezzenger::Error::Closed].<br>
+    /// See [set_connecting_queue_limit](Transport::set_connecting_queue_limit).
     pub fn new_assuming_open(web_socket: &Rc<WebSocket>, codec: Codec) -> Result<Self, JsError> {
         web_socket.set_binary_type(BinaryType::Arraybuffer);
         let web_socket = web_socket.clone();
@@ -146,9 +379,32 @@ where
         let state = Rc::new(RefCell::new(State::new()));
         let state_clone = state.clone();
         let message_listener = web_socket.when("message", move |event: MessageEvent| {
-            let array = Uint8Array::new(&event.data());
-            let vector = array.to_vec();
-            let result: Result<Incoming, _> = codec_clone.decode(&vector[..]);
+            let data = event.data();
+            if let Some(buffer) = data.dyn_ref::<ArrayBuffer>() {
+                let large_messages = state_clone.borrow().large_messages.clone();
+                let mut large_messages = large_messages.borrow_mut();
+                if large_messages.is_large(buffer) {
+                    large_messages.message(buffer.clone());
+                    return;
+                }
+            }
+            let array = Uint8Array::new(&data);
+            let max_message_size = state_clone.borrow().max_message_size;
+            if array.byte_length() as usize > max_message_size {
+                state_clone
+                    .borrow_mut()
+                    .error(Error::MessageTooLarge(array.byte_length() as usize));
+                return;
+            }
+            #[cfg(feature = "type-tag")]
+            let array = match untag::<Incoming, Outgoing>(array) {
+                Ok(array) => array,
+                Err(mismatch) => {
+                    state_clone.borrow_mut().error(mismatch.into());
+                    return;
+                }
+            };
+            let result: Result<Incoming, _> = codec_clone.decode(array_reader(array));
             match result {
                 Ok(message) => state_clone.borrow_mut().message(message),
                 Err(error) => state_clone
@@ -157,13 +413,26 @@ where
             }
         })?;
         let state_clone = state.clone();
+        let web_socket_clone = web_socket.clone();
+        let open_listener = web_socket.when("open", move |_event: Event| {
+            let mut state = state_clone.borrow_mut();
+            while let Some(message) = state.outgoing.pop_front() {
+                if let Err(error) = web_socket_clone.send_with_u8_array(&message) {
+                    state.error(Error::SendingError(error.into()));
+                }
+            }
+            state.wake_outgoing();
+        })?;
+        let state_clone = state.clone();
         let error_listener = web_socket.when("error", move |event: Event| {
             state_clone.borrow_mut().error(Error::WebSocketError(event));
             state_clone.borrow_mut().close();
         })?;
         let state_clone = state.clone();
-        let close_listener = web_socket.when("close", move |_event: CloseEvent| {
-            state_clone.borrow_mut().close();
+        let close_listener = web_socket.when("close", move |event: CloseEvent| {
+            let mut state = state_clone.borrow_mut();
+            state.close_event = Some((event.code(), event.reason()));
+            state.close();
         })?;
 
         let buffer = RefCell::new(vec![]);
@@ -173,6 +442,7 @@ where
             state,
             buffer,
             _message_listener: message_listener,
+            _open_listener: open_listener,
             _error_listener: error_listener,
             _close_listener: close_listener,
             _outgoing: PhantomData,
@@ -201,14 +471,162 @@ where
         Ok(transport)
     }
 
+    /// Codec used to encode outgoing messages.
+    ///
+    /// Incoming messages are decoded with a clone of the codec
+    /// provided when the transport was created.
+    pub fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    /// Deliver incoming messages larger than `threshold` bytes as raw
+    /// [ArrayBuffer]s through returned stream instead of decoding them.
+    ///
+    /// Useful for processing very large messages incrementally without
+    /// copying them into Rust memory.
+    ///
+    /// Calling it again changes threshold of previously returned streams
+    /// (all of them share the same message queue).
+    ///
+    /// **NOTE**: With `type-tag` feature enabled raw messages start with
+    /// [type tag](mezzenger::type_tag) - it isn't checked nor stripped.
+    pub fn large_messages(&self, threshold: u32) -> LargeMessages {
+        let state = self.state.borrow().large_messages.clone();
+        state.borrow_mut().threshold = Some(threshold);
+        LargeMessages { state }
+    }
+
+    /// Set maximum number of outgoing messages queued while WebSocket is connecting
+    /// ([DEFAULT_CONNECTING_QUEUE_LIMIT] by default).
+    ///
+    /// When the queue is full [poll_ready](Sink::poll_ready) waits for WebSocket to open
+    /// (sending without it fails with [Error::QueueFull]).<br>
+    /// `0` disables queueing - sending while WebSocket is connecting fails with [Error::SendingError].
+    pub fn set_connecting_queue_limit(&mut self, limit: usize) {
+        self.state.borrow_mut().outgoing_limit = limit;
+    }
+
+    /// Set threshold of WebSocket's
+    /// [bufferedAmount](https://developer.mozilla.org/en-US/docs/Web/API/WebSocket/bufferedAmount)
+    /// (number of bytes queued by the browser but not yet sent) above which
+    /// [poll_ready](Sink::poll_ready) and [poll_flush](Sink::poll_flush) wait
+    /// ([DEFAULT_BUFFERED_AMOUNT_THRESHOLD] by default).
+    ///
+    /// While waiting `bufferedAmount` is checked again every few milliseconds.<br>
+    /// `None` disables waiting - messages are handed to the browser immediately
+    /// regardless of how much data it already buffers.
+    pub fn set_buffered_amount_threshold(&mut self, threshold: Option<u32>) {
+        self.state.borrow_mut().buffered_amount_threshold = threshold;
+    }
+
+    /// Set maximum serialized message size ([DEFAULT_MAX_MESSAGE_SIZE] by default).
+    ///
+    /// Sending larger messages fails with [Error::MessageTooLarge], larger incoming messages
+    /// are not decoded - receiving stream returns [Error::MessageTooLarge] instead.<br>
+    /// Messages delivered raw through [large_messages](Transport::large_messages) stream
+    /// are not limited.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.state.borrow_mut().max_message_size = max_message_size;
+    }
+
+    /// Maximum serialized message size.
+    ///
+    /// See [set_max_message_size](Transport::set_max_message_size).
+    pub fn max_message_size(&self) -> usize {
+        self.state.borrow().max_message_size
+    }
+
+    /// Threshold of WebSocket's `bufferedAmount` above which sending waits.
+    ///
+    /// See [set_buffered_amount_threshold](Transport::set_buffered_amount_threshold).
+    pub fn buffered_amount_threshold(&self) -> Option<u32> {
+        self.state.borrow().buffered_amount_threshold
+    }
+
+    /// Number of received items (messages and errors) waiting to be taken
+    /// from the receiving stream.
+    ///
+    /// Steadily growing count indicates consumer can't keep up (or got stuck).
+    pub fn pending_count(&self) -> usize {
+        self.state.borrow().incoming.len()
+    }
+
+    /// Close code received from WebSocket's `close` event.
+    ///
+    /// Returns `None` if the `close` event wasn't received (yet).
+    pub fn close_code(&self) -> Option<u16> {
+        self.state.borrow().close_event.as_ref().map(|(code, _)| *code)
+    }
+
+    /// Close reason received from WebSocket's `close` event.
+    ///
+    /// Returns `None` if the `close` event wasn't received (yet).
+    pub fn close_reason(&self) -> Option<String> {
+        self.state
+            .borrow()
+            .close_event
+            .as_ref()
+            .map(|(_, reason)| reason.clone())
+    }
+
+    /// Subprotocol selected by the server (WebSocket's `protocol` property).
+    ///
+    /// Empty if the server didn't select any subprotocol or WebSocket
+    /// isn't open yet.
+    pub fn protocol(&self) -> String {
+        self.web_socket.protocol()
+    }
+
+    /// Extensions selected by the server (WebSocket's `extensions` property).
+    ///
+    /// Empty if the server didn't accept any extension or WebSocket
+    /// isn't open yet.
+    pub fn extensions(&self) -> String {
+        self.web_socket.extensions()
+    }
+
+    fn poll_buffered_amount(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let threshold = self.state.borrow().buffered_amount_threshold;
+        match threshold {
+            Some(threshold) if self.web_socket.buffered_amount() > threshold => {
+                wake_later(cx.waker().clone());
+                Poll::Pending
+            }
+            _ => Poll::Ready(()),
+        }
+    }
+
     fn send_inner(
         &self,
         message: Outgoing,
     ) -> Result<(), Error<<Codec as Encode>::Error, <Codec as Decode>::Error>> {
         let mut buffer = self.buffer.borrow_mut();
+        #[cfg(feature = "type-tag")]
+        buffer.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
         self.codec
             .encode(&mut *buffer, &message)
             .map_err(Error::SerializationError)?;
+        let mut state = self.state.borrow_mut();
+        if buffer.len() > state.max_message_size {
+            let size = buffer.len();
+            buffer.clear();
+            return Err(Error::MessageTooLarge(size));
+        }
+        if state.outgoing_limit > 0
+            && (self.web_socket.ready_state() == WebSocket::CONNECTING
+                || !state.outgoing.is_empty())
+        {
+            // Keep order - messages queued before `open` event have to be sent first.
+            let result = if state.is_outgoing_full() {
+                Err(Error::QueueFull)
+            } else {
+                state.outgoing.push_back(buffer.clone());
+                Ok(())
+            };
+            buffer.clear();
+            return result;
+        }
+        drop(state);
         self.web_socket
             .send_with_u8_array(&buffer[..])
             .map_err(|error| Error::SendingError(error.into()))?;
@@ -228,10 +646,16 @@ where
 {
     type Error = mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
 
-    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        if self.state.borrow().closed {
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut state = self.state.borrow_mut();
+        if state.closed {
             Poll::Ready(Err(mezzenger::Error::Closed))
+        } else if state.is_outgoing_full() {
+            state.outgoing_waker = Some(cx.waker().clone());
+            Poll::Pending
         } else {
+            drop(state);
+            ready!(self.poll_buffered_amount(cx));
             Poll::Ready(Ok(()))
         }
     }
@@ -246,19 +670,26 @@ where
 
     fn poll_flush(
         self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
     ) -> std::task::Poll<Result<(), Self::Error>> {
-        if self.state.borrow().closed {
+        let mut state = self.state.borrow_mut();
+        if state.closed {
             Poll::Ready(Err(mezzenger::Error::Closed))
+        } else if !state.outgoing.is_empty() {
+            state.outgoing_waker = Some(cx.waker().clone());
+            Poll::Pending
         } else {
+            drop(state);
+            ready!(self.poll_buffered_amount(cx));
             Poll::Ready(Ok(()))
         }
     }
 
-    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         if self.state.borrow().closed {
             Poll::Ready(Err(mezzenger::Error::Closed))
         } else {
+            ready!(self.as_mut().poll_flush(cx))?;
             let result = self
                 .web_socket
                 .close()
@@ -281,15 +712,7 @@ where
     type Item = Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let mut state = self.state.borrow_mut();
-        if state.closed && state.incoming.is_empty() {
-            Poll::Ready(None)
-        } else if let Some(item) = state.incoming.pop_front() {
-            Poll::Ready(Some(item))
-        } else {
-            state.update_waker_with(cx.waker());
-            Poll::Pending
-        }
+        self.state.borrow_mut().poll_next(cx)
     }
 }
 
@@ -299,7 +722,12 @@ where
 {
     fn is_terminated(&self) -> bool {
         let state = self.state.borrow();
-        state.closed && state.incoming.is_empty()
+        let terminated = state.is_terminated();
+        debug_assert!(
+            !terminated || state.incoming.is_empty(),
+            "stream terminated with undelivered items"
+        );
+        terminated
     }
 }
 
@@ -312,3 +740,164 @@ impl<Codec, Incoming, Outgoing> mezzenger::Order for Transport<Codec, Incoming,
     Codec: kodec::Codec
 {
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::RefCell,
+        pin::Pin,
+        rc::Rc,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    };
+
+    use futures::{
+        stream::FusedStream,
+        task::{waker, ArcWake},
+        FutureExt, Stream, StreamExt,
+    };
+    use js_sys::Uint8Array;
+    use kodec::{binary::Codec, Decode, Encode};
+    use mezzenger::{Messages, Receive};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{array_reader, State};
+
+    const PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Allocator counting allocations of at least [PAYLOAD_SIZE] bytes.
+    struct CountingAllocator;
+
+    static LARGE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            if layout.size() >= PAYLOAD_SIZE {
+                LARGE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            }
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            if new_size >= PAYLOAD_SIZE {
+                LARGE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            }
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[wasm_bindgen_test]
+    fn test_decode_large_message() {
+        let codec = Codec::default();
+        let message: Vec<u8> = (0..PAYLOAD_SIZE).map(|i| i as u8).collect();
+        let array = {
+            let mut encoded = vec![];
+            codec.encode(&mut encoded, &message).unwrap();
+            Uint8Array::from(&encoded[..])
+        };
+
+        let allocations = LARGE_ALLOCATIONS.load(Ordering::Relaxed);
+        let decoded: Vec<u8> = codec.decode(array_reader(array)).unwrap();
+        // Only decoded message itself should be allocated in full size.
+        assert_eq!(LARGE_ALLOCATIONS.load(Ordering::Relaxed) - allocations, 1);
+        assert_eq!(decoded, message);
+    }
+
+    /// Receiving side of a transport polling its state the same way
+    /// [Transport](super::Transport) does.
+    struct Incoming(Rc<RefCell<State<u32, ()>>>);
+
+    impl Stream for Incoming {
+        type Item = Result<u32, ()>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.0.borrow_mut().poll_next(cx)
+        }
+    }
+
+    impl FusedStream for Incoming {
+        fn is_terminated(&self) -> bool {
+            self.0.borrow().is_terminated()
+        }
+    }
+
+    #[derive(Default)]
+    struct Woken(AtomicBool);
+
+    impl ArcWake for Woken {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_messages_and_close_in_one_turn() {
+        let state = Rc::new(RefCell::new(State::new()));
+        let mut incoming = Incoming(state.clone());
+
+        // `message` and `close` events dispatched back to back
+        state.borrow_mut().message(1);
+        state.borrow_mut().message(2);
+        state.borrow_mut().close();
+        assert!(!incoming.is_terminated());
+
+        assert_eq!(incoming.next().now_or_never(), Some(Some(Ok(1))));
+        assert!(!incoming.is_terminated());
+        assert_eq!(incoming.next().now_or_never(), Some(Some(Ok(2))));
+        assert!(incoming.is_terminated());
+        assert_eq!(incoming.next().now_or_never(), Some(None));
+
+        // events arriving after closing don't resurrect the stream
+        state.borrow_mut().message(3);
+        state.borrow_mut().error(());
+        assert!(incoming.is_terminated());
+        assert_eq!(incoming.next().now_or_never(), Some(None));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_waiting_consumer_gets_final_message() {
+        let state = Rc::new(RefCell::new(State::new()));
+        let mut messages = Incoming(state.clone()).messages();
+
+        let woken = Arc::new(Woken::default());
+        let waker = waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(messages.poll_next_unpin(&mut cx), Poll::Pending);
+
+        state.borrow_mut().message(1);
+        state.borrow_mut().close();
+        assert!(woken.0.load(Ordering::SeqCst));
+
+        assert_eq!(messages.poll_next_unpin(&mut cx), Poll::Ready(Some(1)));
+        assert!(!messages.is_terminated());
+        assert_eq!(messages.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert_eq!(messages.termination(), Some(mezzenger::Termination::Closed));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_close_after_last_message() {
+        let state = Rc::new(RefCell::new(State::new()));
+        let mut incoming = Incoming(state.clone());
+
+        // peer closes right after sending its last message
+        state.borrow_mut().message(1);
+        state.borrow_mut().close();
+
+        assert_eq!(incoming.receive().now_or_never(), Some(Ok(1)));
+        assert_eq!(
+            incoming.receive().now_or_never(),
+            Some(Err(mezzenger::Error::Closed))
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Transport for communication over
+//! [BroadcastChannel](https://developer.mozilla.org/en-US/docs/Web/API/BroadcastChannel)
+//! (for example to coordinate state across same-origin tabs).
+//!
+//! BroadcastChannel is many-to-many with no concept of connection - there is no open
+//! handshake, messages are sent to every other channel with the same name and
+//! received from all of them.
+
+use std::{
+    cell::RefCell,
+    marker::PhantomData,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures::{stream::FusedStream, Sink, Stream};
+use js_sys::Uint8Array;
+use js_utils::{
+    event::{EventListener, When},
+    JsError,
+};
+use kodec::{Decode, Encode};
+use serde::Serialize;
+use web_sys::{BroadcastChannel, MessageEvent};
+
+use crate::{Error, State};
+
+/// Transport for communication over [BroadcastChannel].
+///
+/// Stream yields every decoded message posted to the channel by other
+/// [BroadcastChannel] objects with the same name - **including** our own messages
+/// echoed back if the same context (tab/worker) opened more than one [BroadcastChannel]
+/// with that name.<br>
+/// Sink posts messages to the channel.
+///
+/// Closing the transport closes the underlying channel, other participants
+/// are not notified.
+pub struct BroadcastChannelTransport<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+{
+    channel: Rc<BroadcastChannel>,
+    codec: Codec,
+    #[allow(clippy::type_complexity)]
+    state: Rc<RefCell<State<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>>,
+    buffer: RefCell<Vec<u8>>,
+    _message_listener: EventListener<BroadcastChannel, MessageEvent>,
+    _message_error_listener: EventListener<BroadcastChannel, MessageEvent>,
+    _outgoing: PhantomData<Outgoing>,
+}
+
+impl<Codec, Incoming, Outgoing> BroadcastChannelTransport<Codec, Incoming, Outgoing>
+where
+    Codec: 'static + kodec::Codec + Clone,
+    Incoming: 'static,
+    Outgoing: 'static + Serialize,
+    <Codec as Encode>::Error: 'static,
+    <Codec as Decode>::Error: 'static,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    /// Create new transport joining channel with provided name.
+    pub fn new(name: &str, codec: Codec) -> Result<Self, JsError> {
+        let channel = BroadcastChannel::new(name).map_err(JsError::from)?;
+        BroadcastChannelTransport::new_with_channel(&Rc::new(channel), codec)
+    }
+
+    /// Create new transport communicating over provided channel.
+    pub fn new_with_channel(channel: &Rc<BroadcastChannel>, codec: Codec) -> Result<Self, JsError> {
+        let channel = channel.clone();
+        let codec_clone = codec.clone();
+        let state = Rc::new(RefCell::new(State::new()));
+        let state_clone = state.clone();
+        let message_listener = channel.when("message", move |event: MessageEvent| {
+            let array = Uint8Array::new(&event.data());
+            let vector = array.to_vec();
+            let message = &vector[..];
+            #[cfg(feature = "type-tag")]
+            let message = match mezzenger::type_tag::check::<Incoming, Outgoing>(message) {
+                Ok(message) => message,
+                Err(mismatch) => {
+                    state_clone.borrow_mut().error(mismatch.into());
+                    return;
+                }
+            };
+            let result: Result<Incoming, _> = codec_clone.decode(message);
+            match result {
+                Ok(message) => state_clone.borrow_mut().message(message),
+                Err(error) => state_clone
+                    .borrow_mut()
+                    .error(Error::DeserializationError(error)),
+            }
+        })?;
+        let state_clone = state.clone();
+        let message_error_listener = channel.when("messageerror", move |event: MessageEvent| {
+            state_clone.borrow_mut().error(Error::MessageError(event));
+        })?;
+        Ok(BroadcastChannelTransport {
+            channel,
+            codec,
+            state,
+            buffer: RefCell::new(vec![]),
+            _message_listener: message_listener,
+            _message_error_listener: message_error_listener,
+            _outgoing: PhantomData,
+        })
+    }
+
+    /// Name of the underlying channel.
+    pub fn name(&self) -> String {
+        self.channel.name()
+    }
+
+    /// Number of received items (messages and errors) waiting to be taken
+    /// from the receiving stream.
+    ///
+    /// Steadily growing count indicates consumer can't keep up (or got stuck).
+    pub fn pending_count(&self) -> usize {
+        self.state.borrow().incoming.len()
+    }
+
+    fn send_inner(
+        &self,
+        message: &Outgoing,
+    ) -> Result<(), Error<<Codec as Encode>::Error, <Codec as Decode>::Error>> {
+        let mut buffer = self.buffer.borrow_mut();
+        #[cfg(feature = "type-tag")]
+        buffer.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
+        self.codec
+            .encode(&mut *buffer, message)
+            .map_err(Error::SerializationError)?;
+        let js_array = Uint8Array::from(&buffer[..]);
+        buffer.clear();
+        self.channel
+            .post_message(&js_array)
+            .map_err(|error| Error::SendingError(error.into()))
+    }
+}
+
+impl<Codec, Incoming, Outgoing> Sink<Outgoing>
+    for BroadcastChannelTransport<Codec, Incoming, Outgoing>
+where
+    Codec: 'static + kodec::Codec + Clone,
+    Incoming: 'static,
+    Outgoing: 'static + Serialize,
+    <Codec as Encode>::Error: 'static,
+    <Codec as Decode>::Error: 'static,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    type Error = mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.state.borrow().closed {
+            Poll::Ready(Err(mezzenger::Error::Closed))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Outgoing) -> Result<(), Self::Error> {
+        if self.state.borrow().closed {
+            Err(mezzenger::Error::Closed)
+        } else {
+            self.send_inner(&item).map_err(mezzenger::Error::Other)
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.state.borrow().closed {
+            Poll::Ready(Err(mezzenger::Error::Closed))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.state.borrow().closed {
+            Poll::Ready(Err(mezzenger::Error::Closed))
+        } else {
+            self.channel.close();
+            self.state.borrow_mut().close();
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+impl<Codec, Incoming, Outgoing> Stream for BroadcastChannelTransport<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+{
+    type Item = Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.state.borrow_mut().poll_next(cx)
+    }
+}
+
+impl<Codec, Incoming, Outgoing> FusedStream for BroadcastChannelTransport<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+{
+    fn is_terminated(&self) -> bool {
+        let state = self.state.borrow();
+        let terminated = state.is_terminated();
+        debug_assert!(
+            !terminated || state.incoming.is_empty(),
+            "stream terminated with undelivered items"
+        );
+        terminated
+    }
+}
+
+impl<Codec, Incoming, Outgoing> mezzenger::Reliable
+    for BroadcastChannelTransport<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+{
+}
+
+/// Messages from a single sender are received in order they were sent
+/// (messages from different senders may interleave).
+impl<Codec, Incoming, Outgoing> mezzenger::Order
+    for BroadcastChannelTransport<Codec, Incoming, Outgoing>
+where
+    Codec: kodec::Codec,
+{
+}
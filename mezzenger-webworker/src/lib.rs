@@ -14,7 +14,7 @@ use std::{
 };
 
 use futures::{stream::FusedStream, Sink, Stream};
-use js_sys::Uint8Array;
+use js_sys::{Array, Uint8Array};
 use js_utils::{
     event::{EventListener, When},
     JsError, Queue,
@@ -24,20 +24,53 @@ use serde::{Deserialize, Serialize};
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{DedicatedWorkerGlobalScope, Event, EventTarget, MessageEvent, Worker};
 
+pub mod broadcast;
+pub mod port;
+
+pub use broadcast::BroadcastChannelTransport;
+
 pub trait PostMessage {
     fn post_message(&self, message: &JsValue) -> Result<(), JsValue>;
+
+    /// Post message transferring ownership of objects in `transfer` list
+    /// (instead of copying them).
+    ///
+    /// Default implementation ignores `transfer` list and copies the message.
+    fn post_message_with_transfer(
+        &self,
+        message: &JsValue,
+        _transfer: &JsValue,
+    ) -> Result<(), JsValue> {
+        self.post_message(message)
+    }
 }
 
 impl PostMessage for Worker {
     fn post_message(&self, message: &JsValue) -> Result<(), JsValue> {
         self.post_message(message)
     }
+
+    fn post_message_with_transfer(
+        &self,
+        message: &JsValue,
+        transfer: &JsValue,
+    ) -> Result<(), JsValue> {
+        self.post_message_with_transfer(message, transfer)
+    }
 }
 
 impl PostMessage for DedicatedWorkerGlobalScope {
     fn post_message(&self, message: &JsValue) -> Result<(), JsValue> {
         self.post_message(message)
     }
+
+    fn post_message_with_transfer(
+        &self,
+        message: &JsValue,
+        transfer: &JsValue,
+    ) -> Result<(), JsValue> {
+        self.post_message_with_transfer(message, transfer)
+    }
 }
 
 #[derive(Debug)]
@@ -45,6 +78,11 @@ pub enum Error<SerializationError, DeserializationError> {
     SendingError(JsError),
     SerializationError(SerializationError),
     DeserializationError(DeserializationError),
+    #[cfg(feature = "type-tag")]
+    TypeMismatch {
+        expected: &'static str,
+        got: String,
+    },
     WorkerError(Event),
     MessageError(MessageEvent),
 }
@@ -62,6 +100,10 @@ where
             Error::DeserializationError(error) => {
                 write!(f, "failed to deserialize message: {error}")
             }
+            #[cfg(feature = "type-tag")]
+            Error::TypeMismatch { expected, got } => {
+                write!(f, "message type mismatch (expected {expected}, got {got})")
+            }
             Error::WorkerError(error) => write!(f, "error occurred in worker: {error:?}"),
             Error::MessageError(error) => write!(f, "message error occurred: {error:?}"),
         }
@@ -76,6 +118,18 @@ where
 {
 }
 
+#[cfg(feature = "type-tag")]
+impl<SerializationError, DeserializationError> From<mezzenger::type_tag::TypeMismatch>
+    for Error<SerializationError, DeserializationError>
+{
+    fn from(mismatch: mezzenger::type_tag::TypeMismatch) -> Self {
+        Error::TypeMismatch {
+            expected: mismatch.expected,
+            got: mismatch.got,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 enum Wrapper<Message> {
     Open,
@@ -83,6 +137,37 @@ enum Wrapper<Message> {
     Close,
 }
 
+/// Decode wrapped message (checking its [type tag](mezzenger::type_tag)
+/// if `type-tag` feature is enabled).
+#[allow(clippy::type_complexity)]
+#[cfg_attr(not(feature = "type-tag"), allow(clippy::extra_unused_type_parameters))]
+fn decode<Codec, Incoming, Outgoing>(
+    codec: &Codec,
+    message: &[u8],
+) -> Result<Wrapper<Incoming>, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>
+where
+    Codec: kodec::Codec,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    #[cfg(feature = "type-tag")]
+    let message = match mezzenger::type_tag::check::<Incoming, Outgoing>(message) {
+        Ok(message) => message,
+        Err(mismatch) => {
+            // Control messages don't contain the mismatched type - keep handling them,
+            // so opening and closing the transport doesn't get stuck.
+            let message = message
+                .get(mezzenger::type_tag::TAG_SIZE..)
+                .unwrap_or_default();
+            return match codec.decode(message) {
+                Ok(Wrapper::<serde::de::IgnoredAny>::Open) => Ok(Wrapper::Open),
+                Ok(Wrapper::Close) => Ok(Wrapper::Close),
+                _ => Err(mismatch.into()),
+            };
+        }
+    };
+    codec.decode(message).map_err(Error::DeserializationError)
+}
+
 struct State<Incoming, Error> {
     incoming: VecDeque<Result<Incoming, Error>>,
     waker: Option<Waker>,
@@ -98,14 +183,20 @@ impl<Incoming, Error> State<Incoming, Error> {
         }
     }
 
+    // Items arriving after closing are dropped - otherwise a stream that
+    // already ended (or reported being terminated) could yield items again.
     fn message(&mut self, message: Incoming) {
-        self.incoming.push_back(Ok(message));
-        self.wake();
+        if !self.closed {
+            self.incoming.push_back(Ok(message));
+            self.wake();
+        }
     }
 
     fn error(&mut self, error: Error) {
-        self.incoming.push_back(Err(error));
-        self.wake();
+        if !self.closed {
+            self.incoming.push_back(Err(error));
+            self.wake();
+        }
     }
 
     fn close(&mut self) {
@@ -128,6 +219,26 @@ impl<Incoming, Error> State<Incoming, Error> {
             waker.wake();
         }
     }
+
+    /// Queued items are delivered before reporting closed transport.
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Incoming, Error>>> {
+        if let Some(item) = self.incoming.pop_front() {
+            Poll::Ready(Some(item))
+        } else if self.closed {
+            debug_assert!(
+                self.incoming.is_empty(),
+                "stream ended with undelivered items"
+            );
+            Poll::Ready(None)
+        } else {
+            self.update_waker_with(cx.waker());
+            Poll::Pending
+        }
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.closed && self.incoming.is_empty()
+    }
 }
 
 impl<Incoming, Error> Drop for State<Incoming, Error> {
@@ -177,7 +288,7 @@ where
         let message_listener = target.when("message", move |event: MessageEvent| {
             let array = Uint8Array::new(&event.data());
             let vector = array.to_vec();
-            let result: Result<Wrapper<Incoming>, _> = codec_clone.decode(&vector[..]);
+            let result = decode::<_, _, Outgoing>(&codec_clone, &vector[..]);
             match result {
                 Ok(message) => match message {
                     Wrapper::Open => {
@@ -190,9 +301,7 @@ where
                     Wrapper::Message(message) => state_clone.borrow_mut().message(message),
                     Wrapper::Close => state_clone.borrow_mut().close(),
                 },
-                Err(error) => state_clone
-                    .borrow_mut()
-                    .error(Error::DeserializationError(error)),
+                Err(error) => state_clone.borrow_mut().error(error),
             }
         })?;
         let state_clone = state.clone();
@@ -216,28 +325,68 @@ where
         };
 
         if is_worker {
-            let _ = transport.send_inner(Wrapper::Open);
+            let _ = transport.send_inner(Wrapper::Open, false);
             open_notifier.pop().await;
         } else {
             open_notifier.pop().await;
-            let _ = transport.send_inner(Wrapper::Open);
+            let _ = transport.send_inner(Wrapper::Open, false);
         }
 
         Ok(transport)
     }
 
+    /// Number of received items (messages and errors) waiting to be taken
+    /// from the receiving stream.
+    ///
+    /// Steadily growing count indicates consumer can't keep up (or got stuck).
+    pub fn pending_count(&self) -> usize {
+        self.state.borrow().incoming.len()
+    }
+
+    /// Send message transferring its `ArrayBuffer` to the other side
+    /// (as [Transferable](https://developer.mozilla.org/en-US/docs/Web/API/Web_Workers_API/Transferable_objects))
+    /// instead of copying it.
+    ///
+    /// Avoids copying the encoded message into the other side's heap - useful for large
+    /// binary payloads.<br>
+    /// Transferred `ArrayBuffer` is detached on the sending side, so every message sent
+    /// this way gets a freshly allocated one.
+    ///
+    /// Message is sent immediately, there is no need to flush the transport.
+    #[allow(clippy::type_complexity)]
+    pub fn send_transfer(
+        &self,
+        message: Outgoing,
+    ) -> Result<(), mezzenger::Error<Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>>
+    {
+        if self.state.borrow().closed {
+            Err(mezzenger::Error::Closed)
+        } else {
+            self.send_inner(Wrapper::Message(message), true)
+                .map_err(mezzenger::Error::Other)
+        }
+    }
+
     fn send_inner(
         &self,
         message: Wrapper<Outgoing>,
+        transfer: bool,
     ) -> Result<(), Error<<Codec as Encode>::Error, <Codec as Decode>::Error>> {
         let mut buffer = self.buffer.borrow_mut();
+        #[cfg(feature = "type-tag")]
+        buffer.extend_from_slice(&mezzenger::type_tag::tag::<Outgoing>());
         self.codec
             .encode(&mut *buffer, &message)
             .map_err(Error::SerializationError)?;
         let js_array = Uint8Array::from(&buffer[..]);
-        self.target
-            .post_message(&js_array)
-            .map_err(|error| Error::SendingError(error.into()))?;
+        let result = if transfer {
+            let transfer_list = Array::of1(&js_array.buffer());
+            self.target
+                .post_message_with_transfer(&js_array, &transfer_list)
+        } else {
+            self.target.post_message(&js_array)
+        };
+        result.map_err(|error| Error::SendingError(error.into()))?;
         buffer.clear();
         Ok(())
     }
@@ -304,7 +453,7 @@ where
         if self.state.borrow().closed {
             Err(mezzenger::Error::Closed)
         } else {
-            self.send_inner(Wrapper::Message(item))
+            self.send_inner(Wrapper::Message(item), false)
                 .map_err(mezzenger::Error::Other)
         }
     }
@@ -324,7 +473,7 @@ where
         if self.state.borrow().closed {
             Poll::Ready(Err(mezzenger::Error::Closed))
         } else {
-            let _ = self.send_inner(Wrapper::Close);
+            let _ = self.send_inner(Wrapper::Close, false);
             self.state.borrow_mut().close();
             Poll::Ready(Ok(()))
         }
@@ -339,15 +488,7 @@ where
     type Item = Result<Incoming, Error<<Codec as Encode>::Error, <Codec as Decode>::Error>>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let mut state = self.state.borrow_mut();
-        if state.closed && state.incoming.is_empty() {
-            Poll::Ready(None)
-        } else if let Some(item) = state.incoming.pop_front() {
-            Poll::Ready(Some(item))
-        } else {
-            state.update_waker_with(cx.waker());
-            Poll::Pending
-        }
+        self.state.borrow_mut().poll_next(cx)
     }
 }
 
@@ -358,7 +499,12 @@ where
 {
     fn is_terminated(&self) -> bool {
         let state = self.state.borrow();
-        state.closed && state.incoming.is_empty()
+        let terminated = state.is_terminated();
+        debug_assert!(
+            !terminated || state.incoming.is_empty(),
+            "stream terminated with undelivered items"
+        );
+        terminated
     }
 }
 
@@ -375,3 +521,114 @@ where
     Codec: kodec::Codec,
 {
 }
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use std::{
+        cell::RefCell,
+        pin::Pin,
+        rc::Rc,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    };
+
+    use futures::{
+        stream::FusedStream,
+        task::{waker, ArcWake},
+        FutureExt, Stream, StreamExt,
+    };
+    use mezzenger::{Messages, Receive};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::State;
+
+    /// Receiving side of a transport polling its state the same way
+    /// [Transport](super::Transport) does.
+    struct Incoming(Rc<RefCell<State<u32, ()>>>);
+
+    impl Stream for Incoming {
+        type Item = Result<u32, ()>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.0.borrow_mut().poll_next(cx)
+        }
+    }
+
+    impl FusedStream for Incoming {
+        fn is_terminated(&self) -> bool {
+            self.0.borrow().is_terminated()
+        }
+    }
+
+    #[derive(Default)]
+    struct Woken(AtomicBool);
+
+    impl ArcWake for Woken {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_messages_and_close_in_one_turn() {
+        let state = Rc::new(RefCell::new(State::new()));
+        let mut incoming = Incoming(state.clone());
+
+        // `message` and `close` events dispatched back to back
+        state.borrow_mut().message(1);
+        state.borrow_mut().message(2);
+        state.borrow_mut().close();
+        assert!(!incoming.is_terminated());
+
+        assert_eq!(incoming.next().now_or_never(), Some(Some(Ok(1))));
+        assert!(!incoming.is_terminated());
+        assert_eq!(incoming.next().now_or_never(), Some(Some(Ok(2))));
+        assert!(incoming.is_terminated());
+        assert_eq!(incoming.next().now_or_never(), Some(None));
+
+        // events arriving after closing don't resurrect the stream
+        state.borrow_mut().message(3);
+        state.borrow_mut().error(());
+        assert!(incoming.is_terminated());
+        assert_eq!(incoming.next().now_or_never(), Some(None));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_waiting_consumer_gets_final_message() {
+        let state = Rc::new(RefCell::new(State::new()));
+        let mut messages = Incoming(state.clone()).messages();
+
+        let woken = Arc::new(Woken::default());
+        let waker = waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(messages.poll_next_unpin(&mut cx), Poll::Pending);
+
+        state.borrow_mut().message(1);
+        state.borrow_mut().close();
+        assert!(woken.0.load(Ordering::SeqCst));
+
+        assert_eq!(messages.poll_next_unpin(&mut cx), Poll::Ready(Some(1)));
+        assert!(!messages.is_terminated());
+        assert_eq!(messages.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert_eq!(messages.termination(), Some(mezzenger::Termination::Closed));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_close_after_last_message() {
+        let state = Rc::new(RefCell::new(State::new()));
+        let mut incoming = Incoming(state.clone());
+
+        // peer closes right after sending its last message
+        state.borrow_mut().message(1);
+        state.borrow_mut().close();
+
+        assert_eq!(incoming.receive().now_or_never(), Some(Ok(1)));
+        assert_eq!(
+            incoming.receive().now_or_never(),
+            Some(Err(mezzenger::Error::Closed))
+        );
+    }
+}
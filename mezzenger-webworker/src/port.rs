@@ -0,0 +1,53 @@
+//! Transport for communication over
+//! [MessagePort](https://developer.mozilla.org/en-US/docs/Web/API/MessagePort)
+//! (for example with an iframe or through a
+//! [MessageChannel](https://developer.mozilla.org/en-US/docs/Web/API/MessageChannel)).
+
+use std::rc::Rc;
+
+use js_utils::JsError;
+use kodec::{Decode, Encode};
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+use web_sys::MessagePort;
+
+use crate::PostMessage;
+
+/// Transport for communication over [MessagePort].
+pub type Transport<Codec, Incoming, Outgoing> =
+    crate::Transport<MessagePort, Codec, Incoming, Outgoing>;
+
+impl PostMessage for MessagePort {
+    fn post_message(&self, message: &JsValue) -> Result<(), JsValue> {
+        self.post_message(message)
+    }
+
+    fn post_message_with_transfer(
+        &self,
+        message: &JsValue,
+        transfer: &JsValue,
+    ) -> Result<(), JsValue> {
+        self.post_message_with_transferable(message, transfer)
+    }
+}
+
+impl<Codec, Incoming, Outgoing> Transport<Codec, Incoming, Outgoing>
+where
+    Codec: 'static + kodec::Codec + Clone,
+    Incoming: 'static,
+    Outgoing: 'static + Serialize,
+    <Codec as Encode>::Error: 'static,
+    <Codec as Decode>::Error: 'static,
+    for<'de> Incoming: serde::de::Deserialize<'de>,
+{
+    /// Create new transport communicating over provided port.
+    ///
+    /// Starts dispatching port's messages and waits for the transport on the other
+    /// end of the channel to be created as well.
+    pub async fn new_with_port(port: &Rc<MessagePort>, codec: Codec) -> Result<Self, JsError> {
+        // Listeners are added before any queued message gets dispatched
+        // (no event is handled before the first await).
+        port.start();
+        crate::Transport::new_inner(port, codec, true).await
+    }
+}
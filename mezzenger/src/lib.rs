@@ -16,6 +16,19 @@ use futures::{
 
 use pin_project::pin_project;
 
+#[cfg(all(feature = "pump", not(target_arch = "wasm32")))]
+pub mod pump;
+#[cfg(feature = "rt")]
+pub mod rt;
+pub mod send_all;
+pub mod split;
+pub mod type_tag;
+pub mod waker_set;
+
+pub use send_all::{send_all_counted, SendAllCounted};
+pub use split::SplitState;
+pub use waker_set::WakerSet;
+
 /// Transport error.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Error<Other> {
@@ -48,7 +61,7 @@ impl<Other> std::error::Error for Error<Other> where Other: std::error::Error {}
 
 /// Convenience trait for receiving messages.
 pub trait Receive<Message, Error> {
-    fn receive(&mut self) -> Recv<Self>;
+    fn receive(&mut self) -> Recv<'_, Self>;
 }
 
 impl<T, Message, Error> Receive<Message, Error> for T
@@ -56,7 +69,7 @@ where
     T: Stream<Item = Result<Message, Error>> + Unpin,
 {
     /// Receive message from transport.
-    fn receive(&mut self) -> Recv<Self> {
+    fn receive(&mut self) -> Recv<'_, Self> {
         let next = self.next();
         Recv {
             next,
@@ -107,6 +120,10 @@ where
 }
 
 /// Utility trait for creating message stream with filtered out errors.
+///
+/// Errors never end the message stream by themselves - it ends only when the underlying
+/// stream ends. Transports report errors that close them (like timeouts) as the last item
+/// before ending, use [MessageStream::termination] to tell such ending from a clean close.
 pub trait Messages<T, Message, Error>
 where
     Self: Sized,
@@ -136,10 +153,26 @@ where
             stream: self,
             error_callback,
             terminated: false,
+            last_error: false,
+            budget: None,
+            polled: 0,
         }
     }
 }
 
+/// Default number of items after which [fair](MessageStream::fair) stream yields.
+pub const DEFAULT_FAIR_BUDGET: usize = 32;
+
+/// Reason [MessageStream] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Termination {
+    /// Underlying stream ended after a message (or without yielding anything).
+    Closed,
+    /// Underlying stream ended right after an error (passed to the error callback) -
+    /// usually the error that closed the transport.
+    AfterError,
+}
+
 /// Stream of messages.
 ///
 /// Returned by [messages] function.
@@ -151,6 +184,48 @@ pub struct MessageStream<T, F> {
     stream: T,
     error_callback: F,
     terminated: bool,
+    last_error: bool,
+    budget: Option<usize>,
+    polled: usize,
+}
+
+impl<T, F> MessageStream<T, F> {
+    /// Reason the stream ended or `None` if it didn't end yet.
+    pub fn termination(&self) -> Option<Termination> {
+        match (self.terminated, self.last_error) {
+            (false, _) => None,
+            (true, false) => Some(Termination::Closed),
+            (true, true) => Some(Termination::AfterError),
+        }
+    }
+
+    /// Cooperatively yield to other tasks (or other streams polled by the same task)
+    /// after every [DEFAULT_FAIR_BUDGET] consecutive items.
+    ///
+    /// See [fair_with_budget](MessageStream::fair_with_budget).
+    pub fn fair(self) -> Self {
+        self.fair_with_budget(DEFAULT_FAIR_BUDGET)
+    }
+
+    /// Cooperatively yield after every `budget` consecutive items.
+    ///
+    /// Without it a stream that always has messages ready delivers them one after another
+    /// (for example inside `for_each`), starving other connections processed by the same task.<br>
+    /// Fair stream schedules its own wake-up and returns `Poll::Pending` once
+    /// `budget` items (messages or skipped errors) were received without
+    /// the underlying stream returning `Poll::Pending`.
+    ///
+    /// Works over any stream, independently of budgets enforced by transports themselves.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `budget` is `0`.
+    pub fn fair_with_budget(mut self, budget: usize) -> Self {
+        assert!(budget > 0, "budget must be greater than 0");
+        self.budget = Some(budget);
+        self.polled = 0;
+        self
+    }
 }
 
 impl<T, F, Message, Error> Stream for MessageStream<T, F>
@@ -161,22 +236,38 @@ where
     type Item = Message;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.stream.poll_next_unpin(cx) {
-            Poll::Ready(item) => {
-                if let Some(item) = item {
-                    match item {
-                        Ok(message) => Poll::Ready(Some(message)),
-                        Err(error) => {
-                            (self.error_callback)(error);
-                            Poll::Pending
-                        }
-                    }
-                } else {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        loop {
+            if let Some(budget) = self.budget {
+                if self.polled >= budget {
+                    self.polled = 0;
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+            }
+            match self.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(message))) => {
+                    self.polled += 1;
+                    self.last_error = false;
+                    return Poll::Ready(Some(message));
+                }
+                // Keep polling - returning `Pending` here wouldn't schedule a wake up.
+                Poll::Ready(Some(Err(error))) => {
+                    self.polled += 1;
+                    self.last_error = true;
+                    (self.error_callback)(error)
+                }
+                Poll::Ready(None) => {
                     self.terminated = true;
-                    Poll::Ready(None)
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => {
+                    self.polled = 0;
+                    return Poll::Pending;
                 }
             }
-            Poll::Pending => Poll::Pending,
         }
     }
 }
@@ -216,7 +307,30 @@ pub trait Reliable {}
 /// meets guarantees mentioned above.  
 pub trait Order {}
 
+/// What transport does with messages sent but not flushed when it's dropped.
+///
+/// Transports supporting it take it as an option at construction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushOnDrop {
+    /// Pending messages are lost.
+    ///
+    /// This is the default.
+    #[default]
+    Disabled,
+    /// Pending messages are flushed and the transport is closed by a detached task
+    /// spawned on the runtime transport was created within (if there was one).
+    ///
+    /// It's best-effort - dropping doesn't wait for the task and its failure isn't reported
+    /// (for example when the runtime shuts down first).
+    Detached,
+}
+
 /// Trait for transports that implement `mezzenger` interface.
+///
+/// **NOTE to transport implementors**: transport must deliver all messages it already
+/// received before reporting that it was closed - its stream can't return `None`
+/// (nor [FusedStream::is_terminated] return `true`) while undelivered messages remain,
+/// even if the connection was closed concurrently.
 pub trait Transport<Incoming, Outgoing, Error>:
     Sink<Outgoing, Error = crate::Error<Error>> + Stream<Item = Result<Incoming, Error>>
 {
@@ -226,3 +340,118 @@ impl<T, Incoming, Outgoing, Error> Transport<Incoming, Outgoing, Error> for T wh
     T: Sink<Outgoing, Error = crate::Error<Error>> + Stream<Item = Result<Incoming, Error>>
 {
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        task::{Context, Poll},
+    };
+
+    use futures::{
+        channel::mpsc, executor::block_on, future::join, stream, task::noop_waker_ref, FutureExt,
+        StreamExt,
+    };
+
+    use crate::{Error, MessageStream, Messages, Receive, Termination};
+
+    #[test]
+    fn test_messages_skip_errors() {
+        let items: Vec<Result<i32, &str>> = vec![Ok(1), Err("error"), Err("error"), Ok(2)];
+        let mut errors = 0;
+        let mut messages = stream::iter(items).messages_with_error_callback(|_| errors += 1);
+
+        assert_eq!(messages.next().now_or_never(), Some(Some(1)));
+        assert_eq!(messages.next().now_or_never(), Some(Some(2)));
+        assert_eq!(messages.next().now_or_never(), Some(None));
+        assert_eq!(messages.next().now_or_never(), Some(None));
+        drop(messages);
+        assert_eq!(errors, 2);
+    }
+
+    #[test]
+    fn test_messages_error_then_message() {
+        // Errors and message ready at once - message has to be delivered by the same poll,
+        // nothing would wake the task for another one.
+        let items: Vec<Result<i32, &str>> = vec![Err("error"), Err("error"), Ok(1)];
+        let mut messages = stream::iter(items).messages();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert_eq!(messages.poll_next_unpin(&mut cx), Poll::Ready(Some(1)));
+        assert_eq!(messages.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert_eq!(messages.termination(), Some(Termination::Closed));
+    }
+
+    #[test]
+    fn test_messages_termination() {
+        let items: Vec<Result<i32, &str>> = vec![Err("error"), Ok(1)];
+        let mut messages = stream::iter(items).messages();
+        assert_eq!(messages.termination(), None);
+        assert_eq!(messages.next().now_or_never(), Some(Some(1)));
+        assert_eq!(messages.termination(), None);
+        assert_eq!(messages.next().now_or_never(), Some(None));
+        assert_eq!(messages.termination(), Some(Termination::Closed));
+
+        let items: Vec<Result<i32, &str>> = vec![Ok(1), Err("timed out")];
+        let mut messages = stream::iter(items).messages();
+        assert_eq!(messages.next().now_or_never(), Some(Some(1)));
+        assert_eq!(messages.next().now_or_never(), Some(None));
+        assert_eq!(messages.termination(), Some(Termination::AfterError));
+
+        let mut messages = stream::empty::<Result<i32, ()>>().messages();
+        assert_eq!(messages.next().now_or_never(), Some(None));
+        assert_eq!(messages.termination(), Some(Termination::Closed));
+    }
+
+    #[test]
+    fn test_receive_last_message_before_closed() {
+        let items: Vec<Result<i32, ()>> = vec![Ok(1), Ok(2)];
+        let mut stream = stream::iter(items);
+
+        assert_eq!(stream.receive().now_or_never(), Some(Ok(1)));
+        assert_eq!(stream.receive().now_or_never(), Some(Ok(2)));
+        assert_eq!(stream.receive().now_or_never(), Some(Err(Error::Closed)));
+    }
+
+    /// Process two connections with 100 messages ready each on a single task,
+    /// returning the longest run of consecutive messages from the same connection.
+    fn longest_run<F>(modifier: F) -> usize
+    where
+        F: Fn(
+            MessageStream<mpsc::UnboundedReceiver<Result<i32, ()>>, fn(())>,
+        ) -> MessageStream<mpsc::UnboundedReceiver<Result<i32, ()>>, fn(())>,
+    {
+        let connection = || {
+            let (sender, receiver) = mpsc::unbounded();
+            for i in 0..100 {
+                sender.unbounded_send(Ok(i)).unwrap();
+            }
+            modifier(receiver.messages())
+        };
+        let order = RefCell::new(vec![]);
+        block_on(join(
+            connection().for_each(|_| {
+                order.borrow_mut().push('a');
+                async {}
+            }),
+            connection().for_each(|_| {
+                order.borrow_mut().push('b');
+                async {}
+            }),
+        ));
+
+        let order = order.into_inner();
+        assert_eq!(order.len(), 200);
+        order
+            .chunk_by(|left, right| left == right)
+            .map(|run| run.len())
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_fair_messages() {
+        assert_eq!(longest_run(|messages| messages), 100);
+        assert_eq!(longest_run(|messages| messages.fair_with_budget(10)), 10);
+        assert_eq!(longest_run(MessageStream::fair), 32);
+    }
+}
@@ -0,0 +1,199 @@
+//! Bridges between transports and [tokio](https://docs.rs/tokio) channels
+//! (for example to integrate transports with actor-style code).
+//!
+//! - [pump_into] forwards messages received from a transport into a channel,
+//! - [pump_from] sends messages received from a channel through a transport.
+//!
+//! Both run in a background task spawned on tokio runtime (have to be called within one).
+//!
+//! Available natively with `pump` feature enabled.
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::Messages;
+
+/// Forward messages received from `transport` into `sender`.
+///
+/// Errors are passed to `error_callback` (they don't stop forwarding - see [Messages]).<br>
+/// Task ends when transport's stream ends or all receivers of the channel are dropped
+/// (transport is dropped then).
+pub fn pump_into<T, Message, Error, F>(
+    transport: T,
+    sender: mpsc::Sender<Message>,
+    error_callback: F,
+) -> JoinHandle<()>
+where
+    T: Stream<Item = Result<Message, Error>> + Unpin + Send + 'static,
+    Message: Send + 'static,
+    F: FnMut(Error) + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut messages = transport.messages_with_error_callback(error_callback);
+        while let Some(message) = messages.next().await {
+            if sender.send(message).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Send messages received from `receiver` through `transport`.
+///
+/// Every message is flushed before the next one is taken from the channel.<br>
+/// Errors other than [Closed](crate::Error::Closed) are passed to `error_callback`
+/// (message that failed is lost, sending continues with the next one).<br>
+/// Task ends when transport gets closed or when all senders of the channel are dropped
+/// and remaining messages are sent - transport is closed then.
+pub fn pump_from<T, Message, Error, F>(
+    mut transport: T,
+    mut receiver: mpsc::Receiver<Message>,
+    mut error_callback: F,
+) -> JoinHandle<()>
+where
+    T: Sink<Message, Error = crate::Error<Error>> + Unpin + Send + 'static,
+    Message: Send + 'static,
+    F: FnMut(Error) + Send + 'static,
+{
+    tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            match transport.send(message).await {
+                Ok(()) => {}
+                Err(crate::Error::Closed) => return,
+                Err(crate::Error::Other(error)) => error_callback(error),
+            }
+        }
+        if let Err(crate::Error::Other(error)) = transport.close().await {
+            error_callback(error);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+    };
+
+    use futures::{stream, Sink};
+    use tokio::sync::mpsc;
+
+    use super::{pump_from, pump_into};
+    use crate::Error;
+
+    /// Sink collecting messages, rejecting negative ones.
+    #[derive(Default)]
+    struct Collect {
+        messages: Arc<Mutex<Vec<i32>>>,
+        closed: Arc<Mutex<bool>>,
+        capacity: usize,
+    }
+
+    impl Sink<i32> for Collect {
+        type Error = Error<&'static str>;
+
+        fn poll_ready(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            if *self.closed.lock().unwrap() || self.messages.lock().unwrap().len() >= self.capacity
+            {
+                Poll::Ready(Err(Error::Closed))
+            } else {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        fn start_send(self: Pin<&mut Self>, item: i32) -> Result<(), Self::Error> {
+            if item < 0 {
+                Err(Error::Other("negative"))
+            } else {
+                self.messages.lock().unwrap().push(item);
+                Ok(())
+            }
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            *self.closed.lock().unwrap() = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pump_into() {
+        let items: Vec<Result<i32, &str>> = vec![Ok(1), Err("error"), Ok(2), Ok(3)];
+        let (sender, mut receiver) = mpsc::channel(1);
+        let errors = Arc::new(Mutex::new(vec![]));
+        let errors_clone = errors.clone();
+        let pump = pump_into(stream::iter(items), sender, move |error| {
+            errors_clone.lock().unwrap().push(error)
+        });
+
+        let mut received = vec![];
+        while let Some(message) = receiver.recv().await {
+            received.push(message);
+        }
+        pump.await.unwrap();
+        assert_eq!(received, vec![1, 2, 3]);
+        assert_eq!(*errors.lock().unwrap(), vec!["error"]);
+
+        // dropped receiver stops pumping
+        let (sender, receiver) = mpsc::channel(1);
+        drop(receiver);
+        pump_into(stream::repeat(Ok::<_, ()>(1)), sender, |_| {})
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pump_from() {
+        let transport = Collect {
+            capacity: usize::MAX,
+            ..Default::default()
+        };
+        let messages = transport.messages.clone();
+        let closed = transport.closed.clone();
+        let (sender, receiver) = mpsc::channel(4);
+        let errors = Arc::new(Mutex::new(vec![]));
+        let errors_clone = errors.clone();
+        let pump = pump_from(transport, receiver, move |error| {
+            errors_clone.lock().unwrap().push(error)
+        });
+
+        for message in [1, -1, 2, 3] {
+            sender.send(message).await.unwrap();
+        }
+        drop(sender);
+        pump.await.unwrap();
+        assert_eq!(*messages.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(*errors.lock().unwrap(), vec!["negative"]);
+        assert!(*closed.lock().unwrap());
+
+        // closed transport stops pumping
+        let transport = Collect {
+            capacity: 2,
+            ..Default::default()
+        };
+        let messages = transport.messages.clone();
+        let (sender, receiver) = mpsc::channel(4);
+        let pump = pump_from(transport, receiver, |_| {});
+        for message in [1, 2, 3] {
+            sender.send(message).await.unwrap();
+        }
+        pump.await.unwrap();
+        assert_eq!(*messages.lock().unwrap(), vec![1, 2]);
+        assert!(sender.is_closed());
+    }
+}
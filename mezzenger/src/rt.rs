@@ -0,0 +1,355 @@
+//! Timers and task spawning working both natively and in the browser.
+//!
+//! Transports and utilities needing timers (timeouts, heartbeats, throttling...) should use
+//! this module instead of hand-rolling `cfg(target_arch = "wasm32")` blocks:
+//! - natively it's backed by [tokio](https://docs.rs/tokio) (timers have to be polled
+//!   within tokio runtime),
+//! - on `wasm32` it's backed by [gloo-timers](https://docs.rs/gloo-timers) and
+//!   [wasm-bindgen-futures](https://docs.rs/wasm-bindgen-futures).
+//!
+//! Available with `rt` feature enabled.
+//!
+//! ## Deterministic tests
+//!
+//! Native timers follow tokio's clock, so tests can run with time paused
+//! (`#[tokio::test(start_paused = true)]` with tokio's `test-util` feature enabled) -
+//! timers then fire instantly, in order of their deadlines, whenever the runtime has
+//! nothing else to do.
+
+use std::{
+    fmt::Display,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{stream::FusedStream, Future, Stream};
+use pin_project::pin_project;
+
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::time::{Instant, MissedTickBehavior};
+
+#[cfg(target_arch = "wasm32")]
+use gloo_timers::future::{IntervalStream, TimeoutFuture};
+
+/// Future completing after provided duration elapses.
+///
+/// Returned by [sleep] function.
+pub struct Sleep {
+    #[cfg(not(target_arch = "wasm32"))]
+    inner: Pin<Box<tokio::time::Sleep>>,
+    #[cfg(target_arch = "wasm32")]
+    inner: TimeoutFuture,
+}
+
+impl Sleep {
+    /// Restart the timer so it completes after `duration` elapses from now.
+    pub fn reset(&mut self, duration: Duration) {
+        *self = sleep(duration);
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.inner.as_mut().poll(cx);
+        #[cfg(target_arch = "wasm32")]
+        return Pin::new(&mut self.inner).poll(cx);
+    }
+}
+
+/// Wait until `duration` elapses.
+///
+/// **NOTE**: In the browser timers have millisecond resolution - duration is rounded up
+/// to whole milliseconds.
+pub fn sleep(duration: Duration) -> Sleep {
+    #[cfg(not(target_arch = "wasm32"))]
+    return Sleep {
+        inner: Box::pin(tokio::time::sleep(duration)),
+    };
+    #[cfg(target_arch = "wasm32")]
+    return Sleep {
+        inner: TimeoutFuture::new(millis(duration)),
+    };
+}
+
+/// Stream yielding `()` every time provided period elapses.
+///
+/// Returned by [interval] function, never terminates.
+pub struct Interval {
+    #[cfg(not(target_arch = "wasm32"))]
+    inner: tokio::time::Interval,
+    #[cfg(target_arch = "wasm32")]
+    inner: IntervalStream,
+}
+
+impl Stream for Interval {
+    type Item = ();
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.inner.poll_tick(cx).map(|_| Some(()));
+        #[cfg(target_arch = "wasm32")]
+        return Pin::new(&mut self.inner).poll_next(cx);
+    }
+}
+
+impl FusedStream for Interval {
+    fn is_terminated(&self) -> bool {
+        false
+    }
+}
+
+/// Stream yielding `()` every `period`.
+///
+/// First tick happens after `period` elapses (not immediately).<br>
+/// Ticks missed because the stream wasn't polled in time aren't made up for - next
+/// tick happens `period` after the late one.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    assert!(!period.is_zero(), "period must be non-zero");
+    #[cfg(not(target_arch = "wasm32"))]
+    return {
+        let mut inner = tokio::time::interval_at(Instant::now() + period, period);
+        inner.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Interval { inner }
+    };
+    #[cfg(target_arch = "wasm32")]
+    return Interval {
+        inner: IntervalStream::new(millis(period)),
+    };
+}
+
+/// Error returned by [Timeout] when its deadline elapsed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Future completing with output of wrapped future or [Elapsed]
+/// if it didn't complete in time.
+///
+/// Returned by [timeout] function.
+#[pin_project]
+pub struct Timeout<F> {
+    #[pin]
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F> Timeout<F> {
+    /// Consume the timeout, returning the wrapped future.
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F> Future for Timeout<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let me = self.project();
+        if let Poll::Ready(output) = me.future.poll(cx) {
+            Poll::Ready(Ok(output))
+        } else if Pin::new(me.sleep).poll(cx).is_ready() {
+            Poll::Ready(Err(Elapsed))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Require `future` to complete within `duration`.
+///
+/// Wrapped future is polled first - one completing at the same time as the deadline
+/// still succeeds.
+pub fn timeout<F>(duration: Duration, future: F) -> Timeout<F>
+where
+    F: Future,
+{
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+/// Run `future` in the background.
+///
+/// Natively spawns it on tokio runtime (has to be called within one),
+/// in the browser it's the same as [spawn_local].
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::spawn(future);
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Run `!Send` future in the background on the current thread.
+///
+/// Natively spawns it on tokio's [LocalSet](https://docs.rs/tokio/latest/tokio/task/struct.LocalSet.html)
+/// (has to be called within one).
+pub fn spawn_local<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::task::spawn_local(future);
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn millis(duration: Duration) -> u32 {
+    let millis = duration.as_nanos().div_ceil(1_000_000);
+    millis.try_into().unwrap_or(u32::MAX)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::{
+        future::pending,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use futures::StreamExt;
+    use tokio::{task::LocalSet, time::Instant};
+
+    use super::{interval, sleep, spawn, spawn_local, timeout, Elapsed};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sleep() {
+        let start = Instant::now();
+        sleep(Duration::from_millis(250)).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+
+        let mut timer = sleep(Duration::from_secs(10));
+        timer.reset(Duration::from_millis(100));
+        timer.await;
+        assert_eq!(start.elapsed(), Duration::from_millis(350));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_interval() {
+        let start = Instant::now();
+        let mut ticks = interval(Duration::from_millis(100));
+        for i in 1..=3 {
+            ticks.next().await.unwrap();
+            assert_eq!(start.elapsed(), Duration::from_millis(100 * i));
+        }
+
+        // missed ticks aren't made up for
+        sleep(Duration::from_millis(250)).await;
+        ticks.next().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(550));
+        ticks.next().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(650));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout() {
+        let result = timeout(Duration::from_secs(1), async { 7 }).await;
+        assert_eq!(result, Ok(7));
+
+        let start = Instant::now();
+        let result = timeout(Duration::from_secs(1), pending::<()>()).await;
+        assert_eq!(result, Err(Elapsed));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(Elapsed.to_string(), "deadline has elapsed");
+
+        let result = timeout(Duration::from_secs(1), sleep(Duration::from_millis(999))).await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spawn() {
+        let done = Arc::new(AtomicBool::new(false));
+        let done_clone = done.clone();
+        spawn(async move {
+            sleep(Duration::from_millis(10)).await;
+            done_clone.store(true, Ordering::SeqCst);
+        });
+        sleep(Duration::from_millis(20)).await;
+        assert!(done.load(Ordering::SeqCst));
+
+        let local = LocalSet::new();
+        let done = std::rc::Rc::new(std::cell::Cell::new(false));
+        let done_clone = done.clone();
+        local
+            .run_until(async move {
+                spawn_local(async move {
+                    sleep(Duration::from_millis(10)).await;
+                    done_clone.set(true);
+                });
+                sleep(Duration::from_millis(20)).await;
+            })
+            .await;
+        assert!(done.get());
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use std::{cell::Cell, future::pending, rc::Rc, time::Duration};
+
+    use futures::StreamExt;
+    use js_sys::Date;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{interval, sleep, spawn_local, timeout, Elapsed};
+
+    #[wasm_bindgen_test]
+    async fn test_sleep() {
+        let start = Date::now();
+        sleep(Duration::from_millis(50)).await;
+        assert!(Date::now() - start >= 49.0);
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_interval() {
+        let start = Date::now();
+        let ticks = interval(Duration::from_millis(20)).take(3).count().await;
+        assert_eq!(ticks, 3);
+        assert!(Date::now() - start >= 59.0);
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_timeout() {
+        let result = timeout(Duration::from_millis(50), async { 7 }).await;
+        assert_eq!(result, Ok(7));
+        let result = timeout(Duration::from_millis(20), pending::<()>()).await;
+        assert_eq!(result, Err(Elapsed));
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_spawn_local() {
+        let done = Rc::new(Cell::new(false));
+        let done_clone = done.clone();
+        spawn_local(async move {
+            sleep(Duration::from_millis(10)).await;
+            done_clone.set(true);
+        });
+        sleep(Duration::from_millis(30)).await;
+        assert!(done.get());
+    }
+}
@@ -0,0 +1,188 @@
+//! Cancel-safe replacement of [SinkExt::send_all](futures::SinkExt::send_all)
+//! reporting how many messages were sent.
+//!
+//! `send_all` doesn't tell how many messages were handed to the transport when it fails
+//! or gets cancelled midway - and message already taken from the source stream
+//! but not yet sent is lost along with the future.<br>
+//! [send_all_counted] counts messages accepted by the transport and keeps message taken
+//! from the stream retrievable with [SendAllCounted::into_parts], so sending can be
+//! resumed without losing or duplicating messages:
+//!
+//! ```ignore
+//! let mut sending = send_all_counted(&mut transport, &mut messages);
+//! select! {
+//!     result = &mut sending => { /* ... */ }
+//!     _ = shutdown => {
+//!         let (sent, pending) = sending.into_parts();
+//!         // `pending` (if any) has to be sent before remaining `messages`
+//!     }
+//! }
+//! ```
+
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures::{Future, Sink, SinkExt, Stream, StreamExt};
+
+/// Send all messages of `stream` through `sink`, counting messages accepted by the sink.
+///
+/// Resolves to the number of sent messages once the stream ends and the sink is flushed,
+/// or to the number of messages accepted before an error along with that error.
+///
+/// Future is cancel-safe - see [SendAllCounted::into_parts].
+pub fn send_all_counted<'a, Si, St, Item>(
+    sink: &'a mut Si,
+    stream: &'a mut St,
+) -> SendAllCounted<'a, Si, St, Item>
+where
+    Si: Sink<Item> + Unpin + ?Sized,
+    St: Stream<Item = Item> + Unpin + ?Sized,
+{
+    SendAllCounted {
+        sink,
+        stream,
+        pending: None,
+        accepted: 0,
+    }
+}
+
+/// Future returned by [send_all_counted] function.
+pub struct SendAllCounted<'a, Si, St, Item>
+where
+    Si: ?Sized,
+    St: ?Sized,
+{
+    sink: &'a mut Si,
+    stream: &'a mut St,
+    pending: Option<Item>,
+    accepted: usize,
+}
+
+impl<'a, Si, St, Item> SendAllCounted<'a, Si, St, Item>
+where
+    Si: ?Sized,
+    St: ?Sized,
+{
+    /// Number of messages accepted by the sink so far.
+    ///
+    /// Accepted messages might not be flushed yet.
+    pub fn accepted(&self) -> usize {
+        self.accepted
+    }
+
+    /// Stop sending, returning number of messages accepted by the sink so far and the message
+    /// already taken from the stream but not accepted by the sink yet (if any).
+    ///
+    /// Message is returned also when sink failed to get ready for it (future resolved
+    /// to an error then) - it's lost only if the sink failed to accept it.
+    pub fn into_parts(self) -> (usize, Option<Item>) {
+        (self.accepted, self.pending)
+    }
+}
+
+impl<'a, Si, St, Item> Unpin for SendAllCounted<'a, Si, St, Item>
+where
+    Si: ?Sized,
+    St: ?Sized,
+{
+}
+
+impl<'a, Si, St, Item> Future for SendAllCounted<'a, Si, St, Item>
+where
+    Si: Sink<Item> + Unpin + ?Sized,
+    St: Stream<Item = Item> + Unpin + ?Sized,
+{
+    type Output = Result<usize, (usize, Si::Error)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let me = self.get_mut();
+        loop {
+            if let Some(item) = me.pending.take() {
+                match me.sink.poll_ready_unpin(cx) {
+                    Poll::Ready(Ok(())) => {
+                        if let Err(error) = me.sink.start_send_unpin(item) {
+                            return Poll::Ready(Err((me.accepted, error)));
+                        }
+                        me.accepted += 1;
+                    }
+                    Poll::Ready(Err(error)) => {
+                        me.pending = Some(item);
+                        return Poll::Ready(Err((me.accepted, error)));
+                    }
+                    Poll::Pending => {
+                        me.pending = Some(item);
+                        return Poll::Pending;
+                    }
+                }
+            }
+
+            match me.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => me.pending = Some(item),
+                Poll::Ready(None) => {
+                    let result = ready!(me.sink.poll_flush_unpin(cx));
+                    return Poll::Ready(
+                        result
+                            .map(|()| me.accepted)
+                            .map_err(|error| (me.accepted, error)),
+                    );
+                }
+                Poll::Pending => {
+                    // Flush accepted messages while waiting for more.
+                    if let Err(error) = ready!(me.sink.poll_flush_unpin(cx)) {
+                        return Poll::Ready(Err((me.accepted, error)));
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+
+    use futures::{
+        channel::mpsc, executor::block_on, future::join, stream, task::noop_waker_ref, FutureExt,
+        StreamExt,
+    };
+
+    use super::send_all_counted;
+
+    #[test]
+    fn test_send_all_counted() {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let (mut sender, mut receiver) = mpsc::channel::<i32>(1);
+        let mut source = stream::iter(0..10);
+
+        // cancel once the channel is full
+        let mut sending = send_all_counted(&mut sender, &mut source);
+        assert!(sending.poll_unpin(&mut cx).is_pending());
+        let accepted = sending.accepted();
+        let (parts_accepted, pending) = sending.into_parts();
+        assert_eq!(parts_accepted, accepted);
+        assert_eq!(pending, Some(accepted as i32));
+
+        // resume with the message taken from the source before cancelling
+        let mut rest = stream::iter(pending).chain(&mut source);
+        let (result, received) = block_on(join(
+            send_all_counted(&mut sender, &mut rest),
+            receiver.by_ref().take(10).collect::<Vec<_>>(),
+        ));
+        assert_eq!(result.unwrap(), 10 - accepted);
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+
+        // message is kept when sink fails to get ready
+        let mut source = stream::iter(10..12);
+        drop(receiver);
+        let mut sending = send_all_counted(&mut sender, &mut source);
+        match sending.poll_unpin(&mut cx) {
+            Poll::Ready(Err((0, error))) => assert!(error.is_disconnected()),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert_eq!(sending.into_parts(), (0, Some(10)));
+        assert_eq!(source.next().now_or_never(), Some(Some(11)));
+    }
+}
@@ -0,0 +1,71 @@
+//! State shared by halves of a split transport.
+//!
+//! # Contract for split transports
+//!
+//! Transports that can be split into independently owned sending and receiving
+//! halves must behave the same regardless of the order in which halves are dropped:
+//!
+//! - dropping the sending half flushes pending messages on a best-effort basis
+//!   (without blocking) and marks sending side as closed - it does **not** close
+//!   the connection, receiving half keeps receiving messages,
+//! - dropping the receiving half doesn't close the connection either - sending half
+//!   keeps working while data arriving afterwards is discarded (and counted
+//!   in [SplitState::discarded_bytes]),
+//! - connection is closed only once both halves are dropped (or closed explicitly).
+//!
+//! [SplitState] is meant to be shared (through [Arc](std::sync::Arc)) by both halves
+//! to implement the contract above.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// State shared by sending and receiving halves of a split transport.
+///
+/// See [module documentation](self) for the contract split transports must follow.
+#[derive(Debug, Default)]
+pub struct SplitState {
+    send_closed: AtomicBool,
+    receive_closed: AtomicBool,
+    discarded_bytes: AtomicU64,
+}
+
+impl SplitState {
+    /// Create new split state with both halves open.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Mark sending half as closed (or dropped).
+    pub fn close_send(&self) {
+        self.send_closed.store(true, Ordering::Release);
+    }
+
+    /// Was sending half closed (or dropped).
+    pub fn is_send_closed(&self) -> bool {
+        self.send_closed.load(Ordering::Acquire)
+    }
+
+    /// Mark receiving half as closed (or dropped).
+    pub fn close_receive(&self) {
+        self.receive_closed.store(true, Ordering::Release);
+    }
+
+    /// Was receiving half closed (or dropped).
+    pub fn is_receive_closed(&self) -> bool {
+        self.receive_closed.load(Ordering::Acquire)
+    }
+
+    /// Are both halves closed (or dropped).
+    pub fn is_closed(&self) -> bool {
+        self.is_send_closed() && self.is_receive_closed()
+    }
+
+    /// Record incoming data discarded after receiving half was dropped.
+    pub fn add_discarded_bytes(&self, bytes: u64) {
+        self.discarded_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Number of incoming bytes discarded after receiving half was dropped.
+    pub fn discarded_bytes(&self) -> u64 {
+        self.discarded_bytes.load(Ordering::Relaxed)
+    }
+}
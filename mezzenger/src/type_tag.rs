@@ -0,0 +1,141 @@
+//! Type tags for diagnosing mismatched message types.
+//!
+//! Ends of a connection have to agree on message types - one end's `Outgoing` type has to be
+//! the other end's `Incoming` type. Otherwise messages usually fail to deserialize with an
+//! unhelpful codec error (or, worse, deserialize into garbage).
+//!
+//! Transports owning a codec can offer a debug `type-tag` feature built on this module:
+//! every encoded message is prefixed with [tag] of its type ([TAG_SIZE] bytes) and
+//! received messages are [checked](check) against the expected type before decoding,
+//! resulting in [TypeMismatch] naming both types.
+//!
+//! **NOTE**: Tags are derived from [type_name](std::any::type_name), which isn't guaranteed
+//! to be stable across compiler versions - both ends should be built with the same toolchain.
+//! Type tags are meant for debugging, not as a part of a stable protocol.
+
+use std::{any::type_name, fmt::Display};
+
+/// Size of type tag prefixing every message.
+pub const TAG_SIZE: usize = 4;
+
+const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+const FNV_PRIME: u32 = 0x01000193;
+
+/// Tag of provided type - [FNV-1a](https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function)
+/// hash of its [type_name] (big-endian).
+pub fn tag<T: ?Sized>() -> [u8; TAG_SIZE] {
+    type_name::<T>()
+        .bytes()
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+        })
+        .to_be_bytes()
+}
+
+/// Received message wasn't tagged with the expected type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    /// Type receiving end expected.
+    pub expected: &'static str,
+    /// Description of type the message was tagged with.
+    ///
+    /// Tag can't be turned back into a type name, so unless it matches receiving end's own
+    /// outgoing type (common mistake of both ends using the same `Incoming`/`Outgoing` order)
+    /// only the tag itself is known.
+    pub got: String,
+}
+
+impl Display for TypeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "message type mismatch (expected {}, got {})",
+            self.expected, self.got
+        )
+    }
+}
+
+impl std::error::Error for TypeMismatch {}
+
+/// Check tag of received `message` against `Expected` type, returning message
+/// with the tag stripped.
+///
+/// `Ours` is receiving end's own outgoing type - used to name the received type
+/// when the tag matches it.
+pub fn check<Expected, Ours>(message: &[u8]) -> Result<&[u8], TypeMismatch>
+where
+    Expected: ?Sized,
+    Ours: ?Sized,
+{
+    let expected = type_name::<Expected>();
+    if message.len() < TAG_SIZE {
+        return Err(TypeMismatch {
+            expected,
+            got: "message without type tag".to_string(),
+        });
+    }
+    let (received, message) = message.split_at(TAG_SIZE);
+    if received == tag::<Expected>() {
+        Ok(message)
+    } else if received == tag::<Ours>() {
+        Err(TypeMismatch {
+            expected,
+            got: type_name::<Ours>().to_string(),
+        })
+    } else {
+        Err(TypeMismatch {
+            expected,
+            got: format!(
+                "unknown type (tag {:#010x})",
+                u32::from_be_bytes(received.try_into().unwrap())
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, tag, TypeMismatch, TAG_SIZE};
+
+    fn tagged<T>(message: &[u8]) -> Vec<u8> {
+        let mut tagged = tag::<T>().to_vec();
+        tagged.extend_from_slice(message);
+        tagged
+    }
+
+    #[test]
+    fn test_tag() {
+        assert_eq!(tag::<str>(), 0xc24bd190u32.to_be_bytes());
+        assert_eq!(tag::<u32>(), tag::<u32>());
+        assert_ne!(tag::<u32>(), tag::<i32>());
+        assert_ne!(tag::<Vec<u8>>(), tag::<Vec<i8>>());
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(
+            check::<u32, String>(&tagged::<u32>(&[1, 2])),
+            Ok(&[1, 2][..])
+        );
+        assert_eq!(check::<u32, String>(&tagged::<u32>(&[])), Ok(&[][..]));
+
+        let error = check::<u32, String>(&tagged::<String>(&[1, 2])).unwrap_err();
+        assert_eq!(
+            error,
+            TypeMismatch {
+                expected: "u32",
+                got: "alloc::string::String".to_string()
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "message type mismatch (expected u32, got alloc::string::String)"
+        );
+
+        let error = check::<u32, String>(&tagged::<i64>(&[1, 2])).unwrap_err();
+        assert!(error.got.starts_with("unknown type (tag 0x"));
+
+        let error = check::<u32, String>(&[0; TAG_SIZE - 1]).unwrap_err();
+        assert_eq!(error.got, "message without type tag");
+    }
+}
@@ -0,0 +1,136 @@
+//! Set of wakers of tasks waiting on a single shared resource.
+//!
+//! Useful for transports sharing one inner transport (or socket) between many handles
+//! (for example multiplexed channels) - inner transport remembers only the last waker
+//! it was polled with, so it should be polled with a [waker](futures::task::waker)
+//! created from [WakerSet] instead, waking all waiting handles.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    task::Waker,
+};
+
+use futures::task::ArcWake;
+
+/// Wakers of tasks waiting on a shared resource, identified by keys of type `K`.
+///
+/// Waking it as a whole (through [ArcWake]) wakes all registered tasks.
+#[derive(Debug)]
+pub struct WakerSet<K> {
+    wakers: Mutex<HashMap<K, Waker>>,
+}
+
+impl<K> Default for WakerSet<K> {
+    fn default() -> Self {
+        WakerSet {
+            wakers: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K> WakerSet<K>
+where
+    K: Hash + Eq,
+{
+    /// Create new empty waker set.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Register waker of task identified by `key` (replacing previously registered one).
+    pub fn register(&self, key: K, other: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap();
+        match wakers.get(&key) {
+            Some(waker) if waker.will_wake(other) => {}
+            _ => {
+                wakers.insert(key, other.clone());
+            }
+        }
+    }
+
+    /// Remove waker of task identified by `key`.
+    pub fn remove(&self, key: &K) {
+        self.wakers.lock().unwrap().remove(key);
+    }
+
+    /// Wake (and remove) task identified by `key`.
+    pub fn wake_one(&self, key: &K) {
+        let waker = self.wakers.lock().unwrap().remove(key);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Wake (and remove) all registered tasks.
+    pub fn wake_all(&self) {
+        let wakers: Vec<_> = self.wakers.lock().unwrap().drain().collect();
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl<K> ArcWake for WakerSet<K>
+where
+    K: Hash + Eq + Send,
+{
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.wake_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::Waker,
+    };
+
+    use futures::task::{waker, ArcWake};
+
+    use super::WakerSet;
+
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl ArcWake for Counter {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn counting() -> (Arc<Counter>, Waker) {
+        let counter = Arc::new(Counter::default());
+        (counter.clone(), waker(counter))
+    }
+
+    #[test]
+    fn test_waker_set() {
+        let set = Arc::new(WakerSet::new());
+        let (first, first_waker) = counting();
+        let (second, second_waker) = counting();
+        set.register(1, &first_waker);
+        set.register(2, &second_waker);
+
+        set.wake_one(&1);
+        set.wake_one(&1);
+        assert_eq!(first.0.load(Ordering::SeqCst), 1);
+        assert_eq!(second.0.load(Ordering::SeqCst), 0);
+
+        set.register(1, &first_waker);
+        set.register(3, &second_waker);
+        set.remove(&3);
+        waker(set.clone()).wake();
+        assert_eq!(first.0.load(Ordering::SeqCst), 2);
+        assert_eq!(second.0.load(Ordering::SeqCst), 1);
+
+        set.wake_all();
+        assert_eq!(first.0.load(Ordering::SeqCst), 2);
+        assert_eq!(second.0.load(Ordering::SeqCst), 1);
+    }
+}